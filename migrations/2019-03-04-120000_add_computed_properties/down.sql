@@ -0,0 +1,62 @@
+DROP TABLE computed_values;
+
+DELETE FROM properties
+WHERE property_type = 'computed';
+
+ALTER TABLE properties
+DROP CONSTRAINT "Computed property needs formula",
+DROP COLUMN formula;
+
+ALTER TABLE relation_values
+DROP CONSTRAINT "Property must be relation type";
+
+ALTER TABLE property_value_choices
+DROP CONSTRAINT "Property must be choice type";
+
+ALTER TABLE choice_values
+DROP CONSTRAINT "Property must be choice type";
+
+ALTER TABLE text_values
+DROP CONSTRAINT "Property must be text type";
+
+ALTER TABLE timestamptz_values
+DROP CONSTRAINT "Property must be timestamptz type";
+
+DROP FUNCTION property_type_is;
+
+ALTER TYPE property_type RENAME TO property_type_old;
+
+CREATE TYPE property_type AS ENUM (
+  'choice', 'text', 'relation', 'timestamptz'
+);
+
+ALTER TABLE properties
+ALTER COLUMN property_type TYPE property_type
+    USING property_type::text::property_type;
+
+DROP TYPE property_type_old;
+
+CREATE FUNCTION property_type_is(bigint, property_type) RETURNS BOOL AS $$
+SELECT COUNT(*) = 0 FROM properties
+WHERE $1 = id AND property_type != $2;
+$$ LANGUAGE SQL;
+
+ALTER TABLE relation_values
+ADD CONSTRAINT "Property must be relation type"
+    CHECK (property_type_is(property_id, 'relation'));
+
+ALTER TABLE property_value_choices
+ADD CONSTRAINT "Property must be choice type"
+    CHECK (property_type_is(property_id, 'choice'));
+
+ALTER TABLE choice_values
+ADD CONSTRAINT "Property must be choice type"
+    CHECK (property_type_is(property_id, 'choice'));
+
+ALTER TABLE text_values
+ADD CONSTRAINT "Property must be text type"
+    CHECK (property_type_is(property_id, 'text'));
+
+ALTER TABLE timestamptz_values
+ADD CONSTRAINT "Property must be timestamptz type"
+    CHECK (property_type_is(property_id, 'timestamptz'));
@@ -0,0 +1,81 @@
+-- Postgres 11 cannot `ALTER TYPE ... ADD VALUE` inside of the migration's
+-- transaction, so we rebuild the property_type enum with the new variant.
+ALTER TABLE relation_values
+DROP CONSTRAINT "Property must be relation type";
+
+ALTER TABLE property_value_choices
+DROP CONSTRAINT "Property must be choice type";
+
+ALTER TABLE choice_values
+DROP CONSTRAINT "Property must be choice type";
+
+ALTER TABLE text_values
+DROP CONSTRAINT "Property must be text type";
+
+ALTER TABLE timestamptz_values
+DROP CONSTRAINT "Property must be timestamptz type";
+
+DROP FUNCTION property_type_is;
+
+ALTER TYPE property_type RENAME TO property_type_old;
+
+CREATE TYPE property_type AS ENUM (
+  'choice', 'text', 'relation', 'timestamptz', 'computed'
+);
+
+ALTER TABLE properties
+ALTER COLUMN property_type TYPE property_type
+    USING property_type::text::property_type;
+
+DROP TYPE property_type_old;
+
+-- Validate type of property
+CREATE FUNCTION property_type_is(bigint, property_type) RETURNS BOOL AS $$
+SELECT COUNT(*) = 0 FROM properties
+WHERE $1 = id AND property_type != $2;
+$$ LANGUAGE SQL;
+
+ALTER TABLE relation_values
+ADD CONSTRAINT "Property must be relation type"
+    CHECK (property_type_is(property_id, 'relation'));
+
+ALTER TABLE property_value_choices
+ADD CONSTRAINT "Property must be choice type"
+    CHECK (property_type_is(property_id, 'choice'));
+
+ALTER TABLE choice_values
+ADD CONSTRAINT "Property must be choice type"
+    CHECK (property_type_is(property_id, 'choice'));
+
+ALTER TABLE text_values
+ADD CONSTRAINT "Property must be text type"
+    CHECK (property_type_is(property_id, 'text'));
+
+ALTER TABLE timestamptz_values
+ADD CONSTRAINT "Property must be timestamptz type"
+    CHECK (property_type_is(property_id, 'timestamptz'));
+
+-- Computed properties carry the formula which derives their value
+ALTER TABLE properties
+ADD COLUMN formula TEXT,
+ADD CONSTRAINT "Computed property needs formula"
+    CHECK ((property_type = 'computed') = (formula IS NOT NULL));
+
+-- Cached results of evaluating a computed property for an object,
+-- recomputed whenever one of the formula's inputs changes.
+CREATE TABLE computed_values(
+  "object_id" TEXT NOT NULL REFERENCES objects(id) ON DELETE CASCADE,
+  property_id BIGINT NOT NULL REFERENCES properties(id) ON DELETE CASCADE,
+  "value" DOUBLE PRECISION,
+  computed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY ("object_id", property_id),
+  CONSTRAINT "Property must be computed type"
+    CHECK (property_type_is(property_id, 'computed'))
+);
+
+-- Sorting objects by a computed property
+CREATE INDEX ON computed_values (property_id, "value");
+
+INSERT INTO properties(id, created_by, display, property_type, formula)
+VALUES
+  (30, 0, 'Days Since Modified', 'computed', 'days_since({3})');
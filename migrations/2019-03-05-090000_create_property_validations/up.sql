@@ -0,0 +1,28 @@
+-- Validation rules enforced when values are written to a property
+CREATE TABLE property_validations(
+  property_id BIGINT PRIMARY KEY REFERENCES properties(id) ON DELETE CASCADE,
+  required BOOLEAN NOT NULL DEFAULT false,
+  -- Text values must match this regular expression
+  pattern TEXT,
+  min_length INT,
+  max_length INT,
+  -- Text values must parse as a number within these bounds
+  min_number DOUBLE PRECISION,
+  max_number DOUBLE PRECISION,
+  -- Choice properties may hold more than one choice
+  multiple BOOLEAN NOT NULL DEFAULT true,
+  -- Relation targets must have one of these extensions (e.g. '.pdf')
+  target_extensions TEXT[],
+  CONSTRAINT "Length bounds are ordered" CHECK (min_length <= max_length),
+  CONSTRAINT "Number bounds are ordered" CHECK (min_number <= max_number)
+);
+
+-- Every object's filename is required
+INSERT INTO property_validations(property_id, required, min_length)
+VALUES
+  (1, true, 1);
+
+-- An object belongs to a single collection
+INSERT INTO property_validations(property_id, multiple)
+VALUES
+  (20, false);
@@ -0,0 +1,5 @@
+DROP TABLE collection_schemas;
+DROP TABLE schema_properties;
+DROP TABLE schemas;
+
+DROP FUNCTION choice_property_is;
@@ -0,0 +1,34 @@
+-- Validate which property a choice belongs to
+CREATE FUNCTION choice_property_is(bigint, bigint) RETURNS BOOL AS $$
+SELECT COUNT(*) = 0 FROM property_value_choices
+WHERE $1 = id AND property_id != $2;
+$$ LANGUAGE SQL;
+
+-- An ordered set of properties which objects of a collection should have
+CREATE TABLE schemas(
+  id BIGINT PRIMARY KEY DEFAULT id_generator(),
+  display TEXT NOT NULL CONSTRAINT "schema name not empty" CHECK (display <> ''),
+  created_by BIGINT NOT NULL DEFAULT 2 REFERENCES users(id) ON DELETE SET DEFAULT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  CONSTRAINT "Schema name is unique" UNIQUE(display)
+);
+
+CREATE TABLE schema_properties(
+  schema_id BIGINT NOT NULL REFERENCES schemas(id) ON DELETE CASCADE,
+  property_id BIGINT NOT NULL REFERENCES properties(id) ON DELETE CASCADE,
+  ord REAL NOT NULL,
+  required BOOLEAN NOT NULL DEFAULT false,
+  -- A JSON property value such as {"text": ""} assigned to new objects
+  default_value JSONB,
+  PRIMARY KEY (schema_id, property_id),
+  CONSTRAINT "Computed properties have no default"
+    CHECK (default_value IS NULL OR NOT property_type_is(property_id, 'computed'))
+);
+
+-- Collections (choices of property 20) may each use one schema
+CREATE TABLE collection_schemas(
+  collection_id BIGINT PRIMARY KEY REFERENCES property_value_choices(id) ON DELETE CASCADE,
+  schema_id BIGINT NOT NULL REFERENCES schemas(id) ON DELETE CASCADE,
+  CONSTRAINT "Choice must be a collection"
+    CHECK (choice_property_is(collection_id, 20))
+);
@@ -0,0 +1,9 @@
+DELETE FROM properties
+WHERE id = 40;
+
+DROP INDEX relation_values_target_id_idx;
+
+ALTER TABLE properties
+DROP CONSTRAINT "Only relations have an inverse name",
+DROP CONSTRAINT "inverse name not empty",
+DROP COLUMN inverse_display;
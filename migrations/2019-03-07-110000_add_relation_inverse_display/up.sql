@@ -0,0 +1,14 @@
+-- How a relation reads from its target's side, e.g. "Supersedes" ↔ "Superseded by"
+ALTER TABLE properties
+ADD COLUMN inverse_display TEXT,
+ADD CONSTRAINT "Only relations have an inverse name"
+    CHECK (inverse_display IS NULL OR property_type = 'relation'),
+ADD CONSTRAINT "inverse name not empty"
+    CHECK (inverse_display <> '');
+
+-- Finding the objects which relate to an object
+CREATE INDEX ON relation_values (target_id);
+
+INSERT INTO properties(id, created_by, display, property_type, inverse_display)
+VALUES
+  (40, 0, 'Supersedes', 'relation', 'Superseded by');
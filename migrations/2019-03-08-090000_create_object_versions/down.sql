@@ -0,0 +1,4 @@
+ALTER TABLE objects
+DROP COLUMN current_version;
+
+DROP TABLE object_versions;
@@ -0,0 +1,18 @@
+-- Successive contents of an object. The hash is the SHA-256 of the content,
+-- and the key of its blob in the object store.
+CREATE TABLE object_versions(
+  "object_id" TEXT NOT NULL REFERENCES objects(id) ON DELETE CASCADE,
+  version INT NOT NULL CONSTRAINT "version is positive" CHECK (version > 0),
+  hash TEXT NOT NULL CONSTRAINT "hash not empty" CHECK (hash <> ''),
+  size BIGINT NOT NULL,
+  "filename" TEXT NOT NULL,
+  created_by BIGINT NOT NULL DEFAULT 2 REFERENCES users(id) ON DELETE SET DEFAULT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY ("object_id", version)
+);
+
+CREATE INDEX ON object_versions (hash);
+
+-- Objects created before versioning have no content
+ALTER TABLE objects
+ADD COLUMN current_version INT;
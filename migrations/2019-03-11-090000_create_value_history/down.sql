@@ -0,0 +1,8 @@
+DROP TRIGGER relation_values_history ON relation_values;
+DROP TRIGGER choice_values_history ON choice_values;
+DROP TRIGGER timestamptz_values_history ON timestamptz_values;
+DROP TRIGGER text_values_history ON text_values;
+DROP FUNCTION record_value_history;
+
+DROP TABLE value_history;
+DROP FUNCTION value_history_is_append_only;
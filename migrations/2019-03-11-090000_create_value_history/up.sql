@@ -0,0 +1,75 @@
+-- Append-only record of every change to a property value. Rows outlive the
+-- objects, properties and users they mention, so there are no foreign keys.
+CREATE TABLE value_history(
+  id BIGINT PRIMARY KEY DEFAULT id_generator(),
+  "object_id" TEXT NOT NULL,
+  property_id BIGINT NOT NULL,
+  -- NULL when the value was added
+  old_value JSONB,
+  -- NULL when the value was removed
+  new_value JSONB,
+  changed_by BIGINT NOT NULL,
+  changed_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  request_id TEXT
+);
+
+CREATE INDEX ON value_history ("object_id", changed_at);
+
+CREATE FUNCTION value_history_is_append_only() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'value_history is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER value_history_append_only
+BEFORE UPDATE OR DELETE ON value_history
+FOR EACH ROW EXECUTE PROCEDURE value_history_is_append_only();
+
+-- The application identifies who is making changes (and for which request)
+-- with `set_config('dewey.user_id', ..., true)` inside of its transactions.
+-- Otherwise the value's own created_by is used.
+--
+-- TG_ARGV[0] is the name of the table's value column.
+CREATE FUNCTION record_value_history() RETURNS trigger AS $$
+DECLARE
+  old_json JSONB := CASE WHEN TG_OP = 'INSERT' THEN NULL ELSE to_jsonb(OLD) -> TG_ARGV[0] END;
+  new_json JSONB := CASE WHEN TG_OP = 'DELETE' THEN NULL ELSE to_jsonb(NEW) -> TG_ARGV[0] END;
+  row_json JSONB := CASE WHEN TG_OP = 'DELETE' THEN to_jsonb(OLD) ELSE to_jsonb(NEW) END;
+BEGIN
+  IF TG_OP = 'UPDATE' AND old_json IS NOT DISTINCT FROM new_json THEN
+    RETURN NULL;
+  END IF;
+
+  INSERT INTO value_history
+    ("object_id", property_id, old_value, new_value, changed_by, request_id)
+  VALUES
+    (
+      row_json ->> 'object_id',
+      (row_json ->> 'property_id')::BIGINT,
+      old_json,
+      new_json,
+      COALESCE(
+        NULLIF(current_setting('dewey.user_id', true), '')::BIGINT,
+        (row_json ->> 'created_by')::BIGINT
+      ),
+      NULLIF(current_setting('dewey.request_id', true), '')
+    );
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER text_values_history
+AFTER INSERT OR UPDATE OR DELETE ON text_values
+FOR EACH ROW EXECUTE PROCEDURE record_value_history('value');
+
+CREATE TRIGGER timestamptz_values_history
+AFTER INSERT OR UPDATE OR DELETE ON timestamptz_values
+FOR EACH ROW EXECUTE PROCEDURE record_value_history('value');
+
+CREATE TRIGGER choice_values_history
+AFTER INSERT OR UPDATE OR DELETE ON choice_values
+FOR EACH ROW EXECUTE PROCEDURE record_value_history('value_id');
+
+CREATE TRIGGER relation_values_history
+AFTER INSERT OR UPDATE OR DELETE ON relation_values
+FOR EACH ROW EXECUTE PROCEDURE record_value_history('target_id');
@@ -0,0 +1,7 @@
+DROP TRIGGER text_values_search ON text_values;
+DROP FUNCTION text_values_refresh_search;
+DROP FUNCTION set_search_config;
+DROP FUNCTION refresh_object_search;
+
+DROP TABLE object_search;
+DROP TEXT SEARCH CONFIGURATION japanese;
@@ -0,0 +1,72 @@
+-- Postgres has no Japanese parser, so Japanese documents are only split on
+-- whitespace and punctuation. Prefix matching makes up for some of it.
+CREATE TEXT SEARCH CONFIGURATION japanese (COPY = simple);
+
+-- The searchable text of each object, kept up to date from text_values.
+-- Filenames are weighted above the other text properties.
+CREATE TABLE object_search(
+  "object_id" TEXT PRIMARY KEY REFERENCES objects(id) ON DELETE CASCADE,
+  search_config REGCONFIG NOT NULL DEFAULT 'english',
+  content TEXT NOT NULL,
+  document TSVECTOR NOT NULL
+);
+
+CREATE INDEX ON object_search USING GIN (document);
+
+CREATE FUNCTION refresh_object_search(target TEXT) RETURNS VOID AS $$
+DECLARE
+  config REGCONFIG;
+  filename TEXT;
+  other_text TEXT;
+BEGIN
+  SELECT COALESCE(s.search_config, 'english') INTO config
+  FROM objects o LEFT JOIN object_search s ON s."object_id" = o.id
+  WHERE o.id = target;
+  IF NOT FOUND THEN
+    RETURN;
+  END IF;
+
+  SELECT COALESCE(string_agg(value, E'\n' ORDER BY property_id) FILTER (WHERE property_id = 1), ''),
+         COALESCE(string_agg(value, E'\n' ORDER BY property_id) FILTER (WHERE property_id <> 1), '')
+  INTO filename, other_text
+  FROM text_values WHERE "object_id" = target;
+
+  INSERT INTO object_search ("object_id", search_config, content, document)
+  VALUES (
+    target,
+    config,
+    trim(filename || E'\n' || other_text),
+    setweight(to_tsvector(config, translate(filename, '._-', '   ')), 'A')
+      || setweight(to_tsvector(config, other_text), 'B')
+  )
+  ON CONFLICT ("object_id") DO UPDATE
+  SET content = EXCLUDED.content, document = EXCLUDED.document;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Reindex an object in another language
+CREATE FUNCTION set_search_config(target TEXT, config REGCONFIG) RETURNS VOID AS $$
+BEGIN
+  INSERT INTO object_search ("object_id", search_config, content, document)
+  VALUES (target, config, '', ''::TSVECTOR)
+  ON CONFLICT ("object_id") DO UPDATE SET search_config = EXCLUDED.search_config;
+  PERFORM refresh_object_search(target);
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION text_values_refresh_search() RETURNS trigger AS $$
+BEGIN
+  IF TG_OP = 'DELETE' THEN
+    PERFORM refresh_object_search(OLD."object_id");
+  ELSE
+    PERFORM refresh_object_search(NEW."object_id");
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER text_values_search
+AFTER INSERT OR UPDATE OR DELETE ON text_values
+FOR EACH ROW EXECUTE PROCEDURE text_values_refresh_search();
+
+SELECT refresh_object_search(id) FROM objects;
@@ -0,0 +1,40 @@
+DROP TRIGGER extracted_texts_search ON extracted_texts;
+DROP FUNCTION extracted_texts_refresh_search;
+
+CREATE OR REPLACE FUNCTION refresh_object_search(target TEXT) RETURNS VOID AS $$
+DECLARE
+  config REGCONFIG;
+  filename TEXT;
+  other_text TEXT;
+BEGIN
+  SELECT COALESCE(s.search_config, 'english') INTO config
+  FROM objects o LEFT JOIN object_search s ON s."object_id" = o.id
+  WHERE o.id = target;
+  IF NOT FOUND THEN
+    RETURN;
+  END IF;
+
+  SELECT COALESCE(string_agg(value, E'\n' ORDER BY property_id) FILTER (WHERE property_id = 1), ''),
+         COALESCE(string_agg(value, E'\n' ORDER BY property_id) FILTER (WHERE property_id <> 1), '')
+  INTO filename, other_text
+  FROM text_values WHERE "object_id" = target;
+
+  INSERT INTO object_search ("object_id", search_config, content, document)
+  VALUES (
+    target,
+    config,
+    trim(filename || E'\n' || other_text),
+    setweight(to_tsvector(config, translate(filename, '._-', '   ')), 'A')
+      || setweight(to_tsvector(config, other_text), 'B')
+  )
+  ON CONFLICT ("object_id") DO UPDATE
+  SET content = EXCLUDED.content, document = EXCLUDED.document;
+END;
+$$ LANGUAGE plpgsql;
+
+SELECT refresh_object_search("object_id") FROM extracted_texts;
+
+DROP TRIGGER objects_extraction ON objects;
+DROP FUNCTION objects_queue_extraction;
+DROP TABLE extracted_texts;
+DROP TYPE extraction_status;
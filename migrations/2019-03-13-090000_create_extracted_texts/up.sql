@@ -0,0 +1,85 @@
+CREATE TYPE extraction_status AS ENUM ('pending', 'done', 'failed', 'unsupported');
+
+-- Text extracted from the current version of each object's content
+CREATE TABLE extracted_texts(
+  "object_id" TEXT PRIMARY KEY REFERENCES objects(id) ON DELETE CASCADE,
+  "version" INT NOT NULL,
+  status extraction_status NOT NULL DEFAULT 'pending',
+  content TEXT NOT NULL DEFAULT '',
+  -- Why the latest attempt failed
+  error TEXT,
+  attempts INT NOT NULL DEFAULT 0,
+  updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX ON extracted_texts (updated_at) WHERE status = 'pending';
+
+-- Every new current version is extracted again
+CREATE FUNCTION objects_queue_extraction() RETURNS trigger AS $$
+BEGIN
+  IF NEW.current_version IS NOT NULL
+     AND NEW.current_version IS DISTINCT FROM OLD.current_version THEN
+    INSERT INTO extracted_texts ("object_id", "version")
+    VALUES (NEW.id, NEW.current_version)
+    ON CONFLICT ("object_id") DO UPDATE
+    SET "version" = EXCLUDED."version", status = 'pending', error = NULL, attempts = 0,
+        updated_at = CURRENT_TIMESTAMP;
+  END IF;
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER objects_extraction
+AFTER UPDATE OF current_version ON objects
+FOR EACH ROW EXECUTE PROCEDURE objects_queue_extraction();
+
+INSERT INTO extracted_texts ("object_id", "version")
+SELECT id, current_version FROM objects WHERE current_version IS NOT NULL;
+
+-- Extracted text is searched along with the text values, weighted below them
+CREATE OR REPLACE FUNCTION refresh_object_search(target TEXT) RETURNS VOID AS $$
+DECLARE
+  config REGCONFIG;
+  filename TEXT;
+  other_text TEXT;
+  extracted TEXT;
+BEGIN
+  SELECT COALESCE(s.search_config, 'english') INTO config
+  FROM objects o LEFT JOIN object_search s ON s."object_id" = o.id
+  WHERE o.id = target;
+  IF NOT FOUND THEN
+    RETURN;
+  END IF;
+
+  SELECT COALESCE(string_agg(value, E'\n' ORDER BY property_id) FILTER (WHERE property_id = 1), ''),
+         COALESCE(string_agg(value, E'\n' ORDER BY property_id) FILTER (WHERE property_id <> 1), '')
+  INTO filename, other_text
+  FROM text_values WHERE "object_id" = target;
+
+  SELECT COALESCE(max(content), '') INTO extracted
+  FROM extracted_texts WHERE "object_id" = target AND status = 'done';
+
+  INSERT INTO object_search ("object_id", search_config, content, document)
+  VALUES (
+    target,
+    config,
+    trim(filename || E'\n' || other_text || E'\n' || extracted),
+    setweight(to_tsvector(config, translate(filename, '._-', '   ')), 'A')
+      || setweight(to_tsvector(config, other_text), 'B')
+      || setweight(to_tsvector(config, extracted), 'C')
+  )
+  ON CONFLICT ("object_id") DO UPDATE
+  SET content = EXCLUDED.content, document = EXCLUDED.document;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE FUNCTION extracted_texts_refresh_search() RETURNS trigger AS $$
+BEGIN
+  PERFORM refresh_object_search(NEW."object_id");
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER extracted_texts_search
+AFTER INSERT OR UPDATE OF content, status ON extracted_texts
+FOR EACH ROW EXECUTE PROCEDURE extracted_texts_refresh_search();
@@ -0,0 +1,21 @@
+CREATE TYPE view_layout AS ENUM ('table', 'grid');
+
+-- A named query along with how its results are shown
+CREATE TABLE saved_views(
+  id BIGINT PRIMARY KEY DEFAULT id_generator(),
+  display TEXT NOT NULL CONSTRAINT "view name not empty" CHECK (display <> ''),
+  query TEXT NOT NULL DEFAULT '',
+  -- Properties shown for each object, in order
+  columns BIGINT[] NOT NULL DEFAULT '{}',
+  sort TEXT NOT NULL DEFAULT '-created',
+  group_by BIGINT REFERENCES properties(id) ON DELETE SET NULL,
+  layout view_layout NOT NULL DEFAULT 'table',
+  -- Shared with everyone through a collection, or personal when NULL
+  collection_id BIGINT REFERENCES property_value_choices(id) ON DELETE CASCADE
+    CONSTRAINT "Choice must be a collection"
+    CHECK (collection_id IS NULL OR choice_property_is(collection_id, 20)),
+  created_by BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX ON saved_views (created_by);
@@ -0,0 +1,16 @@
+CREATE TYPE token_scope AS ENUM ('read', 'write');
+
+-- Personal tokens for scripts and integrations, sent as `Authorization: Bearer`
+CREATE TABLE api_tokens(
+  id BIGINT PRIMARY KEY DEFAULT id_generator(),
+  user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  display TEXT NOT NULL CONSTRAINT "token name not empty" CHECK (display <> ''),
+  -- SHA-256 of the secret, which is only shown once when the token is created
+  secret_hash TEXT NOT NULL UNIQUE,
+  scope token_scope NOT NULL DEFAULT 'read',
+  expires_at TIMESTAMPTZ,
+  last_used_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX ON api_tokens (user_id);
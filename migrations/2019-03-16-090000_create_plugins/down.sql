@@ -0,0 +1,3 @@
+DROP TABLE plugins;
+DROP FUNCTION check_plugin_user();
+ALTER TABLE users DROP COLUMN admin;
@@ -0,0 +1,30 @@
+-- Integrations which act as a user of their own, so their changes, like the
+-- tags of an OCR plugin, can be told apart from people's
+CREATE TABLE plugins(
+  user_id BIGINT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+  name TEXT NOT NULL UNIQUE
+    CONSTRAINT "plugin name is lowercase letters, digits and dashes"
+    CHECK (name ~ '^[a-z0-9][a-z0-9-]*$'),
+  description TEXT NOT NULL DEFAULT '',
+  homepage TEXT,
+  registered_by BIGINT NOT NULL REFERENCES users(id),
+  registered_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- Disabled plugins have their tokens revoked and may not be given new ones
+  disabled_at TIMESTAMPTZ
+);
+
+CREATE FUNCTION check_plugin_user() RETURNS TRIGGER AS $$
+BEGIN
+  IF (SELECT kind FROM users WHERE id = NEW.user_id) <> 'plugin' THEN
+    RAISE EXCEPTION 'user % is not a plugin', NEW.user_id;
+  END IF;
+  RETURN NEW;
+END;
+$$ LANGUAGE PLPGSQL;
+
+CREATE TRIGGER plugins_are_plugin_users
+BEFORE INSERT OR UPDATE OF user_id ON plugins
+FOR EACH ROW EXECUTE PROCEDURE check_plugin_user();
+
+-- Administrators manage plugins
+ALTER TABLE users ADD COLUMN admin BOOLEAN NOT NULL DEFAULT false;
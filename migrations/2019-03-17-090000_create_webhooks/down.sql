@@ -0,0 +1,11 @@
+DROP TRIGGER value_history_webhook ON value_history;
+DROP FUNCTION value_history_webhook_event();
+DROP TRIGGER objects_deleted_webhook ON objects;
+DROP FUNCTION objects_deleted_webhook_event();
+DROP TRIGGER objects_created_webhook ON objects;
+DROP FUNCTION objects_created_webhook_event();
+DROP FUNCTION queue_webhook_event(webhook_event, JSONB);
+DROP TABLE webhook_deliveries;
+DROP TABLE webhooks;
+DROP TYPE delivery_status;
+DROP TYPE webhook_event;
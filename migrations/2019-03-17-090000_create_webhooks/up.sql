@@ -0,0 +1,114 @@
+CREATE TYPE webhook_event AS ENUM (
+  'object_created', 'value_changed', 'object_deleted', 'collection_changed', 'ping'
+);
+CREATE TYPE delivery_status AS ENUM ('pending', 'delivered', 'failed');
+
+-- Subscriptions to events, which are POSTed to the URL as JSON signed with
+-- the secret in `X-Dewey-Signature`
+CREATE TABLE webhooks(
+  id BIGINT PRIMARY KEY DEFAULT id_generator(),
+  url TEXT NOT NULL CONSTRAINT "webhook url is http or https" CHECK (url ~ '^https?://'),
+  secret TEXT NOT NULL,
+  -- Names of the events delivered, every event when empty
+  events TEXT[] NOT NULL DEFAULT '{}',
+  created_by BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX ON webhooks (created_by);
+
+-- Each event for each subscription, kept as the delivery log
+CREATE TABLE webhook_deliveries(
+  id BIGINT PRIMARY KEY DEFAULT id_generator(),
+  webhook_id BIGINT NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+  event webhook_event NOT NULL,
+  payload JSONB NOT NULL,
+  status delivery_status NOT NULL DEFAULT 'pending',
+  attempts INT NOT NULL DEFAULT 0,
+  next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  -- Of the latest attempt
+  response_status INT,
+  response_body TEXT,
+  error TEXT,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  delivered_at TIMESTAMPTZ
+);
+
+CREATE INDEX ON webhook_deliveries (webhook_id, created_at);
+CREATE INDEX ON webhook_deliveries (next_attempt_at) WHERE status = 'pending';
+
+CREATE FUNCTION queue_webhook_event(kind webhook_event, body JSONB) RETURNS VOID AS $$
+BEGIN
+  INSERT INTO webhook_deliveries (webhook_id, event, payload)
+  SELECT id, kind, body FROM webhooks
+  WHERE events = '{}' OR kind::TEXT = ANY(events);
+END;
+$$ LANGUAGE plpgsql;
+
+-- Objects are created along with their values, so object_created waits for the
+-- end of the transaction to include them
+CREATE FUNCTION objects_created_webhook_event() RETURNS trigger AS $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM objects WHERE id = NEW.id) THEN
+    RETURN NULL;
+  END IF;
+  PERFORM queue_webhook_event('object_created', jsonb_build_object(
+    'object_id', NEW.id,
+    'filename', (SELECT value FROM text_values
+                 WHERE "object_id" = NEW.id AND property_id = 1),
+    'collections', COALESCE((SELECT jsonb_agg(value_id ORDER BY value_id) FROM choice_values
+                             WHERE "object_id" = NEW.id AND property_id = 20), '[]'),
+    'created_by', NEW.created_by,
+    'created_at', NEW.created_at
+  ));
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER objects_created_webhook
+AFTER INSERT ON objects
+DEFERRABLE INITIALLY DEFERRED
+FOR EACH ROW EXECUTE PROCEDURE objects_created_webhook_event();
+
+CREATE FUNCTION objects_deleted_webhook_event() RETURNS trigger AS $$
+BEGIN
+  PERFORM queue_webhook_event('object_deleted', jsonb_build_object(
+    'object_id', OLD.id,
+    'deleted_by', NULLIF(current_setting('dewey.user_id', true), '')::BIGINT
+  ));
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER objects_deleted_webhook
+AFTER DELETE ON objects
+FOR EACH ROW EXECUTE PROCEDURE objects_deleted_webhook_event();
+
+-- Changes to the Collection property are collection_changed, others
+-- value_changed. Values set along with a new object belong to its
+-- object_created, and values removed along with an object to its object_deleted.
+CREATE FUNCTION value_history_webhook_event() RETURNS trigger AS $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM objects
+                 WHERE id = NEW."object_id" AND created_at <> CURRENT_TIMESTAMP) THEN
+    RETURN NULL;
+  END IF;
+  PERFORM queue_webhook_event(
+    CASE WHEN NEW.property_id = 20 THEN 'collection_changed' ELSE 'value_changed' END::webhook_event,
+    jsonb_build_object(
+      'object_id', NEW."object_id",
+      'property_id', NEW.property_id,
+      'old_value', NEW.old_value,
+      'new_value', NEW.new_value,
+      'changed_by', NEW.changed_by,
+      'changed_at', NEW.changed_at,
+      'request_id', NEW.request_id
+    )
+  );
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER value_history_webhook
+AFTER INSERT ON value_history
+FOR EACH ROW EXECUTE PROCEDURE value_history_webhook_event();
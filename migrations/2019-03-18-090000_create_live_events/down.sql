@@ -0,0 +1,56 @@
+CREATE OR REPLACE FUNCTION value_history_webhook_event() RETURNS trigger AS $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM objects
+                 WHERE id = NEW."object_id" AND created_at <> CURRENT_TIMESTAMP) THEN
+    RETURN NULL;
+  END IF;
+  PERFORM queue_webhook_event(
+    CASE WHEN NEW.property_id = 20 THEN 'collection_changed' ELSE 'value_changed' END::webhook_event,
+    jsonb_build_object(
+      'object_id', NEW."object_id",
+      'property_id', NEW.property_id,
+      'old_value', NEW.old_value,
+      'new_value', NEW.new_value,
+      'changed_by', NEW.changed_by,
+      'changed_at', NEW.changed_at,
+      'request_id', NEW.request_id
+    )
+  );
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER objects_deleted_webhook ON objects;
+CREATE OR REPLACE FUNCTION objects_deleted_webhook_event() RETURNS trigger AS $$
+BEGIN
+  PERFORM queue_webhook_event('object_deleted', jsonb_build_object(
+    'object_id', OLD.id,
+    'deleted_by', NULLIF(current_setting('dewey.user_id', true), '')::BIGINT
+  ));
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER objects_deleted_webhook
+AFTER DELETE ON objects
+FOR EACH ROW EXECUTE PROCEDURE objects_deleted_webhook_event();
+
+CREATE OR REPLACE FUNCTION objects_created_webhook_event() RETURNS trigger AS $$
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM objects WHERE id = NEW.id) THEN
+    RETURN NULL;
+  END IF;
+  PERFORM queue_webhook_event('object_created', jsonb_build_object(
+    'object_id', NEW.id,
+    'filename', (SELECT value FROM text_values
+                 WHERE "object_id" = NEW.id AND property_id = 1),
+    'collections', COALESCE((SELECT jsonb_agg(value_id ORDER BY value_id) FROM choice_values
+                             WHERE "object_id" = NEW.id AND property_id = 20), '[]'),
+    'created_by', NEW.created_by,
+    'created_at', NEW.created_at
+  ));
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP FUNCTION queue_live_event(webhook_event, JSONB);
+DROP TABLE live_events;
@@ -0,0 +1,98 @@
+-- The webhook events, queued again for the open browsers they concern. The
+-- server publishes them to Redis channels of each collection the object is in
+-- and of the object's creator, then deletes them.
+CREATE TABLE live_events(
+  id BIGSERIAL PRIMARY KEY,
+  event webhook_event NOT NULL,
+  collections BIGINT[] NOT NULL DEFAULT '{}',
+  users BIGINT[] NOT NULL DEFAULT '{}',
+  payload JSONB NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- An object which left a collection is still of interest to it, so the
+-- choices of a collection change are added to the object's current ones
+CREATE FUNCTION queue_live_event(kind webhook_event, body JSONB) RETURNS VOID AS $$
+DECLARE
+  target TEXT := body ->> 'object_id';
+BEGIN
+  INSERT INTO live_events (event, collections, users, payload)
+  VALUES (
+    kind,
+    ARRAY(
+      SELECT value_id FROM choice_values WHERE "object_id" = target AND property_id = 20
+      UNION
+      SELECT (body ->> side)::BIGINT FROM unnest(ARRAY['old_value', 'new_value']) side
+      WHERE kind = 'collection_changed' AND body ->> side IS NOT NULL
+    ),
+    ARRAY(SELECT created_by FROM objects WHERE id = target),
+    body
+  );
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION objects_created_webhook_event() RETURNS trigger AS $$
+DECLARE
+  body JSONB;
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM objects WHERE id = NEW.id) THEN
+    RETURN NULL;
+  END IF;
+  body := jsonb_build_object(
+    'object_id', NEW.id,
+    'filename', (SELECT value FROM text_values
+                 WHERE "object_id" = NEW.id AND property_id = 1),
+    'collections', COALESCE((SELECT jsonb_agg(value_id ORDER BY value_id) FROM choice_values
+                             WHERE "object_id" = NEW.id AND property_id = 20), '[]'),
+    'created_by', NEW.created_by,
+    'created_at', NEW.created_at
+  );
+  PERFORM queue_webhook_event('object_created', body);
+  PERFORM queue_live_event('object_created', body);
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Before the delete cascades to the object's values, so its collections are known
+CREATE OR REPLACE FUNCTION objects_deleted_webhook_event() RETURNS trigger AS $$
+DECLARE
+  body JSONB := jsonb_build_object(
+    'object_id', OLD.id,
+    'deleted_by', NULLIF(current_setting('dewey.user_id', true), '')::BIGINT
+  );
+BEGIN
+  PERFORM queue_webhook_event('object_deleted', body);
+  PERFORM queue_live_event('object_deleted', body);
+  RETURN OLD;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER objects_deleted_webhook ON objects;
+CREATE TRIGGER objects_deleted_webhook
+BEFORE DELETE ON objects
+FOR EACH ROW EXECUTE PROCEDURE objects_deleted_webhook_event();
+
+CREATE OR REPLACE FUNCTION value_history_webhook_event() RETURNS trigger AS $$
+DECLARE
+  kind webhook_event := CASE WHEN NEW.property_id = 20 THEN 'collection_changed'
+                             ELSE 'value_changed' END;
+  body JSONB;
+BEGIN
+  IF NOT EXISTS (SELECT 1 FROM objects
+                 WHERE id = NEW."object_id" AND created_at <> CURRENT_TIMESTAMP) THEN
+    RETURN NULL;
+  END IF;
+  body := jsonb_build_object(
+    'object_id', NEW."object_id",
+    'property_id', NEW.property_id,
+    'old_value', NEW.old_value,
+    'new_value', NEW.new_value,
+    'changed_by', NEW.changed_by,
+    'changed_at', NEW.changed_at,
+    'request_id', NEW.request_id
+  );
+  PERFORM queue_webhook_event(kind, body);
+  PERFORM queue_live_event(kind, body);
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,2 @@
+-- Disabled users may not sign in, and their tokens are refused
+ALTER TABLE users ADD COLUMN disabled_at TIMESTAMPTZ;
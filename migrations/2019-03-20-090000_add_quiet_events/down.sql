@@ -0,0 +1,26 @@
+CREATE OR REPLACE FUNCTION queue_webhook_event(kind webhook_event, body JSONB) RETURNS VOID AS $$
+BEGIN
+  INSERT INTO webhook_deliveries (webhook_id, event, payload)
+  SELECT id, kind, body FROM webhooks
+  WHERE events = '{}' OR kind::TEXT = ANY(events);
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION queue_live_event(kind webhook_event, body JSONB) RETURNS VOID AS $$
+DECLARE
+  target TEXT := body ->> 'object_id';
+BEGIN
+  INSERT INTO live_events (event, collections, users, payload)
+  VALUES (
+    kind,
+    ARRAY(
+      SELECT value_id FROM choice_values WHERE "object_id" = target AND property_id = 20
+      UNION
+      SELECT (body ->> side)::BIGINT FROM unnest(ARRAY['old_value', 'new_value']) side
+      WHERE kind = 'collection_changed' AND body ->> side IS NOT NULL
+    ),
+    ARRAY(SELECT created_by FROM objects WHERE id = target),
+    body
+  );
+END;
+$$ LANGUAGE plpgsql;
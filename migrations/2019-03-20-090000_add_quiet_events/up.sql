@@ -0,0 +1,35 @@
+-- Restoring an archive inserts every object and value again, which is no news
+-- to webhooks or open browsers. A transaction which sets `dewey.quiet` to 'on'
+-- queues no events.
+CREATE OR REPLACE FUNCTION queue_webhook_event(kind webhook_event, body JSONB) RETURNS VOID AS $$
+BEGIN
+  IF current_setting('dewey.quiet', true) = 'on' THEN
+    RETURN;
+  END IF;
+  INSERT INTO webhook_deliveries (webhook_id, event, payload)
+  SELECT id, kind, body FROM webhooks
+  WHERE events = '{}' OR kind::TEXT = ANY(events);
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION queue_live_event(kind webhook_event, body JSONB) RETURNS VOID AS $$
+DECLARE
+  target TEXT := body ->> 'object_id';
+BEGIN
+  IF current_setting('dewey.quiet', true) = 'on' THEN
+    RETURN;
+  END IF;
+  INSERT INTO live_events (event, collections, users, payload)
+  VALUES (
+    kind,
+    ARRAY(
+      SELECT value_id FROM choice_values WHERE "object_id" = target AND property_id = 20
+      UNION
+      SELECT (body ->> side)::BIGINT FROM unnest(ARRAY['old_value', 'new_value']) side
+      WHERE kind = 'collection_changed' AND body ->> side IS NOT NULL
+    ),
+    ARRAY(SELECT created_by FROM objects WHERE id = target),
+    body
+  );
+END;
+$$ LANGUAGE plpgsql;
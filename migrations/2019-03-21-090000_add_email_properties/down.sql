@@ -0,0 +1,4 @@
+DROP INDEX text_values_value_idx;
+
+DELETE FROM properties
+WHERE id BETWEEN 50 AND 58;
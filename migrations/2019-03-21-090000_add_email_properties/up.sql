@@ -0,0 +1,16 @@
+-- Assigned to emails when they are uploaded, from their headers. Attachments
+-- become objects of their own, and replies relate to the message they reply to.
+INSERT INTO properties(id, created_by, display, property_type, inverse_display)
+VALUES
+  (50, 0, 'From', 'text', NULL),
+  (51, 0, 'To', 'text', NULL),
+  (52, 0, 'Cc', 'text', NULL),
+  (53, 0, 'Subject', 'text', NULL),
+  (54, 0, 'Date', 'timestamptz', NULL),
+  (55, 0, 'Message-ID', 'text', NULL),
+  (56, 0, 'In-Reply-To', 'text', NULL),
+  (57, 0, 'Attachments', 'relation', 'Attached To'),
+  (58, 0, 'Replies To', 'relation', 'Replies');
+
+-- Threading finds messages by their ids
+CREATE INDEX ON text_values (value) WHERE property_id IN (55, 56);
@@ -0,0 +1 @@
+ALTER TABLE extracted_texts DROP COLUMN pages;
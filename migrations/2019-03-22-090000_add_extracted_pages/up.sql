@@ -0,0 +1,2 @@
+-- Pages of paged content such as pdfs, for the `pages()` formula function
+ALTER TABLE extracted_texts ADD COLUMN pages INT;
@@ -0,0 +1 @@
+DROP TABLE drop_box_tokens;
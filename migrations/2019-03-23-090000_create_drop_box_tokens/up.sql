@@ -0,0 +1,16 @@
+-- Tokens of mail drop-box addresses, each only filing mail into its collection
+-- as its owner. Unlike API tokens they sign nothing else in, so an address
+-- seen in a forwarded mail or a mail server's log gives no access to the API.
+CREATE TABLE drop_box_tokens(
+  id BIGINT PRIMARY KEY DEFAULT id_generator(),
+  user_id BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+  collection_id BIGINT NOT NULL REFERENCES property_value_choices(id) ON DELETE CASCADE
+    CONSTRAINT "Choice must be a collection"
+    CHECK (choice_property_is(collection_id, 20)),
+  -- SHA-256 of the secret, which is only shown once when the token is created
+  secret_hash TEXT NOT NULL UNIQUE,
+  last_used_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX ON drop_box_tokens (user_id);
@@ -0,0 +1,38 @@
+#!/bin/bash
+# Exports the database of DATABASE_URL, restores the archive into a new empty
+# database, exports that one and checks both archives hold the same tables and
+# contents. Needs psql, unzip and jq, and the services of `docker-compose up`.
+set -euo pipefail
+
+: "${DATABASE_URL:?DATABASE_URL is required}"
+RESTORED_DB="dewey_roundtrip_$$"
+RESTORED_URL="${DATABASE_URL%/*}/$RESTORED_DB"
+WORK="$(mktemp -d)"
+DEWEY="${DEWEY:-cargo run --quiet --}"
+
+cleanup() {
+  psql "$DATABASE_URL" -q -c "DROP DATABASE IF EXISTS $RESTORED_DB" || true
+  rm -rf "$WORK"
+}
+trap cleanup EXIT
+
+contents() {
+  unzip -p "$1" dewey-archive.json \
+    | jq -S '{migration, tables: [.tables[] | {name, rows, sha256}], blobs: [.blobs[] | .sha256] | sort}'
+}
+
+$DEWEY archive export "$WORK/original.zip"
+
+psql "$DATABASE_URL" -q -c "CREATE DATABASE $RESTORED_DB"
+DATABASE_URL="$RESTORED_URL" $DEWEY migrate
+DATABASE_URL="$RESTORED_URL" $DEWEY archive import "$WORK/original.zip"
+DATABASE_URL="$RESTORED_URL" $DEWEY archive export "$WORK/restored.zip"
+
+contents "$WORK/original.zip" > "$WORK/original.json"
+contents "$WORK/restored.zip" > "$WORK/restored.json"
+if diff -u "$WORK/original.json" "$WORK/restored.json"; then
+  echo "archive round trip ok: $(jq '.tables | length' "$WORK/original.json") tables, $(jq '.blobs | length' "$WORK/original.json") contents"
+else
+  echo "archive round trip FAILED: the restored database differs" >&2
+  exit 1
+fi
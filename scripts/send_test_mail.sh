@@ -0,0 +1,41 @@
+#!/bin/bash
+# Sends an email with FILE attached to the drop-box of a running server, to be
+# filed into COLLECTION_ID as the owner of TOKEN, a drop-box token of it.
+# Needs curl, and the server started with MAIL_LISTEN set to MAIL_LISTEN.
+set -euo pipefail
+
+if [ $# -lt 3 ]; then
+  echo "usage: $0 COLLECTION_ID TOKEN FILE" >&2
+  exit 2
+fi
+COLLECTION_ID="$1"
+TOKEN="$2"
+FILE="$3"
+MAIL_LISTEN="${MAIL_LISTEN:-127.0.0.1:2525}"
+RECIPIENT="$COLLECTION_ID+$TOKEN@localhost"
+BOUNDARY="dewey-$$"
+NAME="$(basename "$FILE")"
+
+MESSAGE="$(mktemp)"
+trap 'rm -f "$MESSAGE"' EXIT
+{
+  printf 'From: Test <test@localhost>\r\n'
+  printf 'To: <%s>\r\n' "$RECIPIENT"
+  printf 'Subject: %s\r\n' "$NAME"
+  printf 'Date: %s\r\n' "$(date -R)"
+  printf 'Message-ID: <%s.%s@localhost>\r\n' "$(date +%s)" "$$"
+  printf 'MIME-Version: 1.0\r\n'
+  printf 'Content-Type: multipart/mixed; boundary="%s"\r\n\r\n' "$BOUNDARY"
+  printf -- '--%s\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n' "$BOUNDARY"
+  printf 'Sent by %s\r\n' "$0"
+  printf -- '--%s\r\nContent-Type: application/octet-stream; name="%s"\r\n' "$BOUNDARY" "$NAME"
+  printf 'Content-Disposition: attachment; filename="%s"\r\n' "$NAME"
+  printf 'Content-Transfer-Encoding: base64\r\n\r\n'
+  base64 "$FILE" | sed 's/$/\r/'
+  printf -- '--%s--\r\n' "$BOUNDARY"
+} > "$MESSAGE"
+
+curl --silent --show-error --verbose "smtp://$MAIL_LISTEN" \
+  --mail-from "test@localhost" \
+  --mail-rcpt "$RECIPIENT" \
+  --upload-file "$MESSAGE" 2>&1 | grep -E '^[<>] '
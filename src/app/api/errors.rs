@@ -0,0 +1,35 @@
+use actix_web::http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use actix_web::middleware::{Middleware, Response};
+use actix_web::{HttpRequest, HttpResponse, Result};
+
+/// Gives every error response of the API a JSON body, `{"error": "..."}`,
+/// in place of the plain text of `db_error` and extractors. Responses which
+/// are JSON already, like invalid queries and values, are left alone.
+pub struct JsonErrors;
+
+impl<S> Middleware<S> for JsonErrors {
+    fn response(&self, _: &HttpRequest<S>, resp: HttpResponse) -> Result<Response> {
+        let status = resp.status();
+        let is_json = resp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |value| value.starts_with("application/json"));
+        if is_json || !(status.is_client_error() || status.is_server_error()) {
+            return Ok(Response::Done(resp));
+        }
+
+        // server errors may carry database details, which stay in the logs
+        let message = match resp.error() {
+            Some(e) if status.is_client_error() => e.to_string(),
+            _ => status.canonical_reason().unwrap_or("Error").to_string(),
+        };
+        let mut json = HttpResponse::build(status);
+        for (name, value) in resp.headers() {
+            if name != CONTENT_TYPE && name != CONTENT_LENGTH {
+                json.header(name.clone(), value.clone());
+            }
+        }
+        Ok(Response::Done(json.json(json!({ "error": message }))))
+    }
+}
@@ -0,0 +1,22 @@
+//! The versioned JSON API, `/api/v1`, described by `/api/v1/openapi.json`
+use actix_web::{HttpResponse, Scope};
+
+use crate::State;
+
+mod errors;
+mod objects;
+mod openapi;
+mod plugins;
+mod properties;
+mod users;
+mod webhooks;
+
+use errors::JsonErrors;
+
+/// Routes of the API, those of the operations `openapi` describes
+pub fn api_scope(scope: Scope<State>) -> Scope<State> {
+    let scope = scope
+        .middleware(JsonErrors)
+        .resource("/openapi.json", |r| r.f(openapi::openapi));
+    openapi::routes(scope).default_resource(|r| r.f(|_| HttpResponse::NotFound().finish()))
+}
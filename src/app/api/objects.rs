@@ -0,0 +1,44 @@
+use futures::Future;
+
+use actix_web::{error, FutureResponse, HttpRequest, HttpResponse, Path};
+
+use crate::db::GetObject;
+use crate::object::ObjectId;
+use crate::sessions::session_routes::require_session;
+use crate::State;
+
+/// `GET /api/v1/objects/{object_id}`, the object with every value it has set
+pub fn get_object(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(GetObject(object_id.clone()))
+            .from_err()
+            .and_then(|res| res)
+            .and_then(move |object| match object {
+                Some(object) => Ok(HttpResponse::Ok().json(object)),
+                None => Err(error::ErrorNotFound(format!("No object {}", object_id))),
+            })
+    }))
+}
+
+/// `GET /api/v1/objects/{object_id}/values`, in the shape `PUT` accepts them
+pub fn get_values(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(GetObject(object_id.clone()))
+            .from_err()
+            .and_then(|res| res)
+            .and_then(move |object| match object {
+                Some(object) => Ok(HttpResponse::Ok().json(json!({ "values": object.values }))),
+                None => Err(error::ErrorNotFound(format!("No object {}", object_id))),
+            })
+    }))
+}
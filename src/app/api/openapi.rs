@@ -0,0 +1,1130 @@
+//! The OpenAPI 3 description of `/api/v1`, generated from the table of
+//! operations below, which `api_scope` registers the routes of.
+use actix_web::dev::Route;
+use actix_web::{http, HttpRequest, HttpResponse, Scope};
+use serde_json::{Map, Value};
+
+use super::{objects, plugins, properties, users, webhooks};
+use crate::app::{
+    history, query, relations, schemas, search, spreadsheets, upload, values, versions,
+};
+use crate::State;
+
+/// Request or response body
+#[derive(Clone, Copy)]
+enum Body {
+    /// A schema of `components/schemas`
+    Json(&'static str),
+    /// An array of a schema
+    List(&'static str),
+    /// `multipart/form-data` files
+    Files,
+    /// Raw content of an object version
+    Content,
+    /// `text/csv` rows below a header
+    Csv,
+    Empty,
+}
+
+struct Operation {
+    method: &'static str,
+    path: &'static str,
+    /// Registers the handler of the operation on its route
+    handler: fn(&mut Route<State>),
+    id: &'static str,
+    tag: &'static str,
+    summary: &'static str,
+    /// Query parameters and their types
+    query: &'static [(&'static str, &'static str)],
+    request: Body,
+    status: u16,
+    response: Body,
+}
+
+const OPERATIONS: &[Operation] = &[
+    Operation {
+        method: "get",
+        path: "/objects",
+        handler: |route| route.with(query::query_objects),
+        id: "queryObjects",
+        tag: "objects",
+        summary: "Objects matching a query, a page at a time",
+        query: &[
+            ("q", "string"),
+            ("sort", "string"),
+            ("page", "integer"),
+            ("per_page", "integer"),
+            ("after", "string"),
+        ],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Json("ObjectPage"),
+    },
+    Operation {
+        method: "post",
+        path: "/objects",
+        handler: |route| route.with(upload::upload),
+        id: "uploadObjects",
+        tag: "objects",
+        summary: "Create an object of each uploaded file, and of the attachments of emails \
+                  unless split is false",
+        query: &[("collection", "integer"), ("split", "boolean")],
+        request: Body::Files,
+        status: 200,
+        response: Body::List("ObjectId"),
+    },
+    Operation {
+        method: "get",
+        path: "/objects/facets",
+        handler: |route| route.with(query::facets),
+        id: "objectFacets",
+        tag: "objects",
+        summary: "Counts of the choices and timestamps of the objects matching a query",
+        query: &[("q", "string"), ("interval", "string")],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Json("Facets"),
+    },
+    Operation {
+        method: "get",
+        path: "/objects/csv",
+        handler: |route| route.with(spreadsheets::export_csv),
+        id: "exportObjectsCsv",
+        tag: "objects",
+        summary: "The objects matching a query as CSV, with a column for every property",
+        query: &[("q", "string"), ("sort", "string"), ("delimiter", "string")],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Csv,
+    },
+    Operation {
+        method: "post",
+        path: "/objects/csv",
+        handler: |route| route.with(spreadsheets::import_csv),
+        id: "importObjectsCsv",
+        tag: "objects",
+        summary: "Set the values of the objects named by id or Hash in each row of a CSV",
+        query: &[("dry_run", "boolean"), ("delimiter", "string")],
+        request: Body::Csv,
+        status: 200,
+        response: Body::Json("ImportReport"),
+    },
+    Operation {
+        method: "get",
+        path: "/objects/{object_id}",
+        handler: |route| route.with(objects::get_object),
+        id: "getObject",
+        tag: "objects",
+        summary: "An object with every value it has set",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Json("Object"),
+    },
+    Operation {
+        method: "get",
+        path: "/objects/{object_id}/content",
+        handler: |route| route.with(versions::download_current),
+        id: "downloadObject",
+        tag: "objects",
+        summary: "Content of the object's current version",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Content,
+    },
+    Operation {
+        method: "get",
+        path: "/objects/{object_id}/history",
+        handler: |route| route.with(history::history),
+        id: "objectHistory",
+        tag: "objects",
+        summary: "Changes to the object's values, newest first",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("ValueChange"),
+    },
+    Operation {
+        method: "get",
+        path: "/objects/{object_id}/relations",
+        handler: |route| route.with(relations::relations),
+        id: "objectRelations",
+        tag: "objects",
+        summary: "Objects the object relates to, or which relate to it",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("Relation"),
+    },
+    Operation {
+        method: "get",
+        path: "/objects/{object_id}/versions",
+        handler: |route| route.with(versions::list_versions),
+        id: "listVersions",
+        tag: "objects",
+        summary: "Versions of the object's content, newest first",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("ObjectVersion"),
+    },
+    Operation {
+        method: "post",
+        path: "/objects/{object_id}/versions",
+        handler: |route| route.with(versions::add_version),
+        id: "addVersion",
+        tag: "objects",
+        summary: "Upload a single file as the object's new version",
+        query: &[],
+        request: Body::Files,
+        status: 201,
+        response: Body::Json("Version"),
+    },
+    Operation {
+        method: "get",
+        path: "/objects/{object_id}/values",
+        handler: |route| route.with(objects::get_values),
+        id: "getValues",
+        tag: "values",
+        summary: "Values the object has set",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Json("ValueUpdates"),
+    },
+    Operation {
+        method: "put",
+        path: "/objects/{object_id}/values",
+        handler: |route| route.with(values::set_values),
+        id: "setValues",
+        tag: "values",
+        summary: "Replace values of the object, nothing is saved unless all are valid",
+        query: &[],
+        request: Body::Json("ValueUpdates"),
+        status: 204,
+        response: Body::Empty,
+    },
+    Operation {
+        method: "put",
+        path: "/objects/{object_id}/values/{property_id}",
+        handler: |route| route.with(values::set_value),
+        id: "setValue",
+        tag: "values",
+        summary: "Replace the object's value of one property",
+        query: &[],
+        request: Body::Json("PropertyValue"),
+        status: 204,
+        response: Body::Empty,
+    },
+    Operation {
+        method: "get",
+        path: "/search",
+        handler: |route| route.with(search::search),
+        id: "search",
+        tag: "objects",
+        summary: "Objects whose text matches, best first",
+        query: &[
+            ("text", "string"),
+            ("lang", "string"),
+            ("page", "integer"),
+            ("per_page", "integer"),
+        ],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Json("SearchResults"),
+    },
+    Operation {
+        method: "get",
+        path: "/properties",
+        handler: |route| route.with(properties::list_properties),
+        id: "listProperties",
+        tag: "properties",
+        summary: "Every property in display order",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("Property"),
+    },
+    Operation {
+        method: "post",
+        path: "/properties",
+        handler: |route| route.with(properties::create_property),
+        id: "createProperty",
+        tag: "properties",
+        summary: "Create a property, computed properties need a formula",
+        query: &[],
+        request: Body::Json("NewProperty"),
+        status: 201,
+        response: Body::Json("Created"),
+    },
+    Operation {
+        method: "put",
+        path: "/properties/{property_id}/rules",
+        handler: |route| route.with(crate::app::properties::set_rules),
+        id: "setRules",
+        tag: "properties",
+        summary: "Replace the property's validation rules",
+        query: &[],
+        request: Body::Json("ValidationRules"),
+        status: 204,
+        response: Body::Empty,
+    },
+    Operation {
+        method: "get",
+        path: "/properties/{property_id}/choices",
+        handler: |route| route.with(properties::list_choices),
+        id: "listChoices",
+        tag: "choices",
+        summary: "Choices of a choice property, alphabetically",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("Choice"),
+    },
+    Operation {
+        method: "post",
+        path: "/properties/{property_id}/choices",
+        handler: |route| route.with(properties::create_choice),
+        id: "createChoice",
+        tag: "choices",
+        summary: "Add a choice to a choice property",
+        query: &[],
+        request: Body::Json("NewChoice"),
+        status: 201,
+        response: Body::Json("Created"),
+    },
+    Operation {
+        method: "get",
+        path: "/collections",
+        handler: |route| route.with(properties::list_collections),
+        id: "listCollections",
+        tag: "collections",
+        summary: "Collections with the schema each one uses",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("Collection"),
+    },
+    Operation {
+        method: "post",
+        path: "/collections",
+        handler: |route| route.with(properties::create_collection),
+        id: "createCollection",
+        tag: "collections",
+        summary: "Create a collection",
+        query: &[],
+        request: Body::Json("NewChoice"),
+        status: 201,
+        response: Body::Json("Created"),
+    },
+    Operation {
+        method: "put",
+        path: "/collections/{collection_id}/schema",
+        handler: |route| route.with(schemas::set_collection_schema),
+        id: "setCollectionSchema",
+        tag: "collections",
+        summary: "Use a schema for the collection's objects, or `null` to stop",
+        query: &[],
+        request: Body::Json("CollectionSchema"),
+        status: 204,
+        response: Body::Empty,
+    },
+    Operation {
+        method: "get",
+        path: "/schemas",
+        handler: |route| route.with(schemas::list_schemas),
+        id: "listSchemas",
+        tag: "collections",
+        summary: "Every schema with its fields",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("PropertySchema"),
+    },
+    Operation {
+        method: "post",
+        path: "/schemas",
+        handler: |route| route.with(schemas::create_schema),
+        id: "createSchema",
+        tag: "collections",
+        summary: "Create a schema",
+        query: &[],
+        request: Body::Json("NewPropertySchema"),
+        status: 201,
+        response: Body::Json("Created"),
+    },
+    Operation {
+        method: "get",
+        path: "/plugins",
+        handler: |route| route.with(plugins::list_plugins),
+        id: "listPlugins",
+        tag: "plugins",
+        summary: "Every plugin, by name",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("Plugin"),
+    },
+    Operation {
+        method: "post",
+        path: "/plugins",
+        handler: |route| route.with(plugins::register_plugin),
+        id: "registerPlugin",
+        tag: "plugins",
+        summary: "Register a plugin as a user of its own, with a first token, administrators only",
+        query: &[],
+        request: Body::Json("NewPlugin"),
+        status: 201,
+        response: Body::Json("RegisteredPlugin"),
+    },
+    Operation {
+        method: "delete",
+        path: "/plugins/{name}",
+        handler: |route| route.with(plugins::disable_plugin),
+        id: "disablePlugin",
+        tag: "plugins",
+        summary: "Revoke the plugin's tokens and refuse it new ones, administrators only",
+        query: &[],
+        request: Body::Empty,
+        status: 204,
+        response: Body::Empty,
+    },
+    Operation {
+        method: "post",
+        path: "/plugins/{name}/tokens",
+        handler: |route| route.with(plugins::issue_token),
+        id: "issuePluginToken",
+        tag: "plugins",
+        summary: "Another token for the plugin, administrators only",
+        query: &[],
+        request: Body::Json("NewPluginToken"),
+        status: 201,
+        response: Body::Json("PluginToken"),
+    },
+    Operation {
+        method: "get",
+        path: "/webhooks",
+        handler: |route| route.with(webhooks::list_webhooks),
+        id: "listWebhooks",
+        tag: "webhooks",
+        summary: "The signed in user's webhooks, newest first",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("Webhook"),
+    },
+    Operation {
+        method: "post",
+        path: "/webhooks",
+        handler: |route| route.with(webhooks::create_webhook),
+        id: "createWebhook",
+        tag: "webhooks",
+        summary: "Subscribe a URL to events, signed with the webhook's secret",
+        query: &[],
+        request: Body::Json("NewWebhook"),
+        status: 201,
+        response: Body::Json("CreatedWebhook"),
+    },
+    Operation {
+        method: "delete",
+        path: "/webhooks/{webhook_id}",
+        handler: |route| route.with(webhooks::delete_webhook),
+        id: "deleteWebhook",
+        tag: "webhooks",
+        summary: "Unsubscribe, dropping the webhook's deliveries",
+        query: &[],
+        request: Body::Empty,
+        status: 204,
+        response: Body::Empty,
+    },
+    Operation {
+        method: "get",
+        path: "/webhooks/{webhook_id}/deliveries",
+        handler: |route| route.with(webhooks::list_deliveries),
+        id: "listDeliveries",
+        tag: "webhooks",
+        summary: "The webhook's latest deliveries and their attempts, newest first",
+        query: &[("limit", "integer")],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("Delivery"),
+    },
+    Operation {
+        method: "post",
+        path: "/webhooks/{webhook_id}/ping",
+        handler: |route| route.with(webhooks::ping_webhook),
+        id: "pingWebhook",
+        tag: "webhooks",
+        summary: "Queue a ping event to the webhook, to check its receiver",
+        query: &[],
+        request: Body::Empty,
+        status: 202,
+        response: Body::Json("Delivery"),
+    },
+    Operation {
+        method: "get",
+        path: "/users",
+        handler: |route| route.with(users::list_users),
+        id: "listUsers",
+        tag: "users",
+        summary: "Every person and plugin",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::List("User"),
+    },
+    Operation {
+        method: "get",
+        path: "/users/me",
+        handler: |route| route.with(users::current_user),
+        id: "currentUser",
+        tag: "users",
+        summary: "The signed in user",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Json("User"),
+    },
+    Operation {
+        method: "get",
+        path: "/users/{user_id}",
+        handler: |route| route.with(users::get_user),
+        id: "getUser",
+        tag: "users",
+        summary: "A user's profile",
+        query: &[],
+        request: Body::Empty,
+        status: 200,
+        response: Body::Json("User"),
+    },
+];
+
+impl Operation {
+    fn http_method(&self) -> http::Method {
+        http::Method::from_bytes(self.method.to_uppercase().as_bytes()).unwrap()
+    }
+}
+
+/// A resource for each path of `OPERATIONS`, routing each of its methods
+pub fn routes(mut scope: Scope<State>) -> Scope<State> {
+    let mut paths: Vec<&'static str> = Vec::new();
+    for op in OPERATIONS {
+        if !paths.contains(&op.path) {
+            paths.push(op.path);
+        }
+    }
+    for path in paths {
+        scope = scope.resource(path, move |r| {
+            for op in OPERATIONS.iter().filter(|op| op.path == path) {
+                (op.handler)(r.method(op.http_method()));
+            }
+        });
+    }
+    scope
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn content(body: Body) -> Option<Value> {
+    match body {
+        Body::Json(name) => Some(json!({ "application/json": { "schema": schema_ref(name) } })),
+        Body::List(name) => Some(json!({
+            "application/json": { "schema": { "type": "array", "items": schema_ref(name) } }
+        })),
+        Body::Files => Some(json!({
+            "multipart/form-data": {
+                "schema": {
+                    "type": "object",
+                    "properties": {
+                        "file": { "type": "array", "items": { "type": "string", "format": "binary" } }
+                    }
+                }
+            }
+        })),
+        Body::Content => Some(json!({
+            "application/octet-stream": { "schema": { "type": "string", "format": "binary" } }
+        })),
+        Body::Csv => Some(json!({ "text/csv": { "schema": { "type": "string" } } })),
+        Body::Empty => None,
+    }
+}
+
+/// Parameters of the `{name}`s of a path, object ids and names are strings, other ids integers
+fn path_parameters(path: &str) -> Vec<Value> {
+    path.split('/')
+        .filter(|part| part.starts_with('{') && part.ends_with('}'))
+        .map(|part| {
+            let name = &part[1..part.len() - 1];
+            let kind = match name {
+                "object_id" | "name" => "string",
+                _ => "integer",
+            };
+            json!({ "name": name, "in": "path", "required": true, "schema": { "type": kind } })
+        })
+        .collect()
+}
+
+fn operation(op: &Operation) -> Value {
+    let mut parameters = path_parameters(op.path);
+    parameters.extend(op.query.iter().map(|(name, kind)| {
+        json!({ "name": name, "in": "query", "required": false, "schema": { "type": kind } })
+    }));
+
+    let mut success = json!({ "description": op.summary });
+    if let Some(body) = content(op.response) {
+        success["content"] = body;
+    }
+    let mut responses = Map::new();
+    responses.insert(op.status.to_string(), success);
+    responses.insert(
+        "default".to_string(),
+        json!({
+            "description": "Error",
+            "content": { "application/json": { "schema": schema_ref("Error") } }
+        }),
+    );
+
+    let mut value = json!({
+        "operationId": op.id,
+        "tags": [op.tag],
+        "summary": op.summary,
+        "parameters": parameters,
+        "responses": responses,
+    });
+    if let Some(body) = content(op.request) {
+        value["requestBody"] = json!({ "required": true, "content": body });
+    }
+    value
+}
+
+fn schemas() -> Value {
+    let id = json!({ "type": "integer", "format": "int64" });
+    let timestamp = json!({ "type": "string", "format": "date-time" });
+    json!({
+        "Error": {
+            "type": "object",
+            "required": ["error"],
+            "properties": {
+                "error": { "type": "string" },
+                "position": {
+                    "type": "integer",
+                    "description": "Character of the query the error is at"
+                },
+                "errors": {
+                    "type": "array",
+                    "description": "Invalid values, one per property",
+                    "items": {
+                        "type": "object",
+                        "properties": { "property_id": id, "message": { "type": "string" } }
+                    }
+                }
+            }
+        },
+        "Created": { "type": "object", "properties": { "id": id } },
+        "Version": { "type": "object", "properties": { "version": { "type": "integer" } } },
+        "ObjectId": { "type": "string" },
+        "ObjectSummary": {
+            "type": "object",
+            "properties": {
+                "id": schema_ref("ObjectId"),
+                "filename": { "type": "string", "nullable": true },
+                "extension": { "type": "string" },
+                "created_at": timestamp
+            }
+        },
+        "ObjectPage": {
+            "type": "object",
+            "properties": {
+                "objects": { "type": "array", "items": schema_ref("ObjectSummary") },
+                "total": { "type": "integer" },
+                "page": { "type": "integer" },
+                "per_page": { "type": "integer" },
+                "next_cursor": {
+                    "type": "string",
+                    "nullable": true,
+                    "description": "Pass as `after` for the next page"
+                }
+            }
+        },
+        "Object": {
+            "type": "object",
+            "properties": {
+                "id": schema_ref("ObjectId"),
+                "filename": { "type": "string", "nullable": true },
+                "extension": { "type": "string" },
+                "created_by": id,
+                "created_at": timestamp,
+                "current_version": { "type": "integer", "nullable": true },
+                "values": { "type": "array", "items": schema_ref("ValueUpdate") },
+                "computed": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "property_id": id,
+                            "value": { "type": "number", "nullable": true }
+                        }
+                    }
+                }
+            }
+        },
+        "ObjectVersion": {
+            "type": "object",
+            "properties": {
+                "object_id": schema_ref("ObjectId"),
+                "version": { "type": "integer" },
+                "hash": { "type": "string" },
+                "size": { "type": "integer" },
+                "filename": { "type": "string" },
+                "created_by": id,
+                "created_at": timestamp
+            }
+        },
+        "PropertyValue": {
+            "description": "A value tagged by the type of its property",
+            "oneOf": [
+                {
+                    "type": "object",
+                    "properties": { "text": { "type": "string" } }
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "timestamptz": { "type": "string", "format": "date-time", "nullable": true }
+                    }
+                },
+                {
+                    "type": "object",
+                    "properties": { "relation": { "type": "array", "items": schema_ref("ObjectId") } }
+                },
+                {
+                    "type": "object",
+                    "properties": { "choice": { "type": "array", "items": id } }
+                }
+            ]
+        },
+        "ValueUpdate": {
+            "type": "object",
+            "properties": { "property_id": id, "value": schema_ref("PropertyValue") }
+        },
+        "ValueUpdates": {
+            "type": "object",
+            "properties": { "values": { "type": "array", "items": schema_ref("ValueUpdate") } }
+        },
+        "ImportReport": {
+            "type": "object",
+            "properties": {
+                "dry_run": { "type": "boolean" },
+                "key": { "type": "string", "enum": ["id", "hash"] },
+                "updated": { "type": "integer" },
+                "unchanged": { "type": "integer" },
+                "failed": { "type": "integer" },
+                "rows": {
+                    "type": "array",
+                    "description": "The rows which changed, or would on a dry run, and those which failed",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "line": { "type": "integer" },
+                            "key": { "type": "string" },
+                            "object_id": { "type": "string", "nullable": true },
+                            "changes": {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "property_id": id,
+                                        "property": { "type": "string" },
+                                        "old_value": { "type": "string" },
+                                        "new_value": { "type": "string" }
+                                    }
+                                }
+                            },
+                            "errors": { "type": "array", "items": { "type": "string" } }
+                        }
+                    }
+                }
+            }
+        },
+        "ValueChange": {
+            "type": "object",
+            "properties": {
+                "property_id": id,
+                "property": { "type": "string", "nullable": true },
+                "old_value": { "nullable": true },
+                "new_value": { "nullable": true },
+                "old_display": { "type": "string", "nullable": true },
+                "new_display": { "type": "string", "nullable": true },
+                "changed_by": id,
+                "changed_by_name": { "type": "string", "nullable": true },
+                "changed_by_plugin": { "type": "boolean" },
+                "changed_at": timestamp,
+                "request_id": { "type": "string", "nullable": true }
+            }
+        },
+        "Relation": {
+            "type": "object",
+            "properties": {
+                "property_id": id,
+                "direction": { "type": "string", "enum": ["outgoing", "incoming"] },
+                "label": { "type": "string" },
+                "object_id": schema_ref("ObjectId"),
+                "filename": { "type": "string", "nullable": true }
+            }
+        },
+        "Facets": {
+            "type": "object",
+            "properties": {
+                "total": { "type": "integer" },
+                "choices": { "type": "array", "items": { "type": "object" } },
+                "timestamps": { "type": "array", "items": { "type": "object" } }
+            }
+        },
+        "SearchResults": {
+            "type": "object",
+            "properties": {
+                "hits": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "object_id": schema_ref("ObjectId"),
+                            "filename": { "type": "string", "nullable": true },
+                            "rank": { "type": "number" },
+                            "snippet": { "type": "string" }
+                        }
+                    }
+                },
+                "total": { "type": "integer" },
+                "page": { "type": "integer" },
+                "per_page": { "type": "integer" }
+            }
+        },
+        "PropertyType": {
+            "type": "string",
+            "enum": ["timestamptz", "text", "relation", "choice", "computed"]
+        },
+        "Property": {
+            "type": "object",
+            "properties": {
+                "id": id,
+                "display": { "type": "string" },
+                "kind": schema_ref("PropertyType"),
+                "formula": { "type": "string", "nullable": true },
+                "inverse_display": { "type": "string", "nullable": true },
+                "created_by": id,
+                "created_at": timestamp
+            }
+        },
+        "NewProperty": {
+            "type": "object",
+            "required": ["display", "kind"],
+            "properties": {
+                "display": { "type": "string" },
+                "kind": schema_ref("PropertyType"),
+                "formula": { "type": "string", "description": "e.g. `days_since({3})`" },
+                "inverse_display": { "type": "string" }
+            }
+        },
+        "ValidationRules": {
+            "type": "object",
+            "properties": {
+                "required": { "type": "boolean" },
+                "pattern": { "type": "string", "nullable": true },
+                "min_length": { "type": "integer", "nullable": true },
+                "max_length": { "type": "integer", "nullable": true },
+                "min_number": { "type": "number", "nullable": true },
+                "max_number": { "type": "number", "nullable": true },
+                "multiple": { "type": "boolean" },
+                "target_extensions": {
+                    "type": "array",
+                    "nullable": true,
+                    "items": { "type": "string" }
+                }
+            }
+        },
+        "Choice": {
+            "type": "object",
+            "properties": { "id": id, "property_id": id, "display": { "type": "string" } }
+        },
+        "NewChoice": {
+            "type": "object",
+            "required": ["display"],
+            "properties": { "display": { "type": "string" } }
+        },
+        "Collection": {
+            "type": "object",
+            "properties": {
+                "id": id,
+                "display": { "type": "string" },
+                "schema_id": { "type": "integer", "nullable": true }
+            }
+        },
+        "CollectionSchema": {
+            "type": "object",
+            "properties": { "schema_id": { "type": "integer", "nullable": true } }
+        },
+        "PropertySchemaField": {
+            "type": "object",
+            "properties": {
+                "property_id": id,
+                "ord": { "type": "number" },
+                "required": { "type": "boolean" },
+                "default_value": schema_ref("PropertyValue")
+            }
+        },
+        "PropertySchema": {
+            "type": "object",
+            "properties": {
+                "id": id,
+                "display": { "type": "string" },
+                "fields": { "type": "array", "items": schema_ref("PropertySchemaField") }
+            }
+        },
+        "NewPropertySchema": {
+            "type": "object",
+            "required": ["display", "fields"],
+            "properties": {
+                "display": { "type": "string" },
+                "fields": { "type": "array", "items": schema_ref("PropertySchemaField") }
+            }
+        },
+        "TokenScope": { "type": "string", "enum": ["read", "write"] },
+        "Plugin": {
+            "type": "object",
+            "properties": {
+                "user_id": id,
+                "name": { "type": "string" },
+                "display_name": { "type": "string" },
+                "description": { "type": "string" },
+                "homepage": { "type": "string", "nullable": true },
+                "registered_by": id,
+                "registered_at": timestamp,
+                "disabled_at": { "type": "string", "format": "date-time", "nullable": true }
+            }
+        },
+        "NewPlugin": {
+            "type": "object",
+            "required": ["name", "display_name"],
+            "properties": {
+                "name": { "type": "string", "pattern": "^[a-z0-9][a-z0-9-]*$" },
+                "display_name": { "type": "string" },
+                "description": { "type": "string" },
+                "homepage": { "type": "string" },
+                "scope": schema_ref("TokenScope")
+            }
+        },
+        "RegisteredPlugin": {
+            "type": "object",
+            "properties": {
+                "plugin": schema_ref("Plugin"),
+                "token": { "type": "string", "description": "Only shown once" }
+            }
+        },
+        "NewPluginToken": {
+            "type": "object",
+            "required": ["display"],
+            "properties": {
+                "display": { "type": "string" },
+                "scope": schema_ref("TokenScope"),
+                "expires_in_days": { "type": "integer" }
+            }
+        },
+        "PluginToken": {
+            "type": "object",
+            "properties": { "token": { "type": "string", "description": "Only shown once" } }
+        },
+        "WebhookEvent": {
+            "type": "string",
+            "enum": ["object_created", "value_changed", "object_deleted", "collection_changed", "ping"]
+        },
+        "Webhook": {
+            "type": "object",
+            "properties": {
+                "id": id,
+                "url": { "type": "string" },
+                "events": {
+                    "type": "array",
+                    "items": schema_ref("WebhookEvent"),
+                    "description": "Every event when empty"
+                },
+                "created_by": id,
+                "created_at": timestamp
+            }
+        },
+        "NewWebhook": {
+            "type": "object",
+            "required": ["url"],
+            "properties": {
+                "url": {
+                    "type": "string",
+                    "pattern": "^https?://",
+                    "description": "Refused when its host resolves to a loopback, link-local or private address"
+                },
+                "secret": { "type": "string", "description": "Generated when not given" },
+                "events": { "type": "array", "items": schema_ref("WebhookEvent") }
+            }
+        },
+        "CreatedWebhook": {
+            "type": "object",
+            "properties": {
+                "webhook": schema_ref("Webhook"),
+                "secret": {
+                    "type": "string",
+                    "description": "Key of the HMAC-SHA256 in `X-Dewey-Signature`, only shown once"
+                }
+            }
+        },
+        "Delivery": {
+            "type": "object",
+            "properties": {
+                "id": id,
+                "webhook_id": id,
+                "event": schema_ref("WebhookEvent"),
+                "payload": { "type": "object" },
+                "status": { "type": "string", "enum": ["pending", "delivered", "failed"] },
+                "attempts": { "type": "integer" },
+                "next_attempt_at": timestamp,
+                "response_status": { "type": "integer", "nullable": true },
+                "response_body": { "type": "string", "nullable": true },
+                "error": { "type": "string", "nullable": true },
+                "created_at": timestamp,
+                "delivered_at": { "type": "string", "format": "date-time", "nullable": true }
+            }
+        },
+        "User": {
+            "type": "object",
+            "properties": {
+                "id": id,
+                "kind": { "type": "string", "enum": ["person", "reserved", "plugin"] },
+                "display_name": { "type": "string" },
+                "full_name": { "type": "string" },
+                "public_email": { "type": "string", "nullable": true },
+                "photo_url": { "type": "string", "nullable": true },
+                "admin": { "type": "boolean" },
+                "disabled_at": { "type": "string", "format": "date-time", "nullable": true }
+            }
+        }
+    })
+}
+
+/// The whole document
+pub fn document() -> Value {
+    let mut paths = Map::new();
+    for op in OPERATIONS {
+        let item = paths
+            .entry(op.path.to_string())
+            .or_insert_with(|| Value::Object(Map::new()));
+        item[op.method] = operation(op);
+    }
+
+    json!({
+        "openapi": "3.0.2",
+        "info": {
+            "title": "Dewey",
+            "version": "1",
+            "description": "Objects, their properties and values, collections, users and webhooks"
+        },
+        "servers": [{ "url": "/api/v1" }],
+        "paths": paths,
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "session": { "type": "apiKey", "in": "cookie", "name": "sess" },
+                "token": {
+                    "type": "http",
+                    "scheme": "bearer",
+                    "description": "A personal API token from `/tokens`, read only tokens may only `GET`"
+                }
+            }
+        },
+        "security": [{ "token": [] }, { "session": [] }]
+    })
+}
+
+/// `GET /api/v1/openapi.json`
+pub fn openapi(_: &HttpRequest<State>) -> HttpResponse {
+    HttpResponse::Ok().json(document())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn operations_are_distinct() {
+        for (i, op) in OPERATIONS.iter().enumerate() {
+            for other in &OPERATIONS[i + 1..] {
+                assert!(
+                    op.method != other.method || op.path != other.path,
+                    "{} {} is routed twice",
+                    op.method,
+                    op.path
+                );
+                assert_ne!(op.id, other.id, "{} names two operations", op.id);
+            }
+        }
+    }
+
+    #[test]
+    fn no_path_shadows_a_later_one() {
+        let matches = |pattern: &str, path: &str| {
+            let (pattern, path): (Vec<_>, Vec<_>) =
+                (pattern.split('/').collect(), path.split('/').collect());
+            pattern.len() == path.len()
+                && pattern
+                    .iter()
+                    .zip(&path)
+                    .all(|(p, s)| p == s || p.starts_with('{'))
+        };
+        for (i, op) in OPERATIONS.iter().enumerate() {
+            for later in &OPERATIONS[i + 1..] {
+                if later.path != op.path {
+                    assert!(
+                        !matches(op.path, later.path),
+                        "{} is routed before {}, which it matches",
+                        op.path,
+                        later.path
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn operations_have_http_methods() {
+        for op in OPERATIONS {
+            assert!(
+                ["get", "post", "put", "delete"].contains(&op.method),
+                "{} is not a method of {}",
+                op.method,
+                op.path
+            );
+            assert_eq!(op.http_method().as_str().to_lowercase(), op.method);
+        }
+    }
+
+    #[test]
+    fn every_operation_is_documented() {
+        let document = document();
+        for op in OPERATIONS {
+            let documented = &document["paths"][op.path][op.method];
+            assert_eq!(documented["operationId"], op.id);
+            let parameters = documented["parameters"].as_array().unwrap();
+            for segment in op.path.split('/').filter(|s| s.starts_with('{')) {
+                let name = segment.trim_start_matches('{').trim_end_matches('}');
+                assert!(
+                    parameters
+                        .iter()
+                        .any(|p| p["name"] == name && p["in"] == "path"),
+                    "{} of {} is not described",
+                    name,
+                    op.path
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use chrono::{Duration, Utc};
+use futures::future;
+use futures::Future;
+
+use actix_web::{error, Error, FutureResponse, HttpRequest, HttpResponse, Json, Path};
+
+use crate::db::{DisablePlugin, GetUser, IssuePluginToken, ListPlugins, RegisterPlugin};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::{TokenScope, UserSession};
+use crate::user::UserKind;
+use crate::State;
+
+/// Resolves to the session of an administrator, only they may manage plugins
+fn require_admin(req: &HttpRequest<State>) -> impl Future<Item = UserSession, Error = Error> {
+    let db = req.state().db.clone();
+
+    require_session(req).and_then(move |session: UserSession| {
+        db.send(GetUser(session.key.user_id.clone()))
+            .from_err()
+            .and_then(|res| res)
+            .and_then(move |user| match user {
+                Some(ref user) if user.admin && user.kind == UserKind::Person => Ok(session),
+                _ => Err(error::ErrorForbidden("Only administrators may manage plugins")),
+            })
+    })
+}
+
+/// `GET /api/v1/plugins`
+pub fn list_plugins(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(ListPlugins)
+            .from_err()
+            .and_then(|res| res)
+            .map(|plugins| HttpResponse::Ok().json(plugins))
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NewPlugin {
+    pub name: String,
+    pub display_name: String,
+    #[serde(default)]
+    pub description: String,
+    pub homepage: Option<String>,
+    #[serde(default)]
+    pub scope: TokenScope,
+}
+
+/// `POST /api/v1/plugins` with `{"name", "display_name", "description", "homepage", "scope"}`
+///
+/// Responds with `{"plugin", "token"}`, the token is not shown again.
+pub fn register_plugin(
+    (req, body): (HttpRequest<State>, Json<NewPlugin>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let plugin = body.into_inner();
+
+    Box::new(require_admin(&req).and_then(move |session| {
+        db.send(RegisterPlugin {
+            name: plugin.name,
+            display_name: plugin.display_name,
+            description: plugin.description,
+            homepage: plugin.homepage,
+            scope: plugin.scope,
+            registered_by: session.key.user_id,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|(plugin, token)| {
+            HttpResponse::Created().json(json!({ "plugin": plugin, "token": token }))
+        })
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NewPluginToken {
+    pub display: String,
+    #[serde(default)]
+    pub scope: TokenScope,
+    pub expires_in_days: Option<i64>,
+}
+
+/// `POST /api/v1/plugins/{name}/tokens` with `{"display", "scope", "expires_in_days"}`,
+/// responds with `{"token"}`
+pub fn issue_token(
+    (req, path, body): (HttpRequest<State>, Path<String>, Json<NewPluginToken>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let name = path.into_inner();
+    let token = body.into_inner();
+    let expires_at = match token.expires_in_days {
+        Some(days) if days <= 0 => {
+            return Box::new(future::err(error::ErrorBadRequest(
+                "Expiry must be a positive number of days",
+            )))
+        }
+        Some(days) => Some(Utc::now() + Duration::days(days)),
+        None => None,
+    };
+
+    Box::new(require_admin(&req).and_then(move |_| {
+        db.send(IssuePluginToken {
+            name,
+            display: token.display,
+            scope: token.scope,
+            expires_at,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|token| HttpResponse::Created().json(json!({ "token": token })))
+    }))
+}
+
+/// `DELETE /api/v1/plugins/{name}`, revokes the plugin's tokens
+pub fn disable_plugin(
+    (req, path): (HttpRequest<State>, Path<String>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let name = path.into_inner();
+
+    Box::new(require_admin(&req).and_then(move |_| {
+        db.send(DisablePlugin { name })
+            .from_err()
+            .and_then(|res| res)
+            .map(|_| HttpResponse::NoContent().finish())
+    }))
+}
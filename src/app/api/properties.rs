@@ -0,0 +1,141 @@
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::{error, FutureResponse, HttpRequest, HttpResponse, Json, Path};
+
+use crate::db::{
+    CreateChoice, CreateComputedProperty, CreateProperty, ListChoices, ListCollections,
+    ListProperties,
+};
+use crate::property::{PropertyId, PropertyType};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::State;
+
+/// `GET /api/v1/properties`
+pub fn list_properties(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(ListProperties)
+            .from_err()
+            .and_then(|res| res)
+            .map(|properties| HttpResponse::Ok().json(properties))
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NewProperty {
+    pub display: String,
+    pub kind: PropertyType,
+    /// Required for computed properties
+    pub formula: Option<String>,
+    pub inverse_display: Option<String>,
+}
+
+/// `POST /api/v1/properties` with `{"display", "kind", "formula", "inverse_display"}`,
+/// responds with the new property's id
+pub fn create_property(
+    (req, body): (HttpRequest<State>, Json<NewProperty>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let property = body.into_inner();
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            let created = match (property.kind, property.formula) {
+                (PropertyType::Computed, Some(formula)) => Either::A(Either::A(
+                    db.send(CreateComputedProperty {
+                        display: property.display,
+                        formula,
+                        created_by: session.key.user_id,
+                    })
+                    .from_err()
+                    .and_then(|res| res),
+                )),
+                (PropertyType::Computed, None) => Either::B(future::err(
+                    error::ErrorBadRequest("Computed properties need a formula"),
+                )),
+                (kind, _) => Either::A(Either::B(
+                    db.send(CreateProperty {
+                        display: property.display,
+                        kind,
+                        inverse_display: property.inverse_display,
+                        created_by: session.key.user_id,
+                    })
+                    .from_err()
+                    .and_then(|res| res),
+                )),
+            };
+            created.map(|id| HttpResponse::Created().json(json!({ "id": id })))
+        }),
+    )
+}
+
+/// `GET /api/v1/properties/{property_id}/choices`
+pub fn list_choices(
+    (req, path): (HttpRequest<State>, Path<PropertyId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let property_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(ListChoices { property_id })
+            .from_err()
+            .and_then(|res| res)
+            .map(|choices| HttpResponse::Ok().json(choices))
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NewChoice {
+    pub display: String,
+}
+
+fn add_choice(
+    req: &HttpRequest<State>,
+    property_id: PropertyId,
+    display: String,
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(
+        require_session(req).and_then(move |session: UserSession| {
+            db.send(CreateChoice {
+                property_id,
+                display,
+                created_by: session.key.user_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|id| HttpResponse::Created().json(json!({ "id": id })))
+        }),
+    )
+}
+
+/// `POST /api/v1/properties/{property_id}/choices` with `{"display"}`,
+/// responds with the new choice's id
+pub fn create_choice(
+    (req, path, body): (HttpRequest<State>, Path<PropertyId>, Json<NewChoice>),
+) -> FutureResponse<HttpResponse> {
+    add_choice(&req, path.into_inner(), body.into_inner().display)
+}
+
+/// `GET /api/v1/collections`, with the schema each one uses
+pub fn list_collections(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(ListCollections)
+            .from_err()
+            .and_then(|res| res)
+            .map(|collections| HttpResponse::Ok().json(collections))
+    }))
+}
+
+/// `POST /api/v1/collections` with `{"display"}`, responds with the new collection's id
+pub fn create_collection(
+    (req, body): (HttpRequest<State>, Json<NewChoice>),
+) -> FutureResponse<HttpResponse> {
+    add_choice(&req, PropertyId::COLLECTION, body.into_inner().display)
+}
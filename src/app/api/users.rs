@@ -0,0 +1,54 @@
+use futures::Future;
+
+use actix_web::{error, FutureResponse, HttpRequest, HttpResponse, Path};
+
+use crate::db::{GetUser, ListUsers};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::user::UserId;
+use crate::State;
+
+/// `GET /api/v1/users`
+pub fn list_users(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(ListUsers)
+            .from_err()
+            .and_then(|res| res)
+            .map(|users| HttpResponse::Ok().json(users))
+    }))
+}
+
+/// `GET /api/v1/users/me`, the signed in user
+pub fn current_user(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(require_session(&req).and_then(move |session: UserSession| {
+        db.send(GetUser(session.key.user_id))
+            .from_err()
+            .and_then(|res| res)
+            .and_then(|user| match user {
+                Some(user) => Ok(HttpResponse::Ok().json(user)),
+                None => Err(error::ErrorNotFound("The signed in user no longer exists")),
+            })
+    }))
+}
+
+/// `GET /api/v1/users/{user_id}`
+pub fn get_user(
+    (req, path): (HttpRequest<State>, Path<UserId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let user_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(GetUser(user_id.clone()))
+            .from_err()
+            .and_then(|res| res)
+            .and_then(move |user| match user {
+                Some(user) => Ok(HttpResponse::Ok().json(user)),
+                None => Err(error::ErrorNotFound(format!("No user {}", user_id))),
+            })
+    }))
+}
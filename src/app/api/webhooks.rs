@@ -0,0 +1,125 @@
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Json, Path, Query};
+
+use crate::db::{CreateWebhook, DeleteWebhook, ListDeliveries, ListWebhooks, PingWebhook};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::webhook::{WebhookEvent, WebhookId};
+use crate::State;
+
+/// Deliveries listed when no `limit` is given, and the most listed
+const DEFAULT_DELIVERIES: i64 = 50;
+const MAX_DELIVERIES: i64 = 500;
+
+/// `GET /api/v1/webhooks`, the signed in user's webhooks
+pub fn list_webhooks(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(require_session(&req).and_then(move |session: UserSession| {
+        db.send(ListWebhooks {
+            user_id: session.key.user_id,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|webhooks| HttpResponse::Ok().json(webhooks))
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NewWebhook {
+    pub url: String,
+    pub secret: Option<String>,
+    /// Every event when empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+}
+
+/// `POST /api/v1/webhooks` with `{"url", "secret", "events"}`
+///
+/// Responds with `{"webhook", "secret"}`, the secret is not shown again.
+pub fn create_webhook(
+    (req, body): (HttpRequest<State>, Json<NewWebhook>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let webhook = body.into_inner();
+
+    Box::new(require_session(&req).and_then(move |session: UserSession| {
+        db.send(CreateWebhook {
+            url: webhook.url,
+            secret: webhook.secret,
+            events: webhook.events,
+            created_by: session.key.user_id,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|webhook| {
+            let secret = webhook.secret.clone();
+            HttpResponse::Created().json(json!({ "webhook": webhook, "secret": secret }))
+        })
+    }))
+}
+
+/// `DELETE /api/v1/webhooks/{webhook_id}`
+pub fn delete_webhook(
+    (req, path): (HttpRequest<State>, Path<WebhookId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let webhook_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |session: UserSession| {
+        db.send(DeleteWebhook {
+            webhook_id,
+            user_id: session.key.user_id,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|_| HttpResponse::NoContent().finish())
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct DeliveriesParams {
+    pub limit: Option<i64>,
+}
+
+/// `GET /api/v1/webhooks/{webhook_id}/deliveries?limit=`, the delivery log
+pub fn list_deliveries(
+    (req, path, params): (HttpRequest<State>, Path<WebhookId>, Query<DeliveriesParams>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let webhook_id = path.into_inner();
+    let limit = params
+        .limit
+        .unwrap_or(DEFAULT_DELIVERIES)
+        .clamp(1, MAX_DELIVERIES);
+
+    Box::new(require_session(&req).and_then(move |session: UserSession| {
+        db.send(ListDeliveries {
+            webhook_id,
+            user_id: session.key.user_id,
+            limit,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|deliveries| HttpResponse::Ok().json(deliveries))
+    }))
+}
+
+/// `POST /api/v1/webhooks/{webhook_id}/ping`, responds with the queued delivery
+pub fn ping_webhook(
+    (req, path): (HttpRequest<State>, Path<WebhookId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let webhook_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |session: UserSession| {
+        db.send(PingWebhook {
+            webhook_id,
+            user_id: session.key.user_id,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|delivery| HttpResponse::Accepted().json(delivery))
+    }))
+}
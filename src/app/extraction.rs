@@ -0,0 +1,39 @@
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Path};
+
+use crate::db::{GetExtraction, RetryExtraction};
+use crate::object::ObjectId;
+use crate::sessions::session_routes::require_session;
+use crate::State;
+
+/// `GET /objects/{object_id}/extraction`, how extracting the text of the
+/// object's current version went, or `null` if it has no content
+pub fn extraction(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(GetExtraction(object_id))
+            .from_err()
+            .and_then(|res| res)
+            .map(|extraction| HttpResponse::Ok().json(extraction))
+    }))
+}
+
+/// `POST /objects/{object_id}/extraction/retry`, queue the object's text to be extracted again
+pub fn retry_extraction(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(RetryExtraction(object_id))
+            .from_err()
+            .and_then(|res| res)
+            .map(|_| HttpResponse::Accepted().finish())
+    }))
+}
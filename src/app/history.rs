@@ -0,0 +1,23 @@
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Path};
+
+use crate::db::GetValueHistory;
+use crate::object::ObjectId;
+use crate::sessions::session_routes::require_session;
+use crate::State;
+
+/// `GET /objects/{object_id}/history`, changes to the object's values, newest first
+pub fn history(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(GetValueHistory(object_id))
+            .from_err()
+            .and_then(|res| res)
+            .map(|changes| HttpResponse::Ok().json(changes))
+    }))
+}
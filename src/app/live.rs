@@ -0,0 +1,70 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use actix_web::{error, Error, FutureResponse, HttpRequest, HttpResponse, Path};
+use bytes::Bytes;
+use futures::{Future, Stream};
+use redis_async::client::pubsub_connect;
+use redis_async::resp::{FromResp, RespValue};
+use tokio_timer::Interval;
+
+use crate::live::{collection_channel, user_channel};
+use crate::property::SelectChoiceId;
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::State;
+
+/// How often a comment is sent while there are no events, so proxies keep
+/// the connection open and closed connections are noticed
+const KEEPALIVE_SECONDS: u64 = 15;
+
+/// Server-Sent Events of a Redis channel, each with the published message as
+/// its `data`. Every stream subscribes with a Redis connection of its own,
+/// which is closed when the browser goes away.
+fn event_stream(
+    redis: SocketAddr,
+    channel: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    pubsub_connect(&redis)
+        .and_then(move |connection| connection.subscribe(&channel))
+        .map_err(|e| {
+            warn!("Failed to subscribe to live events: {:?}", e);
+            error::ErrorServiceUnavailable("Live updates are unavailable")
+        })
+        .map(|messages| {
+            let events = messages
+                .map(|message: RespValue| match String::from_resp(message) {
+                    Ok(message) => Bytes::from(format!("data: {}\n\n", message)),
+                    Err(_) => Bytes::from_static(b": unreadable event\n\n"),
+                })
+                .map_err(|e| error::ErrorInternalServerError(format!("{:?}", e)));
+            let keepalive = Interval::new(Instant::now(), Duration::from_secs(KEEPALIVE_SECONDS))
+                .map(|_| Bytes::from_static(b": keepalive\n\n"))
+                .map_err(error::ErrorInternalServerError);
+
+            HttpResponse::Ok()
+                .content_type("text/event-stream")
+                .header("Cache-Control", "no-cache")
+                .streaming(keepalive.select(events))
+        })
+}
+
+/// `GET /live/collections/{collection_id}`, events of the objects in a
+/// collection, including those which just left it
+pub fn collection_events(
+    (req, path): (HttpRequest<State>, Path<SelectChoiceId>),
+) -> FutureResponse<HttpResponse> {
+    let redis = req.state().config.redis_addr;
+    let channel = collection_channel(&path.into_inner());
+
+    Box::new(require_session(&req).and_then(move |_| event_stream(redis, channel)))
+}
+
+/// `GET /live/me`, events of the objects the signed in user created
+pub fn my_events(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let redis = req.state().config.redis_addr;
+
+    Box::new(require_session(&req).and_then(move |session: UserSession| {
+        event_stream(redis, user_channel(&session.key.user_id))
+    }))
+}
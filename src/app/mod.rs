@@ -3,13 +3,16 @@ use askama::Template; // bring trait in scope
 use actix_web::middleware::session::{SessionStorage, RequestSession};
 use actix_web::{http, Error};
 use actix_web::{middleware, server, App, HttpRequest, HttpResponse};
+use futures::future::{self, Either};
 use futures::Future;
-use actix::{Actor, SyncArbiter};
+use actix::{Actor, Arbiter, SyncArbiter};
 use actix_redis::{RedisSessionBackend, RedisActor};
+use std::sync::Arc;
 
+use super::config::Config;
 use super::logging;
 use super::sessions;
-use super::db::DbExecutor;
+use super::db::{DbExecutor, ListSavedViews};
 pub use super::State;
 
 use sessions::session_manager::SessionManager;
@@ -17,22 +20,43 @@ use sessions::session_routes::{self, is_signed_in_guard, SigninState};
 use sessions::flash::SessionFlash; // enable inserting and applying flash messages to the page
 
 pub mod templates;
+mod api;
+mod extraction;
+mod history;
+mod live;
+mod objects;
+mod properties;
+mod query;
+mod relations;
+mod saved_views;
+mod schemas;
+mod search;
+mod spreadsheets;
+mod tokens;
 mod upload;
+mod values;
+mod versions;
 
+use crate::live::LivePublisher;
+use crate::mail::MailListener;
+use crate::property::ComputedRefresher;
+use crate::object::{Ingest, TextExtractor};
 use crate::store::ObjectStore;
+use crate::webhook::WebhookDispatcher;
 
-pub fn start() {
+pub fn start(config: Config) {
     ::std::env::set_var("RUST_LOG", "actix_web=info,dewey=info");
     logging::init();
     let sys = actix::System::new("dewey");
+    let config = Arc::new(config);
 
     // r2d2 pool
-    let manager = diesel::r2d2::ConnectionManager::new(dotenv!("DATABASE_URL"));
+    let manager = diesel::r2d2::ConnectionManager::new(config.database_url.as_str());
     let pool = diesel::r2d2::Pool::new(manager).unwrap();
 
     // Start db executor actors
     let db_addr = SyncArbiter::start(3, move || DbExecutor(pool.clone()));
-    let redis_addr = RedisActor::start(dotenv!("REDIS_URL"));
+    let redis_addr = RedisActor::start(config.redis_url.as_str());
 
     let session_actor = SessionManager {
         redis: redis_addr.clone(),
@@ -41,17 +65,42 @@ pub fn start() {
 
     let session_addr = session_actor.start();
 
+    ComputedRefresher::new(db_addr.clone()).start();
+
     let store_actor = ObjectStore::new_with_s3_credentials(
-        dotenv!("S3_ACCESS_KEY_ID"),
-        dotenv!("S3_SECRET_ACCESS_KEY"),
+        &config.s3_access_key_id,
+        &config.s3_secret_access_key,
     )
     .expect("No TLS errors starting store_actor");
 
     let store_addr = store_actor.start();
 
+    let (extractor_pg, extractor_store) = (db_addr.clone(), store_addr.clone());
+    Arbiter::start(move |_| TextExtractor::new(extractor_pg, extractor_store));
+
+    WebhookDispatcher::new(db_addr.clone()).start();
+    LivePublisher::new(db_addr.clone(), redis_addr.clone()).start();
+
+    if let Some(mail_listen) = config.mail_listen {
+        let ingest = Ingest {
+            db: db_addr.clone(),
+            store: store_addr.clone(),
+        };
+        let hostname = config.mail_hostname().to_string();
+        MailListener::listen(&mail_listen, config.mail_protocol, hostname, ingest).unwrap_or_else(
+            |e| {
+                eprintln!("Can't listen for mail on {}: {}", mail_listen, e);
+                std::process::exit(2)
+            },
+        );
+        info!("Started {} drop-box: {}", config.mail_protocol, mail_listen);
+    }
+
     use listenfd::ListenFd;
     let mut listenfd = ListenFd::from_env();
+    let server_config = config.clone();
     let mut server = server::new(move || {
+        let config = server_config.clone();
         vec![
             App::new()
                 .prefix("/static")
@@ -64,20 +113,117 @@ pub fn start() {
                 .boxed(),
             App::with_state(State {
                 db: db_addr.clone(),
-                mem: redis_addr.clone(),
                 sessions: session_addr.clone(),
                 store: store_addr.clone(),
+                config: config.clone(),
             })
             .middleware(middleware::Logger::new(r#"%T "%r" %s %b "%{Referer}i""#))
             .middleware(SessionStorage::new(
-                RedisSessionBackend::new(dotenv!("REDIS_URL"), &[0; 32])
+                RedisSessionBackend::new(config.redis_url.as_str(), &[0; 32])
                     .cookie_secure(true) // cookies require https
                     .cookie_name("sess"),
             ))
+            .scope("/api/v1", api::api_scope)
             .resource("/example", |r| r.f(upload_example))
             .resource("/upload", |r| {
                 r.method(http::Method::POST).with(upload::upload)
             })
+            .resource("/properties/computed", |r| {
+                r.method(http::Method::POST).with(properties::create_computed)
+            })
+            .resource("/properties/{property_id}/rules", |r| {
+                r.method(http::Method::POST).with(properties::set_rules)
+            })
+            .resource("/schemas", |r| {
+                r.method(http::Method::GET).with(schemas::list_schemas);
+                r.method(http::Method::POST).with(schemas::create_schema)
+            })
+            .resource("/collections/{collection_id}/schema", |r| {
+                r.method(http::Method::POST).with(schemas::set_collection_schema)
+            })
+            .resource("/views", |r| {
+                r.method(http::Method::GET).with(saved_views::list_views);
+                r.method(http::Method::POST).with(saved_views::create_view)
+            })
+            .resource("/views/{view_id}", |r| {
+                r.method(http::Method::GET).with(saved_views::view_page);
+                r.method(http::Method::DELETE).with(saved_views::delete_view)
+            })
+            .resource("/views/{view_id}/objects", |r| {
+                r.method(http::Method::GET).with(saved_views::view_objects)
+            })
+            .resource("/search", |r| {
+                r.method(http::Method::GET).with(search::search)
+            })
+            .resource("/objects", |r| {
+                r.method(http::Method::GET).with(query::query_objects)
+            })
+            .resource("/objects/facets", |r| {
+                r.method(http::Method::GET).with(query::facets)
+            })
+            .resource("/objects/csv", |r| {
+                r.method(http::Method::GET).with(spreadsheets::export_csv);
+                r.method(http::Method::POST).with(spreadsheets::import_csv)
+            })
+            .resource("/objects/{object_id}", |r| {
+                r.method(http::Method::GET).with(objects::object_page)
+            })
+            .resource("/objects/{object_id}/extraction", |r| {
+                r.method(http::Method::GET).with(extraction::extraction)
+            })
+            .resource("/objects/{object_id}/extraction/retry", |r| {
+                r.method(http::Method::POST).with(extraction::retry_extraction)
+            })
+            .resource("/objects/{object_id}/history", |r| {
+                r.method(http::Method::GET).with(history::history)
+            })
+            .resource("/objects/{object_id}/language", |r| {
+                r.method(http::Method::POST).with(search::set_language)
+            })
+            .resource("/objects/{object_id}/relations", |r| {
+                r.method(http::Method::GET).with(relations::relations)
+            })
+            .resource("/objects/{object_id}/relations/graph", |r| {
+                r.method(http::Method::GET).with(relations::graph)
+            })
+            .resource("/objects/{object_id}/content", |r| {
+                r.method(http::Method::GET).with(versions::download_current)
+            })
+            .resource("/objects/{object_id}/versions", |r| {
+                r.method(http::Method::GET).with(versions::list_versions);
+                r.method(http::Method::POST).with(versions::add_version)
+            })
+            .resource("/objects/{object_id}/versions/{version}/content", |r| {
+                r.method(http::Method::GET).with(versions::download_version)
+            })
+            .resource("/objects/{object_id}/versions/{version}/restore", |r| {
+                r.method(http::Method::POST).with(versions::restore_version)
+            })
+            .resource("/objects/{object_id}/values", |r| {
+                r.method(http::Method::POST).with(values::set_values)
+            })
+            .resource("/objects/{object_id}/values/{property_id}", |r| {
+                r.method(http::Method::POST).with(values::set_value)
+            })
+            .resource("/live/collections/{collection_id}", |r| {
+                r.method(http::Method::GET).with(live::collection_events)
+            })
+            .resource("/live/me", |r| {
+                r.method(http::Method::GET).with(live::my_events)
+            })
+            .resource("/tokens", |r| {
+                r.method(http::Method::GET).with(tokens::tokens_page);
+                r.method(http::Method::POST).with(tokens::create_token)
+            })
+            .resource("/tokens/{token_id}/revoke", |r| {
+                r.method(http::Method::POST).with(tokens::revoke_token)
+            })
+            .resource("/drop-boxes", |r| {
+                r.method(http::Method::POST).with(tokens::create_drop_box)
+            })
+            .resource("/drop-boxes/{token_id}/revoke", |r| {
+                r.method(http::Method::POST).with(tokens::revoke_drop_box)
+            })
             .scope("/login", session_routes::login_scope)
             .resource("/logout", |r| r.f(session_routes::logout_endpoint))
             .resource("/", |r| r.f(index))
@@ -90,33 +236,63 @@ pub fn start() {
     server = if let Some(l) = listenfd.take_tcp_listener(0).unwrap() {
         server.listen(l)
     } else {
-        server.bind("127.0.0.1:8088").unwrap()
+        server
+            .bind((config.bind.as_str(), config.port))
+            .unwrap_or_else(|e| {
+                eprintln!("Can't listen on {}:{}: {}", config.bind, config.port, e);
+                std::process::exit(2)
+            })
     };
 
-    info!("Started http server: 127.0.0.1:8088");
-    info!("                     {}", dotenv!("ROOT_HOST"));
+    info!("Started http server: {}:{}", config.bind, config.port);
+    info!("                     {}", config.root_host);
     server.run();
 }
 
 
+/// The caller's `X-Request-Id`, or a new random one, used to group audited changes
+pub fn request_id(req: &HttpRequest<State>) -> String {
+    use rand::distributions::Alphanumeric;
+    use rand::{thread_rng, Rng};
+
+    req.headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.chars().take(64).collect())
+        .unwrap_or_else(|| thread_rng().sample_iter(&Alphanumeric).take(16).collect())
+}
+
 fn index(req: &HttpRequest<State>) -> Box<Future<Item = HttpResponse, Error = Error>> {
     use templates::*;
     let req_session = req.session();
+    let db = req.state().db.clone();
     Box::new(
         is_signed_in_guard(req).and_then(move |signin_state: SigninState| {
-            let mut page = Page::default();
-            req_session.apply_flash(&mut page)?;
-
-            match signin_state {
-                SigninState::Valid(ref auth) => page.person(&auth.person),
-                SigninState::SignedOutByThirdParty => {
-                    page.info("You've been signed out by a third party.")
-                }
-                SigninState::NotSignedIn => {}
+            let views = match signin_state {
+                SigninState::Valid(ref auth) => Either::A(
+                    db.send(ListSavedViews {
+                        user_id: auth.key.user_id.clone(),
+                    })
+                    .from_err()
+                    .and_then(|res| res),
+                ),
+                _ => Either::B(future::ok(Vec::new())),
             };
-            Ok(HttpResponse::Ok()
-                .header(http::header::CONTENT_TYPE, "text/html")
-                .body(templates::HelloTemplate { page }.render().unwrap()))
+            views.and_then(move |views| {
+                let mut page = Page::default();
+                req_session.apply_flash(&mut page)?;
+
+                match signin_state {
+                    SigninState::Valid(ref auth) => page.person(&auth.person),
+                    SigninState::SignedOutByThirdParty => {
+                        page.info("You've been signed out by a third party.")
+                    }
+                    SigninState::NotSignedIn => {}
+                };
+                Ok(HttpResponse::Ok()
+                    .header(http::header::CONTENT_TYPE, "text/html")
+                    .body(templates::HelloTemplate { page, views }.render().unwrap()))
+            })
         }),
     )
 }
@@ -0,0 +1,52 @@
+use askama::Template; // bring trait in scope
+
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::middleware::session::RequestSession;
+use actix_web::{http, Error, HttpRequest, HttpResponse, Path};
+
+use crate::db::GetObjectDetails;
+use crate::object::ObjectId;
+use crate::sessions::flash::SessionFlash;
+use crate::sessions::session_routes::{is_signed_in_guard, SigninState};
+use crate::State;
+
+use super::templates::{ObjectTemplate, Page};
+
+/// `GET /objects/{object_id}`, the object's properties as an editable form
+pub fn object_page(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let req_session = req.session();
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(
+        is_signed_in_guard(&req).and_then(move |signin_state: SigninState| {
+            let session = match signin_state {
+                SigninState::Valid(session) => session,
+                _ => {
+                    return Either::A(future::ok(
+                        HttpResponse::Found().header("location", "/").finish(),
+                    ))
+                }
+            };
+
+            Either::B(
+                db.send(GetObjectDetails(object_id))
+                    .from_err()
+                    .and_then(|res| res)
+                    .and_then(move |details| {
+                        let mut page = Page::default();
+                        req_session.apply_flash(&mut page)?;
+                        page.person(&session.person);
+
+                        Ok(HttpResponse::Ok()
+                            .header(http::header::CONTENT_TYPE, "text/html")
+                            .body(ObjectTemplate { page, details }.render().unwrap()))
+                    }),
+            )
+        }),
+    )
+}
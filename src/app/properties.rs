@@ -0,0 +1,53 @@
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Json, Path};
+
+use crate::db::{CreateComputedProperty, SetValidationRules};
+use crate::property::{PropertyId, ValidationRules};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::State;
+
+#[derive(Deserialize)]
+pub struct NewComputedProperty {
+    pub display: String,
+    /// e.g. `days_since({3})`
+    pub formula: String,
+}
+
+/// `POST /properties/computed`, responds with the new property's id
+pub fn create_computed(
+    (req, body): (HttpRequest<State>, Json<NewComputedProperty>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let NewComputedProperty { display, formula } = body.into_inner();
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            db.send(CreateComputedProperty {
+                display,
+                formula,
+                created_by: session.key.user_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|id| HttpResponse::Created().json(json!({ "id": id })))
+        }),
+    )
+}
+
+/// `POST /properties/{property_id}/rules` with JSON `ValidationRules`
+pub fn set_rules(
+    (req, path, body): (HttpRequest<State>, Path<PropertyId>, Json<ValidationRules>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let property_id = path.into_inner();
+    let rules = body.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(SetValidationRules { property_id, rules })
+            .from_err()
+            .and_then(|res| res)
+            .map(|_| HttpResponse::NoContent().finish())
+    }))
+}
@@ -0,0 +1,110 @@
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Query};
+
+use crate::db::{BucketInterval, FacetsResult, GetFacets, QueryObjects, QueryObjectsResult};
+use crate::query::{parse, Cursor, QueryError, Sort};
+use crate::sessions::session_routes::require_session;
+use crate::State;
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+#[derive(Deserialize)]
+pub struct ObjectsParams {
+    #[serde(default)]
+    pub q: String,
+    pub sort: Option<String>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// `next_cursor` of the previous page
+    pub after: Option<String>,
+}
+
+/// `GET /objects?q=tags:Proposal company:"HBO"&sort=-modified&page=2`
+///
+/// Responds with `{"objects", "total", "page", "per_page", "next_cursor"}`, or
+/// with `{"error", "position"}` and 400 if the query is invalid. Passing
+/// `after=<next_cursor>` instead of `page` gives the following page without
+/// skipping or repeating objects when others are added in between.
+pub fn query_objects(
+    (req, params): (HttpRequest<State>, Query<ObjectsParams>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let params = params.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        let query = match parse(&params.q) {
+            Ok(query) => query,
+            Err(e) => return Either::A(future::ok(invalid_query(&e))),
+        };
+        let after = match params.after.as_ref().map(|after| Cursor::decode(after)) {
+            Some(Err(e)) => return Either::A(future::ok(invalid_query(&e))),
+            Some(Ok(cursor)) => Some(cursor),
+            None => None,
+        };
+        let msg = QueryObjects {
+            query,
+            sort: params.sort.as_ref().map_or_else(Sort::default, |s| Sort::parse(s)),
+            page: params.page.unwrap_or(1).max(1),
+            per_page: params.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+            after,
+        };
+        Either::B(
+            db.send(msg)
+                .from_err()
+                .and_then(|res| res)
+                .map(|result| match result {
+                    QueryObjectsResult::Found(page) => HttpResponse::Ok().json(page),
+                    QueryObjectsResult::Invalid(e) => invalid_query(&e),
+                }),
+        )
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct FacetsParams {
+    #[serde(default)]
+    pub q: String,
+    #[serde(default)]
+    pub interval: BucketInterval,
+}
+
+/// `GET /objects/facets?q=tags:Proposal&interval=month`, how many of the
+/// matching objects have each choice, and histograms of their timestamps
+///
+/// Responds with `{"total", "choices": [{"property_id", "display", "counts"}],
+/// "timestamps": [{"property_id", "display", "interval", "buckets"}]}`.
+pub fn facets(
+    (req, params): (HttpRequest<State>, Query<FacetsParams>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let params = params.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        let query = match parse(&params.q) {
+            Ok(query) => query,
+            Err(e) => return Either::A(future::ok(invalid_query(&e))),
+        };
+        Either::B(
+            db.send(GetFacets {
+                query,
+                interval: params.interval,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|result| match result {
+                FacetsResult::Found(facets) => HttpResponse::Ok().json(facets),
+                FacetsResult::Invalid(e) => invalid_query(&e),
+            }),
+        )
+    }))
+}
+
+pub fn invalid_query(e: &QueryError) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "error": e.message,
+        "position": e.position,
+    }))
+}
@@ -0,0 +1,44 @@
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Path, Query};
+
+use crate::db::{GetRelations, TraverseRelations};
+use crate::object::ObjectId;
+use crate::sessions::session_routes::require_session;
+use crate::State;
+
+/// `GET /objects/{object_id}/relations`, outgoing and incoming relations
+pub fn relations(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(GetRelations(object_id))
+            .from_err()
+            .and_then(|res| res)
+            .map(|relations| HttpResponse::Ok().json(relations))
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct GraphQuery {
+    pub hops: Option<i32>,
+}
+
+/// `GET /objects/{object_id}/relations/graph?hops=2`, objects within `hops` relations
+pub fn graph(
+    (req, path, query): (HttpRequest<State>, Path<ObjectId>, Query<GraphQuery>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+    let hops = query.hops.unwrap_or(1);
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(TraverseRelations { object_id, hops })
+            .from_err()
+            .and_then(|res| res)
+            .map(|related| HttpResponse::Ok().json(related))
+    }))
+}
@@ -0,0 +1,188 @@
+use askama::Template; // bring trait in scope
+
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::middleware::session::RequestSession;
+use actix_web::{http, Error, FutureResponse, HttpRequest, HttpResponse, Json, Path, Query};
+
+use crate::db::{
+    CreateSavedView, CreateSavedViewResult, DeleteSavedView, GetSavedViewPage, ListSavedViews,
+};
+use crate::property::{PropertyId, SelectChoiceId};
+use crate::query::{SavedViewId, ViewLayout};
+use crate::sessions::flash::SessionFlash;
+use crate::sessions::session_routes::{is_signed_in_guard, require_session, SigninState};
+use crate::sessions::UserSession;
+use crate::State;
+
+use super::templates::{Page, SavedViewTemplate};
+
+const DEFAULT_PER_PAGE: i64 = 50;
+const MAX_PER_PAGE: i64 = 200;
+
+/// `GET /views`, the user's personal views and every shared view
+pub fn list_views(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            db.send(ListSavedViews {
+                user_id: session.key.user_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|views| HttpResponse::Ok().json(views))
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct NewSavedView {
+    pub display: String,
+    #[serde(default)]
+    pub query: String,
+    #[serde(default)]
+    pub columns: Vec<PropertyId>,
+    pub sort: Option<String>,
+    pub group_by: Option<PropertyId>,
+    #[serde(default)]
+    pub layout: ViewLayout,
+    /// Share the view through a collection instead of keeping it personal
+    pub collection_id: Option<SelectChoiceId>,
+}
+
+/// `POST /views` with `{"display", "query", "columns", "sort", "group_by", "layout",
+/// "collection_id"}`, responds with the new view's id
+pub fn create_view(
+    (req, body): (HttpRequest<State>, Json<NewSavedView>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let view = body.into_inner();
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            db.send(CreateSavedView {
+                display: view.display,
+                query: view.query,
+                columns: view.columns,
+                sort: view.sort.unwrap_or_else(|| "-created".to_string()),
+                group_by: view.group_by,
+                layout: view.layout,
+                collection_id: view.collection_id,
+                created_by: session.key.user_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|result| match result {
+                CreateSavedViewResult::Created(id) => {
+                    HttpResponse::Created().json(json!({ "id": id }))
+                }
+                CreateSavedViewResult::Invalid(e) => HttpResponse::BadRequest().json(json!({
+                    "error": e.message,
+                    "position": e.position,
+                })),
+            })
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct PageParams {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+impl PageParams {
+    fn page(&self) -> i64 {
+        self.page.unwrap_or(1).max(1)
+    }
+
+    fn per_page(&self) -> i64 {
+        self.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE)
+    }
+}
+
+/// `GET /views/{view_id}`, the view's objects as a table or grid
+pub fn view_page(
+    (req, path, params): (HttpRequest<State>, Path<SavedViewId>, Query<PageParams>),
+) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let req_session = req.session();
+    let db = req.state().db.clone();
+    let view_id = path.into_inner();
+
+    Box::new(
+        is_signed_in_guard(&req).and_then(move |signin_state: SigninState| {
+            let session = match signin_state {
+                SigninState::Valid(session) => session,
+                _ => {
+                    return Either::A(future::ok(
+                        HttpResponse::Found().header("location", "/").finish(),
+                    ))
+                }
+            };
+
+            Either::B(
+                db.send(GetSavedViewPage {
+                    view_id,
+                    user_id: session.key.user_id.clone(),
+                    page: params.page(),
+                    per_page: params.per_page(),
+                })
+                .from_err()
+                .and_then(|res| res)
+                .and_then(move |results| {
+                    let mut page = Page::default();
+                    req_session.apply_flash(&mut page)?;
+                    page.person(&session.person);
+
+                    Ok(HttpResponse::Ok()
+                        .header(http::header::CONTENT_TYPE, "text/html")
+                        .body(SavedViewTemplate { page, results }.render().unwrap()))
+                }),
+            )
+        }),
+    )
+}
+
+/// `GET /views/{view_id}/objects`, the view's page of objects as JSON
+pub fn view_objects(
+    (req, path, params): (HttpRequest<State>, Path<SavedViewId>, Query<PageParams>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let view_id = path.into_inner();
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            db.send(GetSavedViewPage {
+                view_id,
+                user_id: session.key.user_id,
+                page: params.page(),
+                per_page: params.per_page(),
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|results| HttpResponse::Ok().json(results))
+        }),
+    )
+}
+
+/// `DELETE /views/{view_id}`
+pub fn delete_view(
+    (req, path): (HttpRequest<State>, Path<SavedViewId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let view_id = path.into_inner();
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            db.send(DeleteSavedView {
+                view_id,
+                user_id: session.key.user_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|_| HttpResponse::NoContent().finish())
+        }),
+    )
+}
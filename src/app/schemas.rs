@@ -0,0 +1,72 @@
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Json, Path};
+
+use crate::db::{CreatePropertySchema, ListPropertySchemas, SetCollectionSchema};
+use crate::property::{PropertySchemaField, PropertySchemaId, SelectChoiceId};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::State;
+
+/// `GET /schemas`
+pub fn list_schemas(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(ListPropertySchemas)
+            .from_err()
+            .and_then(|res| res)
+            .map(|schemas| HttpResponse::Ok().json(schemas))
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct NewPropertySchema {
+    pub display: String,
+    pub fields: Vec<PropertySchemaField>,
+}
+
+/// `POST /schemas`, responds with the new schema's id
+pub fn create_schema(
+    (req, body): (HttpRequest<State>, Json<NewPropertySchema>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let NewPropertySchema { display, fields } = body.into_inner();
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            db.send(CreatePropertySchema {
+                display,
+                fields,
+                created_by: session.key.user_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|id| HttpResponse::Created().json(json!({ "id": id })))
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct CollectionSchema {
+    pub schema_id: Option<PropertySchemaId>,
+}
+
+/// `POST /collections/{collection_id}/schema` with `{"schema_id": ...}` or `null` to unset
+pub fn set_collection_schema(
+    (req, path, body): (HttpRequest<State>, Path<SelectChoiceId>, Json<CollectionSchema>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let collection_id = path.into_inner();
+    let schema_id = body.into_inner().schema_id;
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(SetCollectionSchema {
+            collection_id,
+            schema_id,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|_| HttpResponse::NoContent().finish())
+    }))
+}
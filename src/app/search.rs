@@ -0,0 +1,79 @@
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Json, Path, Query};
+
+use crate::db::{prefix_tsquery, SearchLanguage, SearchObjects, SetSearchLanguage};
+use crate::object::ObjectId;
+use crate::sessions::session_routes::require_session;
+use crate::State;
+
+const DEFAULT_PER_PAGE: i64 = 20;
+const MAX_PER_PAGE: i64 = 100;
+
+#[derive(Deserialize)]
+pub struct SearchParams {
+    #[serde(default)]
+    pub text: String,
+    pub lang: Option<SearchLanguage>,
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+/// `GET /search?text=quarterly rep&lang=en`, objects containing words starting with
+/// each of the searched words, best matches first
+///
+/// Responds with `{"hits": [{"object_id", "filename", "rank", "snippet"}], "total", ...}`.
+pub fn search(
+    (req, params): (HttpRequest<State>, Query<SearchParams>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let params = params.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        let tsquery = match prefix_tsquery(&params.text) {
+            Some(tsquery) => tsquery,
+            None => {
+                return Either::A(future::ok(HttpResponse::BadRequest().json(json!({
+                    "error": "expected words to search for",
+                }))))
+            }
+        };
+        Either::B(
+            db.send(SearchObjects {
+                tsquery,
+                language: params.lang,
+                page: params.page.unwrap_or(1).max(1),
+                per_page: params.per_page.unwrap_or(DEFAULT_PER_PAGE).clamp(1, MAX_PER_PAGE),
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|results| HttpResponse::Ok().json(results)),
+        )
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct LanguageUpdate {
+    pub language: SearchLanguage,
+}
+
+/// `POST /objects/{object_id}/language` with `{"language": "finnish"}`, the
+/// language the object's text is indexed in for search
+pub fn set_language(
+    (req, path, body): (HttpRequest<State>, Path<ObjectId>, Json<LanguageUpdate>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+    let language = body.into_inner().language;
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(SetSearchLanguage {
+            object_id,
+            language,
+        })
+        .from_err()
+        .and_then(|res| res)
+        .map(|_| HttpResponse::NoContent().finish())
+    }))
+}
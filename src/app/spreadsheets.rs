@@ -0,0 +1,161 @@
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::{
+    error, http, Error, FutureResponse, HttpMessage, HttpRequest, HttpResponse, Query,
+};
+
+use crate::db::{ExportSpreadsheet, ExportSpreadsheetResult, ImportSpreadsheet, SpreadsheetRow};
+use crate::query::{parse, Sort};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::State;
+
+use super::query::invalid_query;
+use super::request_id;
+
+/// Joins the values of a property with several, e.g. `Proposal;Signed`
+const DEFAULT_DELIMITER: &str = ";";
+
+/// Largest CSV accepted for import
+const MAX_CSV_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Deserialize)]
+pub struct ExportParams {
+    #[serde(default)]
+    pub q: String,
+    pub sort: Option<String>,
+    pub delimiter: Option<String>,
+}
+
+/// `GET /objects/csv?q=tags:Exhibit&sort=filename&delimiter=;`
+///
+/// Responds with the matching objects as CSV, a row of each with its id and
+/// a column for every property, or like `GET /objects` if the query is invalid.
+pub fn export_csv(
+    (req, params): (HttpRequest<State>, Query<ExportParams>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let params = params.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        let query = match parse(&params.q) {
+            Ok(query) => query,
+            Err(e) => return Either::A(future::ok(invalid_query(&e))),
+        };
+        Either::B(
+            db.send(ExportSpreadsheet {
+                query,
+                sort: params
+                    .sort
+                    .as_ref()
+                    .map_or_else(Sort::default, |s| Sort::parse(s)),
+                delimiter: params
+                    .delimiter
+                    .filter(|delimiter| !delimiter.is_empty())
+                    .unwrap_or_else(|| DEFAULT_DELIMITER.to_string()),
+            })
+            .from_err()
+            .and_then(|res| res)
+            .and_then(|result| match result {
+                ExportSpreadsheetResult::Found(sheet) => {
+                    let mut writer = csv::Writer::from_writer(Vec::new());
+                    writer
+                        .write_record(&sheet.header)
+                        .map_err(error::ErrorInternalServerError)?;
+                    for row in &sheet.rows {
+                        writer
+                            .write_record(row)
+                            .map_err(error::ErrorInternalServerError)?;
+                    }
+                    let content = writer
+                        .into_inner()
+                        .map_err(|e| error::ErrorInternalServerError(e.to_string()))?;
+                    Ok(HttpResponse::Ok()
+                        .header(http::header::CONTENT_TYPE, "text/csv; charset=utf-8")
+                        .header(
+                            http::header::CONTENT_DISPOSITION,
+                            "attachment; filename=\"objects.csv\"",
+                        )
+                        .body(content))
+                }
+                ExportSpreadsheetResult::Invalid(e) => Ok(invalid_query(&e)),
+            }),
+        )
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ImportParams {
+    #[serde(default)]
+    pub dry_run: bool,
+    pub delimiter: Option<String>,
+}
+
+/// `POST /objects/csv?dry_run=true` with a CSV body whose first row names an
+/// `id` or `Hash` column and the properties to set
+///
+/// Responds with `{"dry_run", "key", "updated", "unchanged", "failed",
+/// "rows": [{"line", "key", "object_id", "changes", "errors"}]}`, listing the
+/// rows which changed, or would change on a dry run, and those which failed.
+pub fn import_csv(
+    (req, params): (HttpRequest<State>, Query<ImportParams>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let request_id = request_id(&req);
+    let params = params.into_inner();
+    let body = req.body().limit(MAX_CSV_BYTES).from_err::<Error>();
+
+    Box::new(
+        require_session(&req)
+            .join(body)
+            .and_then(|(session, content)| {
+                read_csv(&content)
+                    .map(|(header, rows)| (session, header, rows))
+                    .map_err(error::ErrorBadRequest)
+            })
+            .and_then(move |(session, header, rows): (UserSession, _, _)| {
+                db.send(ImportSpreadsheet {
+                    header,
+                    rows,
+                    delimiter: params
+                        .delimiter
+                        .filter(|delimiter| !delimiter.is_empty())
+                        .unwrap_or_else(|| DEFAULT_DELIMITER.to_string()),
+                    dry_run: params.dry_run,
+                    user_id: session.key.user_id,
+                    request_id: Some(request_id),
+                })
+                .from_err()
+                .and_then(|res| res)
+                .map(|report| HttpResponse::Ok().json(report))
+            }),
+    )
+}
+
+/// The header and the rows below it, leaving out blank rows
+fn read_csv(content: &[u8]) -> Result<(Vec<String>, Vec<SpreadsheetRow>), String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(content);
+    let header = reader
+        .headers()
+        .map_err(|e| format!("Can't read the first row: {}", e))?
+        .iter()
+        // spreadsheets may start their files with a byte order mark
+        .map(|name| name.trim_start_matches('\u{feff}').to_string())
+        .collect();
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Can't read the CSV: {}", e))?;
+        if record.iter().all(|cell| cell.trim().is_empty()) {
+            continue;
+        }
+        rows.push(SpreadsheetRow {
+            line: record.position().map_or(0, |position| position.line()),
+            cells: record.iter().map(String::from).collect(),
+        });
+    }
+    Ok((header, rows))
+}
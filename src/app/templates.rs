@@ -1,5 +1,9 @@
 use askama::Template; // bring trait in scope
 
+use crate::db::{Collection, ObjectDetails, ViewPage, ViewRow};
+use crate::mail::DropBoxToken;
+use crate::query::{SavedView, ViewLayout};
+use crate::sessions::ApiToken;
 use crate::user::PersonUser;
 
 #[derive(Clone)]
@@ -44,6 +48,7 @@ pub struct HelloTemplate<'a> {
     // the name of the struct can be anything
     pub page: Page<'a>, // the field name should match the variable name
                         // in your template
+    pub views: Vec<SavedView>,
 }
 
 #[derive(Template)] // this will generate the code...
@@ -54,3 +59,59 @@ pub struct UploadTemplate<'a> {
     pub page: Page<'a>, // the field name should match the variable name
                         // in your template
 }
+
+#[derive(Template)]
+#[template(path = "object.html.j2")]
+pub struct ObjectTemplate<'a> {
+    pub page: Page<'a>,
+    pub details: ObjectDetails,
+}
+
+#[derive(Template)]
+#[template(path = "view.html.j2")]
+pub struct SavedViewTemplate<'a> {
+    pub page: Page<'a>,
+    pub results: ViewPage,
+}
+
+impl<'a> SavedViewTemplate<'a> {
+    pub fn has_previous(&self) -> bool {
+        self.results.page > 1
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.results.page * self.results.per_page < self.results.total
+    }
+
+    /// The row's cells along with their column names, for the grid layout
+    pub fn labelled<'r>(&'r self, row: &'r ViewRow) -> Vec<LabelledCell<'r>> {
+        self.results
+            .columns
+            .iter()
+            .zip(&row.cells)
+            .map(|(column, value)| LabelledCell {
+                label: column.display.clone(),
+                value,
+            })
+            .collect()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "tokens.html.j2")]
+pub struct TokensTemplate<'a> {
+    pub page: Page<'a>,
+    pub tokens: Vec<ApiToken>,
+    /// Secret of the token just created
+    pub secret: Option<String>,
+    pub drop_boxes: Vec<DropBoxToken>,
+    /// What drop-boxes may be created for
+    pub collections: Vec<Collection>,
+    /// Address of the drop-box just created
+    pub address: Option<String>,
+}
+
+pub struct LabelledCell<'a> {
+    pub label: String,
+    pub value: &'a str,
+}
@@ -0,0 +1,254 @@
+use askama::Template; // bring trait in scope
+
+use chrono::{Duration, Utc};
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::middleware::session::RequestSession;
+use actix_web::{error, http, Error, Form, HttpRequest, HttpResponse, Path};
+
+use crate::db::{
+    CreateApiToken, CreateDropBoxToken, ListApiTokens, ListCollections, ListDropBoxTokens,
+    RevokeApiToken, RevokeDropBoxToken,
+};
+use crate::mail::{drop_box_address, DropBoxTokenId};
+use crate::property::SelectChoiceId;
+use crate::sessions::flash::SessionFlash;
+use crate::sessions::session_routes::{bearer_token, is_signed_in_guard, SigninState};
+use crate::sessions::{ApiTokenId, TokenScope, UserSession};
+use crate::State;
+
+use super::templates::{Page, TokensTemplate};
+
+/// Resolves to the browser session, tokens may not manage tokens
+fn require_browser_session(
+    req: &HttpRequest<State>,
+) -> impl Future<Item = Option<UserSession>, Error = Error> {
+    let uses_token = bearer_token(req).is_some();
+    is_signed_in_guard(req).and_then(move |signin_state| match signin_state {
+        _ if uses_token => Err(error::ErrorForbidden(
+            "API tokens are managed from the browser",
+        )),
+        SigninState::Valid(session) => Ok(Some(session)),
+        _ => Ok(None),
+    })
+}
+
+/// The page, with the secret of a token or address of a drop-box just created
+fn render_tokens(
+    req: &HttpRequest<State>,
+    session: UserSession,
+    secret: Option<String>,
+    address: Option<String>,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    let req_session = req.session();
+    let db = &req.state().db;
+    let user_id = session.key.user_id.clone();
+
+    db.send(ListApiTokens {
+        user_id: user_id.clone(),
+    })
+    .from_err()
+    .and_then(|res| res)
+    .join3(
+        db.send(ListDropBoxTokens { user_id })
+            .from_err()
+            .and_then(|res| res),
+        db.send(ListCollections).from_err().and_then(|res| res),
+    )
+    .and_then(move |(tokens, drop_boxes, collections)| {
+        let mut page = Page::default();
+        req_session.apply_flash(&mut page)?;
+        page.person(&session.person);
+
+        Ok(HttpResponse::Ok()
+            .header(http::header::CONTENT_TYPE, "text/html")
+            .body(
+                TokensTemplate {
+                    page,
+                    tokens,
+                    secret,
+                    drop_boxes,
+                    collections,
+                    address,
+                }
+                .render()
+                .unwrap(),
+            ))
+    })
+}
+
+/// `GET /tokens`, the user's API tokens
+pub fn tokens_page(req: HttpRequest<State>) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    Box::new(
+        require_browser_session(&req).and_then(move |session| match session {
+            Some(session) => Either::A(render_tokens(&req, session, None, None)),
+            None => Either::B(future::ok(
+                HttpResponse::Found().header("location", "/").finish(),
+            )),
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct NewApiToken {
+    pub display: String,
+    #[serde(default)]
+    pub scope: TokenScope,
+    /// Days until the token expires, never when empty
+    #[serde(default)]
+    pub expires_in_days: String,
+}
+
+/// `POST /tokens` from the form, shows the new token's secret once
+pub fn create_token(
+    (req, form): (HttpRequest<State>, Form<NewApiToken>),
+) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let db = req.state().db.clone();
+    let token = form.into_inner();
+    let expires_at = match token.expires_in_days.trim() {
+        "" => None,
+        days => match days.parse::<i64>() {
+            Ok(days) if days > 0 => Some(Utc::now() + Duration::days(days)),
+            _ => {
+                return Box::new(future::err(error::ErrorBadRequest(
+                    "Expiry must be a number of days",
+                )))
+            }
+        },
+    };
+
+    Box::new(
+        require_browser_session(&req).and_then(move |session| {
+            let session = match session {
+                Some(session) => session,
+                None => {
+                    return Either::A(future::ok(
+                        HttpResponse::Found().header("location", "/").finish(),
+                    ))
+                }
+            };
+            Either::B(
+                db.send(CreateApiToken {
+                    user_id: session.key.user_id.clone(),
+                    display: token.display,
+                    scope: token.scope,
+                    expires_at,
+                })
+                .from_err()
+                .and_then(|res| res)
+                .and_then(move |(_, secret)| render_tokens(&req, session, Some(secret), None)),
+            )
+        }),
+    )
+}
+
+/// `POST /tokens/{token_id}/revoke`
+pub fn revoke_token(
+    (req, path): (HttpRequest<State>, Path<ApiTokenId>),
+) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let req_session = req.session();
+    let db = req.state().db.clone();
+    let token_id = path.into_inner();
+
+    Box::new(
+        require_browser_session(&req).and_then(move |session| {
+            let session = match session {
+                Some(session) => session,
+                None => {
+                    return Either::A(future::ok(
+                        HttpResponse::Found().header("location", "/").finish(),
+                    ))
+                }
+            };
+            Either::B(
+                db.send(RevokeApiToken {
+                    token_id,
+                    user_id: session.key.user_id,
+                })
+                .from_err()
+                .and_then(|res| res)
+                .and_then(move |_| {
+                    req_session.flash("The token was revoked.")?;
+                    Ok(HttpResponse::Found().header("location", "/tokens").finish())
+                }),
+            )
+        }),
+    )
+}
+
+#[derive(Deserialize)]
+pub struct NewDropBox {
+    pub collection_id: SelectChoiceId,
+}
+
+/// `POST /drop-boxes` from the form, shows the new drop-box's address once
+pub fn create_drop_box(
+    (req, form): (HttpRequest<State>, Form<NewDropBox>),
+) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let db = req.state().db.clone();
+    let collection_id = form.into_inner().collection_id;
+
+    Box::new(
+        require_browser_session(&req).and_then(move |session| {
+            let session = match session {
+                Some(session) => session,
+                None => {
+                    return Either::A(future::ok(
+                        HttpResponse::Found().header("location", "/").finish(),
+                    ))
+                }
+            };
+            Either::B(
+                db.send(CreateDropBoxToken {
+                    user_id: session.key.user_id.clone(),
+                    collection_id: collection_id.clone(),
+                })
+                .from_err()
+                .and_then(|res| res)
+                .and_then(move |(_, secret)| {
+                    let address = drop_box_address(
+                        &collection_id,
+                        &secret,
+                        req.state().config.mail_hostname(),
+                    );
+                    render_tokens(&req, session, None, Some(address))
+                }),
+            )
+        }),
+    )
+}
+
+/// `POST /drop-boxes/{token_id}/revoke`
+pub fn revoke_drop_box(
+    (req, path): (HttpRequest<State>, Path<DropBoxTokenId>),
+) -> Box<Future<Item = HttpResponse, Error = Error>> {
+    let req_session = req.session();
+    let db = req.state().db.clone();
+    let token_id = path.into_inner();
+
+    Box::new(
+        require_browser_session(&req).and_then(move |session| {
+            let session = match session {
+                Some(session) => session,
+                None => {
+                    return Either::A(future::ok(
+                        HttpResponse::Found().header("location", "/").finish(),
+                    ))
+                }
+            };
+            Either::B(
+                db.send(RevokeDropBoxToken {
+                    token_id,
+                    user_id: session.key.user_id,
+                })
+                .from_err()
+                .and_then(|res| res)
+                .and_then(move |_| {
+                    req_session.flash("The drop-box was revoked.")?;
+                    Ok(HttpResponse::Found().header("location", "/tokens").finish())
+                }),
+            )
+        }),
+    )
+}
@@ -1,94 +1,187 @@
 use futures::future;
 use futures::{Future, Stream};
 
-use actix_web::{
-    dev, error, multipart, Error, FutureResponse, HttpMessage, HttpRequest, HttpResponse,
-};
-
-use std::fs;
-use std::io::Write;
+use actix_web::{dev, error, multipart, Error, FutureResponse, HttpMessage, HttpRequest, HttpResponse};
 
+use crate::object::Upload;
+use crate::property::SelectChoiceId;
 use crate::State;
 
-/// from payload, save file
-pub fn save_file(field: multipart::Field<dev::Payload>) -> Box<Future<Item = i64, Error = Error>> {
-    use std::ffi::OsStr;
-    use std::path::Path;
-
+/// from payload, read file, failing with 413 once it is over `max_bytes`
+pub fn read_file(
+    field: multipart::Field<dev::Payload>,
+    max_bytes: usize,
+) -> Box<Future<Item = Upload, Error = Error>> {
     let filename = field
         .content_disposition()
         .and_then(|cd| cd.get_filename().map(|st| st.to_string()))
         .unwrap_or("upload".to_string());
-    let fileext = Path::new(&filename)
-        .extension()
-        .and_then(OsStr::to_str)
-        .map_or("".to_string(), |ext| format!(".{}", ext));
 
-    println!(
-        "Saving file: filename {:?}; extension: {:?}",
-        filename, fileext
-    );
-    let file_path_string = format!("./static/uploads/{}", filename);
-    let mut file = match fs::File::create(file_path_string) {
-        Ok(file) => file,
-        Err(e) => return Box::new(future::err(error::ErrorInternalServerError(e))),
-    };
+    info!("Receiving file: filename {:?}", filename);
+    let too_large = format!("{} is larger than {} bytes", filename, max_bytes);
     Box::new(
         field
-            .fold(0i64, move |acc, bytes| {
-                let rt = file
-                    .write_all(bytes.as_ref())
-                    .map(|_| acc + bytes.len() as i64)
-                    .map_err(|e| {
-                        println!("file.write_all failed: {:?}", e);
-                        error::MultipartError::Payload(error::PayloadError::Io(e))
-                    });
-                future::result(rt)
-            })
             .map_err(|e| {
-                println!("save_file failed, {:?}", e);
+                warn!("read_file failed, {:?}", e);
                 error::ErrorInternalServerError(e)
+            })
+            .fold(Vec::new(), move |mut content, bytes| {
+                if content.len() + bytes.len() > max_bytes {
+                    return Err(error::ErrorPayloadTooLarge(too_large.clone()));
+                }
+                content.extend_from_slice(bytes.as_ref());
+                Ok(content)
+            })
+            .map(move |content| Upload {
+                filename,
+                content,
+                modified: None,
             }),
     )
 }
 
 pub fn handle_multipart_item(
     item: multipart::MultipartItem<dev::Payload>,
-) -> Box<Stream<Item = i64, Error = Error>> {
+    max_bytes: usize,
+) -> Box<Stream<Item = Upload, Error = Error>> {
     match item {
-        multipart::MultipartItem::Field(field) => Box::new(save_file(field).into_stream()),
+        multipart::MultipartItem::Field(field) => {
+            Box::new(read_file(field, max_bytes).into_stream())
+        }
         multipart::MultipartItem::Nested(mp) => Box::new(
             mp.map_err(error::ErrorInternalServerError)
-                .map(handle_multipart_item)
+                .map(move |item| handle_multipart_item(item, max_bytes))
                 .flatten(),
         ),
     }
 }
 
-pub fn upload(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
-    use actix::Addr;
-    use actix_web::error;
+/// Every file of a multipart request, none of them over the configured maximum
+pub fn read_uploads(req: &HttpRequest<State>) -> impl Future<Item = Vec<Upload>, Error = Error> {
+    let max_bytes = req.state().config.max_upload_bytes;
+    req.multipart()
+        .map_err(error::ErrorInternalServerError)
+        .map(move |item| handle_multipart_item(item, max_bytes))
+        .flatten()
+        .collect()
+}
 
+/// `POST /upload?collection=<collection id>`, responds with the new objects' ids
+///
+/// Emails (`.eml`) and the messages of mailboxes (`.mbox`) are followed by
+/// the ids of their attachments, unless `split=false` asks for every file
+/// to be stored as it is, as a single object.
+pub fn upload(req: HttpRequest<State>) -> FutureResponse<HttpResponse> {
+    use crate::sessions::session_routes::require_session;
     use crate::sessions::UserSession;
-    use crate::{is_signed_in_guard, SigninState};
+
+    let ingest = req.state().ingest();
+    let request_id = super::request_id(&req);
+    let collection = match req.query().get("collection") {
+        Some(id) => match id.parse::<i64>() {
+            Ok(id) => Some(SelectChoiceId::from(id)),
+            Err(_) => return Box::new(future::err(error::ErrorBadRequest("Invalid collection"))),
+        },
+        None => None,
+    };
+    let split = req.query().get("split").map_or(true, |split| split != "false");
 
     Box::new(
-        is_signed_in_guard(&req)
-            .and_then(|state| match state {
-                SigninState::Valid(session) => Ok(session),
-                _ => Err(error::ErrorForbidden("Must log in to upload")),
-            })
+        require_session(&req)
             .and_then(move |session: UserSession| {
-                req.multipart()
-                    .map_err(error::ErrorInternalServerError)
-                    .map(handle_multipart_item)
-                    .flatten()
-                    .collect()
-                    .map(|sizes| HttpResponse::Ok().json(sizes))
-                    .map_err(|e| {
-                        println!("failed: {}", e);
-                        e
-                    })
+                read_uploads(&req).and_then(move |uploads| {
+                    let user_id = session.key.user_id;
+                    future::join_all(uploads.into_iter().map(move |upload| {
+                        let (user_id, collection) = (user_id.clone(), collection.clone());
+                        let request_id = Some(request_id.clone());
+                        if split {
+                            future::Either::A(
+                                ingest.create_objects(upload, user_id, collection, request_id),
+                            )
+                        } else {
+                            future::Either::B(
+                                ingest
+                                    .create_object(upload, user_id, collection, request_id)
+                                    .map(|object_id| vec![object_id]),
+                            )
+                        }
+                    }))
+                })
+            })
+            .map(|object_ids| {
+                let object_ids: Vec<_> = object_ids.into_iter().flatten().collect();
+                HttpResponse::Ok().json(object_ids)
+            })
+            .map_err(|e| {
+                warn!("upload failed: {}", e);
+                e
             }),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http, test};
+
+    /// Responds with the names of the files uploaded, each at most 8 bytes
+    fn server() -> test::TestServer {
+        test::TestServer::new(|app| {
+            app.resource("/", |r| {
+                r.f(|req: &HttpRequest| -> FutureResponse<HttpResponse> {
+                    Box::new(
+                        req.multipart()
+                            .map_err(error::ErrorInternalServerError)
+                            .map(|item| handle_multipart_item(item, 8))
+                            .flatten()
+                            .collect()
+                            .map(|uploads| {
+                                let names: Vec<_> =
+                                    uploads.into_iter().map(|u| u.filename).collect();
+                                HttpResponse::Ok().body(names.join(","))
+                            }),
+                    )
+                })
+            });
+        })
+    }
+
+    fn upload(server: &mut test::TestServer, content: &str) -> (http::StatusCode, String) {
+        let body = format!(
+            "--boundary\r\n\
+             Content-Disposition: form-data; name=\"file\"; filename=\"notes.txt\"\r\n\
+             Content-Type: text/plain\r\n\r\n\
+             {}\r\n\
+             --boundary--\r\n",
+            content
+        );
+        let request = server
+            .post()
+            .header(
+                http::header::CONTENT_TYPE,
+                "multipart/form-data; boundary=boundary",
+            )
+            .body(body)
+            .unwrap();
+        let response = server.execute(request.send()).unwrap();
+        let status = response.status();
+        let body = server.execute(response.body()).unwrap();
+        (status, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[test]
+    fn within_the_limit() {
+        let mut server = server();
+        assert_eq!(
+            upload(&mut server, "12345678"),
+            (http::StatusCode::OK, "notes.txt".to_string())
+        );
+    }
+
+    #[test]
+    fn over_the_limit() {
+        let mut server = server();
+        let (status, _) = upload(&mut server, "123456789");
+        assert_eq!(status, http::StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
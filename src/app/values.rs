@@ -0,0 +1,69 @@
+use futures::Future;
+
+use actix_web::{FutureResponse, HttpRequest, HttpResponse, Json, Path};
+
+use crate::db::{SetValues, SetValuesResult, ValueUpdate};
+use crate::object::ObjectId;
+use crate::property::{PropertyId, PropertyValue};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::State;
+
+use super::request_id;
+
+#[derive(Deserialize)]
+pub struct ValueUpdates {
+    pub values: Vec<ValueUpdate>,
+}
+
+fn save_values(
+    req: &HttpRequest<State>,
+    object_id: ObjectId,
+    values: Vec<ValueUpdate>,
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let request_id = request_id(req);
+
+    Box::new(
+        require_session(req).and_then(move |session: UserSession| {
+            db.send(SetValues {
+                object_id,
+                values,
+                user_id: session.key.user_id,
+                request_id: Some(request_id),
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|result| match result {
+                SetValuesResult::Saved => HttpResponse::NoContent().finish(),
+                SetValuesResult::Invalid(errors) => {
+                    HttpResponse::BadRequest().json(json!({ "errors": errors }))
+                }
+            })
+        }),
+    )
+}
+
+/// `POST /objects/{object_id}/values` with `{"values": [{"property_id", "value"}]}`
+///
+/// Responds with `{"errors": [{"property_id", "message"}]}` if any value is invalid.
+pub fn set_values(
+    (req, path, body): (HttpRequest<State>, Path<ObjectId>, Json<ValueUpdates>),
+) -> FutureResponse<HttpResponse> {
+    save_values(&req, path.into_inner(), body.into_inner().values)
+}
+
+/// `POST /objects/{object_id}/values/{property_id}` with a JSON `PropertyValue`
+pub fn set_value(
+    (req, path, body): (HttpRequest<State>, Path<(ObjectId, PropertyId)>, Json<PropertyValue>),
+) -> FutureResponse<HttpResponse> {
+    let (object_id, property_id) = path.into_inner();
+    save_values(
+        &req,
+        object_id,
+        vec![ValueUpdate {
+            property_id,
+            value: body.into_inner(),
+        }],
+    )
+}
@@ -0,0 +1,142 @@
+use futures::future::{self, Either};
+use futures::Future;
+
+use actix_web::{error, http, FutureResponse, HttpRequest, HttpResponse, Path};
+
+use crate::db::{GetObjectVersion, ListObjectVersions, RestoreObjectVersion};
+use crate::object::store::GetBlob;
+use crate::object::{ObjectId, ObjectVersion};
+use crate::sessions::session_routes::require_session;
+use crate::sessions::UserSession;
+use crate::State;
+
+use super::request_id;
+use super::upload::read_uploads;
+
+/// `GET /objects/{object_id}/versions`, newest first
+pub fn list_versions(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let object_id = path.into_inner();
+
+    Box::new(require_session(&req).and_then(move |_| {
+        db.send(ListObjectVersions(object_id))
+            .from_err()
+            .and_then(|res| res)
+            .map(|versions| HttpResponse::Ok().json(versions))
+    }))
+}
+
+/// `POST /objects/{object_id}/versions` with a single multipart file
+pub fn add_version(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let ingest = req.state().ingest();
+    let object_id = path.into_inner();
+    let request_id = request_id(&req);
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            read_uploads(&req).and_then(move |mut uploads| {
+                if uploads.len() != 1 {
+                    return Either::A(future::err(error::ErrorBadRequest(
+                        "Upload exactly one file as the new version",
+                    )));
+                }
+                Either::B(
+                    ingest
+                        .add_version(
+                            object_id,
+                            uploads.remove(0),
+                            session.key.user_id,
+                            Some(request_id),
+                        )
+                        .map(|version| HttpResponse::Created().json(json!({ "version": version }))),
+                )
+            })
+        }),
+    )
+}
+
+fn download(req: &HttpRequest<State>, msg: GetObjectVersion) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let store = req.state().store.clone();
+
+    Box::new(require_session(req).and_then(move |_| {
+        db.send(msg)
+            .from_err()
+            .and_then(|res| res)
+            .and_then(move |version: ObjectVersion| {
+                store
+                    .send(GetBlob {
+                        hash: version.hash.clone(),
+                    })
+                    .from_err()
+                    .and_then(|res| res)
+                    .map(move |content| {
+                        HttpResponse::Ok()
+                            .header(http::header::CONTENT_TYPE, "application/octet-stream")
+                            .header(
+                                http::header::CONTENT_DISPOSITION,
+                                format!(
+                                    "attachment; filename=\"{}\"",
+                                    version.filename.replace('"', "")
+                                ),
+                            )
+                            .body(content)
+                    })
+            })
+    }))
+}
+
+/// `GET /objects/{object_id}/content`, the current version's content
+pub fn download_current(
+    (req, path): (HttpRequest<State>, Path<ObjectId>),
+) -> FutureResponse<HttpResponse> {
+    let object_id = path.into_inner();
+    download(
+        &req,
+        GetObjectVersion {
+            object_id,
+            version: None,
+        },
+    )
+}
+
+/// `GET /objects/{object_id}/versions/{version}/content`
+pub fn download_version(
+    (req, path): (HttpRequest<State>, Path<(ObjectId, i32)>),
+) -> FutureResponse<HttpResponse> {
+    let (object_id, version) = path.into_inner();
+    download(
+        &req,
+        GetObjectVersion {
+            object_id,
+            version: Some(version),
+        },
+    )
+}
+
+/// `POST /objects/{object_id}/versions/{version}/restore`, responds with the new version number
+pub fn restore_version(
+    (req, path): (HttpRequest<State>, Path<(ObjectId, i32)>),
+) -> FutureResponse<HttpResponse> {
+    let db = req.state().db.clone();
+    let (object_id, version) = path.into_inner();
+    let request_id = request_id(&req);
+
+    Box::new(
+        require_session(&req).and_then(move |session: UserSession| {
+            db.send(RestoreObjectVersion {
+                object_id,
+                version,
+                restored_by: session.key.user_id,
+                request_id: Some(request_id),
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(|version| HttpResponse::Created().json(json!({ "version": version })))
+        }),
+    )
+}
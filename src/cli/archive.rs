@@ -0,0 +1,313 @@
+//! `dewey archive export` and `dewey archive import`
+//!
+//! An archive is a zip of the archived tables as `tables/<table>.json`, the
+//! content of every object version as `blobs/<sha256>`, and a manifest
+//! describing both with their SHA-256 checksums. Importing one into an empty
+//! database at the same migration restores what was exported.
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use super::Runner;
+use crate::db::{ArchivedTable, ExportTables, ImportTables};
+use crate::object::store::{GetBlob, PutBlob};
+
+const MANIFEST: &str = "dewey-archive.json";
+const FORMAT: &str = "dewey-archive";
+const VERSION: u32 = 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: String,
+    version: u32,
+    created_at: DateTime<Utc>,
+    /// The database migration the tables are at
+    migration: String,
+    /// In the order they are restored
+    tables: Vec<TableEntry>,
+    blobs: Vec<BlobEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TableEntry {
+    name: String,
+    file: String,
+    rows: i64,
+    sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BlobEntry {
+    file: String,
+    size: u64,
+    sha256: String,
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Write every archived table, and the content of every object version, to
+/// the archive `path`
+pub fn export(runner: &mut Runner, path: &str) -> Result<(), String> {
+    let (migration, tables) = runner.db(ExportTables)?;
+    let store = runner.store()?;
+
+    let out = File::create(path).map_err(|e| format!("can't create {}: {}", path, e))?;
+    let mut zip = ZipWriter::new(out);
+    let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    // contents are mostly compressed already
+    let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+    let write_error = |e: &dyn std::fmt::Display| format!("can't write {}: {}", path, e);
+
+    let mut manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: VERSION,
+        created_at: Utc::now(),
+        migration,
+        tables: Vec::new(),
+        blobs: Vec::new(),
+    };
+    let mut hashes = BTreeSet::new();
+    for table in &tables {
+        if table.name == "object_versions" {
+            hashes = version_hashes(&table.json)?;
+        }
+        let file = format!("tables/{}.json", table.name);
+        zip.start_file(file.as_str(), deflated)
+            .map_err(|e| write_error(&e))?;
+        zip.write_all(table.json.as_bytes())
+            .map_err(|e| write_error(&e))?;
+        println!("table\t{}\t{}", table.name, table.rows);
+        manifest.tables.push(TableEntry {
+            name: table.name.clone(),
+            file,
+            rows: table.rows,
+            sha256: sha256(table.json.as_bytes()),
+        });
+    }
+
+    // the blobs of the versions exported, rather than those stored, which
+    // may include ones of versions created since
+    for hash in hashes {
+        let content = runner
+            .wait(store.send(GetBlob { hash: hash.clone() }))?
+            .map_err(|e| format!("can't read the content {}: {}", hash, e))?;
+        if sha256(&content) != hash {
+            return Err(format!(
+                "the stored content {} does not match its hash",
+                hash
+            ));
+        }
+        let file = format!("blobs/{}", hash);
+        zip.start_file(file.as_str(), stored)
+            .map_err(|e| write_error(&e))?;
+        zip.write_all(&content).map_err(|e| write_error(&e))?;
+        manifest.blobs.push(BlobEntry {
+            file,
+            size: content.len() as u64,
+            sha256: hash,
+        });
+    }
+
+    let json = serde_json::to_vec_pretty(&manifest).map_err(|e| e.to_string())?;
+    zip.start_file(MANIFEST, deflated)
+        .map_err(|e| write_error(&e))?;
+    zip.write_all(&json).map_err(|e| write_error(&e))?;
+    zip.finish().map_err(|e| write_error(&e))?;
+    println!(
+        "{} tables and {} contents exported to {}",
+        manifest.tables.len(),
+        manifest.blobs.len(),
+        path
+    );
+    Ok(())
+}
+
+/// The distinct hashes of the rows of the object_versions table
+fn version_hashes(json: &str) -> Result<BTreeSet<String>, String> {
+    #[derive(Deserialize)]
+    struct VersionRow {
+        hash: String,
+    }
+    serde_json::from_str::<Vec<VersionRow>>(json)
+        .map(|rows| rows.into_iter().map(|row| row.hash).collect())
+        .map_err(|e| format!("can't read the exported object versions: {}", e))
+}
+
+/// Check the archive `path` against its manifest, store its contents and
+/// restore its tables into an empty database
+pub fn import(runner: &mut Runner, path: &str) -> Result<(), String> {
+    let file = File::open(path).map_err(|e| format!("can't open {}: {}", path, e))?;
+    let mut zip = ZipArchive::new(file).map_err(|e| format!("{} is not a zip: {}", path, e))?;
+
+    let manifest = read_manifest(&mut zip, path)?;
+
+    // every table is checked before anything is stored
+    let mut tables = Vec::new();
+    for entry in &manifest.tables {
+        let content = read_verified(&mut zip, &entry.file, &entry.sha256)?;
+        let json = String::from_utf8(content)
+            .map_err(|_| format!("{} in the archive is not UTF-8", entry.file))?;
+        tables.push(ArchivedTable {
+            name: entry.name.clone(),
+            rows: entry.rows,
+            json,
+        });
+    }
+
+    // contents are stored under their hash, so ones stored already are
+    // written again unchanged
+    let store = runner.store()?;
+    for entry in &manifest.blobs {
+        let content = read_verified(&mut zip, &entry.file, &entry.sha256)?;
+        runner
+            .wait(store.send(PutBlob {
+                hash: entry.sha256.clone(),
+                content,
+            }))?
+            .map_err(|e| format!("can't store the content {}: {}", entry.sha256, e))?;
+    }
+    println!("{} contents stored", manifest.blobs.len());
+
+    let restored = runner.db(ImportTables {
+        migration: manifest.migration,
+        tables,
+        request_id: Some(format!("restore-{}", Utc::now().timestamp())),
+    })?;
+    for (table, rows) in &restored {
+        println!("table\t{}\t{}", table, rows);
+    }
+    println!("{} tables restored from {}", restored.len(), path);
+    Ok(())
+}
+
+/// The manifest of the archive `path`, if it is of the format and version
+/// this build reads
+fn read_manifest<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    path: &str,
+) -> Result<Manifest, String> {
+    let manifest: Manifest = serde_json::from_slice(&read_entry(zip, MANIFEST)?)
+        .map_err(|e| format!("can't read the manifest: {}", e))?;
+    if manifest.format != FORMAT || manifest.version != VERSION {
+        return Err(format!(
+            "{} is a {} archive of version {}, only {} archives of version {} can be imported",
+            path, manifest.format, manifest.version, FORMAT, VERSION
+        ));
+    }
+    Ok(manifest)
+}
+
+fn read_entry<R: Read + Seek>(zip: &mut ZipArchive<R>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = zip
+        .by_name(name)
+        .map_err(|e| format!("the archive has no {}: {}", name, e))?;
+    let mut content = Vec::new();
+    entry
+        .read_to_end(&mut content)
+        .map_err(|e| format!("can't read {} from the archive: {}", name, e))?;
+    Ok(content)
+}
+
+/// The entry `name`, if its SHA-256 is `checksum`
+fn read_verified<R: Read + Seek>(
+    zip: &mut ZipArchive<R>,
+    name: &str,
+    checksum: &str,
+) -> Result<Vec<u8>, String> {
+    let content = read_entry(zip, name)?;
+    if sha256(&content) != checksum {
+        return Err(format!(
+            "{} in the archive does not match its checksum",
+            name
+        ));
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::process::Command;
+
+    /// An archive of `entries` and a manifest describing them as they are,
+    /// except for the format and version
+    fn archive(
+        format: &str,
+        version: u32,
+        entries: &[(&str, &[u8])],
+    ) -> ZipArchive<Cursor<Vec<u8>>> {
+        let manifest = Manifest {
+            format: format.to_string(),
+            version,
+            created_at: Utc::now(),
+            migration: "00000000000000".to_string(),
+            tables: Vec::new(),
+            blobs: entries
+                .iter()
+                .map(|(file, content)| BlobEntry {
+                    file: file.to_string(),
+                    size: content.len() as u64,
+                    sha256: sha256(content),
+                })
+                .collect(),
+        };
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (file, content) in entries {
+            zip.start_file(*file, FileOptions::default()).unwrap();
+            zip.write_all(content).unwrap();
+        }
+        zip.start_file(MANIFEST, FileOptions::default()).unwrap();
+        zip.write_all(&serde_json::to_vec(&manifest).unwrap())
+            .unwrap();
+        ZipArchive::new(zip.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn verified_entries() {
+        let mut zip = archive(FORMAT, VERSION, &[("blobs/a", b"some content")]);
+        let manifest = read_manifest(&mut zip, "test.zip").unwrap();
+        let blob = &manifest.blobs[0];
+        assert_eq!(
+            read_verified(&mut zip, &blob.file, &blob.sha256).unwrap(),
+            b"some content"
+        );
+    }
+
+    #[test]
+    fn tampered_entry() {
+        let mut zip = archive(FORMAT, VERSION, &[("blobs/a", b"some content")]);
+        let error = read_verified(&mut zip, "blobs/a", &sha256(b"other content")).unwrap_err();
+        assert_eq!(error, "blobs/a in the archive does not match its checksum");
+
+        let error = read_verified(&mut zip, "blobs/b", &sha256(b"some content")).unwrap_err();
+        assert!(error.starts_with("the archive has no blobs/b"));
+    }
+
+    #[test]
+    fn other_format_or_version() {
+        let error = read_manifest(&mut archive("zip", VERSION, &[]), "test.zip").unwrap_err();
+        assert!(error.starts_with("test.zip is a zip archive of version 1"));
+
+        let error = read_manifest(&mut archive(FORMAT, VERSION + 1, &[]), "test.zip").unwrap_err();
+        assert!(error.starts_with("test.zip is a dewey-archive archive of version 2"));
+    }
+
+    /// Needs `DATABASE_URL` and the services of `docker-compose up`
+    #[test]
+    #[ignore]
+    fn round_trip() {
+        let script = concat!(env!("CARGO_MANIFEST_DIR"), "/scripts/archive_roundtrip.sh");
+        let status = Command::new(script)
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .status()
+            .unwrap();
+        assert!(status.success(), "{} failed", script);
+    }
+}
@@ -0,0 +1,55 @@
+//! `dewey check`
+use ::actix::prelude::*;
+use diesel::prelude::*;
+use futures::Future;
+use redis_async::client::paired_connect;
+
+use crate::config::Config;
+use crate::store::{CheckBucket, ObjectStore};
+
+/// Report whether each service the server needs can be reached, failing if
+/// any can't. The configuration was validated on loading it.
+pub fn check(config: &Config) -> Result<(), String> {
+    let mut sys = System::new("dewey");
+    println!("configuration\tok");
+
+    let postgres = PgConnection::establish(&config.database_url)
+        .map(|_| ())
+        .map_err(|e| e.to_string());
+
+    let redis = sys
+        .block_on(
+            paired_connect(&config.redis_addr)
+                .and_then(|redis| redis.send::<String>(resp_array!["PING"])),
+        )
+        .map(|_| ())
+        .map_err(|e| format!("{:?}", e));
+
+    let store = ObjectStore::new_with_s3_credentials(
+        &config.s3_access_key_id,
+        &config.s3_secret_access_key,
+    )
+    .map_err(|e| e.to_string())
+    .and_then(|store| {
+        sys.block_on(store.start().send(CheckBucket).then(|sent| match sent {
+            Ok(checked) => checked.map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        }))
+    });
+
+    let mut failed = 0;
+    let services = [("postgres", postgres), ("redis", redis), ("object store", store)];
+    for (service, reached) in &services {
+        match reached {
+            Ok(()) => println!("{}\tok", service),
+            Err(e) => {
+                failed += 1;
+                println!("{}\tFAILED: {}", service, e);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} of the services can't be reached", failed));
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! `dewey gc`
+use chrono::{Duration, Utc};
+use std::collections::HashSet;
+
+use super::Runner;
+use crate::db::ListVersionHashes;
+use crate::object::store::{DeleteBlob, ListBlobs};
+
+/// Blobs younger than this are kept, their object may still be being created
+const GRACE_HOURS: i64 = 24;
+
+/// Delete the blobs which no object version refers to, or with `dry_run`
+/// only list them
+pub fn gc(runner: &mut Runner, dry_run: bool) -> Result<(), String> {
+    let store = runner.store()?;
+    let blobs = runner
+        .wait(store.send(ListBlobs))?
+        .map_err(|e| format!("can't list the stored contents: {}", e))?;
+    let referenced: HashSet<String> = runner.db(ListVersionHashes)?.into_iter().collect();
+
+    let cutoff = Utc::now() - Duration::hours(GRACE_HOURS);
+    let orphaned = blobs
+        .into_iter()
+        .filter(|blob| !referenced.contains(&blob.hash))
+        .filter(|blob| blob.last_modified.map_or(false, |at| at < cutoff))
+        .collect::<Vec<_>>();
+
+    let mut freed = 0;
+    for blob in &orphaned {
+        if !dry_run {
+            runner
+                .wait(store.send(DeleteBlob {
+                    hash: blob.hash.clone(),
+                }))?
+                .map_err(|e| format!("can't delete {}: {}", blob.hash, e))?;
+        }
+        freed += blob.size;
+        println!("{}\t{}", blob.hash, blob.size);
+    }
+
+    println!(
+        "{} {} orphaned blobs, {} bytes",
+        if dry_run { "Would delete" } else { "Deleted" },
+        orphaned.len(),
+        freed
+    );
+    Ok(())
+}
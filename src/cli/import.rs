@@ -0,0 +1,451 @@
+//! `dewey import`, onboarding a directory tree of files
+//!
+//! Files in a top folder are filed into the collection of the same name, and
+//! optionally tagged with the names of the folders below it. Files whose
+//! content is already stored are reported as duplicates instead of being
+//! stored again, so an interrupted import resumes by running it again. The
+//! stored object is still filed and tagged like the file, keeping the
+//! collections and tags it has.
+use actix::Addr;
+use actix_web::Error;
+use chrono::Utc;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use super::objects::read_upload;
+use super::Runner;
+use crate::db::{
+    CreateChoice, DbExecutor, FindObjectByHash, GetObject, ListChoices, SetValues,
+    SetValuesResult, ValueUpdate,
+};
+use crate::object::{Ingest, ObjectId, Upload};
+use crate::property::{PropertyId, PropertyValue, SelectChoiceId};
+use crate::user::UserId;
+
+/// Files imported at a time when not given, and the most allowed
+pub const DEFAULT_JOBS: usize = 4;
+pub const MAX_JOBS: usize = 32;
+
+pub struct ImportOptions {
+    pub dir: String,
+    pub jobs: usize,
+    /// For the files directly in `dir`, the others are filed by their top folder
+    pub collection: Option<SelectChoiceId>,
+    /// Tag files with the folders below their top folder
+    pub tags: bool,
+    pub created_by: UserId,
+    /// Where to write the outcome of every file as JSON
+    pub report: Option<String>,
+}
+
+/// A file in the tree, with the folders it is in below the root
+pub struct FoundFile {
+    pub path: PathBuf,
+    pub folders: Vec<String>,
+}
+
+/// What became of a file
+#[derive(Debug, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+enum Outcome {
+    Created {
+        path: String,
+        object_id: ObjectId,
+        /// Set when the object was created but its tags were refused
+        warning: Option<String>,
+    },
+    /// Its content is stored already, as an object or by another file of the import
+    Duplicate {
+        path: String,
+        object_id: Option<ObjectId>,
+        same_as: Option<String>,
+        /// Set when the file's collection or tags were refused for the object
+        warning: Option<String>,
+    },
+    Failed {
+        path: String,
+        error: String,
+    },
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    created: usize,
+    duplicates: usize,
+    failed: usize,
+    files: Vec<Outcome>,
+}
+
+pub fn import(runner: &mut Runner, options: ImportOptions) -> Result<(), String> {
+    let jobs = options.jobs;
+    let mut files = Vec::new();
+    walk(Path::new(&options.dir), &[], &mut files)?;
+
+    let mut top_folders = BTreeSet::new();
+    let mut nested_folders = BTreeSet::new();
+    for file in &files {
+        let mut folders = file.folders.iter();
+        top_folders.extend(folders.next().cloned());
+        nested_folders.extend(folders.cloned());
+    }
+    let collections = resolve_choices(runner, PropertyId::COLLECTION, &top_folders)?;
+    let tags = if options.tags {
+        resolve_choices(runner, PropertyId::TAGS, &nested_folders)?
+    } else {
+        HashMap::new()
+    };
+
+    let importer = Rc::new(Importer {
+        ingest: Ingest {
+            db: runner.db.clone(),
+            store: runner.store()?,
+        },
+        db: runner.db.clone(),
+        created_by: options.created_by,
+        request_id: format!("import-{}", Utc::now().timestamp()),
+        root_collection: options.collection,
+        collections,
+        tags,
+        claimed: RefCell::new(HashMap::new()),
+    });
+    println!("Importing {} files, {} at a time", files.len(), jobs);
+    let outcomes = runner.wait(
+        stream::iter_ok::<_, String>(files)
+            .map(move |file| importer.clone().import(file))
+            .buffer_unordered(jobs)
+            .inspect(print_outcome)
+            .collect(),
+    )?;
+
+    let mut report = Report::default();
+    for outcome in outcomes {
+        match outcome {
+            Outcome::Created { .. } => report.created += 1,
+            Outcome::Duplicate { .. } => report.duplicates += 1,
+            Outcome::Failed { .. } => report.failed += 1,
+        }
+        report.files.push(outcome);
+    }
+    println!(
+        "{} created, {} duplicates, {} failed",
+        report.created, report.duplicates, report.failed
+    );
+    if let Some(ref path) = options.report {
+        let json = serde_json::to_vec_pretty(&report).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("can't write the report {}: {}", path, e))?;
+    }
+    if report.failed > 0 {
+        return Err(format!(
+            "{} files failed, running the import again retries them",
+            report.failed
+        ));
+    }
+    Ok(())
+}
+
+/// Find the files below `dir` in name order, leaving out hidden ones like `.DS_Store`
+pub fn walk(dir: &Path, folders: &[String], found: &mut Vec<FoundFile>) -> Result<(), String> {
+    let mut entries = fs::read_dir(dir)
+        .and_then(|entries| entries.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("can't read {}: {}", dir.display(), e))?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with('.') {
+            continue;
+        }
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("can't read {}: {}", entry.path().display(), e))?;
+        if file_type.is_dir() {
+            let mut nested = folders.to_vec();
+            nested.push(name);
+            walk(&entry.path(), &nested, found)?;
+        } else if file_type.is_file() {
+            found.push(FoundFile {
+                path: entry.path(),
+                folders: folders.to_vec(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// The choices of `property_id` named like `names`, ignoring case, creating
+/// the missing ones
+fn resolve_choices(
+    runner: &mut Runner,
+    property_id: PropertyId,
+    names: &BTreeSet<String>,
+) -> Result<HashMap<String, SelectChoiceId>, String> {
+    let existing = runner.db(ListChoices {
+        property_id: property_id.clone(),
+    })?;
+    let mut choices = HashMap::new();
+    for name in names.iter().filter(|name| !name.trim().is_empty()) {
+        let found = existing
+            .iter()
+            .find(|choice| choice.display.trim().to_lowercase() == name.trim().to_lowercase());
+        let choice_id = match found {
+            Some(choice) => choice.id.clone(),
+            None => {
+                let created = runner.db(CreateChoice {
+                    property_id: property_id.clone(),
+                    display: name.clone(),
+                    created_by: UserId::ADMINISTRATOR,
+                })?;
+                println!("created choice {} of property {}: {}", created, property_id, name);
+                created
+            }
+        };
+        choices.insert(name.clone(), choice_id);
+    }
+    Ok(choices)
+}
+
+fn print_outcome(outcome: &Outcome) {
+    match outcome {
+        Outcome::Created {
+            path,
+            object_id,
+            warning,
+        } => match warning {
+            Some(warning) => println!("created\t{}\t{}\t{}", object_id, path, warning),
+            None => println!("created\t{}\t{}", object_id, path),
+        },
+        Outcome::Duplicate {
+            path,
+            object_id: Some(object_id),
+            warning,
+            ..
+        } => match warning {
+            Some(warning) => println!("duplicate\t{}\t{}\t{}", object_id, path, warning),
+            None => println!("duplicate\t{}\t{}", object_id, path),
+        },
+        Outcome::Duplicate { path, same_as, .. } => println!(
+            "duplicate\t-\t{}\tsame as {}",
+            path,
+            same_as.as_ref().map_or("", String::as_str)
+        ),
+        Outcome::Failed { path, error } => eprintln!("failed\t-\t{}\t{}", path, error),
+    }
+}
+
+/// Imports files one at a time, shared by the files being imported at once
+struct Importer {
+    ingest: Ingest,
+    db: Addr<DbExecutor>,
+    created_by: UserId,
+    /// Recorded with the values the import sets, telling them apart in their history
+    request_id: String,
+    root_collection: Option<SelectChoiceId>,
+    collections: HashMap<String, SelectChoiceId>,
+    tags: HashMap<String, SelectChoiceId>,
+    /// The hash of every file of this import, and the file's path
+    claimed: RefCell<HashMap<String, String>>,
+}
+
+impl Importer {
+    /// Read and store a file, only once the future is first polled so that
+    /// no more than the files being imported at once are held in memory
+    fn import(self: Rc<Self>, file: FoundFile) -> impl Future<Item = Outcome, Error = String> {
+        future::lazy(move || {
+            let path = file.path.display().to_string();
+            let upload = match read_upload(&file.path) {
+                Ok(upload) => upload,
+                Err(error) => return Either::A(future::ok(Outcome::Failed { path, error })),
+            };
+
+            // files with the same content aren't checked against the store at once
+            let hash = upload.hash();
+            if let Some(same_as) = self.claimed.borrow().get(&hash) {
+                return Either::A(future::ok(Outcome::Duplicate {
+                    path,
+                    object_id: None,
+                    same_as: Some(same_as.clone()),
+                    warning: None,
+                }));
+            }
+            self.claimed.borrow_mut().insert(hash.clone(), path.clone());
+
+            let failed_path = path.clone();
+            Either::B(
+                self.db
+                    .send(FindObjectByHash { hash })
+                    .from_err::<Error>()
+                    .and_then(|res| res)
+                    .and_then(move |existing| match existing {
+                        Some(object_id) => Either::A(
+                            self.file_existing(&object_id, &file.folders)
+                                .map(move |warning| Outcome::Duplicate {
+                                    path,
+                                    object_id: Some(object_id),
+                                    same_as: None,
+                                    warning,
+                                }),
+                        ),
+                        None => Either::B(self.create(upload, &file.folders).map(
+                            move |(object_id, warning)| Outcome::Created {
+                                path,
+                                object_id,
+                                warning,
+                            },
+                        )),
+                    })
+                    .or_else(move |e| {
+                        Ok(Outcome::Failed {
+                            path: failed_path,
+                            error: e.to_string(),
+                        })
+                    }),
+            )
+        })
+    }
+
+    /// The collection and tags of a file in `folders`
+    fn folder_values(&self, folders: &[String]) -> (Option<SelectChoiceId>, Vec<SelectChoiceId>) {
+        let collection = match folders.first() {
+            Some(top) => self.collections.get(top).cloned(),
+            None => self.root_collection.clone(),
+        };
+        let mut tags = Vec::new();
+        for tag in folders.iter().skip(1).filter_map(|folder| self.tags.get(folder)) {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        (collection, tags)
+    }
+
+    /// Add the collection and tags of a file to the object already storing its
+    /// content, keeping the ones it has. Resolves to a warning if they were refused.
+    fn file_existing(
+        &self,
+        object_id: &ObjectId,
+        folders: &[String],
+    ) -> impl Future<Item = Option<String>, Error = Error> {
+        let (collection, tags) = self.folder_values(folders);
+        let db = self.db.clone();
+        let object_id = object_id.clone();
+        let (created_by, request_id) = (self.created_by.clone(), self.request_id.clone());
+        self.db
+            .send(GetObject(object_id.clone()))
+            .from_err()
+            .and_then(|res| res)
+            .and_then(move |object| {
+                let current = object.map(|object| object.values).unwrap_or_default();
+                let mut values = Vec::new();
+                for (property_id, added) in [
+                    (PropertyId::COLLECTION, collection.into_iter().collect()),
+                    (PropertyId::TAGS, tags),
+                ] {
+                    if let Some(choices) = with_choices(&current, &property_id, added) {
+                        values.push(ValueUpdate {
+                            property_id,
+                            value: PropertyValue::Choice(choices),
+                        });
+                    }
+                }
+                if values.is_empty() {
+                    return Either::A(future::ok(None));
+                }
+                Either::B(
+                    db.send(SetValues {
+                        object_id,
+                        values,
+                        user_id: created_by,
+                        request_id: Some(request_id),
+                    })
+                    .from_err()
+                    .and_then(|res| res)
+                    .map(|result| refused("collection and tags", result)),
+                )
+            })
+    }
+
+    /// Store the file as an object in the collection of its top folder, tagged
+    /// with the folders below it
+    fn create(
+        &self,
+        upload: Upload,
+        folders: &[String],
+    ) -> impl Future<Item = (ObjectId, Option<String>), Error = Error> {
+        let (collection, tags) = self.folder_values(folders);
+        let db = self.db.clone();
+        let (created_by, request_id) = (self.created_by.clone(), self.request_id.clone());
+        self.ingest
+            .create_object(
+                upload,
+                created_by.clone(),
+                collection,
+                Some(request_id.clone()),
+            )
+            .and_then(move |object_id| {
+                if tags.is_empty() {
+                    return Either::A(future::ok((object_id, None)));
+                }
+                Either::B(
+                    db.send(SetValues {
+                        object_id: object_id.clone(),
+                        values: vec![ValueUpdate {
+                            property_id: PropertyId::TAGS,
+                            value: PropertyValue::Choice(tags),
+                        }],
+                        user_id: created_by,
+                        request_id: Some(request_id),
+                    })
+                    .from_err()
+                    .and_then(|res| res)
+                    .map(move |result| (object_id, refused("tags", result))),
+                )
+            })
+    }
+}
+
+/// The choices `current` has for `property_id` along with `added`, `None` if
+/// it has all of them already
+fn with_choices(
+    current: &[ValueUpdate],
+    property_id: &PropertyId,
+    added: Vec<SelectChoiceId>,
+) -> Option<Vec<SelectChoiceId>> {
+    let mut choices = current
+        .iter()
+        .find(|update| &update.property_id == property_id)
+        .and_then(|update| match update.value {
+            PropertyValue::Choice(ref choices) => Some(choices.clone()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let before = choices.len();
+    for choice in added {
+        if !choices.contains(&choice) {
+            choices.push(choice);
+        }
+    }
+    if choices.len() > before {
+        Some(choices)
+    } else {
+        None
+    }
+}
+
+/// A warning that `what` was not set, if the values were refused
+fn refused(what: &str, result: SetValuesResult) -> Option<String> {
+    match result {
+        SetValuesResult::Saved => None,
+        SetValuesResult::Invalid(errors) => {
+            let messages = errors
+                .into_iter()
+                .map(|error| error.message)
+                .collect::<Vec<_>>();
+            Some(format!("{} not set: {}", what, messages.join("; ")))
+        }
+    }
+}
@@ -0,0 +1,345 @@
+//! Commands for operators, run with the same configuration as the server.
+//! Each command starts the actors it needs on a system of its own and waits
+//! for their results, so the handlers are shared with the server.
+use ::actix::prelude::*;
+use ::actix::SystemRunner;
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use diesel::prelude::*;
+use diesel::r2d2::{ConnectionManager, Pool};
+use futures::Future;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::config::{Config, Section};
+use crate::db::DbExecutor;
+use crate::store::ObjectStore;
+use crate::user::UserId;
+
+mod archive;
+mod check;
+mod gc;
+mod import;
+mod objects;
+mod properties;
+mod sync;
+mod users;
+
+embed_migrations!("migrations");
+
+/// Connections to Postgres a command may use at a time
+const DB_CONNECTIONS: u32 = 2;
+
+/// The subcommands besides `start`
+pub fn subcommands() -> Vec<App<'static, 'static>> {
+    let user_id = || Arg::with_name("USER_ID").required(true).help("Id of the user");
+    vec![
+        SubCommand::with_name("migrate")
+            .about("Runs the database migrations which have not been run yet"),
+        SubCommand::with_name("check")
+            .about("Checks the configuration, and that Postgres, Redis and the object store can be reached"),
+        SubCommand::with_name("user")
+            .about("Lists and manages users")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                        .about("Lists every person and plugin"))
+            .subcommand(SubCommand::with_name("disable")
+                        .about("Signs a user out everywhere, and refuses their sign ins and tokens")
+                        .arg(user_id())
+                        .arg(Arg::with_name("enable")
+                             .long("enable")
+                             .help("Enables the user again instead")))
+            .subcommand(SubCommand::with_name("promote")
+                        .about("Makes a person an administrator, who may manage plugins")
+                        .arg(user_id())
+                        .arg(Arg::with_name("demote")
+                             .long("demote")
+                             .help("Makes the user no longer an administrator instead"))),
+        SubCommand::with_name("property")
+            .about("Lists and creates properties")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("list")
+                        .about("Lists every property in order"))
+            .subcommand(SubCommand::with_name("create")
+                        .about("Creates a property, after every existing one")
+                        .arg(Arg::with_name("NAME")
+                             .required(true)
+                             .help("Display name of the property"))
+                        .arg(Arg::with_name("kind")
+                             .short("k")
+                             .long("kind")
+                             .value_name("KIND")
+                             .possible_values(&["text", "timestamptz", "choice", "relation"])
+                             .default_value("text")
+                             .help("Type of the property's values"))
+                        .arg(Arg::with_name("inverse")
+                             .long("inverse")
+                             .value_name("NAME")
+                             .help("How a relation reads from the target's side"))),
+        SubCommand::with_name("collection")
+            .about("Creates collections")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("create")
+                        .about("Creates a collection, a choice of the Collection property")
+                        .arg(Arg::with_name("NAME")
+                             .required(true)
+                             .help("Display name of the collection"))),
+        SubCommand::with_name("import")
+            .about("Creates an object of each file below a directory, filed into the collection named like its top folder")
+            .arg(Arg::with_name("DIR")
+                 .required(true)
+                 .help("Directory of the files"))
+            .arg(Arg::with_name("jobs")
+                 .short("j")
+                 .long("jobs")
+                 .value_name("N")
+                 .help("Files imported at a time [default: 4]"))
+            .arg(Arg::with_name("collection")
+                 .long("collection")
+                 .value_name("COLLECTION_ID")
+                 .help("Collection of the files directly in DIR"))
+            .arg(Arg::with_name("tags")
+                 .long("tags")
+                 .help("Tags files with the names of the folders below their top folder"))
+            .arg(Arg::with_name("as")
+                 .long("as")
+                 .value_name("USER_ID")
+                 .help("User the objects are created by [default: the reserved Administrator]"))
+            .arg(Arg::with_name("report")
+                 .long("report")
+                 .value_name("FILE")
+                 .help("Writes what became of every file to FILE as JSON")),
+        SubCommand::with_name("object")
+            .about("Exports object contents")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("export")
+                        .about("Writes the current content of every object to a directory, with a manifest.json")
+                        .arg(Arg::with_name("DIR")
+                             .required(true)
+                             .help("Directory to write to, created if missing"))),
+        SubCommand::with_name("archive")
+            .about("Exports everything to a portable archive, and restores one")
+            .setting(AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(SubCommand::with_name("export")
+                        .about("Writes the objects, properties, choices, values, relations, users and contents to a zip with checksums")
+                        .arg(Arg::with_name("FILE")
+                             .required(true)
+                             .help("Archive to write")))
+            .subcommand(SubCommand::with_name("import")
+                        .about("Restores an archive into a database without people or object contents, at the same migration")
+                        .arg(Arg::with_name("FILE")
+                             .required(true)
+                             .help("Archive to restore"))),
+        SubCommand::with_name("sync")
+            .about("Mirrors a folder into Dewey through the API, watching it for changes, and optionally pulls a collection into it")
+            .arg(Arg::with_name("DIR")
+                 .required(true)
+                 .help("Folder to sync, whose state is kept in it as .dewey-sync.json"))
+            .arg(Arg::with_name("server")
+                 .long("server")
+                 .value_name("URL")
+                 .env("DEWEY_SERVER")
+                 .required(true)
+                 .help("Where the server is reached, e.g. https://dewey.example.com"))
+            .arg(Arg::with_name("token")
+                 .long("token")
+                 .value_name("TOKEN")
+                 .env("DEWEY_TOKEN")
+                 .hide_env_values(true)
+                 .required(true)
+                 .help("API token with write scope, signing in as its owner"))
+            .arg(Arg::with_name("collection")
+                 .long("collection")
+                 .value_name("COLLECTION_ID")
+                 .help("Collection of the uploaded files"))
+            .arg(Arg::with_name("pull")
+                 .long("pull")
+                 .requires("collection")
+                 .help("Also downloads the collection's new and changed objects into the folder"))
+            .arg(Arg::with_name("interval")
+                 .long("interval")
+                 .value_name("SECONDS")
+                 .help("Time between pulls, and between retries of files which failed [default: 60]"))
+            .arg(Arg::with_name("once")
+                 .long("once")
+                 .help("Syncs the folder once and exits instead of watching it")),
+        SubCommand::with_name("gc")
+            .about("Deletes stored contents which no object version refers to")
+            .arg(Arg::with_name("dry-run")
+                 .long("dry-run")
+                 .help("Lists what would be deleted without deleting it")),
+    ]
+}
+
+/// The parts of the configuration the subcommand `name` uses, so only they
+/// need to be set and valid
+pub fn sections(name: &str, args: &ArgMatches) -> &'static [Section] {
+    match (name, args.subcommand_name().unwrap_or_default()) {
+        ("check", _) => Section::ALL,
+        ("user", "disable") => &[Section::Database, Section::Redis],
+        ("import", _) | ("object", _) | ("archive", _) | ("gc", _) => {
+            &[Section::Database, Section::Store]
+        }
+        _ => &[Section::Database],
+    }
+}
+
+/// Run the subcommand `name`, describing why it failed otherwise
+pub fn run(config: Config, name: &str, args: &ArgMatches) -> Result<(), String> {
+    let (action, action_args) = args.subcommand();
+    let action_args = action_args.unwrap_or(args);
+    match (name, action) {
+        ("migrate", _) => migrate(&config),
+        ("check", _) => check::check(&config),
+        ("user", "list") => users::list(&mut Runner::new(config)?),
+        ("user", "disable") => users::disable(
+            &mut Runner::new(config)?,
+            parse_id(action_args, "USER_ID")?,
+            action_args.is_present("enable"),
+        ),
+        ("user", "promote") => users::promote(
+            &mut Runner::new(config)?,
+            parse_id(action_args, "USER_ID")?,
+            action_args.is_present("demote"),
+        ),
+        ("property", "list") => properties::list(&mut Runner::new(config)?),
+        ("property", "create") => properties::create(
+            &mut Runner::new(config)?,
+            action_args.value_of("NAME").unwrap_or_default(),
+            action_args.value_of("kind").unwrap_or("text"),
+            action_args.value_of("inverse"),
+        ),
+        ("collection", "create") => properties::create_collection(
+            &mut Runner::new(config)?,
+            action_args.value_of("NAME").unwrap_or_default(),
+        ),
+        ("import", _) => {
+            let jobs: usize = match args.value_of("jobs") {
+                Some(jobs) => jobs
+                    .parse()
+                    .map_err(|_| format!("jobs {} is not a number", jobs))?,
+                None => import::DEFAULT_JOBS,
+            };
+            let jobs = jobs.clamp(1, import::MAX_JOBS);
+            let options = import::ImportOptions {
+                dir: args.value_of("DIR").unwrap_or_default().to_string(),
+                jobs,
+                collection: parse_optional_id(args, "collection")?,
+                tags: args.is_present("tags"),
+                created_by: parse_optional_id(args, "as")?.unwrap_or(UserId::ADMINISTRATOR),
+                report: args.value_of("report").map(String::from),
+            };
+            // every file being imported may wait on the database at once
+            let mut runner = Runner::with_connections(config, jobs as u32 + 1)?;
+            import::import(&mut runner, options)
+        }
+        ("object", "export") => objects::export(
+            &mut Runner::new(config)?,
+            action_args.value_of("DIR").unwrap_or_default(),
+        ),
+        ("archive", "export") => archive::export(
+            &mut Runner::new(config)?,
+            action_args.value_of("FILE").unwrap_or_default(),
+        ),
+        ("archive", "import") => archive::import(
+            &mut Runner::new(config)?,
+            action_args.value_of("FILE").unwrap_or_default(),
+        ),
+        ("gc", _) => gc::gc(&mut Runner::new(config)?, args.is_present("dry-run")),
+        _ => Err(format!("unknown command {} {}", name, action)),
+    }
+}
+
+/// Run `sync`, which talks to a server through its API, so needs none of
+/// the server's configuration
+pub fn sync(args: &ArgMatches) -> Result<(), String> {
+    let interval: u64 = match args.value_of("interval") {
+        Some(interval) => interval
+            .parse()
+            .map_err(|_| format!("interval {} is not a number", interval))?,
+        None => sync::DEFAULT_INTERVAL,
+    };
+    sync::sync(sync::SyncOptions {
+        dir: args.value_of("DIR").unwrap_or_default().to_string(),
+        server: args.value_of("server").unwrap_or_default().to_string(),
+        token: args.value_of("token").unwrap_or_default().to_string(),
+        collection: parse_optional_id(args, "collection")?,
+        pull: args.is_present("pull"),
+        interval: interval.max(1),
+        once: args.is_present("once"),
+    })
+}
+
+/// Run every migration which has not been run, printing each one
+fn migrate(config: &Config) -> Result<(), String> {
+    let conn = PgConnection::establish(&config.database_url)
+        .map_err(|e| format!("can't connect to Postgres: {}", e))?;
+    embedded_migrations::run_with_output(&conn, &mut std::io::stdout())
+        .map_err(|e| format!("migrating failed: {}", e))
+}
+
+fn parse_id<T: From<i64>>(args: &ArgMatches, name: &str) -> Result<T, String> {
+    parse_optional_id(args, name)?.ok_or_else(|| format!("{} is required", name))
+}
+
+fn parse_optional_id<T: From<i64>>(args: &ArgMatches, name: &str) -> Result<Option<T>, String> {
+    match args.value_of(name) {
+        Some(value) => i64::from_str(value)
+            .map(|id| Some(T::from(id)))
+            .map_err(|_| format!("{} {} is not a number", name, value)),
+        None => Ok(None),
+    }
+}
+
+/// The actors a command sends messages to, and the system they run on
+pub struct Runner {
+    sys: SystemRunner,
+    pub config: Config,
+    pub db: Addr<DbExecutor>,
+}
+
+impl Runner {
+    pub fn new(config: Config) -> Result<Runner, String> {
+        Runner::with_connections(config, DB_CONNECTIONS)
+    }
+
+    /// With as many db executors, each with a connection of its own
+    pub fn with_connections(config: Config, connections: u32) -> Result<Runner, String> {
+        let sys = System::new("dewey");
+        let pool = Pool::builder()
+            .max_size(connections)
+            .build(ConnectionManager::new(config.database_url.as_str()))
+            .map_err(|e| format!("can't connect to Postgres: {}", e))?;
+        let db = SyncArbiter::start(connections as usize, move || DbExecutor(pool.clone()));
+        Ok(Runner { sys, config, db })
+    }
+
+    /// Run the system until `future` resolves
+    pub fn wait<F, I, E>(&mut self, future: F) -> Result<I, String>
+    where
+        F: Future<Item = I, Error = E>,
+        E: Display,
+    {
+        self.sys.block_on(future).map_err(|e| e.to_string())
+    }
+
+    /// Send `msg` to the db executor and wait for its result
+    pub fn db<M, I>(&mut self, msg: M) -> Result<I, String>
+    where
+        M: Message<Result = actix_web::Result<I>> + Send + 'static,
+        I: Send + 'static,
+        DbExecutor: Handler<M>,
+    {
+        let sent = self.db.send(msg).from_err::<actix_web::Error>().and_then(|res| res);
+        self.wait(sent)
+    }
+
+    /// Start an object store with the configured credentials
+    pub fn store(&self) -> Result<Addr<ObjectStore>, String> {
+        ObjectStore::new_with_s3_credentials(
+            &self.config.s3_access_key_id,
+            &self.config.s3_secret_access_key,
+        )
+        .map(|store| store.start())
+        .map_err(|e| format!("can't start the object store: {}", e))
+    }
+}
@@ -0,0 +1,59 @@
+//! `dewey object export`
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::Path;
+
+use super::Runner;
+use crate::db::ListCurrentVersions;
+use crate::object::store::GetBlob;
+use crate::object::Upload;
+
+/// The file as an upload, named and dated like the file
+pub fn read_upload(path: &Path) -> Result<Upload, String> {
+    let filename = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| "no filename".to_string())?;
+    let content = fs::read(path).map_err(|e| e.to_string())?;
+    let modified = fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .map(DateTime::<Utc>::from);
+    Ok(Upload {
+        filename,
+        content,
+        modified,
+    })
+}
+
+/// Write the current content of every object to `dir/<object id>/<filename>`,
+/// and the versions written to `dir/manifest.json`
+pub fn export(runner: &mut Runner, dir: &str) -> Result<(), String> {
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir).map_err(|e| format!("can't create {}: {}", dir.display(), e))?;
+
+    let versions = runner.db(ListCurrentVersions)?;
+    let store = runner.store()?;
+    for version in &versions {
+        let content = runner.wait(store.send(GetBlob {
+            hash: version.hash.clone(),
+        }))?
+        .map_err(|e| format!("can't read the content of {}: {}", version.object_id, e))?;
+
+        // only the last component, so filenames can't reach outside of `dir`
+        let filename = Path::new(&version.filename)
+            .file_name()
+            .map(|name| name.to_owned())
+            .unwrap_or_else(|| "content".into());
+        let object_dir = dir.join(version.object_id.to_string());
+        fs::create_dir_all(&object_dir)
+            .and_then(|_| fs::write(object_dir.join(filename), content))
+            .map_err(|e| format!("can't write {}: {}", object_dir.display(), e))?;
+    }
+
+    let manifest = serde_json::to_vec_pretty(&versions).map_err(|e| e.to_string())?;
+    fs::write(dir.join("manifest.json"), manifest)
+        .map_err(|e| format!("can't write the manifest: {}", e))?;
+    println!("{} objects exported to {}", versions.len(), dir.display());
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! `dewey property list|create` and `dewey collection create`
+use super::Runner;
+use crate::db::{CreateChoice, CreateProperty, ListProperties};
+use crate::property::{PropertyId, PropertyType};
+use crate::user::UserId;
+
+/// Every property in order, tab separated
+pub fn list(runner: &mut Runner) -> Result<(), String> {
+    println!("ID\tKIND\tNAME\tDETAIL");
+    for property in runner.db(ListProperties)? {
+        let detail = match (property.formula, property.inverse_display) {
+            (Some(formula), _) => format!("= {}", formula),
+            (None, Some(inverse)) => format!("inverse: {}", inverse),
+            (None, None) => "-".to_string(),
+        };
+        println!(
+            "{}\t{}\t{}\t{}",
+            property.id,
+            property.kind.name(),
+            property.display,
+            detail
+        );
+    }
+    Ok(())
+}
+
+pub fn create(
+    runner: &mut Runner,
+    display: &str,
+    kind: &str,
+    inverse_display: Option<&str>,
+) -> Result<(), String> {
+    let kind = match kind {
+        "text" => PropertyType::Text,
+        "timestamptz" => PropertyType::Timestamptz,
+        "choice" => PropertyType::Choice,
+        "relation" => PropertyType::Relation,
+        other => return Err(format!("{} is not a property type", other)),
+    };
+    let property_id = runner.db(CreateProperty {
+        display: display.to_string(),
+        kind,
+        inverse_display: inverse_display.map(String::from),
+        created_by: UserId::ADMINISTRATOR,
+    })?;
+    println!("{}", property_id);
+    Ok(())
+}
+
+pub fn create_collection(runner: &mut Runner, display: &str) -> Result<(), String> {
+    let collection_id = runner.db(CreateChoice {
+        property_id: PropertyId::COLLECTION,
+        display: display.to_string(),
+        created_by: UserId::ADMINISTRATOR,
+    })?;
+    println!("{}", collection_id);
+    Ok(())
+}
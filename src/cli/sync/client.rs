@@ -0,0 +1,230 @@
+use actix::SystemRunner;
+use actix_web::client::{self, ClientRequest, ClientResponse};
+use actix_web::{http, HttpMessage};
+use futures::Future;
+use serde::de::DeserializeOwned;
+use std::time::Duration;
+
+use crate::object::{ObjectId, Upload};
+
+/// Longest a request may take, uploads and downloads included
+const TIMEOUT_SECONDS: u64 = 300;
+/// Largest response read, in bytes
+const MAX_RESPONSE_BYTES: usize = 1024 * 1024 * 1024;
+/// Objects listed at a time when pulling
+const PER_PAGE: i64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct RemoteCollection {
+    pub id: i64,
+    pub display: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RemoteObject {
+    pub id: ObjectId,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ObjectPage {
+    pub objects: Vec<RemoteObject>,
+    pub next_cursor: Option<String>,
+}
+
+/// An object's current version
+#[derive(Debug, Clone, Deserialize)]
+pub struct RemoteVersion {
+    pub version: i32,
+    pub hash: String,
+    pub filename: String,
+}
+
+#[derive(Deserialize)]
+struct NewVersion {
+    version: i32,
+}
+
+/// Calls the API of a server as the owner of an API token, waiting for each
+/// response
+pub struct ApiClient {
+    sys: SystemRunner,
+    /// e.g. `https://dewey.example.com/api/v1`
+    base: String,
+    token: String,
+}
+
+impl ApiClient {
+    pub fn new(server: &str, token: &str) -> ApiClient {
+        ApiClient {
+            sys: actix::System::new("dewey-sync"),
+            base: format!("{}/api/v1", server.trim_end_matches('/')),
+            token: token.to_string(),
+        }
+    }
+
+    fn request(
+        &self,
+        mut builder: client::ClientRequestBuilder,
+        body: Vec<u8>,
+    ) -> Result<ClientRequest, String> {
+        builder
+            .header(
+                http::header::AUTHORIZATION,
+                format!("Bearer {}", self.token),
+            )
+            .header(http::header::USER_AGENT, "Dewey-Sync")
+            .body(body)
+            .map_err(|e| format!("building request: {}", e))
+    }
+
+    /// Send a request and read its response, failing unless it succeeded
+    fn send(
+        &mut self,
+        method: &str,
+        path: &str,
+        request: ClientRequest,
+    ) -> Result<Vec<u8>, String> {
+        let failed = format!("{} {}", method, path);
+        let response = request
+            .send()
+            .timeout(Duration::from_secs(TIMEOUT_SECONDS))
+            .map_err(|e| e.to_string())
+            .and_then(|resp: ClientResponse| {
+                let status = resp.status();
+                resp.body()
+                    .limit(MAX_RESPONSE_BYTES)
+                    .map_err(|e| e.to_string())
+                    .map(move |body| (status, body))
+            });
+        let (status, body) = self
+            .sys
+            .block_on(response)
+            .map_err(|e| format!("{}: {}", failed, e))?;
+        if status.is_success() {
+            return Ok(body.to_vec());
+        }
+        // errors of the API are `{"error"}`
+        let message = serde_json::from_slice::<serde_json::Value>(&body)
+            .ok()
+            .and_then(|json| json["error"].as_str().map(String::from))
+            .unwrap_or_else(|| String::from_utf8_lossy(&body).chars().take(200).collect());
+        Err(format!("{}: {} {}", failed, status, message))
+    }
+
+    fn get<T: DeserializeOwned>(&mut self, path: &str) -> Result<T, String> {
+        let request = self.request(client::get(format!("{}{}", self.base, path)), Vec::new())?;
+        let body = self.send("GET", path, request)?;
+        serde_json::from_slice(&body)
+            .map_err(|e| format!("GET {}: unexpected response: {}", path, e))
+    }
+
+    /// `POST` a multipart form of the file
+    fn post_file<T: DeserializeOwned>(&mut self, path: &str, upload: &Upload) -> Result<T, String> {
+        let boundary = format!("dewey-sync-{}", upload.hash());
+        let filename: String = upload
+            .filename
+            .chars()
+            .map(|c| {
+                if c == '"' || c == '\\' || c.is_control() {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect();
+        let mut body = format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: application/octet-stream\r\n\r\n",
+            boundary, filename
+        )
+        .into_bytes();
+        body.extend_from_slice(&upload.content);
+        body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+        let mut builder = client::post(format!("{}{}", self.base, path));
+        builder.header(
+            http::header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={}", boundary),
+        );
+        let request = self.request(builder, body)?;
+        let body = self.send("POST", path, request)?;
+        serde_json::from_slice(&body)
+            .map_err(|e| format!("POST {}: unexpected response: {}", path, e))
+    }
+
+    pub fn collections(&mut self) -> Result<Vec<RemoteCollection>, String> {
+        self.get("/collections")
+    }
+
+    /// A page of the objects matching the query `q`, in the order they were created
+    pub fn query(&mut self, q: &str, after: Option<&str>) -> Result<ObjectPage, String> {
+        let mut path = format!(
+            "/objects?q={}&sort=created&per_page={}",
+            encode(q),
+            PER_PAGE
+        );
+        if let Some(after) = after {
+            path.push_str(&format!("&after={}", encode(after)));
+        }
+        self.get(&path)
+    }
+
+    /// An object whose Hash is `hash`, if there is one
+    pub fn find_by_hash(&mut self, hash: &str) -> Result<Option<ObjectId>, String> {
+        let page = self.query(&format!("hash:{}", quote(hash)), None)?;
+        Ok(page.objects.into_iter().next().map(|object| object.id))
+    }
+
+    pub fn current_version(&mut self, object_id: &ObjectId) -> Result<RemoteVersion, String> {
+        let versions: Vec<RemoteVersion> = self.get(&format!("/objects/{}/versions", object_id))?;
+        versions
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("object {} has no versions", object_id))
+    }
+
+    pub fn download(&mut self, object_id: &ObjectId) -> Result<Vec<u8>, String> {
+        let path = format!("/objects/{}/content", object_id);
+        let request = self.request(client::get(format!("{}{}", self.base, path)), Vec::new())?;
+        self.send("GET", &path, request)
+    }
+
+    /// Create an object of the file, responding with its id. Emails and
+    /// mailboxes are stored as they are, since their messages and attachments
+    /// would otherwise be pulled back as files of their own.
+    pub fn upload(&mut self, upload: &Upload, collection: Option<i64>) -> Result<ObjectId, String> {
+        let path = match collection {
+            Some(collection) => format!("/objects?split=false&collection={}", collection),
+            None => "/objects?split=false".to_string(),
+        };
+        let created: Vec<ObjectId> = self.post_file(&path, upload)?;
+        created
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("POST {}: no object was created", path))
+    }
+
+    /// Add the file as the object's new version, responding with its number
+    pub fn add_version(&mut self, object_id: &ObjectId, upload: &Upload) -> Result<i32, String> {
+        let created: NewVersion =
+            self.post_file(&format!("/objects/{}/versions", object_id), upload)?;
+        Ok(created.version)
+    }
+}
+
+/// A value of a query, e.g. a collection's name
+pub fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Percent-encode a query string value
+fn encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,487 @@
+//! `dewey sync`, an agent mirroring a local folder into Dewey
+//!
+//! Files written in the folder are uploaded through the API as the owner of
+//! an API token, as new objects or as new versions of the objects they were
+//! synced with, unless an object has the same content already. Emails and
+//! mailboxes are uploaded as single objects, rather than split into their
+//! messages and attachments as other uploads are. With `--pull`
+//! the objects of a collection are downloaded into the folder too. What was
+//! synced is kept in the folder, so changes made while the agent wasn't
+//! running are found when it starts again. When a file and its object both
+//! changed, the object's content takes the file's place and the file is kept
+//! beside it as a conflicting copy, which is uploaded as an object of its own.
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::import::walk;
+use super::objects::read_upload;
+use crate::object::ObjectId;
+
+mod client;
+mod state;
+mod watch;
+
+use client::{quote, ApiClient, RemoteVersion};
+use state::{modified, SyncState, SyncedFile};
+use watch::{Change, Watcher};
+
+/// Seconds between pulls when not given
+pub const DEFAULT_INTERVAL: u64 = 60;
+/// How long a file must go unchanged before it is uploaded
+const SETTLE_MILLIS: u64 = 2000;
+/// How often the folder is checked for changes
+const POLL_MILLIS: u64 = 500;
+
+pub struct SyncOptions {
+    pub dir: String,
+    /// e.g. `https://dewey.example.com`
+    pub server: String,
+    pub token: String,
+    /// Collection of the uploaded files, and the one pulled
+    pub collection: Option<i64>,
+    pub pull: bool,
+    /// Seconds between pulls, and between retries of files which failed
+    pub interval: u64,
+    /// Sync once and exit rather than watching the folder
+    pub once: bool,
+}
+
+pub fn sync(options: SyncOptions) -> Result<(), String> {
+    let root = PathBuf::from(&options.dir);
+    if !root.is_dir() {
+        return Err(format!("{} is not a folder", root.display()));
+    }
+    let mut client = ApiClient::new(&options.server, &options.token);
+    let pulled = match (options.pull, options.collection) {
+        (false, _) => None,
+        (true, None) => return Err("--pull needs the --collection to pull".to_string()),
+        (true, Some(id)) => {
+            let collection = client
+                .collections()?
+                .into_iter()
+                .find(|collection| collection.id == id)
+                .ok_or_else(|| format!("there is no collection {}", id))?;
+            Some(collection.display)
+        }
+    };
+    let state = SyncState::load(&root, &options.server, options.collection)?;
+
+    let mut syncer = Syncer {
+        root,
+        client,
+        state,
+        collection: options.collection,
+        pulled,
+        failed: BTreeSet::new(),
+    };
+    // watching before comparing, so nothing changed in between is missed
+    let watcher = if options.once {
+        None
+    } else {
+        Some(Watcher::new(&syncer.root)?)
+    };
+    syncer.compare()?;
+    syncer.pull()?;
+    let mut last_pull = Instant::now();
+
+    let mut watcher = match watcher {
+        Some(watcher) => watcher,
+        None if syncer.failed.is_empty() => return Ok(()),
+        None => {
+            return Err(format!(
+                "{} files failed, syncing again retries them",
+                syncer.failed.len()
+            ))
+        }
+    };
+    println!("Watching {} for changes", syncer.root.display());
+
+    // written files by when they were last written
+    let mut written: HashMap<PathBuf, Instant> = HashMap::new();
+    loop {
+        for change in watcher
+            .changes()
+            .map_err(|e| format!("can't read changes: {}", e))?
+        {
+            match change {
+                Change::Written(path) => {
+                    written.insert(path, Instant::now());
+                }
+                Change::Removed(path) => {
+                    written.remove(&path);
+                    syncer.remove(&path)?;
+                }
+                Change::Folder(path) => {
+                    watcher.watch_tree(&path)?;
+                    syncer.compare()?;
+                }
+                Change::Overflow => syncer.compare()?,
+            }
+        }
+
+        let settled: Vec<PathBuf> = written
+            .iter()
+            .filter(|(_, at)| at.elapsed() >= Duration::from_millis(SETTLE_MILLIS))
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            written.remove(&path);
+            syncer.push(&path)?;
+        }
+
+        if last_pull.elapsed() >= Duration::from_secs(options.interval) {
+            for relative in std::mem::take(&mut syncer.failed) {
+                let path = syncer.root.join(relative);
+                syncer.push(&path)?;
+            }
+            syncer.pull()?;
+            last_pull = Instant::now();
+        }
+        thread::sleep(Duration::from_millis(POLL_MILLIS));
+    }
+}
+
+struct Syncer {
+    root: PathBuf,
+    client: ApiClient,
+    state: SyncState,
+    collection: Option<i64>,
+    /// Name of the collection pulled, if any
+    pulled: Option<String>,
+    /// Files which failed to sync, to retry
+    failed: BTreeSet<String>,
+}
+
+impl Syncer {
+    /// The path as the state records it, relative to the folder
+    fn relative(&self, path: &Path) -> String {
+        path.strip_prefix(&self.root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Sync every file in the folder, and forget the ones no longer there
+    fn compare(&mut self) -> Result<(), String> {
+        let mut files = Vec::new();
+        walk(&self.root, &[], &mut files)?;
+        let found: BTreeSet<String> = files.iter().map(|file| self.relative(&file.path)).collect();
+
+        let gone: Vec<String> = self
+            .state
+            .files
+            .keys()
+            .filter(|path| !found.contains(*path))
+            .cloned()
+            .collect();
+        for relative in gone {
+            let path = self.root.join(relative);
+            self.remove(&path)?;
+        }
+        for file in files {
+            self.push(&file.path)?;
+        }
+        Ok(())
+    }
+
+    /// Upload the file if it changed since it was synced, recording files
+    /// which failed to retry them later. Fails only if the state can't be saved.
+    fn push(&mut self, path: &Path) -> Result<(), String> {
+        let relative = self.relative(path);
+        match self.try_push(path, &relative) {
+            Ok(()) => {
+                self.failed.remove(&relative);
+                Ok(())
+            }
+            Err(SyncError::State(e)) => Err(e),
+            Err(SyncError::File(e)) => {
+                eprintln!("failed\t-\t{}\t{}", relative, e);
+                self.failed.insert(relative);
+                Ok(())
+            }
+        }
+    }
+
+    fn try_push(&mut self, path: &Path, relative: &str) -> Result<(), SyncError> {
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            // removed since, which its own change handles
+            Err(_) => return Ok(()),
+        };
+        let synced = self.state.files.get(relative).cloned();
+        if synced
+            .as_ref()
+            .map_or(false, |synced| synced.matches(&metadata))
+        {
+            return Ok(());
+        }
+        let upload = read_upload(path)?;
+        let hash = upload.hash();
+
+        let synced = match synced {
+            Some(synced) => synced,
+            None => {
+                // files whose content is stored already are linked to its object
+                let (object_id, remote, verb) = match self.client.find_by_hash(&hash)? {
+                    Some(object_id) => {
+                        let remote = self.client.current_version(&object_id)?;
+                        (object_id, remote, "linked")
+                    }
+                    None => {
+                        let object_id = self.client.upload(&upload, self.collection)?;
+                        let remote = self.client.current_version(&object_id)?;
+                        (object_id, remote, "uploaded")
+                    }
+                };
+                println!("{}\t{}\t{}", verb, object_id, relative);
+                return self.record(relative, &object_id, remote.version, &hash);
+            }
+        };
+        if synced.hash == hash {
+            // only touched
+            return self.record(relative, &synced.object_id, synced.version, &hash);
+        }
+
+        let remote = self.client.current_version(&synced.object_id)?;
+        if remote.hash == hash {
+            return self.record(relative, &synced.object_id, remote.version, &hash);
+        }
+        if remote.version != synced.version && remote.hash != synced.hash {
+            return self.conflict(path, relative, &synced.object_id, &remote);
+        }
+        let version = self.client.add_version(&synced.object_id, &upload)?;
+        println!(
+            "updated\t{}\t{}\tversion {}",
+            synced.object_id, relative, version
+        );
+        self.record(relative, &synced.object_id, version, &hash)
+    }
+
+    /// Forget a file deleted here, leaving its object as it is
+    fn remove(&mut self, path: &Path) -> Result<(), String> {
+        let relative = self.relative(path);
+        self.failed.remove(&relative);
+        if let Some(synced) = self.state.files.remove(&relative) {
+            println!("removed\t{}\t{}", synced.object_id, relative);
+            self.state
+                .removed
+                .insert(synced.object_id.to_string(), synced.version);
+            self.state.save()?;
+        }
+        Ok(())
+    }
+
+    /// Download the objects of the pulled collection which are new, or
+    /// changed since they were synced
+    fn pull(&mut self) -> Result<(), String> {
+        let collection = match self.pulled {
+            Some(ref collection) => format!("collection:{}", quote(collection)),
+            None => return Ok(()),
+        };
+        let mut after: Option<String> = None;
+        loop {
+            let page = self
+                .client
+                .query(&collection, after.as_ref().map(String::as_str))?;
+            for object in page.objects {
+                match self.try_pull(&object.id) {
+                    Ok(()) => {}
+                    Err(SyncError::State(e)) => return Err(e),
+                    Err(SyncError::File(e)) => eprintln!("failed\t{}\t-\t{}", object.id, e),
+                }
+            }
+            match page.next_cursor {
+                Some(cursor) => after = Some(cursor),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    fn try_pull(&mut self, object_id: &ObjectId) -> Result<(), SyncError> {
+        let remote = self.client.current_version(object_id)?;
+        let relative = match self.state.path_of(object_id) {
+            Some(relative) => relative,
+            None => {
+                let removed_at = self.state.removed.get(&object_id.to_string()).cloned();
+                if removed_at.map_or(false, |version| remote.version <= version) {
+                    return Ok(());
+                }
+                let relative = self.free_path(&remote, object_id)?;
+                return self.download(&relative, object_id, &remote);
+            }
+        };
+        let synced = self.state.files[&relative].clone();
+        if remote.version == synced.version || remote.hash == synced.hash {
+            return Ok(());
+        }
+
+        let path = self.root.join(&relative);
+        let unchanged = match fs::metadata(&path) {
+            Ok(ref metadata) if synced.matches(metadata) => true,
+            Ok(_) => hash_of(&path)? == synced.hash,
+            // removed since, which its own change handles
+            Err(_) => return Ok(()),
+        };
+        if unchanged {
+            self.download(&relative, object_id, &remote)
+        } else {
+            self.conflict(&path, &relative, object_id, &remote)
+        }
+    }
+
+    /// Where to download an object to, named like its current version, or a
+    /// file which has its content already
+    fn free_path(
+        &mut self,
+        remote: &RemoteVersion,
+        object_id: &ObjectId,
+    ) -> Result<String, SyncError> {
+        let name: String = remote
+            .filename
+            .chars()
+            .map(|c| {
+                if c == '/' || c == '\\' || c.is_control() {
+                    '_'
+                } else {
+                    c
+                }
+            })
+            .collect();
+        let name = name.trim().trim_start_matches('.');
+        let name = if name.is_empty() {
+            object_id.to_string()
+        } else {
+            name.to_string()
+        };
+        let (stem, extension) = split_name(&name);
+        for n in 1.. {
+            let candidate = if n == 1 {
+                name.clone()
+            } else {
+                format!("{} ({}){}", stem, n, extension)
+            };
+            let path = self.root.join(&candidate);
+            if !path.exists() {
+                return Ok(candidate);
+            }
+            if !self.state.files.contains_key(&candidate) && hash_of(&path)? == remote.hash {
+                return Ok(candidate);
+            }
+        }
+        unreachable!()
+    }
+
+    /// Write the object's current content to the file, through a hidden file
+    /// so a partly written file is never synced
+    fn download(
+        &mut self,
+        relative: &str,
+        object_id: &ObjectId,
+        remote: &RemoteVersion,
+    ) -> Result<(), SyncError> {
+        let path = self.root.join(relative);
+        if hash_of(&path).ok().as_ref() != Some(&remote.hash) {
+            let content = self.client.download(object_id)?;
+            let partial = self.root.join(format!(".dewey-sync-{}", object_id));
+            fs::write(&partial, content)
+                .and_then(|_| fs::rename(&partial, &path))
+                .map_err(|e| format!("can't write {}: {}", path.display(), e))?;
+            println!(
+                "downloaded\t{}\t{}\tversion {}",
+                object_id, relative, remote.version
+            );
+        }
+        self.record(relative, object_id, remote.version, &remote.hash)
+    }
+
+    /// Both the file and its object changed: keep the file as a conflicting
+    /// copy, uploaded as an object of its own, and download the object
+    fn conflict(
+        &mut self,
+        path: &Path,
+        relative: &str,
+        object_id: &ObjectId,
+        remote: &RemoteVersion,
+    ) -> Result<(), SyncError> {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (stem, extension) = split_name(&name);
+        let copy = path.with_file_name(format!(
+            "{} (conflict {}){}",
+            stem,
+            Utc::now().format("%Y-%m-%d %H%M%S"),
+            extension
+        ));
+        fs::rename(path, &copy)
+            .map_err(|e| format!("can't move {} aside: {}", path.display(), e))?;
+        println!(
+            "conflict\t{}\t{}\tkept as {}",
+            object_id,
+            relative,
+            self.relative(&copy)
+        );
+        self.download(relative, object_id, remote)?;
+        let copied = self.relative(&copy);
+        self.try_push(&copy, &copied)
+    }
+
+    /// Record the file as in step with the object at `version`
+    fn record(
+        &mut self,
+        relative: &str,
+        object_id: &ObjectId,
+        version: i32,
+        hash: &str,
+    ) -> Result<(), SyncError> {
+        let metadata = fs::metadata(self.root.join(relative))
+            .map_err(|e| format!("can't read {}: {}", relative, e))?;
+        self.state.removed.remove(&object_id.to_string());
+        self.state.files.insert(
+            relative.to_string(),
+            SyncedFile {
+                object_id: object_id.clone(),
+                version,
+                hash: hash.to_string(),
+                size: metadata.len(),
+                modified: modified(&metadata),
+            },
+        );
+        self.state.save().map_err(SyncError::State)
+    }
+}
+
+/// Why syncing a file failed
+enum SyncError {
+    /// This file couldn't be synced now, the others still may be
+    File(String),
+    /// What was synced couldn't be recorded, so syncing has to stop
+    State(String),
+}
+
+impl From<String> for SyncError {
+    fn from(e: String) -> Self {
+        SyncError::File(e)
+    }
+}
+
+/// Hex encoded SHA-256 of the file's content, like the Hash of objects
+fn hash_of(path: &Path) -> Result<String, String> {
+    fs::read(path)
+        .map(|content| format!("{:x}", Sha256::digest(&content)))
+        .map_err(|e| format!("can't read {}: {}", path.display(), e))
+}
+
+/// `report.pdf` as `("report", ".pdf")`
+fn split_name(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(dot) if dot > 0 => (&name[..dot], &name[dot..]),
+        _ => (name, ""),
+    }
+}
@@ -0,0 +1,105 @@
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+use std::fs::{self, Metadata};
+use std::path::{Path, PathBuf};
+
+use crate::object::ObjectId;
+
+/// Kept in the synced folder, and so left out of syncing as a hidden file
+pub const STATE_FILE: &str = ".dewey-sync.json";
+
+/// What was last synced, so changes made while the agent wasn't running are
+/// found when it starts again
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SyncState {
+    /// The server and collection the folder is synced with
+    pub server: String,
+    pub collection: Option<i64>,
+    /// By path relative to the folder
+    pub files: BTreeMap<String, SyncedFile>,
+    /// By object id, the objects whose file was deleted here with the version
+    /// they were at, so pulling brings them back only once they change
+    #[serde(default)]
+    pub removed: BTreeMap<String, i32>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+/// A file and the object it is in step with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncedFile {
+    pub object_id: ObjectId,
+    /// The object's version when both sides last had the same content
+    pub version: i32,
+    /// SHA-256 of that content
+    pub hash: String,
+    /// The file's size and modification time then, to skip hashing files
+    /// which haven't changed
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+}
+
+impl SyncedFile {
+    /// Whether the file looks as it did when last synced
+    pub fn matches(&self, metadata: &Metadata) -> bool {
+        self.size == metadata.len()
+            && self.modified.is_some()
+            && self.modified == modified(metadata)
+    }
+}
+
+pub fn modified(metadata: &Metadata) -> Option<DateTime<Utc>> {
+    metadata.modified().ok().map(DateTime::<Utc>::from)
+}
+
+impl SyncState {
+    /// The state of the folder `dir`, which must have been synced with the
+    /// same server and collection, or a new one
+    pub fn load(dir: &Path, server: &str, collection: Option<i64>) -> Result<SyncState, String> {
+        let path = dir.join(STATE_FILE);
+        if !path.exists() {
+            return Ok(SyncState {
+                server: server.to_string(),
+                collection,
+                files: BTreeMap::new(),
+                removed: BTreeMap::new(),
+                path,
+            });
+        }
+        let content =
+            fs::read(&path).map_err(|e| format!("can't read {}: {}", path.display(), e))?;
+        let mut state: SyncState = serde_json::from_slice(&content)
+            .map_err(|e| format!("{} is invalid: {}", path.display(), e))?;
+        if state.server != server || state.collection != collection {
+            return Err(format!(
+                "{} is synced with collection {} of {}, sync another folder or delete {}",
+                dir.display(),
+                state
+                    .collection
+                    .map_or_else(|| "none".to_string(), |id| id.to_string()),
+                state.server,
+                path.display()
+            ));
+        }
+        state.path = path;
+        Ok(state)
+    }
+
+    /// Write the state next to where it is kept, then move it there, so an
+    /// interruption leaves the last state whole
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_vec_pretty(self).map_err(|e| e.to_string())?;
+        let written = self.path.with_extension("json.tmp");
+        fs::write(&written, json)
+            .and_then(|_| fs::rename(&written, &self.path))
+            .map_err(|e| format!("can't save {}: {}", self.path.display(), e))
+    }
+
+    /// The path of the file in step with an object
+    pub fn path_of(&self, object_id: &ObjectId) -> Option<String> {
+        self.files
+            .iter()
+            .find(|(_, file)| &file.object_id == object_id)
+            .map(|(path, _)| path.clone())
+    }
+}
@@ -0,0 +1,146 @@
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Events read at a time
+const BUFFER_BYTES: usize = 64 * 1024;
+
+/// A change to the watched tree, leaving out hidden files
+#[derive(Debug)]
+pub enum Change {
+    /// Written and closed, or moved in
+    Written(PathBuf),
+    /// Deleted, or moved out
+    Removed(PathBuf),
+    /// Created or moved in, with whatever is in it already
+    Folder(PathBuf),
+    /// Events were lost, so the tree has to be compared again
+    Overflow,
+}
+
+/// Watches a folder and every folder below it with inotify, which only
+/// watches the folders it is given
+pub struct Watcher {
+    inotify: Inotify,
+    folders: HashMap<WatchDescriptor, PathBuf>,
+    buffer: Vec<u8>,
+}
+
+impl Watcher {
+    pub fn new(root: &Path) -> Result<Watcher, String> {
+        let inotify = Inotify::init().map_err(|e| format!("can't start inotify: {}", e))?;
+        let mut watcher = Watcher {
+            inotify,
+            folders: HashMap::new(),
+            buffer: vec![0; BUFFER_BYTES],
+        };
+        watcher.watch_tree(root)?;
+        Ok(watcher)
+    }
+
+    /// Watch `dir` and the folders below it, leaving out hidden ones
+    pub fn watch_tree(&mut self, dir: &Path) -> Result<(), String> {
+        let mask = WatchMask::CLOSE_WRITE
+            | WatchMask::MOVED_TO
+            | WatchMask::MOVED_FROM
+            | WatchMask::CREATE
+            | WatchMask::DELETE;
+        let descriptor = self
+            .inotify
+            .add_watch(dir, mask)
+            .map_err(|e| format!("can't watch {}: {}", dir.display(), e))?;
+        self.folders.insert(descriptor, dir.to_path_buf());
+
+        let entries =
+            fs::read_dir(dir).map_err(|e| format!("can't read {}: {}", dir.display(), e))?;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            let is_dir = entry.file_type().map(|kind| kind.is_dir()).unwrap_or(false);
+            if is_dir && !hidden {
+                self.watch_tree(&entry.path())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The changes since the last call, without waiting for any
+    pub fn changes(&mut self) -> io::Result<Vec<Change>> {
+        let events = match self.inotify.read_events(&mut self.buffer) {
+            Ok(events) => events,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+
+        let mut changes = Vec::new();
+        let mut moved_out = Vec::new();
+        for event in events {
+            if event.mask.contains(EventMask::Q_OVERFLOW) {
+                changes.push(Change::Overflow);
+                continue;
+            }
+            if event.mask.contains(EventMask::IGNORED) {
+                // the folder was deleted, so is no longer watched
+                self.folders.remove(&event.wd);
+                continue;
+            }
+            let name = match event.name {
+                Some(name) if !name.to_string_lossy().starts_with('.') => name,
+                _ => continue,
+            };
+            let path = match self.folders.get(&event.wd) {
+                Some(folder) => folder.join(name),
+                None => continue,
+            };
+            let added = event
+                .mask
+                .intersects(EventMask::CREATE | EventMask::MOVED_TO);
+            let removed = event
+                .mask
+                .intersects(EventMask::DELETE | EventMask::MOVED_FROM);
+            changes.push(if event.mask.contains(EventMask::ISDIR) {
+                if added {
+                    Change::Folder(path)
+                } else if removed {
+                    if event.mask.contains(EventMask::MOVED_FROM) {
+                        moved_out.push(path);
+                    }
+                    // its files were removed too, which comparing the tree finds
+                    Change::Overflow
+                } else {
+                    continue;
+                }
+            } else if event
+                .mask
+                .intersects(EventMask::CLOSE_WRITE | EventMask::MOVED_TO)
+            {
+                Change::Written(path)
+            } else if removed {
+                Change::Removed(path)
+            } else {
+                // created files are synced once they are written and closed
+                continue;
+            });
+        }
+        for folder in moved_out {
+            self.unwatch_tree(&folder);
+        }
+        Ok(changes)
+    }
+
+    /// Stop watching a folder moved out of the tree, and the folders below it,
+    /// which inotify keeps watching wherever they are moved to
+    fn unwatch_tree(&mut self, dir: &Path) {
+        let below: Vec<WatchDescriptor> = self
+            .folders
+            .iter()
+            .filter(|(_, folder)| folder.starts_with(dir))
+            .map(|(descriptor, _)| descriptor.clone())
+            .collect();
+        for descriptor in below {
+            self.folders.remove(&descriptor);
+            self.inotify.rm_watch(descriptor).ok();
+        }
+    }
+}
@@ -0,0 +1,58 @@
+//! `dewey user list|disable|promote`
+use futures::Future;
+use redis_async::client::paired_connect;
+
+use super::Runner;
+use crate::db::{ListUsers, SetUserAdmin, SetUserDisabled, UserProfile};
+use crate::sessions::session_manager::session_key;
+use crate::user::UserId;
+
+fn print_user(user: &UserProfile) {
+    println!(
+        "{}\t{}\t{}\t{}\t{}\t{}",
+        user.id,
+        user.kind.name(),
+        if user.admin { "admin" } else { "-" },
+        user.disabled_at
+            .map_or("-".to_string(), |at| format!("disabled {}", at.to_rfc3339())),
+        user.display_name,
+        user.public_email.as_ref().map_or("", String::as_str),
+    );
+}
+
+/// Every person and plugin, tab separated
+pub fn list(runner: &mut Runner) -> Result<(), String> {
+    println!("ID\tKIND\tADMIN\tDISABLED\tNAME\tEMAIL");
+    for user in runner.db(ListUsers)? {
+        print_user(&user);
+    }
+    Ok(())
+}
+
+/// Disable a user and sign them out of every browser, their tokens are
+/// refused from then on. With `enable`, they may sign in again.
+pub fn disable(runner: &mut Runner, user_id: UserId, enable: bool) -> Result<(), String> {
+    let user = runner.db(SetUserDisabled {
+        user_id: user_id.clone(),
+        disabled: !enable,
+    })?;
+    if !enable {
+        let (redis_addr, key) = (runner.config.redis_addr, session_key(&user_id));
+        let ended = paired_connect(&redis_addr)
+            .and_then(move |redis| redis.send::<i64>(resp_array!["DEL", key]))
+            .map_err(|e| format!("can't sign the user out in Redis: {:?}", e));
+        runner.wait(ended)?;
+    }
+    print_user(&user);
+    Ok(())
+}
+
+/// Make a person an administrator, or with `demote` no longer one
+pub fn promote(runner: &mut Runner, user_id: UserId, demote: bool) -> Result<(), String> {
+    let user = runner.db(SetUserAdmin {
+        user_id,
+        admin: !demote,
+    })?;
+    print_user(&user);
+    Ok(())
+}
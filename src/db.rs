@@ -7,7 +7,6 @@ use diesel::r2d2::ConnectionManager;
 use diesel::r2d2::Pool;
 use diesel::PgConnection;
 
-use crate::property;
 use crate::sessions::GoogleAccessToken;
 use crate::user;
 use crate::user::{PersonUser, User, UserId, UserKind, UserRow};
@@ -19,6 +18,89 @@ mod views;
 mod fetch;
 pub use fetch::Fetch;
 
+mod computed;
+pub use computed::{ComputedPending, CreateComputedProperty, RecomputeBatch};
+
+mod values;
+pub use values::{SetValidationRules, SetValues, SetValuesResult, ValueUpdate};
+
+mod saved_views;
+pub use saved_views::{
+    CreateSavedView, CreateSavedViewResult, DeleteSavedView, GetSavedViewPage, ListSavedViews,
+    ViewPage, ViewRow,
+};
+
+mod schemas;
+pub use schemas::{CreatePropertySchema, ListPropertySchemas, SetCollectionSchema};
+
+mod relations;
+pub use relations::{GetRelations, TraverseRelations};
+
+mod versions;
+pub use versions::{
+    AddObjectVersion, CreateObject, FindObjectByHash, GetObjectVersion, ListCurrentVersions,
+    ListObjectVersions, ListVersionHashes, RestoreObjectVersion, VersionContent,
+};
+
+mod archive;
+pub use archive::{ArchivedTable, ExportTables, ImportTables};
+
+mod emails;
+pub use emails::FileEmail;
+
+mod extraction;
+pub use extraction::{GetExtraction, ListPendingExtractions, RetryExtraction, SaveExtraction};
+
+mod facets;
+pub use facets::{BucketInterval, FacetsResult, GetFacets};
+
+mod history;
+pub use history::GetValueHistory;
+
+mod objects;
+pub use objects::{GetObject, GetObjectDetails, ObjectDetails};
+
+mod properties;
+pub use properties::{
+    Collection, CreateChoice, CreateProperty, ListChoices, ListCollections, ListProperties,
+};
+
+mod users;
+pub use users::{GetUser, ListUsers, SetUserAdmin, SetUserDisabled, UserProfile};
+
+mod api_tokens;
+pub use api_tokens::{
+    AuthenticateApiToken, CreateApiToken, ListApiTokens, RevokeApiToken, TokenAuthentication,
+};
+
+mod drop_box_tokens;
+pub use drop_box_tokens::{
+    AuthenticateDropBoxToken, CreateDropBoxToken, ListDropBoxTokens, RevokeDropBoxToken,
+};
+
+mod plugins;
+pub use plugins::{DisablePlugin, IssuePluginToken, ListPlugins, RegisterPlugin};
+
+mod live;
+pub use live::TakeLiveEvents;
+
+mod webhooks;
+pub use webhooks::{
+    CreateWebhook, DeleteWebhook, DueDelivery, ListDeliveries, ListDueDeliveries, ListWebhooks,
+    PingWebhook, RecordDeliveryAttempt,
+};
+
+mod query;
+pub use query::{QueryObjects, QueryObjectsResult};
+
+mod search;
+pub use search::{prefix_tsquery, SearchLanguage, SearchObjects, SetSearchLanguage};
+
+mod spreadsheets;
+pub use spreadsheets::{
+    ExportSpreadsheet, ExportSpreadsheetResult, ImportSpreadsheet, SpreadsheetRow,
+};
+
 /// Valid User Session comprises of the session's user_id and the user's version
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserSessionKey {
@@ -41,6 +123,18 @@ pub fn db_error<T: Into<String>, U: std::fmt::Debug>(message: T, err: U) -> Erro
     std::io::Error::new(std::io::ErrorKind::Other, mstr).into()
 }
 
+/// Run `f` inside of a transaction, rolling back if it returns an error
+pub fn transaction<T, F: FnOnce() -> Result<T>>(conn: &PgConnection, f: F) -> Result<T> {
+    let mut failure = None;
+    let result = conn.transaction::<T, diesel::result::Error, _>(|| {
+        f().map_err(|e| {
+            failure = Some(e);
+            diesel::result::Error::RollbackTransaction
+        })
+    });
+    result.map_err(|e| failure.unwrap_or_else(|| db_error("db transaction error", e)))
+}
+
 /// Upsert user information, returning the new or existing user's id and version
 pub struct UpsertGoogleUser {
     pub resource_id: String,
@@ -82,6 +176,9 @@ impl Handler<UpsertGoogleUser> for DbExecutor {
                 }
 
                 let existing_user = get_user_by_id(&conn, &token_version.user_id)?;
+                if existing_user.is_disabled() {
+                    return Err(error::ErrorForbidden("This account is disabled"));
+                }
 
                 (existing_user, token_version.version + 1)
             }
@@ -162,6 +259,9 @@ impl Handler<GetUserByResourceId> for DbExecutor {
             .and_then(move |o| match o {
                 Some(v) => get_user_by_id(&conn, &v.user_id).and_then(|db_user: UserRow| {
                     match db_user.kind() {
+                        UserKind::Person if db_user.is_disabled() => {
+                            Err(error::ErrorForbidden("This account is disabled"))
+                        }
                         UserKind::Person => Ok(Some((
                             PersonUser::try_from(&db_user)?,
                             UserSessionKey {
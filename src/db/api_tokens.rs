@@ -0,0 +1,201 @@
+//! Personal API tokens, which sign requests in as their owner
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, Utc};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use super::schema;
+use super::{db_error, get_user_by_id, DbExecutor, UserSessionKey};
+use crate::sessions::{hash_secret, new_secret, ApiToken, ApiTokenId, TokenScope, UserSession};
+use crate::user::{PersonUser, UserId};
+
+/// Create a token for a user, responding with its id and secret. Only the
+/// hash of the secret is kept.
+pub struct CreateApiToken {
+    pub user_id: UserId,
+    pub display: String,
+    pub scope: TokenScope,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Message for CreateApiToken {
+    type Result = Result<(ApiTokenId, String)>;
+}
+
+impl Handler<CreateApiToken> for DbExecutor {
+    type Result = Result<(ApiTokenId, String)>;
+
+    fn handle(&mut self, msg: CreateApiToken, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        create_api_token(&conn, &msg)
+    }
+}
+
+pub fn create_api_token(
+    conn: &PgConnection,
+    msg: &CreateApiToken,
+) -> Result<(ApiTokenId, String)> {
+    use schema::api_tokens::dsl::*;
+
+    if msg.display.trim().is_empty() {
+        return Err(error::ErrorBadRequest("A token needs a name"));
+    }
+    let secret = new_secret();
+    let token_id = insert_into(api_tokens)
+        .values((
+            user_id.eq(&msg.user_id),
+            display.eq(msg.display.trim()),
+            secret_hash.eq(hash_secret(&secret)),
+            scope.eq(msg.scope),
+            expires_at.eq(msg.expires_at),
+        ))
+        .returning(id)
+        .get_result(conn)
+        .map_err(|e| db_error("db insert api token error", e))?;
+    Ok((token_id, secret))
+}
+
+/// A user's tokens, newest first
+pub struct ListApiTokens {
+    pub user_id: UserId,
+}
+
+impl Message for ListApiTokens {
+    type Result = Result<Vec<ApiToken>>;
+}
+
+impl Handler<ListApiTokens> for DbExecutor {
+    type Result = Result<Vec<ApiToken>>;
+
+    fn handle(&mut self, msg: ListApiTokens, _: &mut Self::Context) -> Self::Result {
+        use schema::api_tokens::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        api_tokens
+            .filter(user_id.eq(&msg.user_id))
+            .order(created_at.desc())
+            .select((
+                id,
+                user_id,
+                display,
+                scope,
+                expires_at,
+                last_used_at,
+                created_at,
+            ))
+            .load(&conn)
+            .map_err(|e| db_error("db select api tokens error", e))
+    }
+}
+
+/// Delete one of the user's tokens, after which it no longer signs in
+pub struct RevokeApiToken {
+    pub token_id: ApiTokenId,
+    pub user_id: UserId,
+}
+
+impl Message for RevokeApiToken {
+    type Result = Result<()>;
+}
+
+impl Handler<RevokeApiToken> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: RevokeApiToken, _: &mut Self::Context) -> Self::Result {
+        use schema::api_tokens::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let deleted = diesel::delete(
+            api_tokens
+                .filter(id.eq(&msg.token_id))
+                .filter(user_id.eq(&msg.user_id)),
+        )
+        .execute(&conn)
+        .map_err(|e| db_error("db delete api token error", e))?;
+        if deleted == 0 {
+            return Err(error::ErrorNotFound("No such token"));
+        }
+        Ok(())
+    }
+}
+
+pub enum TokenAuthentication {
+    Valid(UserSession, TokenScope),
+    /// Unknown, revoked or expired
+    Invalid,
+}
+
+/// Sign in with a token's secret, recording that the token was used
+pub struct AuthenticateApiToken {
+    pub secret: String,
+}
+
+impl Message for AuthenticateApiToken {
+    type Result = Result<TokenAuthentication>;
+}
+
+impl Handler<AuthenticateApiToken> for DbExecutor {
+    type Result = Result<TokenAuthentication>;
+
+    fn handle(&mut self, msg: AuthenticateApiToken, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        let token: Option<ApiToken> = {
+            use schema::api_tokens::dsl::*;
+            api_tokens
+                .filter(secret_hash.eq(hash_secret(&msg.secret)))
+                .select((
+                    id,
+                    user_id,
+                    display,
+                    scope,
+                    expires_at,
+                    last_used_at,
+                    created_at,
+                ))
+                .get_result(&conn)
+                .optional()
+                .map_err(|e| db_error("db select api token error", e))?
+        };
+        let token = match token {
+            Some(ref token) if token.is_expired() => return Ok(TokenAuthentication::Invalid),
+            Some(token) => token,
+            None => return Ok(TokenAuthentication::Invalid),
+        };
+        {
+            use schema::api_tokens::dsl::*;
+            diesel::update(api_tokens.filter(id.eq(&token.id)))
+                .set(last_used_at.eq(Utc::now()))
+                .execute(&conn)
+                .map_err(|e| db_error("db update api token last used error", e))?;
+        }
+
+        // the version only matters to cookie sessions, which are checked against it
+        let version: i32 = {
+            use schema::user_tokens::dsl::*;
+            user_tokens
+                .filter(user_id.eq(&token.user_id))
+                .select(version)
+                .get_result(&conn)
+                .optional()
+                .map_err(|e| db_error("db select user token version error", e))?
+                .unwrap_or(0)
+        };
+        let user = get_user_by_id(&conn, &token.user_id)?;
+        if user.is_disabled() {
+            return Ok(TokenAuthentication::Invalid);
+        }
+        Ok(TokenAuthentication::Valid(
+            UserSession {
+                key: UserSessionKey {
+                    user_id: token.user_id,
+                    version,
+                },
+                person: PersonUser::try_from(&user)?,
+            },
+            token.scope,
+        ))
+    }
+}
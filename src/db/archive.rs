@@ -0,0 +1,193 @@
+//! The tables of a portable archive, as JSON arrays of their rows
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Int8, Text};
+use diesel::PgConnection;
+
+use super::computed::recompute_all;
+use super::history::set_audit_context;
+use super::{db_error, transaction, DbExecutor};
+use crate::user::UserId;
+
+/// The archived tables, each after the tables it refers to, with the columns
+/// their rows are ordered by. Tokens, webhooks and sessions are left out as
+/// they hold secrets, and history, search and computed values as they are
+/// derived again.
+pub const ARCHIVED_TABLES: &[(&str, &str)] = &[
+    ("users", "id"),
+    ("plugins", "user_id"),
+    ("properties", "id"),
+    ("property_validations", "property_id"),
+    ("property_value_choices", "id"),
+    ("schemas", "id"),
+    ("schema_properties", "schema_id, property_id"),
+    ("collection_schemas", "collection_id"),
+    ("objects", "id"),
+    ("object_versions", "object_id, version"),
+    ("text_values", "object_id, property_id"),
+    ("timestamptz_values", "object_id, property_id"),
+    ("choice_values", "object_id, property_id, value_id"),
+    ("relation_values", "object_id, property_id, target_id"),
+];
+
+/// The rows of a table, as a JSON array
+#[derive(Debug, Clone, QueryableByName)]
+pub struct ArchivedTable {
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "Int8"]
+    pub rows: i64,
+    #[sql_type = "Text"]
+    pub json: String,
+}
+
+#[derive(QueryableByName)]
+struct LatestMigration {
+    #[sql_type = "Text"]
+    version: String,
+}
+
+/// The migration the database is at, archives only restore into the same one
+fn latest_migration(conn: &PgConnection) -> Result<String> {
+    diesel::sql_query("SELECT MAX(version) AS version FROM __diesel_schema_migrations")
+        .get_result::<LatestMigration>(conn)
+        .map(|latest| latest.version)
+        .map_err(|e| db_error("db select latest migration error", e))
+}
+
+/// Every archived table, read from one snapshot of the database
+pub struct ExportTables;
+
+impl Message for ExportTables {
+    type Result = Result<(String, Vec<ArchivedTable>)>;
+}
+
+impl Handler<ExportTables> for DbExecutor {
+    type Result = Result<(String, Vec<ArchivedTable>)>;
+
+    fn handle(&mut self, _: ExportTables, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        transaction(&conn, || {
+            diesel::sql_query("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY")
+                .execute(&conn)
+                .map_err(|e| db_error("db set export snapshot error", e))?;
+            let migration = latest_migration(&conn)?;
+            let mut tables = Vec::new();
+            for (table, order) in ARCHIVED_TABLES {
+                tables.push(
+                    diesel::sql_query(format!(
+                        "SELECT '{table}'::TEXT AS name, COUNT(*) AS rows, \
+                         COALESCE(json_agg(t ORDER BY {order}), '[]')::TEXT AS json \
+                         FROM {table} t",
+                        order = order,
+                        table = table
+                    ))
+                    .get_result::<ArchivedTable>(&conn)
+                    .map_err(|e| db_error(format!("db export {} error", table), e))?,
+                );
+            }
+            Ok((migration, tables))
+        })
+    }
+}
+
+#[derive(QueryableByName)]
+struct InUse {
+    #[sql_type = "Bool"]
+    in_use: bool,
+}
+
+/// Replace the archived tables of an unused database with an archive's,
+/// responding with the rows restored of each table. Restoring queues no
+/// webhook or live events.
+pub struct ImportTables {
+    /// The migration the archive was exported at
+    pub migration: String,
+    pub tables: Vec<ArchivedTable>,
+    pub request_id: Option<String>,
+}
+
+impl Message for ImportTables {
+    type Result = Result<Vec<(String, usize)>>;
+}
+
+impl Handler<ImportTables> for DbExecutor {
+    type Result = Result<Vec<(String, usize)>>;
+
+    fn handle(&mut self, msg: ImportTables, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        transaction(&conn, || {
+            let migration = latest_migration(&conn)?;
+            if migration != msg.migration {
+                return Err(error::ErrorConflict(format!(
+                    "The archive is of a database at migration {}, this one is at {}",
+                    msg.migration, migration
+                )));
+            }
+            let in_use = diesel::sql_query(
+                "SELECT EXISTS (SELECT 1 FROM users WHERE kind = 'person') \
+                 OR EXISTS (SELECT 1 FROM object_versions) AS in_use",
+            )
+            .get_result::<InUse>(&conn)
+            .map_err(|e| db_error("db select database in use error", e))?;
+            if in_use.in_use {
+                return Err(error::ErrorConflict(
+                    "Archives only restore into a database without people or object contents",
+                ));
+            }
+
+            set_audit_context(&conn, &UserId::ADMINISTRATOR, &msg.request_id)?;
+            diesel::sql_query("SELECT set_config('dewey.quiet', 'on', true)")
+                .execute(&conn)
+                .map_err(|e| db_error("db set quiet error", e))?;
+
+            // the rows the migrations seeded are replaced by the archive's
+            let names = ARCHIVED_TABLES
+                .iter()
+                .map(|(table, _)| *table)
+                .collect::<Vec<_>>();
+            diesel::sql_query(format!("TRUNCATE {} CASCADE", names.join(", ")))
+                .execute(&conn)
+                .map_err(|e| db_error("db truncate archived tables error", e))?;
+
+            let mut restored = Vec::new();
+            for (table, _) in ARCHIVED_TABLES {
+                let archived = msg
+                    .tables
+                    .iter()
+                    .find(|archived| archived.name == *table)
+                    .ok_or_else(|| {
+                        error::ErrorBadRequest(format!("The archive has no {} table", table))
+                    })?;
+                let rows = diesel::sql_query(format!(
+                    "INSERT INTO {table} SELECT * FROM json_populate_recordset(NULL::{table}, $1::JSON)",
+                    table = table
+                ))
+                .bind::<Text, _>(&archived.json)
+                .execute(&conn)
+                .map_err(|e| db_error(format!("db import {} error", table), e))?;
+                if rows as i64 != archived.rows {
+                    return Err(error::ErrorBadRequest(format!(
+                        "The archive's {} table has {} rows, not {}",
+                        table, rows, archived.rows
+                    )));
+                }
+                restored.push((table.to_string(), rows));
+            }
+
+            // restored objects were inserted with their current version, which
+            // queues no extraction
+            diesel::sql_query(
+                "INSERT INTO extracted_texts (object_id, version) \
+                 SELECT id, current_version FROM objects WHERE current_version IS NOT NULL",
+            )
+            .execute(&conn)
+            .map_err(|e| db_error("db queue restored extractions error", e))?;
+            recompute_all(&conn)?;
+            Ok(restored)
+        })
+    }
+}
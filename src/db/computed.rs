@@ -0,0 +1,283 @@
+//! Evaluation and storage of computed property values
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, Utc};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::collections::HashMap;
+
+use super::schema;
+use super::{db_error, DbExecutor};
+use crate::object::{ExtractionStatus, ObjectId};
+use crate::property::formula::Inputs;
+use crate::property::{Formula, PropertyId, PropertyType};
+
+/// Objects recomputed per `RecomputeBatch`
+const RECOMPUTE_BATCH: i64 = 200;
+
+/// All computed properties in `ord` order, with their parsed formulas
+fn computed_properties(conn: &PgConnection) -> Result<Vec<(PropertyId, Formula)>> {
+    use schema::properties::dsl::*;
+
+    let rows: Vec<(PropertyId, Option<String>)> = properties
+        .filter(property_type.eq(PropertyType::Computed))
+        .order(ord.asc())
+        .select((id, formula))
+        .load(conn)
+        .map_err(|e| db_error("db select computed properties error", e))?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|(property_id, source)| {
+            let source = source?;
+            match Formula::parse(&source) {
+                Ok(parsed) => Some((property_id, parsed)),
+                Err(e) => {
+                    warn!("Skipping computed property {}: {}", property_id, e);
+                    None
+                }
+            }
+        })
+        .collect())
+}
+
+/// Numeric value of each text and timestamptz input of an object
+fn load_inputs(
+    conn: &PgConnection,
+    object: &ObjectId,
+    inputs: &[PropertyId],
+) -> Result<HashMap<PropertyId, f64>> {
+    let mut values = HashMap::new();
+    {
+        use schema::text_values::dsl::*;
+        let texts: Vec<(PropertyId, String)> = text_values
+            .filter(object_id.eq(object))
+            .filter(property_id.eq_any(inputs))
+            .select((property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select computed text inputs error", e))?;
+        for (pid, text) in texts {
+            if let Ok(number) = text.trim().parse::<f64>() {
+                values.insert(pid, number);
+            }
+        }
+    }
+    {
+        use schema::timestamptz_values::dsl::*;
+        let timestamps: Vec<(PropertyId, Option<DateTime<Utc>>)> = timestamptz_values
+            .filter(object_id.eq(object))
+            .filter(property_id.eq_any(inputs))
+            .select((property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select computed timestamptz inputs error", e))?;
+        for (pid, timestamp) in timestamps {
+            if let Some(timestamp) = timestamp {
+                values.insert(pid, timestamp.timestamp() as f64);
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Size of the object's current version, if it has any
+fn current_size(conn: &PgConnection, object: &ObjectId) -> Result<Option<f64>> {
+    use schema::object_versions::dsl::*;
+
+    object_versions
+        .filter(object_id.eq(object))
+        .order(version.desc())
+        .select(size)
+        .first::<i64>(conn)
+        .optional()
+        .map(|bytes| bytes.map(|bytes| bytes as f64))
+        .map_err(|e| db_error("db select computed size input error", e))
+}
+
+/// Pages of the object's current version, once its text has been extracted
+fn current_pages(conn: &PgConnection, object: &ObjectId) -> Result<Option<f64>> {
+    use schema::extracted_texts::dsl::*;
+
+    extracted_texts
+        .filter(object_id.eq(object))
+        .filter(status.eq(ExtractionStatus::Done))
+        .select(pages)
+        .first::<Option<i32>>(conn)
+        .optional()
+        .map(|count| count.and_then(|count| count).map(f64::from))
+        .map_err(|e| db_error("db select computed pages input error", e))
+}
+
+/// Re-evaluate every computed property of an object.
+/// Computed properties may refer to computed properties earlier in `ord`.
+pub fn recompute_object(conn: &PgConnection, object: &ObjectId) -> Result<()> {
+    let computed = computed_properties(conn)?;
+    recompute_with(conn, &computed, object)
+}
+
+fn recompute_with(
+    conn: &PgConnection,
+    computed: &[(PropertyId, Formula)],
+    object: &ObjectId,
+) -> Result<()> {
+    if computed.is_empty() {
+        return Ok(());
+    }
+
+    let inputs: Vec<PropertyId> = computed
+        .iter()
+        .flat_map(|(_, formula)| formula.inputs())
+        .collect();
+    let mut inputs = Inputs {
+        values: load_inputs(conn, object, &inputs)?,
+        size: current_size(conn, object)?,
+        pages: current_pages(conn, object)?,
+        now: Utc::now().timestamp() as f64,
+    };
+
+    for (property, formula) in computed {
+        let result = formula.evaluate(&inputs);
+        {
+            use schema::computed_values::dsl::*;
+            insert_into(computed_values)
+                .values((
+                    object_id.eq(object),
+                    property_id.eq(property),
+                    value.eq(result),
+                ))
+                .on_conflict((object_id, property_id))
+                .do_update()
+                .set((value.eq(result), computed_at.eq(Utc::now())))
+                .execute(conn)
+                .map_err(|e| db_error("db upsert computed value error", e))?;
+        }
+        if let Some(result) = result {
+            inputs.values.insert(property.clone(), result);
+        }
+    }
+    Ok(())
+}
+
+/// Validates the formula before creating a computed property. Its values
+/// are filled in by `ComputedRefresher`, so creating one doesn't wait on
+/// every object being recomputed.
+pub struct CreateComputedProperty {
+    pub display: String,
+    pub formula: String,
+    pub created_by: crate::user::UserId,
+}
+
+impl Message for CreateComputedProperty {
+    type Result = Result<PropertyId>;
+}
+
+impl Handler<CreateComputedProperty> for DbExecutor {
+    type Result = Result<PropertyId>;
+
+    fn handle(&mut self, msg: CreateComputedProperty, _: &mut Self::Context) -> Self::Result {
+        use schema::properties::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        Formula::parse(&msg.formula)
+            .map_err(|e| error::ErrorBadRequest(format!("Invalid formula: {}", e)))?;
+
+        insert_into(properties)
+            .values((
+                display.eq(&msg.display),
+                created_by.eq(&msg.created_by),
+                property_type.eq(PropertyType::Computed),
+                formula.eq(&msg.formula),
+            ))
+            .returning(id)
+            .get_result(&conn)
+            .map_err(|e| db_error("db insert computed property error", e))
+    }
+}
+
+/// Recomputes up to `RECOMPUTE_BATCH` objects after `after` in id order,
+/// returning the last one when there may be more
+fn recompute_batch(conn: &PgConnection, after: Option<&ObjectId>) -> Result<Option<ObjectId>> {
+    use schema::objects::dsl::*;
+
+    let computed = computed_properties(conn)?;
+    let mut query = objects
+        .select(id)
+        .order(id.asc())
+        .limit(RECOMPUTE_BATCH)
+        .into_boxed();
+    if let Some(after) = after {
+        query = query.filter(id.gt(after));
+    }
+    let object_ids: Vec<ObjectId> = query
+        .load(conn)
+        .map_err(|e| db_error("db select object ids for recompute error", e))?;
+    for object_id in &object_ids {
+        recompute_with(conn, &computed, object_id)?;
+    }
+
+    Ok(match object_ids.len() as i64 {
+        RECOMPUTE_BATCH => object_ids.into_iter().last(),
+        _ => None,
+    })
+}
+
+pub fn recompute_all(conn: &PgConnection) -> Result<()> {
+    let mut after = None;
+    while let Some(last) = recompute_batch(conn, after.as_ref())? {
+        after = Some(last);
+    }
+    Ok(())
+}
+
+/// Re-evaluate the computed properties of the next objects after `after`,
+/// resolving to the last object recomputed while there may be more
+pub struct RecomputeBatch {
+    pub after: Option<ObjectId>,
+}
+
+impl Message for RecomputeBatch {
+    type Result = Result<Option<ObjectId>>;
+}
+
+impl Handler<RecomputeBatch> for DbExecutor {
+    type Result = Result<Option<ObjectId>>;
+
+    fn handle(&mut self, msg: RecomputeBatch, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        recompute_batch(&conn, msg.after.as_ref())
+    }
+}
+
+/// Whether an object has yet to be given a value of some computed property,
+/// as happens when the property was just created
+pub struct ComputedPending;
+
+impl Message for ComputedPending {
+    type Result = Result<bool>;
+}
+
+impl Handler<ComputedPending> for DbExecutor {
+    type Result = Result<bool>;
+
+    fn handle(&mut self, _: ComputedPending, _: &mut Self::Context) -> Self::Result {
+        use diesel::dsl::{exists, not, select};
+        use schema::{computed_values, objects};
+        let conn = self.0.get().unwrap();
+
+        for (property, _) in computed_properties(&conn)? {
+            let missing = select(exists(
+                objects::table.filter(not(exists(
+                    computed_values::table
+                        .filter(computed_values::object_id.eq(objects::id))
+                        .filter(computed_values::property_id.eq(&property)),
+                ))),
+            ))
+            .get_result(&conn)
+            .map_err(|e| db_error("db select pending computed values error", e))?;
+            if missing {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
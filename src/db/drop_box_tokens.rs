@@ -0,0 +1,164 @@
+//! Tokens of mail drop-box addresses, which only file mail into a collection
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::Utc;
+use diesel::dsl::exists;
+use diesel::insert_into;
+use diesel::prelude::*;
+
+use super::schema;
+use super::{db_error, get_user_by_id, DbExecutor};
+use crate::mail::{new_drop_box_secret, DropBoxToken, DropBoxTokenId};
+use crate::property::{PropertyId, SelectChoiceId};
+use crate::sessions::hash_secret;
+use crate::user::UserId;
+
+/// Create a token filing mail into a collection as the user, responding with
+/// its id and secret. Only the hash of the secret is kept.
+pub struct CreateDropBoxToken {
+    pub user_id: UserId,
+    pub collection_id: SelectChoiceId,
+}
+
+impl Message for CreateDropBoxToken {
+    type Result = Result<(DropBoxTokenId, String)>;
+}
+
+impl Handler<CreateDropBoxToken> for DbExecutor {
+    type Result = Result<(DropBoxTokenId, String)>;
+
+    fn handle(&mut self, msg: CreateDropBoxToken, _: &mut Self::Context) -> Self::Result {
+        use schema::drop_box_tokens::dsl::*;
+        use schema::property_value_choices;
+        let conn = self.0.get().unwrap();
+
+        let is_collection: bool = diesel::select(exists(
+            property_value_choices::table
+                .filter(property_value_choices::id.eq(&msg.collection_id))
+                .filter(property_value_choices::property_id.eq(PropertyId::COLLECTION)),
+        ))
+        .get_result(&conn)
+        .map_err(|e| db_error("db select collection error", e))?;
+        if !is_collection {
+            return Err(error::ErrorBadRequest("No such collection"));
+        }
+
+        let secret = new_drop_box_secret();
+        let token_id = insert_into(drop_box_tokens)
+            .values((
+                user_id.eq(&msg.user_id),
+                collection_id.eq(&msg.collection_id),
+                secret_hash.eq(hash_secret(&secret)),
+            ))
+            .returning(id)
+            .get_result(&conn)
+            .map_err(|e| db_error("db insert drop-box token error", e))?;
+        Ok((token_id, secret))
+    }
+}
+
+/// A user's drop-box tokens, newest first
+pub struct ListDropBoxTokens {
+    pub user_id: UserId,
+}
+
+impl Message for ListDropBoxTokens {
+    type Result = Result<Vec<DropBoxToken>>;
+}
+
+impl Handler<ListDropBoxTokens> for DbExecutor {
+    type Result = Result<Vec<DropBoxToken>>;
+
+    fn handle(&mut self, msg: ListDropBoxTokens, _: &mut Self::Context) -> Self::Result {
+        use schema::{drop_box_tokens, property_value_choices};
+        let conn = self.0.get().unwrap();
+
+        drop_box_tokens::table
+            .inner_join(property_value_choices::table)
+            .filter(drop_box_tokens::user_id.eq(&msg.user_id))
+            .order(drop_box_tokens::created_at.desc())
+            .select((
+                drop_box_tokens::id,
+                drop_box_tokens::user_id,
+                drop_box_tokens::collection_id,
+                property_value_choices::display,
+                drop_box_tokens::last_used_at,
+                drop_box_tokens::created_at,
+            ))
+            .load(&conn)
+            .map_err(|e| db_error("db select drop-box tokens error", e))
+    }
+}
+
+/// Delete one of the user's drop-box tokens, after which mail to its address
+/// is refused
+pub struct RevokeDropBoxToken {
+    pub token_id: DropBoxTokenId,
+    pub user_id: UserId,
+}
+
+impl Message for RevokeDropBoxToken {
+    type Result = Result<()>;
+}
+
+impl Handler<RevokeDropBoxToken> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: RevokeDropBoxToken, _: &mut Self::Context) -> Self::Result {
+        use schema::drop_box_tokens::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let deleted = diesel::delete(
+            drop_box_tokens
+                .filter(id.eq(&msg.token_id))
+                .filter(user_id.eq(&msg.user_id)),
+        )
+        .execute(&conn)
+        .map_err(|e| db_error("db delete drop-box token error", e))?;
+        if deleted == 0 {
+            return Err(error::ErrorNotFound("No such drop-box token"));
+        }
+        Ok(())
+    }
+}
+
+/// The owner of a drop-box token for the collection, recording that the token
+/// was used. None for unknown or revoked tokens, tokens of other collections,
+/// and tokens of disabled users.
+pub struct AuthenticateDropBoxToken {
+    pub secret: String,
+    pub collection_id: SelectChoiceId,
+}
+
+impl Message for AuthenticateDropBoxToken {
+    type Result = Result<Option<UserId>>;
+}
+
+impl Handler<AuthenticateDropBoxToken> for DbExecutor {
+    type Result = Result<Option<UserId>>;
+
+    fn handle(&mut self, msg: AuthenticateDropBoxToken, _: &mut Self::Context) -> Self::Result {
+        use schema::drop_box_tokens::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let token: Option<(DropBoxTokenId, UserId)> = drop_box_tokens
+            .filter(secret_hash.eq(hash_secret(&msg.secret)))
+            .filter(collection_id.eq(&msg.collection_id))
+            .select((id, user_id))
+            .get_result(&conn)
+            .optional()
+            .map_err(|e| db_error("db select drop-box token error", e))?;
+        let (token_id, owner) = match token {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        if get_user_by_id(&conn, &owner)?.is_disabled() {
+            return Ok(None);
+        }
+        diesel::update(drop_box_tokens.filter(id.eq(&token_id)))
+            .set(last_used_at.eq(Utc::now()))
+            .execute(&conn)
+            .map_err(|e| db_error("db update drop-box token last used error", e))?;
+        Ok(Some(owner))
+    }
+}
@@ -0,0 +1,130 @@
+//! Filing uploaded emails: their headers, attachments and threads
+use ::actix::prelude::*;
+use actix_web::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use super::computed::recompute_object;
+use super::history::set_audit_context;
+use super::schema;
+use super::values::{has_value, set_value, ValueUpdate};
+use super::{db_error, transaction, DbExecutor};
+use crate::object::{EmailHeaders, ObjectId};
+use crate::property::{PropertyId, PropertyType, PropertyValue};
+use crate::user::UserId;
+
+/// Set the values of an email's headers, relate it to its attachments, and
+/// thread it with the messages it replies to or which reply to it
+pub struct FileEmail {
+    pub object_id: ObjectId,
+    pub headers: EmailHeaders,
+    pub attachments: Vec<ObjectId>,
+    pub user_id: UserId,
+    pub request_id: Option<String>,
+}
+
+impl Message for FileEmail {
+    type Result = Result<()>;
+}
+
+impl Handler<FileEmail> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: FileEmail, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let headers = &msg.headers;
+        let object = &msg.object_id;
+
+        transaction(&conn, || {
+            set_audit_context(&conn, &msg.user_id, &msg.request_id)?;
+            let texts = vec![
+                (PropertyId::FROM, &headers.from),
+                (PropertyId::TO, &headers.to),
+                (PropertyId::CC, &headers.cc),
+                (PropertyId::SUBJECT, &headers.subject),
+                (PropertyId::MESSAGE_ID, &headers.message_id),
+                (PropertyId::IN_REPLY_TO, &headers.in_reply_to),
+            ];
+            let mut values = texts
+                .into_iter()
+                .filter_map(|(property_id, text)| {
+                    text.clone().map(|text| ValueUpdate {
+                        property_id,
+                        value: PropertyValue::Text(text),
+                    })
+                })
+                .collect::<Vec<_>>();
+            if headers.date.is_some() {
+                values.push(ValueUpdate {
+                    property_id: PropertyId::DATE,
+                    value: PropertyValue::Timestamptz(headers.date),
+                });
+            }
+            if !msg.attachments.is_empty() {
+                values.push(ValueUpdate {
+                    property_id: PropertyId::ATTACHMENTS,
+                    value: PropertyValue::Relation(msg.attachments.clone()),
+                });
+            }
+
+            // replies may be filed before the messages they reply to
+            if let Some(ref in_reply_to) = headers.in_reply_to {
+                if let Some(parent) =
+                    messages_with(&conn, &PropertyId::MESSAGE_ID, in_reply_to, object)?
+                        .into_iter()
+                        .next()
+                {
+                    values.push(ValueUpdate {
+                        property_id: PropertyId::REPLIES_TO,
+                        value: PropertyValue::Relation(vec![parent]),
+                    });
+                }
+            }
+            for update in &values {
+                set_value(&conn, object, update, &msg.user_id)?;
+            }
+            recompute_object(&conn, object)?;
+
+            if let Some(ref message_id) = headers.message_id {
+                let reply = ValueUpdate {
+                    property_id: PropertyId::REPLIES_TO,
+                    value: PropertyValue::Relation(vec![object.clone()]),
+                };
+                for reply_id in messages_with(&conn, &PropertyId::IN_REPLY_TO, message_id, object)?
+                {
+                    if !has_value(
+                        &conn,
+                        &reply_id,
+                        &PropertyId::REPLIES_TO,
+                        &PropertyType::Relation,
+                    )? {
+                        set_value(&conn, &reply_id, &reply, &msg.user_id)?;
+                        recompute_object(&conn, &reply_id)?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// The objects other than `object` whose `property` is `text`, oldest first
+fn messages_with(
+    conn: &PgConnection,
+    property: &PropertyId,
+    text: &str,
+    object: &ObjectId,
+) -> Result<Vec<ObjectId>> {
+    use schema::objects;
+    use schema::text_values::dsl::*;
+
+    text_values
+        .inner_join(objects::table)
+        .filter(property_id.eq(property))
+        .filter(value.eq(text))
+        .filter(object_id.ne(object))
+        .order((objects::created_at.asc(), object_id.asc()))
+        .select(object_id)
+        .load(conn)
+        .map_err(|e| db_error("db select messages by id error", e))
+}
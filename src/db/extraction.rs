@@ -0,0 +1,186 @@
+//! The queue of object contents to extract text from, and its results
+use ::actix::prelude::*;
+use actix_web::Result;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use super::computed::recompute_object;
+use super::schema;
+use super::{db_error, DbExecutor};
+use crate::object::{Extraction, ExtractionStatus, ObjectId};
+
+/// Attempts before an extraction that keeps failing is given up on
+const MAX_ATTEMPTS: i32 = 3;
+
+/// An object version waiting to have its text extracted
+#[derive(Debug, Clone, Queryable)]
+pub struct PendingExtraction {
+    pub object_id: ObjectId,
+    pub version: i32,
+    pub hash: String,
+    pub filename: String,
+}
+
+/// The oldest pending extractions
+pub struct ListPendingExtractions {
+    pub limit: i64,
+}
+
+impl Message for ListPendingExtractions {
+    type Result = Result<Vec<PendingExtraction>>;
+}
+
+impl Handler<ListPendingExtractions> for DbExecutor {
+    type Result = Result<Vec<PendingExtraction>>;
+
+    fn handle(&mut self, msg: ListPendingExtractions, _: &mut Self::Context) -> Self::Result {
+        use schema::{extracted_texts, object_versions};
+        let conn = self.0.get().unwrap();
+
+        extracted_texts::table
+            .inner_join(
+                object_versions::table.on(object_versions::object_id
+                    .eq(extracted_texts::object_id)
+                    .and(object_versions::version.eq(extracted_texts::version))),
+            )
+            .filter(extracted_texts::status.eq(ExtractionStatus::Pending))
+            .order(extracted_texts::updated_at.asc())
+            .limit(msg.limit)
+            .select((
+                extracted_texts::object_id,
+                extracted_texts::version,
+                object_versions::hash,
+                object_versions::filename,
+            ))
+            .load(&conn)
+            .map_err(|e| db_error("db select pending extractions error", e))
+    }
+}
+
+/// Record the outcome of extracting a version's text. Ignored if the object
+/// has had a newer version since.
+pub struct SaveExtraction {
+    pub object_id: ObjectId,
+    pub version: i32,
+    pub extraction: Extraction,
+}
+
+impl Message for SaveExtraction {
+    type Result = Result<()>;
+}
+
+impl Handler<SaveExtraction> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: SaveExtraction, _: &mut Self::Context) -> Self::Result {
+        use schema::extracted_texts::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let row = extracted_texts
+            .filter(object_id.eq(&msg.object_id))
+            .filter(version.eq(msg.version));
+        let (new_status, text, page_count, message) = match msg.extraction {
+            Extraction::Text(text, page_count) => (ExtractionStatus::Done, text, page_count, None),
+            Extraction::Unsupported => (ExtractionStatus::Unsupported, String::new(), None, None),
+            Extraction::Failed(message) => {
+                warn!("extracting text of {} failed: {}", msg.object_id, message);
+                let tried: i32 = row
+                    .clone()
+                    .select(attempts)
+                    .get_result(&conn)
+                    .optional()
+                    .map_err(|e| db_error("db select extraction attempts error", e))?
+                    .unwrap_or(0);
+                // stays pending, at the back of the queue, until out of attempts
+                let new_status = if tried + 1 >= MAX_ATTEMPTS {
+                    ExtractionStatus::Failed
+                } else {
+                    ExtractionStatus::Pending
+                };
+                (new_status, String::new(), None, Some(message))
+            }
+        };
+
+        diesel::update(row)
+            .set((
+                status.eq(new_status),
+                content.eq(text),
+                pages.eq(page_count),
+                error.eq(message),
+                attempts.eq(attempts + 1),
+                updated_at.eq(Utc::now()),
+            ))
+            .execute(&conn)
+            .map_err(|e| db_error("db save extraction error", e))?;
+
+        // `pages()` reads the page count
+        recompute_object(&conn, &msg.object_id)
+    }
+}
+
+/// How extracting an object's text went
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct ExtractedText {
+    pub version: i32,
+    pub status: ExtractionStatus,
+    pub error: Option<String>,
+    pub attempts: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// The extraction status of an object, if it has content
+pub struct GetExtraction(pub ObjectId);
+
+impl Message for GetExtraction {
+    type Result = Result<Option<ExtractedText>>;
+}
+
+impl Handler<GetExtraction> for DbExecutor {
+    type Result = Result<Option<ExtractedText>>;
+
+    fn handle(&mut self, msg: GetExtraction, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        object_extraction(&conn, &msg.0)
+    }
+}
+
+pub fn object_extraction(
+    conn: &PgConnection,
+    object: &ObjectId,
+) -> Result<Option<ExtractedText>> {
+    use schema::extracted_texts::dsl::*;
+
+    extracted_texts
+        .filter(object_id.eq(object))
+        .select((version, status, error, attempts, updated_at))
+        .get_result(conn)
+        .optional()
+        .map_err(|e| db_error("db select extracted text error", e))
+}
+
+/// Extract an object's text again, e.g. after it failed
+pub struct RetryExtraction(pub ObjectId);
+
+impl Message for RetryExtraction {
+    type Result = Result<()>;
+}
+
+impl Handler<RetryExtraction> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: RetryExtraction, _: &mut Self::Context) -> Self::Result {
+        use schema::extracted_texts::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        diesel::update(extracted_texts.filter(object_id.eq(&msg.0)))
+            .set((
+                status.eq(ExtractionStatus::Pending),
+                attempts.eq(0),
+                updated_at.eq(Utc::now()),
+            ))
+            .execute(&conn)
+            .map(|_| ())
+            .map_err(|e| db_error("db retry extraction error", e))
+    }
+}
@@ -0,0 +1,229 @@
+//! Counts of the values of the objects matching a query
+use ::actix::prelude::*;
+use actix_web::Result;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::query_builder::{AstPass, Query as SqlQuery, QueryFragment, QueryId};
+use diesel::sql_types::{Int8, Nullable, Text};
+use std::collections::HashMap;
+
+use super::query::catalog;
+use super::schema::objects;
+use super::{db_error, DbExecutor};
+use crate::property::{Property, PropertyId, SelectChoiceId};
+use crate::query::{compile, Query, QueryError};
+
+/// Width of the buckets of timestamp histograms
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketInterval {
+    Day,
+    Week,
+    #[default]
+    Month,
+    Year,
+}
+
+impl BucketInterval {
+    /// The `date_trunc` field
+    fn field(self) -> &'static str {
+        match self {
+            BucketInterval::Day => "day",
+            BucketInterval::Week => "week",
+            BucketInterval::Month => "month",
+            BucketInterval::Year => "year",
+        }
+    }
+}
+
+pub struct GetFacets {
+    pub query: Query,
+    pub interval: BucketInterval,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Facets {
+    /// Number of matching objects
+    pub total: i64,
+    pub choices: Vec<ChoiceFacet>,
+    pub timestamps: Vec<TimestampFacet>,
+}
+
+/// How many matching objects have each choice of a property
+#[derive(Debug, Serialize)]
+pub struct ChoiceFacet {
+    pub property_id: PropertyId,
+    pub display: String,
+    /// Most common first
+    pub counts: Vec<ChoiceCount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChoiceCount {
+    pub id: SelectChoiceId,
+    pub display: String,
+    pub count: i64,
+}
+
+/// How many matching objects have a timestamp in each interval, oldest first
+#[derive(Debug, Serialize)]
+pub struct TimestampFacet {
+    pub property_id: PropertyId,
+    pub display: String,
+    pub interval: BucketInterval,
+    pub buckets: Vec<TimestampBucket>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TimestampBucket {
+    /// `YYYY-MM-DD` the interval starts on
+    pub start: String,
+    pub count: i64,
+}
+
+pub enum FacetsResult {
+    Found(Facets),
+    Invalid(QueryError),
+}
+
+impl Message for GetFacets {
+    type Result = Result<FacetsResult>;
+}
+
+/// Every count in one statement: rows of (kind, property id, bucket, count)
+/// where kind is `total`, `choice` (bucket is the choice id) or `timestamptz`
+/// (bucket is the start of the interval).
+struct FacetCounts<M> {
+    matching: M,
+    interval: BucketInterval,
+}
+
+impl<M> QueryId for FacetCounts<M> {
+    type QueryId = ();
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+impl<M> SqlQuery for FacetCounts<M> {
+    type SqlType = (Text, Int8, Nullable<Text>, Int8);
+}
+
+impl<M> RunQueryDsl<PgConnection> for FacetCounts<M> {}
+
+impl<M: QueryFragment<Pg>> QueryFragment<Pg> for FacetCounts<M> {
+    fn walk_ast(&self, mut out: AstPass<Pg>) -> QueryResult<()> {
+        out.push_sql("WITH matching AS (");
+        self.matching.walk_ast(out.reborrow())?;
+        out.push_sql(
+            ") \
+             SELECT 'total', 0::BIGINT, NULL::TEXT, count(*) FROM matching \
+             UNION ALL \
+             SELECT 'choice', c.property_id, c.value_id::TEXT, count(*) \
+             FROM choice_values c JOIN matching m ON m.id = c.object_id \
+             GROUP BY c.property_id, c.value_id \
+             UNION ALL \
+             SELECT 'timestamptz', t.property_id, \
+                    to_char(date_trunc('",
+        );
+        out.push_sql(self.interval.field());
+        out.push_sql(
+            "', t.value AT TIME ZONE 'UTC'), 'YYYY-MM-DD') AS bucket, count(*) \
+             FROM timestamptz_values t JOIN matching m ON m.id = t.object_id \
+             WHERE t.value IS NOT NULL \
+             GROUP BY t.property_id, bucket",
+        );
+        Ok(())
+    }
+}
+
+impl Handler<GetFacets> for DbExecutor {
+    type Result = Result<FacetsResult>;
+
+    fn handle(&mut self, msg: GetFacets, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let catalog = catalog(&conn)?;
+        let matching = match compile(&catalog, &msg.query) {
+            Ok(query) => query.select(objects::id),
+            Err(e) => return Ok(FacetsResult::Invalid(e)),
+        };
+
+        let rows: Vec<(String, PropertyId, Option<String>, i64)> = FacetCounts {
+            matching,
+            interval: msg.interval,
+        }
+        .load(&conn)
+        .map_err(|e| db_error("db select facet counts error", e))?;
+
+        let display_of = |id: &PropertyId| {
+            catalog
+                .properties
+                .iter()
+                .find(|p| &p.id == id)
+                .map_or_else(String::new, |p| p.display().to_string())
+        };
+        let mut total = 0;
+        let mut choices: HashMap<PropertyId, ChoiceFacet> = HashMap::new();
+        let mut timestamps: HashMap<PropertyId, TimestampFacet> = HashMap::new();
+        for (kind, property_id, bucket, count) in rows {
+            let bucket = bucket.unwrap_or_default();
+            match kind.as_str() {
+                "total" => total = count,
+                "choice" => {
+                    let id = SelectChoiceId::from(bucket.parse::<i64>().unwrap_or_default());
+                    let display = catalog
+                        .choices
+                        .iter()
+                        .find(|(choice_id, _, _)| choice_id == &id)
+                        .map_or_else(String::new, |(_, _, display)| display.clone());
+                    choices
+                        .entry(property_id.clone())
+                        .or_insert_with(|| ChoiceFacet {
+                            display: display_of(&property_id),
+                            property_id,
+                            counts: Vec::new(),
+                        })
+                        .counts
+                        .push(ChoiceCount { id, display, count });
+                }
+                _ => timestamps
+                    .entry(property_id.clone())
+                    .or_insert_with(|| TimestampFacet {
+                        display: display_of(&property_id),
+                        property_id,
+                        interval: msg.interval,
+                        buckets: Vec::new(),
+                    })
+                    .buckets
+                    .push(TimestampBucket {
+                        start: bucket,
+                        count,
+                    }),
+            }
+        }
+
+        // in the order of the properties
+        let mut choices: Vec<ChoiceFacet> = catalog
+            .properties
+            .iter()
+            .filter_map(|p| choices.remove(&p.id))
+            .collect();
+        for facet in &mut choices {
+            facet
+                .counts
+                .sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.display.cmp(&b.display)));
+        }
+        let mut timestamps: Vec<TimestampFacet> = catalog
+            .properties
+            .iter()
+            .filter_map(|p| timestamps.remove(&p.id))
+            .collect();
+        for facet in &mut timestamps {
+            facet.buckets.sort_by(|a, b| a.start.cmp(&b.start));
+        }
+
+        Ok(FacetsResult::Found(Facets {
+            total,
+            choices,
+            timestamps,
+        }))
+    }
+}
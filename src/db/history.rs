@@ -0,0 +1,101 @@
+//! The audit trail of property value changes recorded in `value_history`
+use ::actix::prelude::*;
+use actix_web::Result;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Int8, Jsonb, Nullable, Text, Timestamptz};
+use diesel::PgConnection;
+
+use super::{db_error, DbExecutor};
+use crate::object::ObjectId;
+use crate::property::PropertyId;
+use crate::user::UserId;
+
+/// Attribute the changes of the current transaction to a user and request.
+/// Must be called inside of a transaction, since the settings are local to it.
+pub fn set_audit_context(
+    conn: &PgConnection,
+    user: &UserId,
+    request_id: &Option<String>,
+) -> Result<()> {
+    diesel::sql_query(
+        "SELECT set_config('dewey.user_id', $1, true), set_config('dewey.request_id', $2, true)",
+    )
+    .bind::<Text, _>(user.to_string())
+    .bind::<Text, _>(request_id.as_ref().map_or("", String::as_str))
+    .execute(conn)
+    .map(|_| ())
+    .map_err(|e| db_error("db set audit context error", e))
+}
+
+/// A change to one of an object's property values
+#[derive(Debug, Clone, Serialize, QueryableByName)]
+pub struct ValueChange {
+    #[sql_type = "Int8"]
+    pub property_id: PropertyId,
+    #[sql_type = "Nullable<Text>"]
+    pub property: Option<String>,
+    #[sql_type = "Nullable<Jsonb>"]
+    pub old_value: Option<serde_json::Value>,
+    #[sql_type = "Nullable<Jsonb>"]
+    pub new_value: Option<serde_json::Value>,
+    /// Value as displayed, with choice ids replaced by their names
+    #[sql_type = "Nullable<Text>"]
+    pub old_display: Option<String>,
+    #[sql_type = "Nullable<Text>"]
+    pub new_display: Option<String>,
+    #[sql_type = "Int8"]
+    pub changed_by: UserId,
+    #[sql_type = "Nullable<Text>"]
+    pub changed_by_name: Option<String>,
+    /// Made by a plugin rather than a person, e.g. automatic tags
+    #[sql_type = "Bool"]
+    pub changed_by_plugin: bool,
+    #[sql_type = "Timestamptz"]
+    pub changed_at: DateTime<Utc>,
+    #[sql_type = "Nullable<Text>"]
+    pub request_id: Option<String>,
+}
+
+/// Every change to an object's values, newest first
+pub fn object_history(conn: &PgConnection, object: &ObjectId) -> Result<Vec<ValueChange>> {
+    diesel::sql_query(
+        r#"
+        SELECT h.property_id, p.display AS property, h.old_value, h.new_value,
+               COALESCE(old_choice.display, h.old_value #>> '{}') AS old_display,
+               COALESCE(new_choice.display, h.new_value #>> '{}') AS new_display,
+               h.changed_by, u.display_name AS changed_by_name,
+               COALESCE(u.kind = 'plugin', false) AS changed_by_plugin,
+               h.changed_at, h.request_id
+        FROM value_history h
+        LEFT JOIN properties p ON p.id = h.property_id
+        LEFT JOIN users u ON u.id = h.changed_by
+        LEFT JOIN property_value_choices old_choice
+            ON old_choice.id = CASE WHEN p.property_type = 'choice'
+                                    THEN (h.old_value #>> '{}')::BIGINT END
+        LEFT JOIN property_value_choices new_choice
+            ON new_choice.id = CASE WHEN p.property_type = 'choice'
+                                    THEN (h.new_value #>> '{}')::BIGINT END
+        WHERE h.object_id = $1
+        ORDER BY h.changed_at DESC, h.id DESC
+        "#,
+    )
+    .bind::<Text, _>(object)
+    .load(conn)
+    .map_err(|e| db_error("db select value history error", e))
+}
+
+pub struct GetValueHistory(pub ObjectId);
+
+impl Message for GetValueHistory {
+    type Result = Result<Vec<ValueChange>>;
+}
+
+impl Handler<GetValueHistory> for DbExecutor {
+    type Result = Result<Vec<ValueChange>>;
+
+    fn handle(&mut self, msg: GetValueHistory, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        object_history(&conn, &msg.0)
+    }
+}
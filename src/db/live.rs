@@ -0,0 +1,41 @@
+//! The queue of events for live updates
+use ::actix::prelude::*;
+use actix_web::Result;
+use diesel::prelude::*;
+use diesel::sql_types::Int8;
+
+use super::{db_error, DbExecutor};
+use crate::live::LiveEvent;
+
+/// Remove the oldest queued events, responding with them. Events being taken
+/// by another server process are skipped, so each is published once.
+pub struct TakeLiveEvents {
+    pub limit: i64,
+}
+
+impl Message for TakeLiveEvents {
+    type Result = Result<Vec<LiveEvent>>;
+}
+
+impl Handler<TakeLiveEvents> for DbExecutor {
+    type Result = Result<Vec<LiveEvent>>;
+
+    fn handle(&mut self, msg: TakeLiveEvents, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        let mut events: Vec<LiveEvent> = diesel::sql_query(
+            r#"
+            DELETE FROM live_events
+            WHERE id IN (
+                SELECT id FROM live_events ORDER BY id LIMIT $1 FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, event::TEXT AS event, collections, users, payload, created_at
+            "#,
+        )
+        .bind::<Int8, _>(msg.limit)
+        .load(&conn)
+        .map_err(|e| db_error("db take live events error", e))?;
+        events.sort_by_key(|event| event.id);
+        Ok(events)
+    }
+}
@@ -0,0 +1,355 @@
+//! Reading objects along with their property values
+use ::actix::prelude::*;
+use actix_web::Result;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::collections::{HashMap, HashSet};
+
+use super::extraction::{object_extraction, ExtractedText};
+use super::history::{object_history, ValueChange};
+use super::relations::{object_relations, Relation};
+use super::schema;
+use super::schemas::object_schema_fields;
+use super::values::{validation_rules, ValueUpdate};
+use super::{db_error, DbExecutor, Fetch};
+use crate::object::{ObjectId, ObjectRow};
+use crate::property::{
+    Property, PropertyId, PropertyRow, PropertyValue, SelectChoiceId, ValidationRules,
+};
+use crate::user::UserId;
+
+/// An object and every property shown on its page
+pub struct ObjectDetails {
+    pub object: ObjectRow,
+    pub fields: Vec<ObjectField>,
+    /// Documents this object relates to, or which relate to it
+    pub related: Vec<Relation>,
+    /// Changes to the object's values, newest first
+    pub history: Vec<ValueChange>,
+    /// `None` for objects without any content
+    pub extraction: Option<ExtractedText>,
+}
+
+/// A property of an object, formatted for display and editing
+pub struct ObjectField {
+    pub property_id: PropertyId,
+    pub display: String,
+    /// One of `PropertyType::name`
+    pub kind: &'static str,
+    pub rules: ValidationRules,
+    /// Current value as it would be typed into the form input
+    pub input: String,
+    /// Available choices for choice properties
+    pub choices: Vec<FieldChoice>,
+}
+
+pub struct FieldChoice {
+    pub id: SelectChoiceId,
+    pub display: String,
+    pub selected: bool,
+}
+
+pub struct GetObjectDetails(pub ObjectId);
+
+impl Message for GetObjectDetails {
+    type Result = Result<ObjectDetails>;
+}
+
+impl Handler<GetObjectDetails> for DbExecutor {
+    type Result = Result<ObjectDetails>;
+
+    fn handle(&mut self, msg: GetObjectDetails, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let object: ObjectRow = msg.0.fetch(&conn)?;
+        let schema_fields = object_schema_fields(&conn, &msg.0)?;
+
+        // Fields of the object's schemas come first, in the schema's order
+        let mut properties = all_properties(&conn)?;
+        properties.sort_by_key(|property| {
+            schema_fields
+                .iter()
+                .position(|field| field.property_id == property.id)
+                .unwrap_or_else(|| schema_fields.len())
+        });
+
+        let mut fields = object_fields(&conn, &msg.0, properties)?;
+        for field in &mut fields {
+            field.rules.required |= schema_fields
+                .iter()
+                .any(|f| f.required && f.property_id == field.property_id);
+        }
+        let related = object_relations(&conn, &msg.0)?;
+        let history = object_history(&conn, &msg.0)?;
+        let extraction = object_extraction(&conn, &msg.0)?;
+        Ok(ObjectDetails {
+            object,
+            fields,
+            related,
+            history,
+            extraction,
+        })
+    }
+}
+
+/// An object with its values as they are set, for API clients
+#[derive(Debug, Serialize)]
+pub struct ObjectInfo {
+    pub id: ObjectId,
+    pub filename: Option<String>,
+    pub extension: String,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    /// `None` for objects without any content
+    pub current_version: Option<i32>,
+    /// In the shape `SetValues` accepts them
+    pub values: Vec<ValueUpdate>,
+    pub computed: Vec<ComputedValue>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ComputedValue {
+    pub property_id: PropertyId,
+    /// `None` when the formula has no result for the object
+    pub value: Option<f64>,
+}
+
+/// An object's values, `None` if there is no such object
+pub struct GetObject(pub ObjectId);
+
+impl Message for GetObject {
+    type Result = Result<Option<ObjectInfo>>;
+}
+
+impl Handler<GetObject> for DbExecutor {
+    type Result = Result<Option<ObjectInfo>>;
+
+    fn handle(&mut self, msg: GetObject, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let object: Option<ObjectRow> = {
+            use schema::objects::dsl::*;
+            objects
+                .filter(id.eq(&msg.0))
+                .get_result(&conn)
+                .optional()
+                .map_err(|e| db_error("db select object error", e))?
+        };
+        let object = match object {
+            Some(object) => object,
+            None => return Ok(None),
+        };
+
+        let values = object_values(&conn, &object.id)?;
+        let filename = values.iter().find_map(|update| match update.value {
+            PropertyValue::Text(ref text) if update.property_id == PropertyId::FILENAME => {
+                Some(text.clone())
+            }
+            _ => None,
+        });
+        let computed = {
+            use schema::computed_values::dsl::*;
+            computed_values
+                .filter(object_id.eq(&object.id))
+                .order(property_id.asc())
+                .select((property_id, value))
+                .load::<(PropertyId, Option<f64>)>(&conn)
+                .map_err(|e| db_error("db select object computed values error", e))?
+                .into_iter()
+                .map(|(pid, number)| ComputedValue {
+                    property_id: pid,
+                    value: number,
+                })
+                .collect()
+        };
+
+        Ok(Some(ObjectInfo {
+            id: object.id,
+            filename,
+            extension: object.extension,
+            created_by: object.created_by,
+            created_at: object.created_at,
+            current_version: object.current_version,
+            values,
+            computed,
+        }))
+    }
+}
+
+/// The values `object` has set, one per property
+pub fn object_values(conn: &PgConnection, object: &ObjectId) -> Result<Vec<ValueUpdate>> {
+    let mut values = Vec::new();
+    {
+        use schema::text_values::dsl::*;
+        let rows: Vec<(PropertyId, String)> = text_values
+            .filter(object_id.eq(object))
+            .select((property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select object text values error", e))?;
+        values.extend(rows.into_iter().map(|(pid, text)| ValueUpdate {
+            property_id: pid,
+            value: PropertyValue::Text(text),
+        }));
+    }
+    {
+        use schema::timestamptz_values::dsl::*;
+        let rows: Vec<(PropertyId, Option<DateTime<Utc>>)> = timestamptz_values
+            .filter(object_id.eq(object))
+            .select((property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select object timestamptz values error", e))?;
+        values.extend(rows.into_iter().map(|(pid, timestamp)| ValueUpdate {
+            property_id: pid,
+            value: PropertyValue::Timestamptz(timestamp),
+        }));
+    }
+    {
+        use schema::choice_values::dsl::*;
+        let rows: Vec<(PropertyId, SelectChoiceId)> = choice_values
+            .filter(object_id.eq(object))
+            .order((property_id.asc(), value_id.asc()))
+            .select((property_id, value_id))
+            .load(conn)
+            .map_err(|e| db_error("db select object choice values error", e))?;
+        let mut choices: Vec<(PropertyId, Vec<SelectChoiceId>)> = Vec::new();
+        for (pid, choice) in rows {
+            match choices.last_mut() {
+                Some((last, ids)) if *last == pid => ids.push(choice),
+                _ => choices.push((pid, vec![choice])),
+            }
+        }
+        values.extend(choices.into_iter().map(|(pid, ids)| ValueUpdate {
+            property_id: pid,
+            value: PropertyValue::Choice(ids),
+        }));
+    }
+    {
+        use schema::relation_values::dsl::*;
+        let rows: Vec<(PropertyId, ObjectId)> = relation_values
+            .filter(object_id.eq(object))
+            .order((property_id.asc(), target_id.asc()))
+            .select((property_id, target_id))
+            .load(conn)
+            .map_err(|e| db_error("db select object relation values error", e))?;
+        let mut targets: Vec<(PropertyId, Vec<ObjectId>)> = Vec::new();
+        for (pid, target) in rows {
+            match targets.last_mut() {
+                Some((last, ids)) if *last == pid => ids.push(target),
+                _ => targets.push((pid, vec![target])),
+            }
+        }
+        values.extend(targets.into_iter().map(|(pid, ids)| ValueUpdate {
+            property_id: pid,
+            value: PropertyValue::Relation(ids),
+        }));
+    }
+    Ok(values)
+}
+
+/// Every property in `ord` order
+pub fn all_properties(conn: &PgConnection) -> Result<Vec<PropertyRow>> {
+    use schema::properties::dsl::*;
+
+    properties
+        .order(ord.asc())
+        .load(conn)
+        .map_err(|e| db_error("db select properties error", e))
+}
+
+/// Format the values `object` has for each of `properties`
+pub fn object_fields(
+    conn: &PgConnection,
+    object: &ObjectId,
+    properties: Vec<PropertyRow>,
+) -> Result<Vec<ObjectField>> {
+    let mut inputs: HashMap<PropertyId, String> = HashMap::new();
+    {
+        use schema::text_values::dsl::*;
+        let rows: Vec<(PropertyId, String)> = text_values
+            .filter(object_id.eq(object))
+            .select((property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select object text values error", e))?;
+        inputs.extend(rows);
+    }
+    {
+        use schema::timestamptz_values::dsl::*;
+        let rows: Vec<(PropertyId, Option<DateTime<Utc>>)> = timestamptz_values
+            .filter(object_id.eq(object))
+            .select((property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select object timestamptz values error", e))?;
+        inputs.extend(rows.into_iter().filter_map(|(pid, timestamp)| {
+            timestamp.map(|t| (pid, t.format("%Y-%m-%dT%H:%M").to_string()))
+        }));
+    }
+    {
+        use schema::computed_values::dsl::*;
+        let rows: Vec<(PropertyId, Option<f64>)> = computed_values
+            .filter(object_id.eq(object))
+            .select((property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select object computed values error", e))?;
+        inputs.extend(
+            rows.into_iter()
+                .filter_map(|(pid, number)| number.map(|n| (pid, format!("{:.2}", n)))),
+        );
+    }
+    {
+        use schema::relation_values::dsl::*;
+        let rows: Vec<(PropertyId, ObjectId)> = relation_values
+            .filter(object_id.eq(object))
+            .select((property_id, target_id))
+            .load(conn)
+            .map_err(|e| db_error("db select object relation values error", e))?;
+        for (pid, target) in rows {
+            let input = inputs.entry(pid).or_insert_with(String::new);
+            if !input.is_empty() {
+                input.push_str(", ");
+            }
+            input.push_str(&target.to_string());
+        }
+    }
+
+    let selected: HashSet<(PropertyId, SelectChoiceId)> = {
+        use schema::choice_values::dsl::*;
+        choice_values
+            .filter(object_id.eq(object))
+            .select((property_id, value_id))
+            .load::<(PropertyId, SelectChoiceId)>(conn)
+            .map_err(|e| db_error("db select object choice values error", e))?
+            .into_iter()
+            .collect()
+    };
+    let mut choices: HashMap<PropertyId, Vec<FieldChoice>> = HashMap::new();
+    {
+        use schema::property_value_choices::dsl::*;
+        let rows: Vec<(SelectChoiceId, PropertyId, String)> = property_value_choices
+            .order(display.asc())
+            .select((id, property_id, display))
+            .load(conn)
+            .map_err(|e| db_error("db select property value choices error", e))?;
+        for (choice_id, pid, choice_display) in rows {
+            let is_selected = selected.contains(&(pid.clone(), choice_id.clone()));
+            choices.entry(pid).or_insert_with(Vec::new).push(FieldChoice {
+                id: choice_id,
+                display: choice_display,
+                selected: is_selected,
+            });
+        }
+    }
+
+    properties
+        .into_iter()
+        .map(|property| {
+            let pid = property.id();
+            Ok(ObjectField {
+                display: property.display().to_string(),
+                kind: property.kind().name(),
+                rules: validation_rules(conn, &pid)?,
+                input: inputs.remove(&pid).unwrap_or_default(),
+                choices: choices.remove(&pid).unwrap_or_default(),
+                property_id: pid,
+            })
+        })
+        .collect()
+}
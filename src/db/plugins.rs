@@ -0,0 +1,242 @@
+//! Plugins, which are users of their own that sign in with API tokens
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, Utc};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use super::api_tokens::{create_api_token, CreateApiToken};
+use super::models::NewUser;
+use super::schema;
+use super::{db_error, transaction, DbExecutor};
+use crate::sessions::TokenScope;
+use crate::user::{Plugin, UserId, UserKind};
+
+/// Create a plugin along with its user and a first token, responding with
+/// the plugin and the token's secret
+pub struct RegisterPlugin {
+    /// Lowercase letters, digits and dashes, e.g. `ocr`
+    pub name: String,
+    /// How the plugin's changes are attributed, e.g. `OCR`
+    pub display_name: String,
+    pub description: String,
+    pub homepage: Option<String>,
+    pub scope: TokenScope,
+    pub registered_by: UserId,
+}
+
+impl Message for RegisterPlugin {
+    type Result = Result<(Plugin, String)>;
+}
+
+fn valid_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .map_or(false, |first| first.is_ascii_lowercase() || first.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+impl Handler<RegisterPlugin> for DbExecutor {
+    type Result = Result<(Plugin, String)>;
+
+    fn handle(&mut self, msg: RegisterPlugin, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        if !valid_name(&msg.name) {
+            return Err(error::ErrorBadRequest(
+                "A plugin's name is lowercase letters, digits and dashes",
+            ));
+        }
+        if msg.display_name.trim().is_empty() {
+            return Err(error::ErrorBadRequest("A plugin needs a display name"));
+        }
+
+        transaction(&conn, || {
+            if plugin_user(&conn, &msg.name)?.is_some() {
+                return Err(error::ErrorConflict(format!(
+                    "The plugin {} is registered already",
+                    msg.name
+                )));
+            }
+            let plugin_kind = UserKind::Plugin;
+            let user: UserId = insert_into(schema::users::table)
+                .values(&NewUser {
+                    google_resource_id: None,
+                    full_name: msg.display_name.trim(),
+                    display_name: msg.display_name.trim(),
+                    public_email: None,
+                    kind: &plugin_kind,
+                    photo_url: None,
+                })
+                .returning(schema::users::id)
+                .get_result(&conn)
+                .map_err(|e| db_error("db insert plugin user error", e))?;
+            {
+                use schema::plugins::dsl::*;
+                insert_into(plugins)
+                    .values((
+                        user_id.eq(&user),
+                        name.eq(&msg.name),
+                        description.eq(&msg.description),
+                        homepage.eq(&msg.homepage),
+                        registered_by.eq(&msg.registered_by),
+                    ))
+                    .execute(&conn)
+                    .map_err(|e| db_error("db insert plugin error", e))?;
+            }
+            let (_, secret) = create_api_token(
+                &conn,
+                &CreateApiToken {
+                    user_id: user.clone(),
+                    display: format!("{} registration", msg.name),
+                    scope: msg.scope,
+                    expires_at: None,
+                },
+            )?;
+            Ok((find_plugin(&conn, &user)?, secret))
+        })
+    }
+}
+
+fn find_plugin(conn: &PgConnection, user: &UserId) -> Result<Plugin> {
+    use schema::{plugins, users};
+
+    plugins::table
+        .inner_join(users::table)
+        .filter(plugins::user_id.eq(user))
+        .select((
+            plugins::user_id,
+            plugins::name,
+            users::display_name,
+            plugins::description,
+            plugins::homepage,
+            plugins::registered_by,
+            plugins::registered_at,
+            plugins::disabled_at,
+        ))
+        .get_result(conn)
+        .map_err(|e| db_error("db select plugin error", e))
+}
+
+/// The user of the plugin called `name`, and whether it is enabled
+fn plugin_user(conn: &PgConnection, plugin_name: &str) -> Result<Option<(UserId, bool)>> {
+    use schema::plugins::dsl::*;
+
+    plugins
+        .filter(name.eq(plugin_name))
+        .select((user_id, disabled_at))
+        .get_result::<(UserId, Option<DateTime<Utc>>)>(conn)
+        .optional()
+        .map(|found| found.map(|(user, disabled)| (user, disabled.is_none())))
+        .map_err(|e| db_error("db select plugin user error", e))
+}
+
+/// Every plugin, by name
+pub struct ListPlugins;
+
+impl Message for ListPlugins {
+    type Result = Result<Vec<Plugin>>;
+}
+
+impl Handler<ListPlugins> for DbExecutor {
+    type Result = Result<Vec<Plugin>>;
+
+    fn handle(&mut self, _: ListPlugins, _: &mut Self::Context) -> Self::Result {
+        use schema::{plugins, users};
+        let conn = self.0.get().unwrap();
+
+        plugins::table
+            .inner_join(users::table)
+            .order(plugins::name.asc())
+            .select((
+                plugins::user_id,
+                plugins::name,
+                users::display_name,
+                plugins::description,
+                plugins::homepage,
+                plugins::registered_by,
+                plugins::registered_at,
+                plugins::disabled_at,
+            ))
+            .load(&conn)
+            .map_err(|e| db_error("db select plugins error", e))
+    }
+}
+
+/// Another token for an enabled plugin, e.g. to rotate its secret
+pub struct IssuePluginToken {
+    pub name: String,
+    pub display: String,
+    pub scope: TokenScope,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Message for IssuePluginToken {
+    type Result = Result<String>;
+}
+
+impl Handler<IssuePluginToken> for DbExecutor {
+    type Result = Result<String>;
+
+    fn handle(&mut self, msg: IssuePluginToken, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        match plugin_user(&conn, &msg.name)? {
+            Some((user_id, true)) => create_api_token(
+                &conn,
+                &CreateApiToken {
+                    user_id,
+                    display: msg.display,
+                    scope: msg.scope,
+                    expires_at: msg.expires_at,
+                },
+            )
+            .map(|(_, secret)| secret),
+            Some((_, false)) => Err(error::ErrorConflict(format!(
+                "The plugin {} is disabled",
+                msg.name
+            ))),
+            None => Err(error::ErrorNotFound(format!("No plugin {}", msg.name))),
+        }
+    }
+}
+
+/// Revoke every token of a plugin and refuse it new ones. Its changes stay
+/// attributed to it.
+pub struct DisablePlugin {
+    pub name: String,
+}
+
+impl Message for DisablePlugin {
+    type Result = Result<()>;
+}
+
+impl Handler<DisablePlugin> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: DisablePlugin, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        let user = match plugin_user(&conn, &msg.name)? {
+            Some((user, _)) => user,
+            None => return Err(error::ErrorNotFound(format!("No plugin {}", msg.name))),
+        };
+        transaction(&conn, || {
+            {
+                use schema::api_tokens::dsl::*;
+                diesel::delete(api_tokens.filter(user_id.eq(&user)))
+                    .execute(&conn)
+                    .map_err(|e| db_error("db delete plugin tokens error", e))?;
+            }
+            use schema::plugins::dsl::*;
+            diesel::update(plugins.filter(user_id.eq(&user)))
+                .set(disabled_at.eq(Utc::now()))
+                .execute(&conn)
+                .map(|_| ())
+                .map_err(|e| db_error("db disable plugin error", e))
+        })
+    }
+}
@@ -0,0 +1,225 @@
+//! Properties, their choices, and the collections objects are filed into
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, Utc};
+use diesel::insert_into;
+use diesel::prelude::*;
+
+use super::objects::all_properties;
+use super::schema;
+use super::{db_error, DbExecutor};
+use crate::property::{PropertyId, PropertySchemaId, PropertyType, SelectChoiceId};
+use crate::user::UserId;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PropertyInfo {
+    pub id: PropertyId,
+    pub display: String,
+    pub kind: PropertyType,
+    pub formula: Option<String>,
+    pub inverse_display: Option<String>,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Every property in `ord` order
+pub struct ListProperties;
+
+impl Message for ListProperties {
+    type Result = Result<Vec<PropertyInfo>>;
+}
+
+impl Handler<ListProperties> for DbExecutor {
+    type Result = Result<Vec<PropertyInfo>>;
+
+    fn handle(&mut self, _: ListProperties, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        Ok(all_properties(&conn)?
+            .into_iter()
+            .map(|row| PropertyInfo {
+                id: row.id,
+                display: row.display,
+                kind: row.property_type,
+                formula: row.formula,
+                inverse_display: row.inverse_display,
+                created_by: row.created_by,
+                created_at: row.created_at,
+            })
+            .collect())
+    }
+}
+
+/// Create a text, timestamp, choice or relation property, which comes after
+/// every existing property. Computed properties need `CreateComputedProperty`.
+pub struct CreateProperty {
+    pub display: String,
+    pub kind: PropertyType,
+    /// How relation properties read from the target's side
+    pub inverse_display: Option<String>,
+    pub created_by: UserId,
+}
+
+impl Message for CreateProperty {
+    type Result = Result<PropertyId>;
+}
+
+impl Handler<CreateProperty> for DbExecutor {
+    type Result = Result<PropertyId>;
+
+    fn handle(&mut self, msg: CreateProperty, _: &mut Self::Context) -> Self::Result {
+        use diesel::dsl::max;
+        use schema::properties::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        if msg.kind == PropertyType::Computed {
+            return Err(error::ErrorBadRequest(
+                "Computed properties are created with a formula",
+            ));
+        }
+        if msg.display.trim().is_empty() {
+            return Err(error::ErrorBadRequest("A property needs a display name"));
+        }
+
+        let last: Option<f32> = properties
+            .select(max(ord))
+            .get_result(&conn)
+            .map_err(|e| db_error("db select last property ord error", e))?;
+        insert_into(properties)
+            .values((
+                display.eq(msg.display.trim()),
+                created_by.eq(&msg.created_by),
+                ord.eq(last.unwrap_or(0.0) + 1.0),
+                property_type.eq(msg.kind.clone()),
+                inverse_display.eq(&msg.inverse_display),
+            ))
+            .returning(id)
+            .get_result(&conn)
+            .map_err(|e| db_error("db insert property error", e))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Choice {
+    pub id: SelectChoiceId,
+    pub property_id: PropertyId,
+    pub display: String,
+}
+
+/// The choices of a choice property, alphabetically
+pub struct ListChoices {
+    pub property_id: PropertyId,
+}
+
+impl Message for ListChoices {
+    type Result = Result<Vec<Choice>>;
+}
+
+impl Handler<ListChoices> for DbExecutor {
+    type Result = Result<Vec<Choice>>;
+
+    fn handle(&mut self, msg: ListChoices, _: &mut Self::Context) -> Self::Result {
+        use schema::property_value_choices::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        property_value_choices
+            .filter(property_id.eq(&msg.property_id))
+            .order(display.asc())
+            .select((id, property_id, display))
+            .load(&conn)
+            .map_err(|e| db_error("db select choices error", e))
+    }
+}
+
+/// Add a choice to a choice property, e.g. a new collection
+pub struct CreateChoice {
+    pub property_id: PropertyId,
+    pub display: String,
+    pub created_by: UserId,
+}
+
+impl Message for CreateChoice {
+    type Result = Result<SelectChoiceId>;
+}
+
+impl Handler<CreateChoice> for DbExecutor {
+    type Result = Result<SelectChoiceId>;
+
+    fn handle(&mut self, msg: CreateChoice, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        let kind: Option<PropertyType> = {
+            use schema::properties::dsl::*;
+            properties
+                .filter(id.eq(&msg.property_id))
+                .select(property_type)
+                .get_result(&conn)
+                .optional()
+                .map_err(|e| db_error("db select choice property type error", e))?
+        };
+        match kind {
+            Some(PropertyType::Choice) => {}
+            Some(_) => {
+                return Err(error::ErrorBadRequest(format!(
+                    "Property {} does not have choices",
+                    msg.property_id
+                )))
+            }
+            None => {
+                return Err(error::ErrorNotFound(format!(
+                    "No property {}",
+                    msg.property_id
+                )))
+            }
+        }
+        if msg.display.trim().is_empty() {
+            return Err(error::ErrorBadRequest("A choice needs a display name"));
+        }
+
+        use schema::property_value_choices::dsl::*;
+        insert_into(property_value_choices)
+            .values((
+                property_id.eq(&msg.property_id),
+                display.eq(msg.display.trim()),
+                created_by.eq(&msg.created_by),
+            ))
+            .returning(id)
+            .get_result(&conn)
+            .map_err(|e| db_error("db insert choice error", e))
+    }
+}
+
+/// A choice of the collection property, and the schema its objects are given
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Collection {
+    pub id: SelectChoiceId,
+    pub display: String,
+    pub schema_id: Option<PropertySchemaId>,
+}
+
+pub struct ListCollections;
+
+impl Message for ListCollections {
+    type Result = Result<Vec<Collection>>;
+}
+
+impl Handler<ListCollections> for DbExecutor {
+    type Result = Result<Vec<Collection>>;
+
+    fn handle(&mut self, _: ListCollections, _: &mut Self::Context) -> Self::Result {
+        use schema::{collection_schemas, property_value_choices};
+        let conn = self.0.get().unwrap();
+
+        property_value_choices::table
+            .left_join(collection_schemas::table)
+            .filter(property_value_choices::property_id.eq(PropertyId::COLLECTION))
+            .order(property_value_choices::display.asc())
+            .select((
+                property_value_choices::id,
+                property_value_choices::display,
+                collection_schemas::schema_id.nullable(),
+            ))
+            .load(&conn)
+            .map_err(|e| db_error("db select collections error", e))
+    }
+}
@@ -0,0 +1,288 @@
+//! Finding objects with the query language
+use ::actix::prelude::*;
+use actix_web::Result;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::collections::HashMap;
+
+use super::objects::all_properties;
+use super::schema::{computed_values, objects, text_values, timestamptz_values};
+use super::{db_error, DbExecutor};
+use crate::object::{ObjectId, ObjectRow};
+use crate::property::{Property, PropertyId, PropertyType};
+use crate::query::{compile, Catalog, Cursor, Field, Query, QueryError, Sort};
+
+/// One page of the objects matching a query. Continuing `after` the cursor
+/// of the previous page keeps the pages consistent while objects are added,
+/// otherwise pages are numbered.
+pub struct QueryObjects {
+    pub query: Query,
+    pub sort: Sort,
+    /// Starting from 1, ignored when continuing `after` a cursor
+    pub page: i64,
+    pub per_page: i64,
+    pub after: Option<Cursor>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ObjectSummary {
+    pub id: ObjectId,
+    pub filename: Option<String>,
+    pub extension: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ObjectPage {
+    pub objects: Vec<ObjectSummary>,
+    /// Number of matching objects across every page
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    /// Continues after the last object of this page, `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+pub enum QueryObjectsResult {
+    Found(ObjectPage),
+    Invalid(QueryError),
+}
+
+impl Message for QueryObjects {
+    type Result = Result<QueryObjectsResult>;
+}
+
+/// Properties and choices to resolve query fields and values against
+pub fn catalog(conn: &PgConnection) -> Result<Catalog> {
+    use super::schema::property_value_choices::dsl::*;
+
+    Ok(Catalog {
+        properties: all_properties(conn)?,
+        choices: property_value_choices
+            .select((id, property_id, display))
+            .load(conn)
+            .map_err(|e| db_error("db select property value choices error", e))?,
+    })
+}
+
+fn invalid_sort(message: String) -> Result<QueryObjectsResult> {
+    Ok(QueryObjectsResult::Invalid(QueryError::new(0, message)))
+}
+
+/// Load a page of `$query` ordered by `$column` of `objects`, whose values are `$key_type`
+/// and read from rows by `$key_of`. Evaluates to the rows and the cursor after the last one.
+macro_rules! page_by_column {
+    ($query:expr, $column:expr, $key_type:ty, $key_of:expr, $msg:expr, $conn:expr) => {{
+        let descending = $msg.sort.descending;
+        let mut query = $query;
+        if let Some(ref cursor) = $msg.after {
+            let key = match cursor.key_for::<$key_type>(&$msg.sort) {
+                Ok(Some(key)) => key,
+                Ok(None) => return invalid_sort("invalid cursor".to_string()),
+                Err(e) => return Ok(QueryObjectsResult::Invalid(e)),
+            };
+            let after = cursor.id.clone();
+            query = if descending {
+                query.filter(
+                    $column
+                        .lt(key.clone())
+                        .or($column.eq(key).and(objects::id.lt(after))),
+                )
+            } else {
+                query.filter(
+                    $column
+                        .gt(key.clone())
+                        .or($column.eq(key).and(objects::id.gt(after))),
+                )
+            };
+        }
+        let ordered = if descending {
+            query.order(($column.desc(), objects::id.desc()))
+        } else {
+            query.order(($column.asc(), objects::id.asc()))
+        };
+        let paged = match $msg.after {
+            Some(_) => ordered,
+            None => ordered.offset(($msg.page - 1) * $msg.per_page),
+        };
+        paged
+            .limit($msg.per_page)
+            .load::<ObjectRow>($conn)
+            .map(|rows| {
+                let cursor = rows.last().map(|row| {
+                    let key: $key_type = $key_of(row);
+                    Cursor::new(&$msg.sort, Some(key), row.id.clone())
+                });
+                (rows, cursor)
+            })
+    }};
+}
+
+/// Load a page of `$matching` objects ordered by their value of property `$pid` in `$table`,
+/// whose values are `$key_type`. Objects without a value come last in ascending order,
+/// and first in descending order. Evaluates to the rows and the cursor after the last one.
+macro_rules! page_by_value {
+    ($table:ident, $key_type:ty, $pid:expr, $matching:expr, $msg:expr, $conn:expr) => {{
+        let descending = $msg.sort.descending;
+        let mut query = objects::table
+            .left_join(
+                $table::table.on($table::object_id
+                    .eq(objects::id)
+                    .and($table::property_id.eq($pid))),
+            )
+            .filter(objects::id.eq_any($matching))
+            .select((objects::all_columns, $table::value.nullable()))
+            .into_boxed();
+        if let Some(ref cursor) = $msg.after {
+            let key = match cursor.key_for::<$key_type>(&$msg.sort) {
+                Ok(key) => key,
+                Err(e) => return Ok(QueryObjectsResult::Invalid(e)),
+            };
+            let after = cursor.id.clone();
+            let value = $table::value.nullable();
+            query = match (key, descending) {
+                (Some(key), false) => query.filter(
+                    value
+                        .gt(key.clone())
+                        .or(value.eq(key).and(objects::id.gt(after)))
+                        .or(value.is_null()),
+                ),
+                (None, false) => query.filter(value.is_null().and(objects::id.gt(after))),
+                (Some(key), true) => query.filter(
+                    value
+                        .lt(key.clone())
+                        .or(value.eq(key).and(objects::id.lt(after))),
+                ),
+                (None, true) => query.filter(
+                    value
+                        .is_null()
+                        .and(objects::id.lt(after))
+                        .or(value.is_not_null()),
+                ),
+            };
+        }
+        let ordered = if descending {
+            query.order(($table::value.desc(), objects::id.desc()))
+        } else {
+            query.order(($table::value.asc(), objects::id.asc()))
+        };
+        let paged = match $msg.after {
+            Some(_) => ordered,
+            None => ordered.offset(($msg.page - 1) * $msg.per_page),
+        };
+        paged
+            .limit($msg.per_page)
+            .load::<(ObjectRow, Option<$key_type>)>($conn)
+            .map(|rows| {
+                let cursor = rows
+                    .last()
+                    .map(|(row, key)| Cursor::new(&$msg.sort, key.clone(), row.id.clone()));
+                (rows.into_iter().map(|(row, _)| row).collect::<Vec<_>>(), cursor)
+            })
+    }};
+}
+
+impl Handler<QueryObjects> for DbExecutor {
+    type Result = Result<QueryObjectsResult>;
+
+    fn handle(&mut self, msg: QueryObjects, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let catalog = catalog(&conn)?;
+        let matching = || compile(&catalog, &msg.query);
+
+        let total = match matching() {
+            Ok(query) => query
+                .count()
+                .get_result::<i64>(&conn)
+                .map_err(|e| db_error("db count query objects error", e))?,
+            Err(e) => return Ok(QueryObjectsResult::Invalid(e)),
+        };
+
+        let sort_field = match catalog.field(Some(msg.sort.field.as_str()), 0) {
+            Ok(field) => field,
+            Err(e) => return invalid_sort(format!("cannot sort, {}", e.message)),
+        };
+        let ids = matching().map_err(|e| db_error("db compile query error", e))?;
+
+        let (rows, last) = match sort_field {
+            Field::Id => page_by_column!(
+                ids,
+                objects::id,
+                String,
+                |row: &ObjectRow| row.id.to_string(),
+                msg,
+                &conn
+            ),
+            Field::Extension => page_by_column!(
+                ids,
+                objects::extension,
+                String,
+                |row: &ObjectRow| row.extension.clone(),
+                msg,
+                &conn
+            ),
+            Field::Created => page_by_column!(
+                ids,
+                objects::created_at,
+                DateTime<Utc>,
+                |row: &ObjectRow| row.created_at,
+                msg,
+                &conn
+            ),
+            Field::Property(property) => {
+                let pid = property.id();
+                let ids = ids.select(objects::id);
+                match property.kind() {
+                    PropertyType::Text => {
+                        page_by_value!(text_values, String, pid, ids, msg, &conn)
+                    }
+                    PropertyType::Timestamptz => {
+                        page_by_value!(timestamptz_values, DateTime<Utc>, pid, ids, msg, &conn)
+                    }
+                    PropertyType::Computed => {
+                        page_by_value!(computed_values, f64, pid, ids, msg, &conn)
+                    }
+                    PropertyType::Choice | PropertyType::Relation => {
+                        return invalid_sort(format!("cannot sort by {}", property.display()));
+                    }
+                }
+            }
+            Field::Related => return invalid_sort("cannot sort by related".to_string()),
+        }
+        .map_err(|e| db_error("db select query objects error", e))?;
+        let next_cursor = if rows.len() as i64 == msg.per_page {
+            last.map(|cursor| cursor.encode())
+        } else {
+            None
+        };
+
+        let filenames: HashMap<ObjectId, String> = {
+            use super::schema::text_values::dsl::*;
+            text_values
+                .filter(property_id.eq(PropertyId::FILENAME))
+                .filter(object_id.eq_any(rows.iter().map(|row| &row.id).collect::<Vec<_>>()))
+                .select((object_id, value))
+                .load::<(ObjectId, String)>(&conn)
+                .map_err(|e| db_error("db select query object filenames error", e))?
+                .into_iter()
+                .collect()
+        };
+
+        Ok(QueryObjectsResult::Found(ObjectPage {
+            objects: rows
+                .into_iter()
+                .map(|row| ObjectSummary {
+                    filename: filenames.get(&row.id).cloned(),
+                    id: row.id,
+                    extension: row.extension,
+                    created_at: row.created_at,
+                })
+                .collect(),
+            total,
+            page: msg.page,
+            per_page: msg.per_page,
+            next_cursor,
+        }))
+    }
+}
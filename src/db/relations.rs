@@ -0,0 +1,180 @@
+//! Navigating the graph of `relation_values` between objects
+use ::actix::prelude::*;
+use actix_web::Result;
+use diesel::prelude::*;
+use diesel::sql_types::{Int4, Nullable, Text};
+use diesel::PgConnection;
+
+use super::{db_error, DbExecutor};
+use crate::object::ObjectId;
+use crate::property::PropertyId;
+
+/// The most hops a traversal may follow
+pub const MAX_HOPS: i32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelationDirection {
+    /// The object relates to the other object
+    Outgoing,
+    /// The other object relates to the object
+    Incoming,
+}
+
+/// A relation from the point of view of one of its objects
+#[derive(Debug, Clone, Serialize)]
+pub struct Relation {
+    pub property_id: PropertyId,
+    pub direction: RelationDirection,
+    /// The property's name for outgoing relations, its inverse name for incoming
+    pub label: String,
+    pub object_id: ObjectId,
+    pub filename: Option<String>,
+}
+
+#[derive(QueryableByName)]
+struct RelationRow {
+    #[sql_type = "diesel::sql_types::Int8"]
+    property_id: PropertyId,
+    #[sql_type = "Text"]
+    display: String,
+    #[sql_type = "Nullable<Text>"]
+    inverse_display: Option<String>,
+    #[sql_type = "Text"]
+    other_id: ObjectId,
+    #[sql_type = "Nullable<Text>"]
+    filename: Option<String>,
+}
+
+fn load_relations(
+    conn: &PgConnection,
+    object: &ObjectId,
+    direction: RelationDirection,
+) -> Result<Vec<Relation>> {
+    let (this_end, other_end) = match direction {
+        RelationDirection::Outgoing => ("object_id", "target_id"),
+        RelationDirection::Incoming => ("target_id", "object_id"),
+    };
+    let query = format!(
+        r#"
+        SELECT relation_values.property_id, properties.display, properties.inverse_display,
+               relation_values.{other} AS other_id, text_values.value AS filename
+        FROM relation_values
+        INNER JOIN properties ON properties.id = relation_values.property_id
+        LEFT JOIN text_values ON text_values.object_id = relation_values.{other}
+                             AND text_values.property_id = $2
+        WHERE relation_values.{this} = $1
+        ORDER BY properties.ord, filename
+        "#,
+        this = this_end,
+        other = other_end,
+    );
+
+    let rows: Vec<RelationRow> = diesel::sql_query(query)
+        .bind::<Text, _>(object)
+        .bind::<diesel::sql_types::Int8, _>(PropertyId::FILENAME)
+        .load(conn)
+        .map_err(|e| db_error("db select relations error", e))?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Relation {
+            label: match direction {
+                RelationDirection::Outgoing => row.display,
+                RelationDirection::Incoming => match row.inverse_display {
+                    Some(inverse) => inverse,
+                    None => format!("{} (inverse)", row.display),
+                },
+            },
+            property_id: row.property_id,
+            direction,
+            object_id: row.other_id,
+            filename: row.filename,
+        })
+        .collect())
+}
+
+/// Outgoing then incoming relations of an object
+pub fn object_relations(conn: &PgConnection, object: &ObjectId) -> Result<Vec<Relation>> {
+    let mut relations = load_relations(conn, object, RelationDirection::Outgoing)?;
+    relations.extend(load_relations(conn, object, RelationDirection::Incoming)?);
+    Ok(relations)
+}
+
+pub struct GetRelations(pub ObjectId);
+
+impl Message for GetRelations {
+    type Result = Result<Vec<Relation>>;
+}
+
+impl Handler<GetRelations> for DbExecutor {
+    type Result = Result<Vec<Relation>>;
+
+    fn handle(&mut self, msg: GetRelations, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        object_relations(&conn, &msg.0)
+    }
+}
+
+/// An object reachable within some number of hops, in either direction
+#[derive(Debug, Clone, Serialize, QueryableByName)]
+pub struct RelatedObject {
+    #[sql_type = "Text"]
+    pub object_id: ObjectId,
+    /// Fewest relations between the origin and this object
+    #[sql_type = "Int4"]
+    pub depth: i32,
+    #[sql_type = "Nullable<Text>"]
+    pub filename: Option<String>,
+}
+
+/// Objects reachable from an object by following up to `hops` relations
+pub struct TraverseRelations {
+    pub object_id: ObjectId,
+    pub hops: i32,
+}
+
+impl Message for TraverseRelations {
+    type Result = Result<Vec<RelatedObject>>;
+}
+
+impl Handler<TraverseRelations> for DbExecutor {
+    type Result = Result<Vec<RelatedObject>>;
+
+    fn handle(&mut self, msg: TraverseRelations, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        // `path` prevents walking in circles
+        diesel::sql_query(
+            r#"
+            WITH RECURSIVE graph(object_id, depth, path) AS (
+                SELECT $1::TEXT, 0, ARRAY[$1::TEXT]
+              UNION ALL
+                SELECT next.object_id, graph.depth + 1, graph.path || next.object_id
+                FROM graph
+                INNER JOIN relation_values
+                    ON graph.object_id IN (relation_values.object_id, relation_values.target_id)
+                CROSS JOIN LATERAL (
+                    SELECT CASE WHEN relation_values.object_id = graph.object_id
+                                THEN relation_values.target_id
+                                ELSE relation_values.object_id
+                           END AS object_id
+                ) next
+                WHERE graph.depth < $2 AND NOT next.object_id = ANY(graph.path)
+            )
+            SELECT graph.object_id, MIN(graph.depth) AS depth, MIN(text_values.value) AS filename
+            FROM graph
+            LEFT JOIN text_values ON text_values.object_id = graph.object_id
+                                 AND text_values.property_id = $3
+            WHERE graph.depth > 0
+            GROUP BY graph.object_id
+            ORDER BY depth, filename
+            "#,
+        )
+        .bind::<Text, _>(&msg.object_id)
+        .bind::<Int4, _>(msg.hops.clamp(1, MAX_HOPS))
+        .bind::<diesel::sql_types::Int8, _>(PropertyId::FILENAME)
+        .load(&conn)
+        .map_err(|e| db_error("db select traverse relations error", e))
+    }
+}
@@ -0,0 +1,429 @@
+//! Saved views, and the pages of objects they show
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, Utc};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::collections::{BTreeMap, HashMap};
+
+use super::query::{catalog, ObjectSummary, QueryObjects, QueryObjectsResult};
+use super::schema;
+use super::schema::objects;
+use super::{db_error, DbExecutor};
+use crate::object::ObjectId;
+use crate::property::{Property, PropertyId, PropertyRow, PropertyType, SelectChoiceId};
+use crate::query::{compile, parse, QueryError, SavedView, SavedViewId, Sort, ViewLayout};
+use crate::user::UserId;
+
+pub struct CreateSavedView {
+    pub display: String,
+    pub query: String,
+    pub columns: Vec<PropertyId>,
+    pub sort: String,
+    pub group_by: Option<PropertyId>,
+    pub layout: ViewLayout,
+    pub collection_id: Option<SelectChoiceId>,
+    pub created_by: UserId,
+}
+
+pub enum CreateSavedViewResult {
+    Created(SavedViewId),
+    Invalid(QueryError),
+}
+
+impl Message for CreateSavedView {
+    type Result = Result<CreateSavedViewResult>;
+}
+
+impl Handler<CreateSavedView> for DbExecutor {
+    type Result = Result<CreateSavedViewResult>;
+
+    fn handle(&mut self, msg: CreateSavedView, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let catalog = catalog(&conn)?;
+
+        // the view is checked as it will be run, so it fails now instead of then
+        if let Err(e) = parse(&msg.query).and_then(|q| compile(&catalog, &q).map(|_| ())) {
+            return Ok(CreateSavedViewResult::Invalid(e));
+        }
+        let sort_field = Sort::parse(&msg.sort).field;
+        if let Err(e) = catalog.field(Some(sort_field.as_str()), 0) {
+            return invalid_view(format!("cannot sort, {}", e.message));
+        }
+        for property in msg.columns.iter().chain(&msg.group_by) {
+            if !catalog.properties.iter().any(|p| &p.id == property) {
+                return invalid_view(format!("unknown property {}", property));
+            }
+        }
+
+        {
+            use schema::saved_views::dsl::*;
+            insert_into(saved_views)
+                .values((
+                    display.eq(&msg.display),
+                    query.eq(&msg.query),
+                    column_ids.eq(&msg.columns),
+                    sort.eq(&msg.sort),
+                    group_by.eq(&msg.group_by),
+                    layout.eq(msg.layout),
+                    collection_id.eq(&msg.collection_id),
+                    created_by.eq(&msg.created_by),
+                ))
+                .returning(id)
+                .get_result(&conn)
+                .map(CreateSavedViewResult::Created)
+                .map_err(|e| db_error("db insert saved view error", e))
+        }
+    }
+}
+
+fn invalid_view(message: String) -> Result<CreateSavedViewResult> {
+    Ok(CreateSavedViewResult::Invalid(QueryError::new(0, message)))
+}
+
+/// The user's personal views and every shared view, by name
+pub struct ListSavedViews {
+    pub user_id: UserId,
+}
+
+impl Message for ListSavedViews {
+    type Result = Result<Vec<SavedView>>;
+}
+
+impl Handler<ListSavedViews> for DbExecutor {
+    type Result = Result<Vec<SavedView>>;
+
+    fn handle(&mut self, msg: ListSavedViews, _: &mut Self::Context) -> Self::Result {
+        use schema::saved_views::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        saved_views
+            .filter(collection_id.is_not_null().or(created_by.eq(&msg.user_id)))
+            .order(display.asc())
+            .load(&conn)
+            .map_err(|e| db_error("db select saved views error", e))
+    }
+}
+
+fn visible_view(conn: &PgConnection, view: &SavedViewId, user: &UserId) -> Result<SavedView> {
+    use schema::saved_views::dsl::*;
+
+    saved_views
+        .filter(id.eq(view))
+        .get_result::<SavedView>(conn)
+        .optional()
+        .map_err(|e| db_error("db select saved view error", e))?
+        .filter(|found| found.visible_to(user))
+        .ok_or_else(|| error::ErrorNotFound(format!("No view {}", view)))
+}
+
+/// Delete a view, which only its creator may do
+pub struct DeleteSavedView {
+    pub view_id: SavedViewId,
+    pub user_id: UserId,
+}
+
+impl Message for DeleteSavedView {
+    type Result = Result<()>;
+}
+
+impl Handler<DeleteSavedView> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: DeleteSavedView, _: &mut Self::Context) -> Self::Result {
+        use schema::saved_views::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let view = visible_view(&conn, &msg.view_id, &msg.user_id)?;
+        if view.created_by != msg.user_id {
+            return Err(error::ErrorForbidden("Only its creator may delete a view"));
+        }
+        diesel::delete(saved_views.filter(id.eq(&msg.view_id)))
+            .execute(&conn)
+            .map(|_| ())
+            .map_err(|e| db_error("db delete saved view error", e))
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ViewColumn {
+    pub property_id: PropertyId,
+    pub display: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ViewRow {
+    pub object_id: ObjectId,
+    pub filename: Option<String>,
+    pub extension: String,
+    pub created_at: DateTime<Utc>,
+    /// Values of the view's columns, as displayed
+    pub cells: Vec<String>,
+}
+
+/// Rows with the same value of the view's `group_by` property
+#[derive(Debug, Serialize)]
+pub struct ViewGroup {
+    /// Empty for the rows without a value, or when the view is not grouped
+    pub label: String,
+    /// Number of objects in the group across every page
+    pub count: i64,
+    /// The group's rows on this page, which may be none
+    pub rows: Vec<ViewRow>,
+}
+
+/// A page of the objects a view shows
+#[derive(Debug, Serialize)]
+pub struct ViewPage {
+    pub view: SavedView,
+    pub columns: Vec<ViewColumn>,
+    /// Every group of the view, whichever page they have rows on, so they
+    /// are the same on every page
+    pub groups: Vec<ViewGroup>,
+    /// Number of objects in the view across every page
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+pub struct GetSavedViewPage {
+    pub view_id: SavedViewId,
+    pub user_id: UserId,
+    /// Starting from 1
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Message for GetSavedViewPage {
+    type Result = Result<ViewPage>;
+}
+
+impl Handler<GetSavedViewPage> for DbExecutor {
+    type Result = Result<ViewPage>;
+
+    fn handle(&mut self, msg: GetSavedViewPage, ctx: &mut Self::Context) -> Self::Result {
+        let (view, catalog) = {
+            let conn = self.0.get().unwrap();
+            (
+                visible_view(&conn, &msg.view_id, &msg.user_id)?,
+                catalog(&conn)?,
+            )
+        };
+        let properties = &catalog.properties;
+        let query = parse(&view.query).map_err(|e| error::ErrorBadRequest(e.to_string()))?;
+
+        let found = match Handler::<QueryObjects>::handle(
+            self,
+            QueryObjects {
+                query: query.clone(),
+                sort: Sort::parse(&view.sort),
+                page: msg.page,
+                per_page: msg.per_page,
+                after: None,
+            },
+            ctx,
+        )? {
+            QueryObjectsResult::Found(found) => found,
+            // properties the view uses may have been renamed since it was saved
+            QueryObjectsResult::Invalid(e) => return Err(error::ErrorBadRequest(e.to_string())),
+        };
+
+        let column_properties: Vec<&PropertyRow> = view
+            .columns
+            .iter()
+            .filter_map(|column| properties.iter().find(|p| &p.id == column))
+            .collect();
+        let group_property = view
+            .group_by
+            .as_ref()
+            .and_then(|group_by| properties.iter().find(|p| &p.id == group_by));
+        let mut shown = column_properties.clone();
+        shown.extend(group_property);
+
+        let conn = self.0.get().unwrap();
+        let object_ids: Vec<ObjectId> = found.objects.iter().map(|o| o.id.clone()).collect();
+        let mut values = display_values(&conn, &object_ids, &shown)?;
+
+        let mut groups: Vec<ViewGroup> = match group_property {
+            Some(property) => {
+                let matching = compile(&catalog, &query)
+                    .map_err(|e| error::ErrorBadRequest(e.to_string()))?
+                    .select(objects::id)
+                    .load::<ObjectId>(&conn)
+                    .map_err(|e| db_error("db select view group objects error", e))?;
+                group_counts(&conn, &matching, property)?
+            }
+            None => vec![(String::new(), found.total)],
+        }
+        .into_iter()
+        .map(|(label, count)| ViewGroup {
+            label,
+            count,
+            rows: Vec::new(),
+        })
+        .collect();
+        for ObjectSummary {
+            id,
+            filename,
+            extension,
+            created_at,
+        } in found.objects
+        {
+            let label = group_property
+                .and_then(|p| values.get(&(id.clone(), p.id())).cloned())
+                .unwrap_or_default();
+            let row = ViewRow {
+                cells: column_properties
+                    .iter()
+                    .map(|p| values.remove(&(id.clone(), p.id())).unwrap_or_default())
+                    .collect(),
+                object_id: id,
+                filename,
+                extension,
+                created_at,
+            };
+            // the object's value may have changed since the groups were counted
+            match groups.iter_mut().find(|group| group.label == label) {
+                Some(group) => group.rows.push(row),
+                None => groups.push(ViewGroup {
+                    label,
+                    count: 1,
+                    rows: vec![row],
+                }),
+            }
+        }
+
+        Ok(ViewPage {
+            columns: column_properties
+                .iter()
+                .map(|p| ViewColumn {
+                    property_id: p.id(),
+                    display: p.display().to_string(),
+                })
+                .collect(),
+            view,
+            groups,
+            total: found.total,
+            page: found.page,
+            per_page: found.per_page,
+        })
+    }
+}
+
+/// How many of `objects` have each value of `property`, by label with the
+/// objects without a value last
+fn group_counts(
+    conn: &PgConnection,
+    objects: &[ObjectId],
+    property: &PropertyRow,
+) -> Result<Vec<(String, i64)>> {
+    let mut labels = display_values(conn, objects, &[property])?;
+    let mut counts: BTreeMap<(bool, String), i64> = BTreeMap::new();
+    for object in objects {
+        let label = labels
+            .remove(&(object.clone(), property.id()))
+            .unwrap_or_default();
+        *counts.entry((label.is_empty(), label)).or_insert(0) += 1;
+    }
+    Ok(counts
+        .into_iter()
+        .map(|((_, label), count)| (label, count))
+        .collect())
+}
+
+/// The values of `properties` for each of `objects`, formatted for display
+pub fn display_values(
+    conn: &PgConnection,
+    objects: &[ObjectId],
+    properties: &[&PropertyRow],
+) -> Result<HashMap<(ObjectId, PropertyId), String>> {
+    let ids_of = |kind: PropertyType| -> Vec<PropertyId> {
+        properties
+            .iter()
+            .filter(|p| p.kind() == &kind)
+            .map(|p| p.id())
+            .collect()
+    };
+    let mut values: HashMap<(ObjectId, PropertyId), String> = HashMap::new();
+    let mut push = |object: ObjectId, property: PropertyId, value: String| {
+        let existing = values.entry((object, property)).or_insert_with(String::new);
+        if !existing.is_empty() {
+            existing.push_str(", ");
+        }
+        existing.push_str(&value);
+    };
+
+    {
+        use schema::text_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, String)> = text_values
+            .filter(object_id.eq_any(objects))
+            .filter(property_id.eq_any(ids_of(PropertyType::Text)))
+            .select((object_id, property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select view text values error", e))?;
+        for (object, property, text) in rows {
+            push(object, property, text);
+        }
+    }
+    {
+        use schema::timestamptz_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, Option<DateTime<Utc>>)> = timestamptz_values
+            .filter(object_id.eq_any(objects))
+            .filter(property_id.eq_any(ids_of(PropertyType::Timestamptz)))
+            .select((object_id, property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select view timestamptz values error", e))?;
+        for (object, property, timestamp) in rows {
+            if let Some(timestamp) = timestamp {
+                push(object, property, timestamp.format("%Y-%m-%d").to_string());
+            }
+        }
+    }
+    {
+        use schema::computed_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, Option<f64>)> = computed_values
+            .filter(object_id.eq_any(objects))
+            .filter(property_id.eq_any(ids_of(PropertyType::Computed)))
+            .select((object_id, property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select view computed values error", e))?;
+        for (object, property, number) in rows {
+            if let Some(number) = number {
+                push(object, property, format!("{:.2}", number));
+            }
+        }
+    }
+    {
+        use schema::choice_values;
+        use schema::property_value_choices;
+        let rows: Vec<(ObjectId, PropertyId, String)> = choice_values::table
+            .inner_join(property_value_choices::table)
+            .filter(choice_values::object_id.eq_any(objects))
+            .filter(choice_values::property_id.eq_any(ids_of(PropertyType::Choice)))
+            .order(property_value_choices::display.asc())
+            .select((
+                choice_values::object_id,
+                choice_values::property_id,
+                property_value_choices::display,
+            ))
+            .load(conn)
+            .map_err(|e| db_error("db select view choice values error", e))?;
+        for (object, property, choice) in rows {
+            push(object, property, choice);
+        }
+    }
+    {
+        use schema::relation_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, ObjectId)> = relation_values
+            .filter(object_id.eq_any(objects))
+            .filter(property_id.eq_any(ids_of(PropertyType::Relation)))
+            .select((object_id, property_id, target_id))
+            .load(conn)
+            .map_err(|e| db_error("db select view relation values error", e))?;
+        for (object, property, target) in rows {
+            push(object, property, target.to_string());
+        }
+    }
+    Ok(values)
+}
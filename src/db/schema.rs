@@ -1,5 +1,24 @@
-use crate::property::{PropertyType, PropertyTypeMapping};
-use crate::user::{UserKind, UserKindMapping};
+use crate::object::ExtractionStatusMapping;
+use crate::property::PropertyTypeMapping;
+use crate::query::ViewLayoutMapping;
+use crate::sessions::TokenScopeMapping;
+use crate::user::UserKindMapping;
+use crate::webhook::{DeliveryStatusMapping, WebhookEventMapping};
+
+table! {
+    use diesel::sql_types::{Int8, Nullable, Text, Timestamptz};
+    use super::TokenScopeMapping;
+    api_tokens (id) {
+        id -> Int8,
+        user_id -> Int8,
+        display -> Text,
+        secret_hash -> Text,
+        scope -> TokenScopeMapping,
+        expires_at -> Nullable<Timestamptz>,
+        last_used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
 
 table! {
     choice_values (object_id, property_id, value_id) {
@@ -11,17 +30,84 @@ table! {
     }
 }
 
+table! {
+    collection_schemas (collection_id) {
+        collection_id -> Int8,
+        schema_id -> Int8,
+    }
+}
+
+table! {
+    computed_values (object_id, property_id) {
+        object_id -> Text,
+        property_id -> Int8,
+        value -> Nullable<Float8>,
+        computed_at -> Timestamptz,
+    }
+}
+
+table! {
+    drop_box_tokens (id) {
+        id -> Int8,
+        user_id -> Int8,
+        collection_id -> Int8,
+        secret_hash -> Text,
+        last_used_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::{Int4, Nullable, Text, Timestamptz};
+    use super::ExtractionStatusMapping;
+    extracted_texts (object_id) {
+        object_id -> Text,
+        version -> Int4,
+        status -> ExtractionStatusMapping,
+        content -> Text,
+        error -> Nullable<Text>,
+        attempts -> Int4,
+        pages -> Nullable<Int4>,
+        updated_at -> Timestamptz,
+    }
+}
+
 table! {
     objects (id) {
         id -> Text,
         created_by -> Int8,
         created_at -> Timestamptz,
         extension -> Text,
+        current_version -> Nullable<Int4>,
     }
 }
 
 table! {
-    use diesel::sql_types::{Float4, Int8, Text, Timestamptz};
+    object_versions (object_id, version) {
+        object_id -> Text,
+        version -> Int4,
+        hash -> Text,
+        size -> Int8,
+        filename -> Text,
+        created_by -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    plugins (user_id) {
+        user_id -> Int8,
+        name -> Text,
+        description -> Text,
+        homepage -> Nullable<Text>,
+        registered_by -> Int8,
+        registered_at -> Timestamptz,
+        disabled_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    use diesel::sql_types::{Float4, Int8, Nullable, Text, Timestamptz};
     use super::PropertyTypeMapping;
     properties (id) {
         id -> Int8,
@@ -30,6 +116,22 @@ table! {
         ord -> Float4,
         display -> Text,
         property_type -> PropertyTypeMapping,
+        formula -> Nullable<Text>,
+        inverse_display -> Nullable<Text>,
+    }
+}
+
+table! {
+    property_validations (property_id) {
+        property_id -> Int8,
+        required -> Bool,
+        pattern -> Nullable<Text>,
+        min_length -> Nullable<Int4>,
+        max_length -> Nullable<Int4>,
+        min_number -> Nullable<Float8>,
+        max_number -> Nullable<Float8>,
+        multiple -> Bool,
+        target_extensions -> Nullable<Array<Text>>,
     }
 }
 
@@ -53,6 +155,43 @@ table! {
     }
 }
 
+table! {
+    use diesel::sql_types::{Array, Int8, Nullable, Text, Timestamptz};
+    use super::ViewLayoutMapping;
+    saved_views (id) {
+        id -> Int8,
+        display -> Text,
+        query -> Text,
+        #[sql_name = "columns"]
+        column_ids -> Array<Int8>,
+        sort -> Text,
+        group_by -> Nullable<Int8>,
+        layout -> ViewLayoutMapping,
+        collection_id -> Nullable<Int8>,
+        created_by -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    schema_properties (schema_id, property_id) {
+        schema_id -> Int8,
+        property_id -> Int8,
+        ord -> Float4,
+        required -> Bool,
+        default_value -> Nullable<Jsonb>,
+    }
+}
+
+table! {
+    schemas (id) {
+        id -> Int8,
+        display -> Text,
+        created_by -> Int8,
+        created_at -> Timestamptz,
+    }
+}
+
 table! {
     text_values (object_id, property_id) {
         object_id -> Text,
@@ -86,7 +225,7 @@ table! {
 }
 
 table! {
-    use diesel::sql_types::{Int8, Nullable, Text};
+    use diesel::sql_types::{Bool, Int8, Nullable, Text, Timestamptz};
     use super::UserKindMapping;
     users (id) {
         id -> Int8,
@@ -96,19 +235,82 @@ table! {
         public_email -> Nullable<Text>,
         kind -> UserKindMapping,
         photo_url -> Nullable<Text>,
+        admin -> Bool,
+        disabled_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    value_history (id) {
+        id -> Int8,
+        object_id -> Text,
+        property_id -> Int8,
+        old_value -> Nullable<Jsonb>,
+        new_value -> Nullable<Jsonb>,
+        changed_by -> Int8,
+        changed_at -> Timestamptz,
+        request_id -> Nullable<Text>,
+    }
+}
+
+table! {
+    use diesel::sql_types::{Int4, Int8, Jsonb, Nullable, Text, Timestamptz};
+    use super::{DeliveryStatusMapping, WebhookEventMapping};
+    webhook_deliveries (id) {
+        id -> Int8,
+        webhook_id -> Int8,
+        event -> WebhookEventMapping,
+        payload -> Jsonb,
+        status -> DeliveryStatusMapping,
+        attempts -> Int4,
+        next_attempt_at -> Timestamptz,
+        response_status -> Nullable<Int4>,
+        response_body -> Nullable<Text>,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        delivered_at -> Nullable<Timestamptz>,
+    }
+}
+
+table! {
+    webhooks (id) {
+        id -> Int8,
+        url -> Text,
+        secret -> Text,
+        events -> Array<Text>,
+        created_by -> Int8,
+        created_at -> Timestamptz,
     }
 }
 
 joinable!(choice_values -> objects (object_id));
 joinable!(choice_values -> properties (property_id));
 joinable!(choice_values -> property_value_choices (value_id));
+joinable!(api_tokens -> users (user_id));
 joinable!(choice_values -> users (created_by));
+joinable!(collection_schemas -> property_value_choices (collection_id));
+joinable!(collection_schemas -> schemas (schema_id));
+joinable!(computed_values -> objects (object_id));
+joinable!(computed_values -> properties (property_id));
+joinable!(drop_box_tokens -> property_value_choices (collection_id));
+joinable!(drop_box_tokens -> users (user_id));
+joinable!(extracted_texts -> objects (object_id));
+joinable!(object_versions -> objects (object_id));
+joinable!(object_versions -> users (created_by));
 joinable!(objects -> users (created_by));
+joinable!(plugins -> users (user_id));
 joinable!(properties -> users (created_by));
+joinable!(property_validations -> properties (property_id));
 joinable!(property_value_choices -> properties (property_id));
 joinable!(property_value_choices -> users (created_by));
 joinable!(relation_values -> properties (property_id));
 joinable!(relation_values -> users (created_by));
+joinable!(saved_views -> property_value_choices (collection_id));
+joinable!(saved_views -> properties (group_by));
+joinable!(saved_views -> users (created_by));
+joinable!(schema_properties -> properties (property_id));
+joinable!(schema_properties -> schemas (schema_id));
+joinable!(schemas -> users (created_by));
 joinable!(text_values -> objects (object_id));
 joinable!(text_values -> properties (property_id));
 joinable!(text_values -> users (created_by));
@@ -116,15 +318,31 @@ joinable!(timestamptz_values -> objects (object_id));
 joinable!(timestamptz_values -> properties (property_id));
 joinable!(timestamptz_values -> users (created_by));
 joinable!(user_tokens -> users (user_id));
+joinable!(webhook_deliveries -> webhooks (webhook_id));
+joinable!(webhooks -> users (created_by));
 
 allow_tables_to_appear_in_same_query!(
+    api_tokens,
     choice_values,
+    collection_schemas,
+    computed_values,
+    drop_box_tokens,
+    extracted_texts,
+    object_versions,
     objects,
+    plugins,
     properties,
+    property_validations,
     property_value_choices,
     relation_values,
+    saved_views,
+    schema_properties,
+    schemas,
     text_values,
     timestamptz_values,
     user_tokens,
     users,
+    value_history,
+    webhook_deliveries,
+    webhooks,
 );
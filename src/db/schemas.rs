@@ -0,0 +1,228 @@
+//! Property schemas and the collections which use them
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use super::schema;
+use super::values::{has_value, set_value, ValueUpdate};
+use super::{db_error, transaction, DbExecutor};
+use crate::object::ObjectId;
+use crate::property::{
+    PropertyId, PropertySchema, PropertySchemaField, PropertySchemaId, PropertyType,
+    PropertyValue, SelectChoiceId,
+};
+use crate::user::UserId;
+
+type SchemaFieldRow = (PropertyId, f32, bool, Option<serde_json::Value>);
+
+fn field_from_row(
+    (property_id, ord, required, default_value): SchemaFieldRow,
+) -> PropertySchemaField {
+    PropertySchemaField {
+        property_id,
+        ord,
+        required,
+        default_value: default_value.and_then(|json| {
+            serde_json::from_value(json)
+                .map_err(|e| warn!("Ignoring invalid schema default value: {}", e))
+                .ok()
+        }),
+    }
+}
+
+/// Fields of every schema used by the collections `object` is in, in `ord` order
+pub fn object_schema_fields(
+    conn: &PgConnection,
+    object: &ObjectId,
+) -> Result<Vec<PropertySchemaField>> {
+    use schema::choice_values;
+    use schema::collection_schemas;
+    use schema::schema_properties::dsl::*;
+
+    let collections = choice_values::table
+        .filter(choice_values::object_id.eq(object))
+        .filter(choice_values::property_id.eq(PropertyId::COLLECTION))
+        .select(choice_values::value_id);
+    let object_schemas = collection_schemas::table
+        .filter(collection_schemas::collection_id.eq_any(collections))
+        .select(collection_schemas::schema_id);
+
+    let rows = schema_properties
+        .filter(schema_id.eq_any(object_schemas))
+        .order(ord.asc())
+        .select((property_id, ord, required, default_value))
+        .load::<SchemaFieldRow>(conn)
+        .map_err(|e| db_error("db select object schema fields error", e))?;
+
+    let mut fields: Vec<PropertySchemaField> = Vec::new();
+    for field in rows.into_iter().map(field_from_row) {
+        // a property may be in more than one of the object's schemas
+        match fields.iter_mut().find(|f| f.property_id == field.property_id) {
+            Some(existing) => existing.required |= field.required,
+            None => fields.push(field),
+        }
+    }
+    Ok(fields)
+}
+
+/// Create the fields of the object's schemas which it does not have a value for yet
+pub fn apply_object_schemas(conn: &PgConnection, object: &ObjectId, user: &UserId) -> Result<()> {
+    for field in object_schema_fields(conn, object)? {
+        let kind: PropertyType = {
+            use schema::properties::dsl::*;
+            properties
+                .filter(id.eq(&field.property_id))
+                .select(property_type)
+                .get_result(conn)
+                .map_err(|e| db_error("db select schema property type error", e))?
+        };
+        if has_value(conn, object, &field.property_id, &kind)? {
+            continue;
+        }
+        let value = match (field.default_value, kind) {
+            (Some(value), _) => value,
+            (None, PropertyType::Text) => PropertyValue::Text(String::new()),
+            (None, PropertyType::Timestamptz) => PropertyValue::Timestamptz(None),
+            // choices, relations, and computed values have nothing to create
+            (None, _) => continue,
+        };
+        set_value(
+            conn,
+            object,
+            &ValueUpdate {
+                property_id: field.property_id,
+                value,
+            },
+            user,
+        )?;
+    }
+    Ok(())
+}
+
+pub struct CreatePropertySchema {
+    pub display: String,
+    pub fields: Vec<PropertySchemaField>,
+    pub created_by: UserId,
+}
+
+impl Message for CreatePropertySchema {
+    type Result = Result<PropertySchemaId>;
+}
+
+impl Handler<CreatePropertySchema> for DbExecutor {
+    type Result = Result<PropertySchemaId>;
+
+    fn handle(&mut self, msg: CreatePropertySchema, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        transaction(&conn, || {
+            let new_id: PropertySchemaId = {
+                use schema::schemas::dsl::*;
+                insert_into(schemas)
+                    .values((display.eq(&msg.display), created_by.eq(&msg.created_by)))
+                    .returning(id)
+                    .get_result(&conn)
+                    .map_err(|e| db_error("db insert schema error", e))?
+            };
+
+            for field in &msg.fields {
+                use schema::schema_properties::dsl::*;
+                let default_json = match field.default_value {
+                    Some(ref value) => Some(
+                        serde_json::to_value(value)
+                            .map_err(|e| error::ErrorBadRequest(e.to_string()))?,
+                    ),
+                    None => None,
+                };
+                insert_into(schema_properties)
+                    .values((
+                        schema_id.eq(&new_id),
+                        property_id.eq(&field.property_id),
+                        ord.eq(field.ord),
+                        required.eq(field.required),
+                        default_value.eq(default_json),
+                    ))
+                    .execute(&conn)
+                    .map_err(|e| db_error("db insert schema property error", e))?;
+            }
+            Ok(new_id)
+        })
+    }
+}
+
+pub struct ListPropertySchemas;
+
+impl Message for ListPropertySchemas {
+    type Result = Result<Vec<PropertySchema>>;
+}
+
+impl Handler<ListPropertySchemas> for DbExecutor {
+    type Result = Result<Vec<PropertySchema>>;
+
+    fn handle(&mut self, _: ListPropertySchemas, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+
+        let rows: Vec<(PropertySchemaId, String)> = {
+            use schema::schemas::dsl::*;
+            schemas
+                .order(display.asc())
+                .select((id, display))
+                .load(&conn)
+                .map_err(|e| db_error("db select schemas error", e))?
+        };
+
+        rows.into_iter()
+            .map(|(schema_row_id, schema_display)| {
+                use schema::schema_properties::dsl::*;
+                let fields = schema_properties
+                    .filter(schema_id.eq(&schema_row_id))
+                    .order(ord.asc())
+                    .select((property_id, ord, required, default_value))
+                    .load::<SchemaFieldRow>(&conn)
+                    .map_err(|e| db_error("db select schema properties error", e))?
+                    .into_iter()
+                    .map(field_from_row)
+                    .collect();
+                Ok(PropertySchema {
+                    id: schema_row_id,
+                    display: schema_display,
+                    fields,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Use a schema for a collection, or stop using one with `None`
+pub struct SetCollectionSchema {
+    pub collection_id: SelectChoiceId,
+    pub schema_id: Option<PropertySchemaId>,
+}
+
+impl Message for SetCollectionSchema {
+    type Result = Result<()>;
+}
+
+impl Handler<SetCollectionSchema> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: SetCollectionSchema, _: &mut Self::Context) -> Self::Result {
+        use schema::collection_schemas::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        match msg.schema_id {
+            Some(ref schema) => insert_into(collection_schemas)
+                .values((collection_id.eq(&msg.collection_id), schema_id.eq(schema)))
+                .on_conflict(collection_id)
+                .do_update()
+                .set(schema_id.eq(schema))
+                .execute(&conn),
+            None => diesel::delete(collection_schemas.filter(collection_id.eq(&msg.collection_id)))
+                .execute(&conn),
+        }
+        .map(|_| ())
+        .map_err(|e| db_error("db set collection schema error", e))
+    }
+}
@@ -0,0 +1,181 @@
+//! Full-text search over the text values of objects, indexed in `object_search`
+use ::actix::prelude::*;
+use actix_web::Result;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Float4, Nullable, Text};
+
+use super::{db_error, DbExecutor};
+use crate::object::ObjectId;
+
+/// Text search configurations objects can be indexed with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchLanguage {
+    #[serde(alias = "en")]
+    English,
+    #[serde(alias = "fi")]
+    Finnish,
+    #[serde(alias = "ja")]
+    Japanese,
+}
+
+impl SearchLanguage {
+    /// The Postgres text search configuration
+    pub fn config(self) -> &'static str {
+        match self {
+            SearchLanguage::English => "english",
+            SearchLanguage::Finnish => "finnish",
+            SearchLanguage::Japanese => "japanese",
+        }
+    }
+}
+
+/// `to_tsquery` input matching objects which contain every word, or words starting with them.
+/// `None` if there are no words to search for.
+pub fn prefix_tsquery(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word))
+        .collect();
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" & "))
+    }
+}
+
+#[derive(Debug, Serialize, QueryableByName)]
+pub struct SearchHit {
+    #[sql_type = "Text"]
+    pub object_id: ObjectId,
+    #[sql_type = "Nullable<Text>"]
+    pub filename: Option<String>,
+    #[sql_type = "Float4"]
+    pub rank: f32,
+    /// HTML escaped text around the matches, which are wrapped in `<mark>`
+    #[sql_type = "Text"]
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResults {
+    pub hits: Vec<SearchHit>,
+    /// Number of matching objects across every page
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+/// Best matching objects first. The words are matched in every language
+/// unless `language` limits the search to objects indexed in it.
+pub struct SearchObjects {
+    /// The output of `prefix_tsquery`
+    pub tsquery: String,
+    pub language: Option<SearchLanguage>,
+    /// Starting from 1
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Message for SearchObjects {
+    type Result = Result<SearchResults>;
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[sql_type = "BigInt"]
+    count: i64,
+}
+
+/// The query in every configuration, since objects are indexed in their own language
+const SEARCH_QUERY: &str = r#"
+    WITH q AS (
+        SELECT to_tsquery('english', $1)
+            || to_tsquery('finnish', $1)
+            || to_tsquery('japanese', $1) AS query
+    )
+"#;
+
+impl Handler<SearchObjects> for DbExecutor {
+    type Result = Result<SearchResults>;
+
+    fn handle(&mut self, msg: SearchObjects, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let language = msg.language.map(SearchLanguage::config);
+
+        let total = diesel::sql_query(format!(
+            r#"{}
+            SELECT count(*) AS count
+            FROM object_search s CROSS JOIN q
+            WHERE s.document @@ q.query
+              AND ($2::TEXT IS NULL OR s.search_config = $2::REGCONFIG)
+            "#,
+            SEARCH_QUERY
+        ))
+        .bind::<Text, _>(&msg.tsquery)
+        .bind::<Nullable<Text>, _>(language)
+        .get_result::<Count>(&conn)
+        .map_err(|e| db_error("db count search results error", e))?
+        .count;
+
+        let hits = diesel::sql_query(format!(
+            r#"{}
+            SELECT s.object_id, f.value AS filename,
+                   ts_rank_cd(s.document, q.query) AS rank,
+                   ts_headline(
+                       s.search_config,
+                       replace(replace(replace(s.content, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'),
+                       q.query,
+                       'StartSel=<mark>, StopSel=</mark>, MaxFragments=2, MaxWords=20, MinWords=5'
+                   ) AS snippet
+            FROM object_search s
+            CROSS JOIN q
+            LEFT JOIN text_values f ON f.object_id = s.object_id AND f.property_id = 1
+            WHERE s.document @@ q.query
+              AND ($2::TEXT IS NULL OR s.search_config = $2::REGCONFIG)
+            ORDER BY rank DESC, s.object_id
+            LIMIT $3 OFFSET $4
+            "#,
+            SEARCH_QUERY
+        ))
+        .bind::<Text, _>(&msg.tsquery)
+        .bind::<Nullable<Text>, _>(language)
+        .bind::<BigInt, _>(msg.per_page)
+        .bind::<BigInt, _>((msg.page - 1) * msg.per_page)
+        .load::<SearchHit>(&conn)
+        .map_err(|e| db_error("db select search results error", e))?;
+
+        Ok(SearchResults {
+            hits,
+            total,
+            page: msg.page,
+            per_page: msg.per_page,
+        })
+    }
+}
+
+/// Reindex an object's text in another language
+pub struct SetSearchLanguage {
+    pub object_id: ObjectId,
+    pub language: SearchLanguage,
+}
+
+impl Message for SetSearchLanguage {
+    type Result = Result<()>;
+}
+
+impl Handler<SetSearchLanguage> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: SetSearchLanguage, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        diesel::sql_query("SELECT set_search_config($1, $2::REGCONFIG)")
+            .bind::<Text, _>(&msg.object_id)
+            .bind::<Text, _>(msg.language.config())
+            .execute(&conn)
+            .map(|_| ())
+            .map_err(|e| db_error("db set search language error", e))
+    }
+}
@@ -0,0 +1,631 @@
+//! Objects' values as the rows of a spreadsheet, one column per property
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use diesel::prelude::*;
+use diesel::PgConnection;
+use std::collections::{HashMap, HashSet};
+
+use super::objects::{all_properties, object_values};
+use super::query::{QueryObjects, QueryObjectsResult};
+use super::schema;
+use super::values::{save_values, validate, ValueUpdate};
+use super::{db_error, DbExecutor, SetValuesResult};
+use crate::object::ObjectId;
+use crate::property::{
+    Property, PropertyId, PropertyRow, PropertyType, PropertyValue, SelectChoiceId,
+};
+use crate::query::{Cursor, Query, QueryError, Sort};
+use crate::user::UserId;
+
+/// Objects read from the query at a time while exporting
+const EXPORT_PAGE: i64 = 500;
+
+/// Header of the column of object ids
+const ID_COLUMN: &str = "id";
+
+/// A header row and the rows below it
+#[derive(Debug)]
+pub struct Spreadsheet {
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+/// The objects matching a query in `sort` order, each with its id and its
+/// value of every property. Choices are written by their names and several
+/// values of a property are joined by `delimiter`.
+pub struct ExportSpreadsheet {
+    pub query: Query,
+    pub sort: Sort,
+    pub delimiter: String,
+}
+
+pub enum ExportSpreadsheetResult {
+    Found(Spreadsheet),
+    Invalid(QueryError),
+}
+
+impl Message for ExportSpreadsheet {
+    type Result = Result<ExportSpreadsheetResult>;
+}
+
+impl Handler<ExportSpreadsheet> for DbExecutor {
+    type Result = Result<ExportSpreadsheetResult>;
+
+    fn handle(&mut self, msg: ExportSpreadsheet, ctx: &mut Self::Context) -> Self::Result {
+        // paging by cursor keeps the order and the rules of `GET /objects`
+        let mut ids = Vec::new();
+        let mut after = None;
+        loop {
+            let page = match Handler::<QueryObjects>::handle(
+                self,
+                QueryObjects {
+                    query: msg.query.clone(),
+                    sort: msg.sort.clone(),
+                    page: 1,
+                    per_page: EXPORT_PAGE,
+                    after,
+                },
+                ctx,
+            )? {
+                QueryObjectsResult::Found(page) => page,
+                QueryObjectsResult::Invalid(e) => return Ok(ExportSpreadsheetResult::Invalid(e)),
+            };
+            ids.extend(page.objects.into_iter().map(|object| object.id));
+            after = match page.next_cursor {
+                Some(cursor) => {
+                    Some(Cursor::decode(&cursor).map_err(error::ErrorInternalServerError)?)
+                }
+                None => break,
+            };
+        }
+
+        let conn = self.0.get().unwrap();
+        let properties = all_properties(&conn)?;
+        let mut cells = object_cells(&conn, &ids)?;
+
+        let mut header = vec![ID_COLUMN.to_string()];
+        header.extend(
+            properties
+                .iter()
+                .map(|property| property.display().to_string()),
+        );
+        let rows = ids
+            .into_iter()
+            .map(|object| {
+                let mut row = vec![object.to_string()];
+                for property in &properties {
+                    let values = cells
+                        .remove(&(object.clone(), property.id()))
+                        .unwrap_or_default();
+                    row.push(values.join(&msg.delimiter));
+                }
+                row
+            })
+            .collect();
+        Ok(ExportSpreadsheetResult::Found(Spreadsheet { header, rows }))
+    }
+}
+
+/// The values of `objects` as text, by object and property
+fn object_cells(
+    conn: &PgConnection,
+    objects: &[ObjectId],
+) -> Result<HashMap<(ObjectId, PropertyId), Vec<String>>> {
+    let mut cells: HashMap<(ObjectId, PropertyId), Vec<String>> = HashMap::new();
+    let mut add = |object: ObjectId, property: PropertyId, text: String| {
+        cells
+            .entry((object, property))
+            .or_insert_with(Vec::new)
+            .push(text)
+    };
+    {
+        use schema::text_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, String)> = text_values
+            .filter(object_id.eq_any(objects))
+            .select((object_id, property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select spreadsheet text values error", e))?;
+        for (object, property, text) in rows {
+            add(object, property, text);
+        }
+    }
+    {
+        use schema::timestamptz_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, Option<DateTime<Utc>>)> = timestamptz_values
+            .filter(object_id.eq_any(objects))
+            .select((object_id, property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select spreadsheet timestamptz values error", e))?;
+        for (object, property, timestamp) in rows {
+            if let Some(timestamp) = timestamp {
+                add(object, property, format_timestamp(&timestamp));
+            }
+        }
+    }
+    {
+        use schema::choice_values::dsl::*;
+        use schema::property_value_choices::dsl as choices;
+        let rows: Vec<(ObjectId, PropertyId, String)> = choice_values
+            .inner_join(choices::property_value_choices)
+            .filter(object_id.eq_any(objects))
+            .order((object_id.asc(), property_id.asc(), value_id.asc()))
+            .select((object_id, property_id, choices::display))
+            .load(conn)
+            .map_err(|e| db_error("db select spreadsheet choice values error", e))?;
+        for (object, property, display) in rows {
+            add(object, property, display);
+        }
+    }
+    {
+        use schema::relation_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, ObjectId)> = relation_values
+            .filter(object_id.eq_any(objects))
+            .order((object_id.asc(), property_id.asc(), target_id.asc()))
+            .select((object_id, property_id, target_id))
+            .load(conn)
+            .map_err(|e| db_error("db select spreadsheet relation values error", e))?;
+        for (object, property, target) in rows {
+            add(object, property, target.to_string());
+        }
+    }
+    {
+        use schema::computed_values::dsl::*;
+        let rows: Vec<(ObjectId, PropertyId, Option<f64>)> = computed_values
+            .filter(object_id.eq_any(objects))
+            .select((object_id, property_id, value))
+            .load(conn)
+            .map_err(|e| db_error("db select spreadsheet computed values error", e))?;
+        for (object, property, number) in rows {
+            if let Some(number) = number {
+                add(object, property, number.to_string());
+            }
+        }
+    }
+    Ok(cells)
+}
+
+fn format_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// A row below the header, with its line in the file for reporting
+#[derive(Debug)]
+pub struct SpreadsheetRow {
+    pub line: u64,
+    pub cells: Vec<String>,
+}
+
+/// Set the values of a spreadsheet's rows, each naming its object by id in
+/// an `id` column or else by content in a `Hash` column. The other columns
+/// are named by property, empty cells leave values as they are, and columns
+/// of computed properties are left out. Rows are saved one at a time, so a
+/// row with errors doesn't hold back the others, and a dry run only reports
+/// what would change.
+pub struct ImportSpreadsheet {
+    pub header: Vec<String>,
+    pub rows: Vec<SpreadsheetRow>,
+    pub delimiter: String,
+    pub dry_run: bool,
+    pub user_id: UserId,
+    pub request_id: Option<String>,
+}
+
+/// What an import changed, or would change when a dry run
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// The column naming objects, `id` or `hash`
+    pub key: &'static str,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub failed: usize,
+    /// The rows which changed or failed
+    pub rows: Vec<RowReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RowReport {
+    pub line: u64,
+    /// The row's cell in the key column
+    pub key: String,
+    pub object_id: Option<ObjectId>,
+    pub changes: Vec<CellChange>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CellChange {
+    pub property_id: PropertyId,
+    pub property: String,
+    pub old_value: String,
+    pub new_value: String,
+}
+
+impl Message for ImportSpreadsheet {
+    type Result = Result<ImportReport>;
+}
+
+/// What a column of the header is
+enum Column<'a> {
+    Key,
+    Ignored,
+    Value(&'a PropertyRow),
+}
+
+/// Choices of every property, by property and name ignoring case
+type ChoiceNames = HashMap<(PropertyId, String), SelectChoiceId>;
+
+impl Handler<ImportSpreadsheet> for DbExecutor {
+    type Result = Result<ImportReport>;
+
+    fn handle(&mut self, msg: ImportSpreadsheet, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        let properties = all_properties(&conn)?;
+        let (by_hash, columns) = columns(&msg.header, &properties)?;
+        let choices: Vec<(SelectChoiceId, PropertyId, String)> = {
+            use schema::property_value_choices::dsl::*;
+            property_value_choices
+                .select((id, property_id, display))
+                .load(&conn)
+                .map_err(|e| db_error("db select property value choices error", e))?
+        };
+        let choice_names: ChoiceNames = choices
+            .iter()
+            .map(|(choice, property, name)| {
+                (
+                    (property.clone(), name.trim().to_lowercase()),
+                    choice.clone(),
+                )
+            })
+            .collect();
+        let choice_displays: HashMap<SelectChoiceId, String> = choices
+            .into_iter()
+            .map(|(choice, _, name)| (choice, name))
+            .collect();
+        let key_index = columns
+            .iter()
+            .position(|column| match column {
+                Column::Key => true,
+                _ => false,
+            })
+            .unwrap_or_default();
+
+        let mut report = ImportReport {
+            dry_run: msg.dry_run,
+            key: if by_hash { "hash" } else { "id" },
+            updated: 0,
+            unchanged: 0,
+            failed: 0,
+            rows: Vec::new(),
+        };
+        for row in &msg.rows {
+            let key = row
+                .cells
+                .get(key_index)
+                .map_or("", |cell| cell.trim())
+                .to_string();
+            let mut row_report = RowReport {
+                line: row.line,
+                key: key.clone(),
+                object_id: None,
+                changes: Vec::new(),
+                errors: Vec::new(),
+            };
+
+            let object = match find_object(&conn, &key, by_hash)? {
+                Ok(object) => object,
+                Err(e) => {
+                    row_report.errors.push(e);
+                    report.failed += 1;
+                    report.rows.push(row_report);
+                    continue;
+                }
+            };
+            let current: HashMap<PropertyId, PropertyValue> = object_values(&conn, &object)?
+                .into_iter()
+                .map(|update| (update.property_id, update.value))
+                .collect();
+
+            let mut updates = Vec::new();
+            for (column, cell) in columns.iter().zip(&row.cells) {
+                let property = match column {
+                    Column::Value(property) => property,
+                    _ => continue,
+                };
+                let cell = cell.trim();
+                if cell.is_empty() {
+                    continue;
+                }
+                let value = match parse_cell(&conn, property, cell, &msg.delimiter, &choice_names)?
+                {
+                    Ok(value) => value,
+                    Err(e) => {
+                        row_report
+                            .errors
+                            .push(format!("{}: {}", property.display(), e));
+                        continue;
+                    }
+                };
+                let old = current.get(&property.id());
+                if old.map_or(false, |old| same_value(old, &value)) {
+                    continue;
+                }
+                row_report.changes.push(CellChange {
+                    property_id: property.id(),
+                    property: property.display().to_string(),
+                    old_value: old.map_or_else(String::new, |old| {
+                        format_value(old, &msg.delimiter, &choice_displays)
+                    }),
+                    new_value: format_value(&value, &msg.delimiter, &choice_displays),
+                });
+                updates.push(ValueUpdate {
+                    property_id: property.id(),
+                    value,
+                });
+            }
+
+            if row_report.errors.is_empty() && !updates.is_empty() {
+                let invalid = if msg.dry_run {
+                    let mut errors = Vec::new();
+                    for update in &updates {
+                        errors.extend(validate(&conn, &object, update)?);
+                    }
+                    errors
+                } else {
+                    match save_values(&conn, &object, &updates, &msg.user_id, &msg.request_id)? {
+                        SetValuesResult::Saved => Vec::new(),
+                        SetValuesResult::Invalid(errors) => errors,
+                    }
+                };
+                for error in invalid {
+                    let display = properties
+                        .iter()
+                        .find(|property| property.id() == error.property_id)
+                        .map_or("", |property| property.display());
+                    row_report
+                        .errors
+                        .push(format!("{}: {}", display, error.message));
+                }
+            }
+
+            row_report.object_id = Some(object);
+            if !row_report.errors.is_empty() {
+                report.failed += 1;
+            } else if updates.is_empty() {
+                report.unchanged += 1;
+                continue;
+            } else {
+                report.updated += 1;
+            }
+            report.rows.push(row_report);
+        }
+        Ok(report)
+    }
+}
+
+/// Whether objects are named by hash, and what each column of `header` is
+fn columns<'a>(
+    header: &[String],
+    properties: &'a [PropertyRow],
+) -> Result<(bool, Vec<Column<'a>>)> {
+    let named = |name: &str| {
+        let name = name.trim();
+        properties.iter().find(|property| {
+            property.display().trim().to_lowercase() == name.to_lowercase()
+                || name.parse::<i64>().ok().map(PropertyId::from) == Some(property.id())
+        })
+    };
+    let is_id = |name: &String| name.trim().eq_ignore_ascii_case(ID_COLUMN);
+    let by_hash = !header.iter().any(is_id);
+    if by_hash
+        && !header
+            .iter()
+            .any(|name| named(name).map_or(false, |property| property.id() == PropertyId::HASH))
+    {
+        return Err(error::ErrorBadRequest(
+            "The first row must name an id or a Hash column",
+        ));
+    }
+
+    let mut columns = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, name) in header.iter().enumerate() {
+        if is_id(name) {
+            columns.push(if by_hash {
+                Column::Ignored
+            } else {
+                Column::Key
+            });
+            continue;
+        }
+        let property = named(name).ok_or_else(|| {
+            error::ErrorBadRequest(format!(
+                "Column {} ({}) is not the name or id of a property",
+                index + 1,
+                name
+            ))
+        })?;
+        if !seen.insert(property.id()) {
+            return Err(error::ErrorBadRequest(format!(
+                "Column {} ({}) repeats a property",
+                index + 1,
+                name
+            )));
+        }
+        // contents decide their hash, and computed values are derived
+        columns.push(if property.id() == PropertyId::HASH {
+            if by_hash {
+                Column::Key
+            } else {
+                Column::Ignored
+            }
+        } else if property.kind() == &PropertyType::Computed {
+            Column::Ignored
+        } else {
+            Column::Value(property)
+        });
+    }
+    Ok((by_hash, columns))
+}
+
+/// The object named by `key`, or why there is none
+fn find_object(
+    conn: &PgConnection,
+    key: &str,
+    by_hash: bool,
+) -> Result<std::result::Result<ObjectId, String>> {
+    if key.is_empty() {
+        return Ok(Err(format!(
+            "No {}",
+            if by_hash { "Hash" } else { "object id" }
+        )));
+    }
+    let found: Vec<ObjectId> = if by_hash {
+        use schema::text_values::dsl::*;
+        text_values
+            .filter(property_id.eq(PropertyId::HASH))
+            .filter(value.eq(key))
+            .select(object_id)
+            .load(conn)
+            .map_err(|e| db_error("db select objects by hash error", e))?
+    } else {
+        use schema::objects::dsl::*;
+        objects
+            .filter(id.eq(key))
+            .select(id)
+            .load(conn)
+            .map_err(|e| db_error("db select object error", e))?
+    };
+    Ok(match found.len() {
+        0 if by_hash => Err(format!("No object has the Hash {}", key)),
+        0 => Err(format!("No object has the id {}", key)),
+        1 => Ok(found.into_iter().next().unwrap()),
+        n => Err(format!(
+            "{} objects have the Hash {}, name one by id",
+            n, key
+        )),
+    })
+}
+
+/// The value of `property` written in `cell`, or why it can't be read
+fn parse_cell(
+    conn: &PgConnection,
+    property: &PropertyRow,
+    cell: &str,
+    delimiter: &str,
+    choice_names: &ChoiceNames,
+) -> Result<std::result::Result<PropertyValue, String>> {
+    let items = || {
+        cell.split(delimiter)
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+    };
+    Ok(match property.kind() {
+        PropertyType::Text => Ok(PropertyValue::Text(cell.to_string())),
+        PropertyType::Timestamptz => parse_timestamp(cell)
+            .map(|timestamp| PropertyValue::Timestamptz(Some(timestamp)))
+            .ok_or_else(|| {
+                format!(
+                    "{} is not a date like 2019-03-21 or 2019-03-21T14:30:00Z",
+                    cell
+                )
+            }),
+        PropertyType::Choice => {
+            let mut choices = Vec::new();
+            let mut unknown = Vec::new();
+            for name in items() {
+                match choice_names.get(&(property.id(), name.to_lowercase())) {
+                    Some(choice) if !choices.contains(choice) => choices.push(choice.clone()),
+                    Some(_) => {}
+                    None => unknown.push(name),
+                }
+            }
+            if unknown.is_empty() {
+                Ok(PropertyValue::Choice(choices))
+            } else {
+                Err(format!("no choice is named {}", unknown.join(", ")))
+            }
+        }
+        PropertyType::Relation => {
+            let mut targets: Vec<ObjectId> = Vec::new();
+            for target in items().map(|target| ObjectId::from(target.to_string())) {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+            let existing: Vec<ObjectId> = {
+                use schema::objects::dsl::*;
+                objects
+                    .filter(id.eq_any(&targets))
+                    .select(id)
+                    .load(conn)
+                    .map_err(|e| db_error("db select relation targets error", e))?
+            };
+            let missing = targets
+                .iter()
+                .filter(|target| !existing.contains(target))
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            if missing.is_empty() {
+                Ok(PropertyValue::Relation(targets))
+            } else {
+                Err(format!("no object has the id {}", missing.join(", ")))
+            }
+        }
+        PropertyType::Computed => Err("computed values cannot be set".to_string()),
+    })
+}
+
+/// An RFC 3339 timestamp, or a date or minute in UTC
+fn parse_timestamp(cell: &str) -> Option<DateTime<Utc>> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(cell) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    let naive = NaiveDateTime::parse_from_str(cell, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(cell, "%Y-%m-%dT%H:%M"))
+        .or_else(|_| NaiveDateTime::parse_from_str(cell, "%Y-%m-%d %H:%M:%S"))
+        .or_else(|_| NaiveDateTime::parse_from_str(cell, "%Y-%m-%d %H:%M"))
+        .or_else(|_| NaiveDate::parse_from_str(cell, "%Y-%m-%d").map(|date| date.and_hms(0, 0, 0)))
+        .ok()?;
+    Some(DateTime::from_utc(naive, Utc))
+}
+
+/// Whether writing `new` would leave `old` as it is, ignoring the order of choices and targets
+fn same_value(old: &PropertyValue, new: &PropertyValue) -> bool {
+    match (old, new) {
+        (PropertyValue::Text(old), PropertyValue::Text(new)) => old == new,
+        (PropertyValue::Timestamptz(old), PropertyValue::Timestamptz(new)) => old == new,
+        (PropertyValue::Choice(old), PropertyValue::Choice(new)) => {
+            old.iter().collect::<HashSet<_>>() == new.iter().collect::<HashSet<_>>()
+        }
+        (PropertyValue::Relation(old), PropertyValue::Relation(new)) => {
+            old.iter().collect::<HashSet<_>>() == new.iter().collect::<HashSet<_>>()
+        }
+        _ => false,
+    }
+}
+
+/// `value` as it would be exported
+fn format_value(
+    value: &PropertyValue,
+    delimiter: &str,
+    choice_displays: &HashMap<SelectChoiceId, String>,
+) -> String {
+    match value {
+        PropertyValue::Text(text) => text.clone(),
+        PropertyValue::Timestamptz(timestamp) => timestamp
+            .as_ref()
+            .map_or_else(String::new, format_timestamp),
+        PropertyValue::Choice(choices) => choices
+            .iter()
+            .map(|choice| choice_displays.get(choice).map_or("", String::as_str))
+            .collect::<Vec<_>>()
+            .join(delimiter),
+        PropertyValue::Relation(targets) => targets
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(delimiter),
+    }
+}
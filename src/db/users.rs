@@ -0,0 +1,155 @@
+//! The people and plugins that use the system
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+
+use super::schema;
+use super::{db_error, DbExecutor};
+use crate::user::{User, UserId, UserKind, UserRow};
+
+/// What other users can see of a user
+#[derive(Debug, Clone, Serialize)]
+pub struct UserProfile {
+    pub id: UserId,
+    pub kind: UserKind,
+    pub display_name: String,
+    pub full_name: String,
+    pub public_email: Option<String>,
+    pub photo_url: Option<String>,
+    pub admin: bool,
+    pub disabled_at: Option<DateTime<Utc>>,
+}
+
+impl UserProfile {
+    pub fn of(user: &UserRow) -> Self {
+        UserProfile {
+            id: user.id(),
+            kind: user.kind().clone(),
+            display_name: user.display_name().to_string(),
+            full_name: user.full_name().to_string(),
+            public_email: user.public_email().cloned(),
+            photo_url: user.photo_url().cloned(),
+            admin: user.is_admin(),
+            disabled_at: user.disabled_at().cloned(),
+        }
+    }
+}
+
+/// Every person and plugin, by display name
+pub struct ListUsers;
+
+impl Message for ListUsers {
+    type Result = Result<Vec<UserProfile>>;
+}
+
+impl Handler<ListUsers> for DbExecutor {
+    type Result = Result<Vec<UserProfile>>;
+
+    fn handle(&mut self, _: ListUsers, _: &mut Self::Context) -> Self::Result {
+        use schema::users::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        Ok(users
+            .filter(kind.ne(UserKind::Reserved))
+            .order(display_name.asc())
+            .load::<UserRow>(&conn)
+            .map_err(|e| db_error("db select users error", e))?
+            .iter()
+            .map(UserProfile::of)
+            .collect())
+    }
+}
+
+/// A user's profile, `None` if there is no such user
+pub struct GetUser(pub UserId);
+
+impl Message for GetUser {
+    type Result = Result<Option<UserProfile>>;
+}
+
+impl Handler<GetUser> for DbExecutor {
+    type Result = Result<Option<UserProfile>>;
+
+    fn handle(&mut self, msg: GetUser, _: &mut Self::Context) -> Self::Result {
+        use schema::users::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        users
+            .filter(id.eq(&msg.0))
+            .get_result::<UserRow>(&conn)
+            .optional()
+            .map(|user| user.as_ref().map(UserProfile::of))
+            .map_err(|e| db_error("db select user error", e))
+    }
+}
+
+/// Make a person an administrator, or no longer one
+pub struct SetUserAdmin {
+    pub user_id: UserId,
+    pub admin: bool,
+}
+
+impl Message for SetUserAdmin {
+    type Result = Result<UserProfile>;
+}
+
+impl Handler<SetUserAdmin> for DbExecutor {
+    type Result = Result<UserProfile>;
+
+    fn handle(&mut self, msg: SetUserAdmin, _: &mut Self::Context) -> Self::Result {
+        use schema::users::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let user: UserRow = users
+            .filter(id.eq(&msg.user_id))
+            .get_result(&conn)
+            .optional()
+            .map_err(|e| db_error("db select user error", e))?
+            .ok_or_else(|| error::ErrorNotFound(format!("No user {}", msg.user_id)))?;
+        if msg.admin && *user.kind() != UserKind::Person {
+            return Err(error::ErrorBadRequest(format!(
+                "User {} is not a person, only people are administrators",
+                msg.user_id
+            )));
+        }
+
+        diesel::update(users.filter(id.eq(&msg.user_id)))
+            .set(admin.eq(msg.admin))
+            .get_result::<UserRow>(&conn)
+            .map(|user| UserProfile::of(&user))
+            .map_err(|e| db_error("db update user admin error", e))
+    }
+}
+
+/// Disable a user, so they may not sign in or use their tokens, or enable them again
+pub struct SetUserDisabled {
+    pub user_id: UserId,
+    pub disabled: bool,
+}
+
+impl Message for SetUserDisabled {
+    type Result = Result<UserProfile>;
+}
+
+impl Handler<SetUserDisabled> for DbExecutor {
+    type Result = Result<UserProfile>;
+
+    fn handle(&mut self, msg: SetUserDisabled, _: &mut Self::Context) -> Self::Result {
+        use schema::users::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let since = if msg.disabled { Some(Utc::now()) } else { None };
+        diesel::update(
+            users
+                .filter(id.eq(&msg.user_id))
+                .filter(kind.ne(UserKind::Reserved)),
+        )
+        .set(disabled_at.eq(since))
+        .get_result::<UserRow>(&conn)
+        .optional()
+        .map_err(|e| db_error("db update user disabled error", e))?
+        .map(|user| UserProfile::of(&user))
+        .ok_or_else(|| error::ErrorNotFound(format!("No user {}", msg.user_id)))
+    }
+}
@@ -0,0 +1,354 @@
+//! Writing property values of objects
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+use super::computed::recompute_object;
+use super::history::set_audit_context;
+use super::schema;
+use super::schemas::{apply_object_schemas, object_schema_fields};
+use super::{db_error, transaction, DbExecutor, Fetch};
+use crate::object::ObjectId;
+use crate::property::{
+    FieldError, Property, PropertyId, PropertyRow, PropertyType, PropertyValue, SelectChoiceId,
+    ValidationRules,
+};
+use crate::user::UserId;
+
+/// A single property's new value
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValueUpdate {
+    pub property_id: PropertyId,
+    pub value: PropertyValue,
+}
+
+/// Validate and replace the values of an object's properties, then recompute
+/// derived values. Nothing is written unless every value is valid.
+pub struct SetValues {
+    pub object_id: ObjectId,
+    pub values: Vec<ValueUpdate>,
+    pub user_id: UserId,
+    pub request_id: Option<String>,
+}
+
+pub enum SetValuesResult {
+    Saved,
+    Invalid(Vec<FieldError>),
+}
+
+impl Message for SetValues {
+    type Result = Result<SetValuesResult>;
+}
+
+impl Handler<SetValues> for DbExecutor {
+    type Result = Result<SetValuesResult>;
+
+    fn handle(&mut self, msg: SetValues, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        save_values(
+            &conn,
+            &msg.object_id,
+            &msg.values,
+            &msg.user_id,
+            &msg.request_id,
+        )
+    }
+}
+
+/// Validate and write `values` of `object` in a transaction, as `SetValues`
+pub fn save_values(
+    conn: &PgConnection,
+    object: &ObjectId,
+    values: &[ValueUpdate],
+    user: &UserId,
+    request_id: &Option<String>,
+) -> Result<SetValuesResult> {
+    transaction(conn, || {
+        set_audit_context(conn, user, request_id)?;
+        let mut errors = Vec::new();
+        for update in values {
+            errors.extend(validate(conn, object, update)?);
+        }
+        if !errors.is_empty() {
+            return Ok(SetValuesResult::Invalid(errors));
+        }
+
+        for update in values {
+            set_value(conn, object, update, user)?;
+        }
+        // A new collection brings the defaults of its schemas along
+        if values
+            .iter()
+            .any(|update| update.property_id == PropertyId::COLLECTION)
+        {
+            apply_object_schemas(conn, object, user)?;
+        }
+        recompute_object(conn, object)?;
+        Ok(SetValuesResult::Saved)
+    })
+}
+
+/// The rules of a property, or the permissive defaults if it has none
+pub fn validation_rules(conn: &PgConnection, property: &PropertyId) -> Result<ValidationRules> {
+    use schema::property_validations::dsl::*;
+
+    property_validations
+        .filter(property_id.eq(property))
+        .select((
+            required,
+            pattern,
+            min_length,
+            max_length,
+            min_number,
+            max_number,
+            multiple,
+            target_extensions,
+        ))
+        .get_result::<ValidationRules>(conn)
+        .optional()
+        .map(Option::unwrap_or_default)
+        .map_err(|e| db_error("db select property validations error", e))
+}
+
+/// Type and rule violations of an update
+pub fn validate(
+    conn: &PgConnection,
+    object: &ObjectId,
+    update: &ValueUpdate,
+) -> Result<Vec<FieldError>> {
+    let field_error = |message: String| FieldError {
+        property_id: update.property_id.clone(),
+        message,
+    };
+
+    let property: PropertyRow = update.property_id.fetch(conn)?;
+    if property.kind() == &PropertyType::Computed {
+        return Ok(vec![field_error(format!(
+            "{} is computed and cannot be set",
+            property.display()
+        ))]);
+    }
+    if property.kind() != &update.value.kind() {
+        return Ok(vec![field_error(format!(
+            "{} expects a {:?} value",
+            property.display(),
+            property.kind()
+        ))]);
+    }
+
+    let target_extensions: Vec<String> = match update.value {
+        PropertyValue::Relation(ref targets) => {
+            use schema::objects::dsl::*;
+            objects
+                .filter(id.eq_any(targets))
+                .select(extension)
+                .load(conn)
+                .map_err(|e| db_error("db select relation target extensions error", e))?
+        }
+        _ => Vec::new(),
+    };
+    let known_choices: Vec<SelectChoiceId> = match update.value {
+        PropertyValue::Choice(ref choices) => {
+            use schema::property_value_choices::dsl::*;
+            property_value_choices
+                .filter(property_id.eq(&update.property_id))
+                .filter(id.eq_any(choices))
+                .select(id)
+                .load(conn)
+                .map_err(|e| db_error("db select property choices error", e))?
+        }
+        _ => Vec::new(),
+    };
+
+    let mut rules = validation_rules(conn, &update.property_id)?;
+    rules.required |= object_schema_fields(conn, object)?
+        .iter()
+        .any(|field| field.required && field.property_id == update.property_id);
+
+    Ok(rules
+        .compile()
+        .check(&update.value, &target_extensions, &known_choices)
+        .into_iter()
+        .map(field_error)
+        .collect())
+}
+
+/// Replace the rules of a property
+pub struct SetValidationRules {
+    pub property_id: PropertyId,
+    pub rules: ValidationRules,
+}
+
+impl Message for SetValidationRules {
+    type Result = Result<()>;
+}
+
+impl Handler<SetValidationRules> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: SetValidationRules, _: &mut Self::Context) -> Self::Result {
+        use schema::property_validations::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        msg.rules.check_rules().map_err(error::ErrorBadRequest)?;
+        let rules = msg.rules;
+        let row = (
+            required.eq(rules.required),
+            pattern.eq(rules.pattern),
+            min_length.eq(rules.min_length),
+            max_length.eq(rules.max_length),
+            min_number.eq(rules.min_number),
+            max_number.eq(rules.max_number),
+            multiple.eq(rules.multiple),
+            target_extensions.eq(rules.target_extensions),
+        );
+
+        insert_into(property_validations)
+            .values((property_id.eq(&msg.property_id), row.clone()))
+            .on_conflict(property_id)
+            .do_update()
+            .set(row)
+            .execute(&conn)
+            .map_err(|e| db_error("db upsert property validations error", e))?;
+        Ok(())
+    }
+}
+
+/// Whether `object` has any value for the property
+pub fn has_value(
+    conn: &PgConnection,
+    object: &ObjectId,
+    property: &PropertyId,
+    kind: &PropertyType,
+) -> Result<bool> {
+    use diesel::dsl::{exists, select};
+
+    let query = match kind {
+        PropertyType::Text => {
+            use schema::text_values::dsl::*;
+            select(exists(
+                text_values.filter(object_id.eq(object).and(property_id.eq(property))),
+            ))
+            .get_result(conn)
+        }
+        PropertyType::Timestamptz => {
+            use schema::timestamptz_values::dsl::*;
+            select(exists(
+                timestamptz_values.filter(object_id.eq(object).and(property_id.eq(property))),
+            ))
+            .get_result(conn)
+        }
+        PropertyType::Choice => {
+            use schema::choice_values::dsl::*;
+            select(exists(
+                choice_values.filter(object_id.eq(object).and(property_id.eq(property))),
+            ))
+            .get_result(conn)
+        }
+        PropertyType::Relation => {
+            use schema::relation_values::dsl::*;
+            select(exists(
+                relation_values.filter(object_id.eq(object).and(property_id.eq(property))),
+            ))
+            .get_result(conn)
+        }
+        PropertyType::Computed => {
+            use schema::computed_values::dsl::*;
+            select(exists(
+                computed_values.filter(object_id.eq(object).and(property_id.eq(property))),
+            ))
+            .get_result(conn)
+        }
+    };
+    query.map_err(|e| db_error("db select has value error", e))
+}
+
+/// Write a value without validating or recomputing
+pub fn set_value(
+    conn: &PgConnection,
+    object: &ObjectId,
+    update: &ValueUpdate,
+    user: &UserId,
+) -> Result<()> {
+    match update.value {
+        PropertyValue::Text(ref text) => {
+            use schema::text_values::dsl::*;
+            insert_into(text_values)
+                .values((
+                    object_id.eq(object),
+                    property_id.eq(&update.property_id),
+                    value.eq(text),
+                    created_by.eq(user),
+                ))
+                .on_conflict((object_id, property_id))
+                .do_update()
+                .set((value.eq(text), created_by.eq(user)))
+                .execute(conn)
+                .map_err(|e| db_error("db upsert text value error", e))?;
+        }
+        PropertyValue::Timestamptz(ref timestamp) => {
+            use schema::timestamptz_values::dsl::*;
+            insert_into(timestamptz_values)
+                .values((
+                    object_id.eq(object),
+                    property_id.eq(&update.property_id),
+                    value.eq(timestamp),
+                    created_by.eq(user),
+                ))
+                .on_conflict((object_id, property_id))
+                .do_update()
+                .set((value.eq(timestamp), created_by.eq(user)))
+                .execute(conn)
+                .map_err(|e| db_error("db upsert timestamptz value error", e))?;
+        }
+        PropertyValue::Choice(ref choices) => {
+            use schema::choice_values::dsl::*;
+            diesel::delete(
+                choice_values
+                    .filter(object_id.eq(object))
+                    .filter(property_id.eq(&update.property_id))
+                    .filter(value_id.ne_all(choices)),
+            )
+            .execute(conn)
+            .map_err(|e| db_error("db delete choice values error", e))?;
+            for choice in choices {
+                insert_into(choice_values)
+                    .values((
+                        object_id.eq(object),
+                        property_id.eq(&update.property_id),
+                        value_id.eq(choice),
+                        created_by.eq(user),
+                    ))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .map_err(|e| db_error("db insert choice value error", e))?;
+            }
+        }
+        PropertyValue::Relation(ref targets) => {
+            use schema::relation_values::dsl::*;
+            diesel::delete(
+                relation_values
+                    .filter(object_id.eq(object))
+                    .filter(property_id.eq(&update.property_id))
+                    .filter(target_id.ne_all(targets)),
+            )
+            .execute(conn)
+            .map_err(|e| db_error("db delete relation values error", e))?;
+            for target in targets {
+                insert_into(relation_values)
+                    .values((
+                        object_id.eq(object),
+                        property_id.eq(&update.property_id),
+                        target_id.eq(target),
+                        created_by.eq(user),
+                    ))
+                    .on_conflict_do_nothing()
+                    .execute(conn)
+                    .map_err(|e| db_error("db insert relation value error", e))?;
+            }
+        }
+    }
+    Ok(())
+}
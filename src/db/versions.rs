@@ -0,0 +1,370 @@
+//! Objects and their successive content versions
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{DateTime, Utc};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::sql_types::{Int8, Text, Timestamptz};
+use diesel::PgConnection;
+
+use super::computed::recompute_object;
+use super::history::set_audit_context;
+use super::schema;
+use super::schemas::apply_object_schemas;
+use super::values::{set_value, ValueUpdate};
+use super::{db_error, transaction, DbExecutor};
+use crate::object::{extension_of, ObjectId, ObjectVersion};
+use crate::property::{PropertyId, PropertyValue, SelectChoiceId};
+use crate::user::UserId;
+
+/// The stored content of an upload
+#[derive(Debug, Clone)]
+pub struct VersionContent {
+    pub filename: String,
+    pub extension: String,
+    pub hash: String,
+    pub size: i64,
+}
+
+/// Create an object whose first version is `content`
+pub struct CreateObject {
+    pub object_id: ObjectId,
+    pub content: VersionContent,
+    pub modified: DateTime<Utc>,
+    pub created_by: UserId,
+    pub collection: Option<SelectChoiceId>,
+    pub request_id: Option<String>,
+}
+
+impl Message for CreateObject {
+    type Result = Result<ObjectId>;
+}
+
+impl Handler<CreateObject> for DbExecutor {
+    type Result = Result<ObjectId>;
+
+    fn handle(&mut self, msg: CreateObject, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        transaction(&conn, || create_object(&conn, &msg))?;
+        Ok(msg.object_id)
+    }
+}
+
+pub fn create_object(conn: &PgConnection, msg: &CreateObject) -> Result<()> {
+    set_audit_context(conn, &msg.created_by, &msg.request_id)?;
+
+    // Assigns the Filename and Last Modified properties
+    diesel::sql_query("SELECT create_object_id_fn_ext_mod_cb($1, $2, $3, $4, $5)")
+        .bind::<Text, _>(&msg.object_id)
+        .bind::<Text, _>(&msg.content.filename)
+        .bind::<Text, _>(&msg.content.extension)
+        .bind::<Timestamptz, _>(&msg.modified)
+        .bind::<Int8, _>(&msg.created_by)
+        .execute(conn)
+        .map_err(|e| db_error("db create object error", e))?;
+
+    insert_version(conn, &msg.object_id, 1, &msg.content, &msg.created_by)?;
+
+    if let Some(ref collection) = msg.collection {
+        set_value(
+            conn,
+            &msg.object_id,
+            &ValueUpdate {
+                property_id: PropertyId::COLLECTION,
+                value: PropertyValue::Choice(vec![collection.clone()]),
+            },
+            &msg.created_by,
+        )?;
+        apply_object_schemas(conn, &msg.object_id, &msg.created_by)?;
+    }
+    recompute_object(conn, &msg.object_id)
+}
+
+/// Record `content` as the object's current version, and update its
+/// Filename and Hash properties to match
+fn insert_version(
+    conn: &PgConnection,
+    object: &ObjectId,
+    new_version: i32,
+    content: &VersionContent,
+    user: &UserId,
+) -> Result<()> {
+    {
+        use schema::object_versions::dsl::*;
+        insert_into(object_versions)
+            .values((
+                object_id.eq(object),
+                version.eq(new_version),
+                hash.eq(&content.hash),
+                size.eq(content.size),
+                filename.eq(&content.filename),
+                created_by.eq(user),
+            ))
+            .execute(conn)
+            .map_err(|e| db_error("db insert object version error", e))?;
+    }
+    {
+        use schema::objects::dsl::*;
+        diesel::update(objects.filter(id.eq(object)))
+            .set((
+                current_version.eq(new_version),
+                extension.eq(&content.extension),
+            ))
+            .execute(conn)
+            .map_err(|e| db_error("db update object current version error", e))?;
+    }
+    for (property_id, text) in &[
+        (PropertyId::FILENAME, &content.filename),
+        (PropertyId::HASH, &content.hash),
+    ] {
+        set_value(
+            conn,
+            object,
+            &ValueUpdate {
+                property_id: property_id.clone(),
+                value: PropertyValue::Text(text.to_string()),
+            },
+            user,
+        )?;
+    }
+    Ok(())
+}
+
+/// The number of the object's next version. Locks the object until the
+/// transaction ends, so versions added at once are numbered one after another.
+fn next_version(conn: &PgConnection, object: &ObjectId) -> Result<i32> {
+    use schema::object_versions::dsl::*;
+
+    {
+        use schema::objects::dsl::{id, objects};
+        objects
+            .filter(id.eq(object))
+            .select(id)
+            .for_update()
+            .get_result::<ObjectId>(conn)
+            .optional()
+            .map_err(|e| db_error("db lock object error", e))?
+            .ok_or_else(|| error::ErrorNotFound(format!("No object {}", object)))?;
+    }
+    object_versions
+        .filter(object_id.eq(object))
+        .select(diesel::dsl::max(version))
+        .get_result::<Option<i32>>(conn)
+        .map(|latest| latest.unwrap_or(0) + 1)
+        .map_err(|e| db_error("db select latest object version error", e))
+}
+
+/// Make `content` the newest version of an existing object
+pub struct AddObjectVersion {
+    pub object_id: ObjectId,
+    pub content: VersionContent,
+    pub created_by: UserId,
+    pub request_id: Option<String>,
+}
+
+impl Message for AddObjectVersion {
+    type Result = Result<i32>;
+}
+
+impl Handler<AddObjectVersion> for DbExecutor {
+    type Result = Result<i32>;
+
+    fn handle(&mut self, msg: AddObjectVersion, _: &mut Self::Context) -> Self::Result {
+        let conn = self.0.get().unwrap();
+        transaction(&conn, || {
+            set_audit_context(&conn, &msg.created_by, &msg.request_id)?;
+            let new_version = next_version(&conn, &msg.object_id)?;
+            insert_version(&conn, &msg.object_id, new_version, &msg.content, &msg.created_by)?;
+            set_value(
+                &conn,
+                &msg.object_id,
+                &ValueUpdate {
+                    property_id: PropertyId::LAST_MODIFIED,
+                    value: PropertyValue::Timestamptz(Some(Utc::now())),
+                },
+                &msg.created_by,
+            )?;
+            recompute_object(&conn, &msg.object_id)?;
+            Ok(new_version)
+        })
+    }
+}
+
+/// Newest version first
+pub struct ListObjectVersions(pub ObjectId);
+
+impl Message for ListObjectVersions {
+    type Result = Result<Vec<ObjectVersion>>;
+}
+
+impl Handler<ListObjectVersions> for DbExecutor {
+    type Result = Result<Vec<ObjectVersion>>;
+
+    fn handle(&mut self, msg: ListObjectVersions, _: &mut Self::Context) -> Self::Result {
+        use schema::object_versions::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        object_versions
+            .filter(object_id.eq(&msg.0))
+            .order(version.desc())
+            .load(&conn)
+            .map_err(|e| db_error("db select object versions error", e))
+    }
+}
+
+fn get_version(conn: &PgConnection, object: &ObjectId, number: i32) -> Result<ObjectVersion> {
+    use schema::object_versions::dsl::*;
+
+    object_versions
+        .filter(object_id.eq(object))
+        .filter(version.eq(number))
+        .get_result(conn)
+        .optional()
+        .map_err(|e| db_error("db select object version error", e))?
+        .ok_or_else(|| error::ErrorNotFound(format!("{} has no version {}", object, number)))
+}
+
+/// A specific version, or the current version with `None`
+pub struct GetObjectVersion {
+    pub object_id: ObjectId,
+    pub version: Option<i32>,
+}
+
+impl Message for GetObjectVersion {
+    type Result = Result<ObjectVersion>;
+}
+
+impl Handler<GetObjectVersion> for DbExecutor {
+    type Result = Result<ObjectVersion>;
+
+    fn handle(&mut self, msg: GetObjectVersion, _: &mut Self::Context) -> Self::Result {
+        use crate::db::Fetch;
+        use crate::object::ObjectRow;
+        let conn = self.0.get().unwrap();
+
+        let number = match msg.version {
+            Some(number) => number,
+            None => {
+                let object: ObjectRow = msg.object_id.fetch(&conn)?;
+                object.current_version.ok_or_else(|| {
+                    error::ErrorNotFound(format!("{} has no content", msg.object_id))
+                })?
+            }
+        };
+        get_version(&conn, &msg.object_id, number)
+    }
+}
+
+/// Make a prior version current again by adding it as the newest version
+pub struct RestoreObjectVersion {
+    pub object_id: ObjectId,
+    pub version: i32,
+    pub restored_by: UserId,
+    pub request_id: Option<String>,
+}
+
+impl Message for RestoreObjectVersion {
+    type Result = Result<i32>;
+}
+
+impl Handler<RestoreObjectVersion> for DbExecutor {
+    type Result = Result<i32>;
+
+    fn handle(&mut self, msg: RestoreObjectVersion, ctx: &mut Self::Context) -> Self::Result {
+        let restored = {
+            let conn = self.0.get().unwrap();
+            get_version(&conn, &msg.object_id, msg.version)?
+        };
+
+        Handler::<AddObjectVersion>::handle(
+            self,
+            AddObjectVersion {
+                object_id: msg.object_id,
+                content: VersionContent {
+                    extension: extension_of(&restored.filename),
+                    filename: restored.filename,
+                    hash: restored.hash,
+                    size: restored.size,
+                },
+                created_by: msg.restored_by,
+                request_id: msg.request_id,
+            },
+            ctx,
+        )
+    }
+}
+
+/// The current version of every object, by object id
+pub struct ListCurrentVersions;
+
+impl Message for ListCurrentVersions {
+    type Result = Result<Vec<ObjectVersion>>;
+}
+
+impl Handler<ListCurrentVersions> for DbExecutor {
+    type Result = Result<Vec<ObjectVersion>>;
+
+    fn handle(&mut self, _: ListCurrentVersions, _: &mut Self::Context) -> Self::Result {
+        use schema::{object_versions, objects};
+        let conn = self.0.get().unwrap();
+
+        object_versions::table
+            .inner_join(objects::table)
+            .filter(objects::current_version.eq(object_versions::version.nullable()))
+            .order(object_versions::object_id.asc())
+            .select(object_versions::all_columns)
+            .load(&conn)
+            .map_err(|e| db_error("db select current object versions error", e))
+    }
+}
+
+/// The hash of every version's content, which are the blobs the store must keep
+pub struct ListVersionHashes;
+
+impl Message for ListVersionHashes {
+    type Result = Result<Vec<String>>;
+}
+
+impl Handler<ListVersionHashes> for DbExecutor {
+    type Result = Result<Vec<String>>;
+
+    fn handle(&mut self, _: ListVersionHashes, _: &mut Self::Context) -> Self::Result {
+        use schema::object_versions::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        object_versions
+            .select(hash)
+            .distinct()
+            .load(&conn)
+            .map_err(|e| db_error("db select object version hashes error", e))
+    }
+}
+
+/// The object whose current content has `hash`, if there is one, so the same
+/// file isn't stored twice
+pub struct FindObjectByHash {
+    pub hash: String,
+}
+
+impl Message for FindObjectByHash {
+    type Result = Result<Option<ObjectId>>;
+}
+
+impl Handler<FindObjectByHash> for DbExecutor {
+    type Result = Result<Option<ObjectId>>;
+
+    fn handle(&mut self, msg: FindObjectByHash, _: &mut Self::Context) -> Self::Result {
+        use schema::{object_versions, objects};
+        let conn = self.0.get().unwrap();
+
+        object_versions::table
+            .inner_join(objects::table)
+            .filter(objects::current_version.eq(object_versions::version.nullable()))
+            .filter(object_versions::hash.eq(&msg.hash))
+            .order(object_versions::created_at.asc())
+            .select(object_versions::object_id)
+            .first(&conn)
+            .optional()
+            .map_err(|e| db_error("db select object by hash error", e))
+    }
+}
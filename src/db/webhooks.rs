@@ -0,0 +1,350 @@
+//! Webhook subscriptions and the log of their deliveries
+use ::actix::prelude::*;
+use actix_web::{error, Result};
+use chrono::{Duration, Utc};
+use diesel::insert_into;
+use diesel::prelude::*;
+use diesel::sql_types::{Int4, Int8};
+use diesel::PgConnection;
+
+use super::schema;
+use super::{db_error, DbExecutor};
+use crate::user::UserId;
+use crate::webhook::{
+    check_receiver, new_webhook_secret, Delivery, DeliveryId, DeliveryOutcome, DeliveryStatus,
+    Webhook, WebhookEvent, WebhookId,
+};
+
+/// Attempts at a delivery before it is given up on
+const MAX_ATTEMPTS: i32 = 8;
+/// Wait before the second attempt, doubled after every failed attempt
+const FIRST_RETRY_SECONDS: i64 = 30;
+/// Longest response body kept in the log, in bytes
+const MAX_LOGGED_BODY_LEN: usize = 2048;
+/// How long a claimed delivery is left to its server process before another
+/// one may attempt it, longer than a receiver has to respond
+const CLAIM_LEASE_SECONDS: i32 = 60;
+
+/// Subscribe `url` to events, all of them when `events` is empty
+pub struct CreateWebhook {
+    pub url: String,
+    /// Generated when not given
+    pub secret: Option<String>,
+    pub events: Vec<WebhookEvent>,
+    pub created_by: UserId,
+}
+
+impl Message for CreateWebhook {
+    type Result = Result<Webhook>;
+}
+
+impl Handler<CreateWebhook> for DbExecutor {
+    type Result = Result<Webhook>;
+
+    fn handle(&mut self, msg: CreateWebhook, _: &mut Self::Context) -> Self::Result {
+        use schema::webhooks::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let target = msg.url.trim();
+        if !(target.starts_with("http://") || target.starts_with("https://")) {
+            return Err(error::ErrorBadRequest(
+                "A webhook's URL starts with http:// or https://",
+            ));
+        }
+        check_receiver(target).map_err(error::ErrorBadRequest)?;
+        let new_secret = match msg.secret {
+            Some(ref given) if given.trim().is_empty() => {
+                return Err(error::ErrorBadRequest("A webhook's secret may not be empty"))
+            }
+            Some(given) => given,
+            None => new_webhook_secret(),
+        };
+        let mut names: Vec<String> = msg
+            .events
+            .iter()
+            .map(|event| event.name().to_string())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        insert_into(webhooks)
+            .values((
+                url.eq(target),
+                secret.eq(&new_secret),
+                events.eq(&names),
+                created_by.eq(&msg.created_by),
+            ))
+            .get_result(&conn)
+            .map_err(|e| db_error("db insert webhook error", e))
+    }
+}
+
+/// A user's webhooks, newest first
+pub struct ListWebhooks {
+    pub user_id: UserId,
+}
+
+impl Message for ListWebhooks {
+    type Result = Result<Vec<Webhook>>;
+}
+
+impl Handler<ListWebhooks> for DbExecutor {
+    type Result = Result<Vec<Webhook>>;
+
+    fn handle(&mut self, msg: ListWebhooks, _: &mut Self::Context) -> Self::Result {
+        use schema::webhooks::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        webhooks
+            .filter(created_by.eq(&msg.user_id))
+            .order(created_at.desc())
+            .load(&conn)
+            .map_err(|e| db_error("db select webhooks error", e))
+    }
+}
+
+/// The user's webhook, not found for other users' webhooks
+fn find_webhook(conn: &PgConnection, webhook: &WebhookId, user: &UserId) -> Result<Webhook> {
+    use schema::webhooks::dsl::*;
+
+    webhooks
+        .filter(id.eq(webhook))
+        .filter(created_by.eq(user))
+        .get_result(conn)
+        .optional()
+        .map_err(|e| db_error("db select webhook error", e))?
+        .ok_or_else(|| error::ErrorNotFound("No such webhook"))
+}
+
+/// Unsubscribe, dropping the webhook's delivery log and pending deliveries
+pub struct DeleteWebhook {
+    pub webhook_id: WebhookId,
+    pub user_id: UserId,
+}
+
+impl Message for DeleteWebhook {
+    type Result = Result<()>;
+}
+
+impl Handler<DeleteWebhook> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: DeleteWebhook, _: &mut Self::Context) -> Self::Result {
+        use schema::webhooks::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let deleted = diesel::delete(
+            webhooks
+                .filter(id.eq(&msg.webhook_id))
+                .filter(created_by.eq(&msg.user_id)),
+        )
+        .execute(&conn)
+        .map_err(|e| db_error("db delete webhook error", e))?;
+        if deleted == 0 {
+            return Err(error::ErrorNotFound("No such webhook"));
+        }
+        Ok(())
+    }
+}
+
+/// The latest deliveries of one of the user's webhooks, newest first
+pub struct ListDeliveries {
+    pub webhook_id: WebhookId,
+    pub user_id: UserId,
+    pub limit: i64,
+}
+
+impl Message for ListDeliveries {
+    type Result = Result<Vec<Delivery>>;
+}
+
+impl Handler<ListDeliveries> for DbExecutor {
+    type Result = Result<Vec<Delivery>>;
+
+    fn handle(&mut self, msg: ListDeliveries, _: &mut Self::Context) -> Self::Result {
+        use schema::webhook_deliveries::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        find_webhook(&conn, &msg.webhook_id, &msg.user_id)?;
+        webhook_deliveries
+            .filter(webhook_id.eq(&msg.webhook_id))
+            .order((created_at.desc(), id.desc()))
+            .limit(msg.limit)
+            .load(&conn)
+            .map_err(|e| db_error("db select webhook deliveries error", e))
+    }
+}
+
+/// Queue a `ping` to one of the user's webhooks, to check its receiver
+pub struct PingWebhook {
+    pub webhook_id: WebhookId,
+    pub user_id: UserId,
+}
+
+impl Message for PingWebhook {
+    type Result = Result<Delivery>;
+}
+
+impl Handler<PingWebhook> for DbExecutor {
+    type Result = Result<Delivery>;
+
+    fn handle(&mut self, msg: PingWebhook, _: &mut Self::Context) -> Self::Result {
+        use schema::webhook_deliveries::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let webhook = find_webhook(&conn, &msg.webhook_id, &msg.user_id)?;
+        insert_into(webhook_deliveries)
+            .values((
+                webhook_id.eq(&webhook.id),
+                event.eq(WebhookEvent::Ping),
+                payload.eq(json!({ "webhook_id": webhook.id, "url": webhook.url })),
+            ))
+            .get_result(&conn)
+            .map_err(|e| db_error("db insert webhook ping error", e))
+    }
+}
+
+/// A delivery whose next attempt is due, with where to send it
+#[derive(Debug, Clone)]
+pub struct DueDelivery {
+    pub delivery: Delivery,
+    pub url: String,
+    pub secret: String,
+}
+
+/// Claim pending deliveries whose next attempt is due, oldest first. Their
+/// next attempt is pushed back by a lease, so other server processes skip
+/// them until the attempt is recorded.
+pub struct ListDueDeliveries {
+    pub limit: i64,
+}
+
+impl Message for ListDueDeliveries {
+    type Result = Result<Vec<DueDelivery>>;
+}
+
+#[derive(QueryableByName)]
+struct Claimed {
+    #[sql_type = "Int8"]
+    id: DeliveryId,
+}
+
+impl Handler<ListDueDeliveries> for DbExecutor {
+    type Result = Result<Vec<DueDelivery>>;
+
+    fn handle(&mut self, msg: ListDueDeliveries, _: &mut Self::Context) -> Self::Result {
+        use schema::{webhook_deliveries, webhooks};
+        let conn = self.0.get().unwrap();
+
+        let claimed: Vec<Claimed> = diesel::sql_query(
+            r#"
+            UPDATE webhook_deliveries
+            SET next_attempt_at = now() + $2 * INTERVAL '1 second'
+            WHERE id IN (
+                SELECT id FROM webhook_deliveries
+                WHERE status = 'pending' AND next_attempt_at <= now()
+                ORDER BY next_attempt_at LIMIT $1 FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id
+            "#,
+        )
+        .bind::<Int8, _>(msg.limit)
+        .bind::<Int4, _>(CLAIM_LEASE_SECONDS)
+        .load(&conn)
+        .map_err(|e| db_error("db claim due webhook deliveries error", e))?;
+        let ids: Vec<DeliveryId> = claimed.into_iter().map(|claimed| claimed.id).collect();
+
+        webhook_deliveries::table
+            .inner_join(webhooks::table)
+            .filter(webhook_deliveries::id.eq_any(ids))
+            .order((
+                webhook_deliveries::created_at.asc(),
+                webhook_deliveries::id.asc(),
+            ))
+            .select((
+                webhook_deliveries::all_columns,
+                webhooks::url,
+                webhooks::secret,
+            ))
+            .load::<(Delivery, String, String)>(&conn)
+            .map(|due| {
+                due.into_iter()
+                    .map(|(delivery, url, secret)| DueDelivery {
+                        delivery,
+                        url,
+                        secret,
+                    })
+                    .collect()
+            })
+            .map_err(|e| db_error("db select due webhook deliveries error", e))
+    }
+}
+
+/// Record an attempt at a delivery, scheduling the next one with exponential
+/// backoff when it failed, or giving up after `MAX_ATTEMPTS`
+pub struct RecordDeliveryAttempt {
+    pub delivery_id: DeliveryId,
+    pub attempts: i32,
+    pub outcome: DeliveryOutcome,
+}
+
+impl Message for RecordDeliveryAttempt {
+    type Result = Result<()>;
+}
+
+fn logged_body(body: &str) -> String {
+    let mut end = body.len().min(MAX_LOGGED_BODY_LEN);
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    body[..end].to_string()
+}
+
+impl Handler<RecordDeliveryAttempt> for DbExecutor {
+    type Result = Result<()>;
+
+    fn handle(&mut self, msg: RecordDeliveryAttempt, _: &mut Self::Context) -> Self::Result {
+        use schema::webhook_deliveries::dsl::*;
+        let conn = self.0.get().unwrap();
+
+        let now = Utc::now();
+        let attempt = msg.attempts + 1;
+        let target = webhook_deliveries.filter(id.eq(&msg.delivery_id));
+        match msg.outcome {
+            DeliveryOutcome::Delivered { status: code, body } => diesel::update(target)
+                .set((
+                    status.eq(DeliveryStatus::Delivered),
+                    attempts.eq(attempt),
+                    response_status.eq(Some(i32::from(code))),
+                    response_body.eq(Some(logged_body(&body))),
+                    schema::webhook_deliveries::error.eq(None::<String>),
+                    delivered_at.eq(Some(now)),
+                ))
+                .execute(&conn),
+            DeliveryOutcome::Failed {
+                status: code,
+                body,
+                error: reason,
+            } => {
+                let (next_status, retry_in) = if attempt >= MAX_ATTEMPTS {
+                    (DeliveryStatus::Failed, Duration::zero())
+                } else {
+                    let backoff = FIRST_RETRY_SECONDS << (attempt - 1);
+                    (DeliveryStatus::Pending, Duration::seconds(backoff))
+                };
+                diesel::update(target)
+                    .set((
+                        status.eq(next_status),
+                        attempts.eq(attempt),
+                        next_attempt_at.eq(now + retry_in),
+                        response_status.eq(code.map(i32::from)),
+                        response_body.eq(body.map(|body| logged_body(&body))),
+                        schema::webhook_deliveries::error.eq(Some(reason)),
+                    ))
+                    .execute(&conn)
+            }
+        }
+        .map(|_| ())
+        .map_err(|e| db_error("db update webhook delivery error", e))
+    }
+}
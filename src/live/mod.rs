@@ -0,0 +1,52 @@
+//! Live updates for open browsers. Object and value events are queued by the
+//! database in `live_events`, published to Redis channels by one of the server
+//! processes, and streamed from Redis to each browser by the process it is
+//! connected to.
+use chrono::{DateTime, Utc};
+use diesel::sql_types::{Array, Int8, Jsonb, Text, Timestamptz};
+
+use crate::property::SelectChoiceId;
+use crate::user::UserId;
+
+mod publisher;
+pub use publisher::LivePublisher;
+
+/// Channel of the events of objects in a collection
+pub fn collection_channel(collection: &SelectChoiceId) -> String {
+    format!("dewey:live:collection:{}", collection)
+}
+
+/// Channel of the events of objects created by a user
+pub fn user_channel(user: &UserId) -> String {
+    format!("dewey:live:user:{}", user)
+}
+
+/// An event as queued by the database, with the webhook event's names and payloads
+#[derive(Debug, Clone, QueryableByName)]
+pub struct LiveEvent {
+    #[sql_type = "Int8"]
+    pub id: i64,
+    #[sql_type = "Text"]
+    pub event: String,
+    #[sql_type = "Array<Int8>"]
+    pub collections: Vec<SelectChoiceId>,
+    #[sql_type = "Array<Int8>"]
+    pub users: Vec<UserId>,
+    #[sql_type = "Jsonb"]
+    pub payload: serde_json::Value,
+    #[sql_type = "Timestamptz"]
+    pub created_at: DateTime<Utc>,
+}
+
+impl LiveEvent {
+    /// What is published and sent to browsers as the `data` of the event
+    pub fn message(&self) -> String {
+        json!({
+            "id": self.id,
+            "event": self.event,
+            "created_at": self.created_at,
+            "data": self.payload,
+        })
+        .to_string()
+    }
+}
@@ -0,0 +1,103 @@
+use ::actix::prelude::*;
+use actix_redis::{Command, RedisActor, RespValue};
+use actix_web::{error, Error};
+use futures::{future, Future};
+use std::time::Duration;
+
+use super::{collection_channel, user_channel, LiveEvent};
+use crate::db::{DbExecutor, TakeLiveEvents};
+
+/// How often the queue of live events is checked
+const POLL_EVERY_MILLIS: u64 = 500;
+/// Events taken from the queue at a time
+const BATCH_SIZE: i64 = 100;
+
+/// Publishes queued live events to the Redis channels of their collections and
+/// users. Events are taken off the queue as they are published, so any number
+/// of server processes may run a publisher, and an event which fails to
+/// publish is dropped rather than retried.
+pub struct LivePublisher {
+    pub pg: Addr<DbExecutor>,
+    pub redis: Addr<RedisActor>,
+    busy: bool,
+}
+
+impl LivePublisher {
+    pub fn new(pg: Addr<DbExecutor>, redis: Addr<RedisActor>) -> Self {
+        LivePublisher {
+            pg,
+            redis,
+            busy: false,
+        }
+    }
+
+    fn publish_queued(&mut self, ctx: &mut Context<Self>) {
+        if self.busy {
+            return;
+        }
+        self.busy = true;
+
+        let redis = self.redis.clone();
+        let work = self
+            .pg
+            .send(TakeLiveEvents { limit: BATCH_SIZE })
+            .from_err::<Error>()
+            .and_then(|res| res)
+            .and_then(move |events| {
+                future::join_all(
+                    events
+                        .into_iter()
+                        .flat_map(|event| {
+                            let message = event.message();
+                            channels_of(&event)
+                                .into_iter()
+                                .map(move |channel| (channel, message.clone()))
+                        })
+                        .map(move |(channel, message)| publish(&redis, channel, message)),
+                )
+            })
+            .into_actor(self)
+            .then(|res, act, _ctx| {
+                if let Err(e) = res {
+                    error!("LivePublisher error: {:?}", e);
+                }
+                act.busy = false;
+                actix::fut::ok(())
+            });
+        ctx.spawn(work);
+    }
+}
+
+fn channels_of(event: &LiveEvent) -> Vec<String> {
+    event
+        .collections
+        .iter()
+        .map(collection_channel)
+        .chain(event.users.iter().map(user_channel))
+        .collect()
+}
+
+fn publish(
+    redis: &Addr<RedisActor>,
+    channel: String,
+    message: String,
+) -> impl Future<Item = (), Error = Error> {
+    redis
+        .send(Command(resp_array!["PUBLISH", channel, message]))
+        .map_err(Error::from)
+        .and_then(|res| match res {
+            Ok(RespValue::Error(err)) => Err(error::ErrorInternalServerError(err)),
+            Ok(_) => Ok(()),
+            Err(err) => Err(error::ErrorInternalServerError(err)),
+        })
+}
+
+impl Actor for LivePublisher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(Duration::from_millis(POLL_EVERY_MILLIS), |act, ctx| {
+            act.publish_queued(ctx)
+        });
+    }
+}
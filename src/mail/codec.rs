@@ -0,0 +1,88 @@
+use bytes::{BufMut, BytesMut};
+use std::io;
+use tokio_codec::{Decoder, Encoder};
+
+/// Longest line read, well above the 1000 characters mail allows
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+/// Lines of a mail conversation, read as bytes as message content need not
+/// be UTF-8, without their line endings. Replies are written with CRLF.
+pub struct LineCodec;
+
+impl Decoder for LineCodec {
+    type Item = Vec<u8>;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Vec<u8>>> {
+        match src.iter().position(|&byte| byte == b'\n') {
+            Some(newline) => {
+                let line = src.split_to(newline + 1);
+                let mut line = line[..newline].to_vec();
+                if line.last() == Some(&b'\r') {
+                    line.pop();
+                }
+                Ok(Some(line))
+            }
+            None if src.len() > MAX_LINE_BYTES => {
+                Err(io::Error::new(io::ErrorKind::InvalidData, "line too long"))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder for LineCodec {
+    type Item = String;
+    type Error = io::Error;
+
+    fn encode(&mut self, reply: String, dst: &mut BytesMut) -> io::Result<()> {
+        dst.reserve(reply.len() + 2);
+        dst.put(reply.as_bytes());
+        dst.put("\r\n");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_lines() {
+        let mut codec = LineCodec;
+        let mut src = BytesMut::from(&b"EHLO a\r\nNOOP\n\r\nDAT"[..]);
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(b"EHLO a".to_vec()));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(b"NOOP".to_vec()));
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(Vec::new()));
+        assert_eq!(codec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(b"A\r\n");
+        assert_eq!(codec.decode(&mut src).unwrap(), Some(b"DATA".to_vec()));
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn decode_bytes() {
+        let mut src = BytesMut::from(&b"caf\xe9 \xff\r\n"[..]);
+        assert_eq!(
+            LineCodec.decode(&mut src).unwrap(),
+            Some(b"caf\xe9 \xff".to_vec())
+        );
+    }
+
+    #[test]
+    fn line_too_long() {
+        let mut src = BytesMut::from(vec![b'a'; MAX_LINE_BYTES]);
+        assert_eq!(LineCodec.decode(&mut src).unwrap(), None);
+        src.extend_from_slice(b"a");
+        let error = LineCodec.decode(&mut src).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn encode_replies() {
+        let mut dst = BytesMut::new();
+        LineCodec.encode("250 OK".to_string(), &mut dst).unwrap();
+        LineCodec.encode("221 Bye".to_string(), &mut dst).unwrap();
+        assert_eq!(&dst[..], &b"250 OK\r\n221 Bye\r\n"[..]);
+    }
+}
@@ -0,0 +1,260 @@
+use ::actix::prelude::*;
+use futures::Stream;
+use std::io;
+use std::net::SocketAddr;
+use tokio_tcp::{TcpListener, TcpStream};
+
+use super::session::MailSession;
+use super::MailProtocol;
+use crate::object::Ingest;
+
+/// Accepts mail connections, each handled by a `MailSession` of its own
+pub struct MailListener {
+    ingest: Ingest,
+    protocol: MailProtocol,
+    hostname: String,
+}
+
+struct Connection(TcpStream);
+
+impl Message for Connection {
+    type Result = ();
+}
+
+impl MailListener {
+    /// Listen on `addr`, failing if it can't be bound
+    pub fn listen(
+        addr: &SocketAddr,
+        protocol: MailProtocol,
+        hostname: String,
+        ingest: Ingest,
+    ) -> io::Result<Addr<MailListener>> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(MailListener::create(move |ctx| {
+            ctx.add_message_stream(
+                listener
+                    .incoming()
+                    .map_err(|e| error!("MailListener accept error: {}", e))
+                    .map(Connection),
+            );
+            MailListener {
+                ingest,
+                protocol,
+                hostname,
+            }
+        }))
+    }
+}
+
+impl Actor for MailListener {
+    type Context = Context<Self>;
+}
+
+impl Handler<Connection> for MailListener {
+    type Result = ();
+
+    fn handle(&mut self, msg: Connection, _: &mut Context<Self>) {
+        let peer = msg
+            .0
+            .peer_addr()
+            .map_or_else(|_| "unknown".to_string(), |addr| addr.to_string());
+        info!("{} connection from {}", self.protocol, peer);
+        MailSession::start(
+            msg.0,
+            peer,
+            self.protocol,
+            self.hostname.clone(),
+            self.ingest.clone(),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use diesel::r2d2::{ConnectionManager, Pool};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net;
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::db::{CreateChoice, CreateDropBoxToken, DbExecutor, RegisterPlugin};
+    use crate::object::ObjectStore;
+    use crate::property::PropertyId;
+    use crate::sessions::TokenScope;
+    use crate::user::UserId;
+
+    /// Start a listener on a free port of its own system, with a database
+    /// which is only connected to once a recipient is checked
+    fn listen(database_url: &str) -> (SocketAddr, Addr<DbExecutor>) {
+        let addr = net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+        let pool = Pool::builder()
+            .max_size(1)
+            .connection_timeout(Duration::from_secs(5))
+            .build_unchecked(ConnectionManager::new(database_url));
+        let (started, db) = mpsc::channel();
+        thread::spawn(move || {
+            let sys = System::new("mail-test");
+            let db = SyncArbiter::start(1, move || DbExecutor(pool.clone()));
+            let store = ObjectStore::new_with_s3_credentials("test", "test")
+                .unwrap()
+                .start();
+            let ingest = Ingest {
+                db: db.clone(),
+                store,
+            };
+            MailListener::listen(&addr, MailProtocol::Smtp, "dewey.test".to_string(), ingest)
+                .unwrap();
+            started.send(db).unwrap();
+            sys.run();
+        });
+        (addr, db.recv().unwrap())
+    }
+
+    struct Client {
+        stream: net::TcpStream,
+        reader: BufReader<net::TcpStream>,
+    }
+
+    impl Client {
+        fn connect(addr: &SocketAddr) -> Client {
+            let stream = net::TcpStream::connect(addr).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            let reader = BufReader::new(stream.try_clone().unwrap());
+            Client { stream, reader }
+        }
+
+        /// The last line of the next reply
+        fn reply(&mut self) -> String {
+            loop {
+                let mut line = String::new();
+                self.reader.read_line(&mut line).unwrap();
+                if line.get(3..4) != Some("-") {
+                    return line.trim_end().to_string();
+                }
+            }
+        }
+
+        fn send(&mut self, line: &str) -> String {
+            self.stream
+                .write_all(format!("{}\r\n", line).as_bytes())
+                .unwrap();
+            self.reply()
+        }
+    }
+
+    fn code(reply: &str) -> &str {
+        &reply[..3]
+    }
+
+    #[test]
+    fn conversation() {
+        let (addr, _) = listen("postgres://localhost:1/unused");
+        let mut client = Client::connect(&addr);
+        assert_eq!(client.reply(), "220 dewey.test Dewey SMTP drop-box ready");
+        assert_eq!(code(&client.send("MAIL FROM:<a@example.com>")), "503");
+        assert_eq!(client.send("EHLO client.test"), "250 ENHANCEDSTATUSCODES");
+        assert_eq!(code(&client.send("LHLO client.test")), "500");
+        assert_eq!(code(&client.send("RCPT TO:<1+dbx_abc@dewey.test>")), "503");
+        assert_eq!(code(&client.send("DATA")), "503");
+        assert_eq!(
+            code(&client.send("MAIL FROM:<a@example.com> SIZE=100")),
+            "250"
+        );
+        assert_eq!(code(&client.send("MAIL FROM:<a@example.com>")), "503");
+        assert_eq!(
+            client.send("RCPT TO:<inbox@dewey.test>"),
+            "550 5.1.1 No such drop-box"
+        );
+        assert_eq!(code(&client.send("RCPT <inbox@dewey.test>")), "501");
+        assert_eq!(code(&client.send("DATA")), "554");
+        assert_eq!(code(&client.send("RSET")), "250");
+        assert_eq!(
+            code(&client.send("MAIL FROM:<a@example.com> SIZE=999999999")),
+            "552"
+        );
+        assert_eq!(code(&client.send("NOOP")), "250");
+        assert_eq!(code(&client.send("TURN")), "502");
+        assert_eq!(client.send("QUIT"), "221 2.0.0 Bye");
+    }
+
+    #[test]
+    fn flood() {
+        // the recipient is checked against a database which can't be
+        // connected to, so the lines after it are held until that times out
+        let (addr, _) = listen("postgres://localhost:1/unused");
+        let mut client = Client::connect(&addr);
+        client.reply();
+        client.send("EHLO client.test");
+        client.send("MAIL FROM:<a@example.com>");
+        let mut flood = "RCPT TO:<1+dbx_abc@dewey.test>\r\n".to_string();
+        flood.push_str(&"NOOP\r\n".repeat(200));
+        client.stream.write_all(flood.as_bytes()).unwrap();
+        assert_eq!(client.reply(), "421 4.7.0 Too many commands at once");
+    }
+
+    /// Needs `DATABASE_URL` of a migrated database
+    #[test]
+    #[ignore]
+    fn drop_box_tokens() {
+        let database_url = std::env::var("DATABASE_URL").unwrap();
+        let (addr, db) = listen(&database_url);
+        let mut sys = System::new("drop-box");
+        let name = format!("mail-test-{}", Utc::now().timestamp_millis());
+        let (_, api_secret) = sys
+            .block_on(db.send(RegisterPlugin {
+                name: name.clone(),
+                display_name: "Mail test".to_string(),
+                description: String::new(),
+                homepage: None,
+                scope: TokenScope::Write,
+                registered_by: UserId::ADMINISTRATOR,
+            }))
+            .unwrap()
+            .unwrap();
+        let collection = sys
+            .block_on(db.send(CreateChoice {
+                property_id: PropertyId::COLLECTION,
+                display: name,
+                created_by: UserId::ADMINISTRATOR,
+            }))
+            .unwrap()
+            .unwrap();
+        let (_, secret) = sys
+            .block_on(db.send(CreateDropBoxToken {
+                user_id: UserId::ADMINISTRATOR,
+                collection_id: collection.clone(),
+            }))
+            .unwrap()
+            .unwrap();
+
+        let mut client = Client::connect(&addr);
+        client.reply();
+        client.send("EHLO client.test");
+        client.send("MAIL FROM:<a@example.com>");
+        // API tokens, unknown tokens and other collections get the same reply
+        for address in &[
+            format!("{}+{}", collection, api_secret),
+            format!("{}+dbx_unknown", collection),
+            format!("20+{}", secret),
+        ] {
+            assert_eq!(
+                client.send(&format!("RCPT TO:<{}@dewey.test>", address)),
+                "550 5.1.1 No such drop-box"
+            );
+        }
+        assert_eq!(code(&client.send("DATA")), "554");
+        assert_eq!(
+            client.send(&format!("RCPT TO:<{}+{}@dewey.test>", collection, secret)),
+            "250 2.1.5 OK"
+        );
+        assert_eq!(code(&client.send("RSET")), "250");
+        assert_eq!(client.send("QUIT"), "221 2.0.0 Bye");
+    }
+}
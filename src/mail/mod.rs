@@ -0,0 +1,109 @@
+//! A mail drop-box, so documents can be forwarded into a collection. Mail for
+//! `<collection id>+<drop-box token>@<host>` is filed into that collection,
+//! created by the token's owner, the same way as an uploaded `.eml`. It speaks
+//! SMTP to mail clients, or LMTP behind a mail server.
+use std::fmt;
+
+use crate::property::SelectChoiceId;
+
+mod codec;
+mod listener;
+mod session;
+pub use listener::MailListener;
+
+mod token;
+pub use token::{new_drop_box_secret, DropBoxToken, DropBoxTokenId};
+
+/// Largest message accepted, in bytes
+pub const MAX_MESSAGE_BYTES: usize = 25 * 1024 * 1024;
+/// Recipients accepted for one message
+pub const MAX_RECIPIENTS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum MailProtocol {
+    #[default]
+    Smtp,
+    /// Like SMTP, but with a reply for each recipient once a message is sent,
+    /// for delivery from a mail server
+    Lmtp,
+}
+
+impl MailProtocol {
+    pub fn parse(name: &str) -> Option<MailProtocol> {
+        match name.trim().to_lowercase().as_str() {
+            "smtp" => Some(MailProtocol::Smtp),
+            "lmtp" => Some(MailProtocol::Lmtp),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MailProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MailProtocol::Smtp => write!(f, "SMTP"),
+            MailProtocol::Lmtp => write!(f, "LMTP"),
+        }
+    }
+}
+
+/// The drop-box address of a collection, e.g. `12+dbx_abc@dewey.example.com`
+pub fn drop_box_address(collection: &SelectChoiceId, secret: &str, hostname: &str) -> String {
+    format!("{}+{}@{}", collection, secret, hostname)
+}
+
+/// The collection and token secret of an address like
+/// `<12+dbx_abc@dewey.example.com>`, whatever its domain
+pub fn parse_recipient(address: &str) -> Option<(SelectChoiceId, String)> {
+    let address = address.trim().trim_start_matches('<').trim_end_matches('>');
+    let local = match address.rfind('@') {
+        Some(at) => &address[..at],
+        None => address,
+    };
+    let mut parts = local.splitn(2, '+');
+    let collection = parts.next()?.parse::<i64>().ok()?;
+    let secret = parts.next().filter(|secret| !secret.is_empty())?;
+    Some((SelectChoiceId::from(collection), secret.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recipients() {
+        assert_eq!(
+            parse_recipient("<12+dbx_abc@dewey.example.com>"),
+            Some((SelectChoiceId::from(12), "dbx_abc".to_string()))
+        );
+        assert_eq!(
+            parse_recipient(" 12+dbx_a+b@c@example.com "),
+            Some((SelectChoiceId::from(12), "dbx_a+b@c".to_string()))
+        );
+        assert_eq!(
+            parse_recipient("12+dbx_abc"),
+            Some((SelectChoiceId::from(12), "dbx_abc".to_string()))
+        );
+        assert_eq!(parse_recipient("<12@dewey.example.com>"), None);
+        assert_eq!(parse_recipient("<12+@dewey.example.com>"), None);
+        assert_eq!(parse_recipient("<inbox+dbx_abc@dewey.example.com>"), None);
+        assert_eq!(parse_recipient("<>"), None);
+    }
+
+    #[test]
+    fn addresses() {
+        let address = drop_box_address(&SelectChoiceId::from(12), "dbx_abc", "dewey.example.com");
+        assert_eq!(address, "12+dbx_abc@dewey.example.com");
+        assert_eq!(
+            parse_recipient(&address),
+            Some((SelectChoiceId::from(12), "dbx_abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn protocols() {
+        assert_eq!(MailProtocol::parse(" LMTP"), Some(MailProtocol::Lmtp));
+        assert_eq!(MailProtocol::parse("smtp"), Some(MailProtocol::Smtp));
+        assert_eq!(MailProtocol::parse("imap"), None);
+    }
+}
@@ -0,0 +1,480 @@
+use ::actix::io::{FramedWrite, WriteHandler};
+use ::actix::prelude::*;
+use actix_web::Error;
+use futures::future::{self, Future};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::collections::VecDeque;
+use std::io;
+use std::mem;
+use std::time::{Duration, Instant};
+use tokio_codec::FramedRead;
+use tokio_io::io::WriteHalf;
+use tokio_io::AsyncRead;
+use tokio_tcp::TcpStream;
+
+use super::codec::LineCodec;
+use super::{parse_recipient, MailProtocol, MAX_MESSAGE_BYTES, MAX_RECIPIENTS};
+use crate::db::AuthenticateDropBoxToken;
+use crate::object::{email_filename, Ingest, Upload};
+use crate::property::SelectChoiceId;
+use crate::user::UserId;
+
+/// How long a client may say nothing before it is disconnected
+const IDLE_TIMEOUT_SECONDS: u64 = 300;
+/// Lines held while a recipient is checked or a message filed, well above
+/// what a pipelining client sends before waiting for a reply
+const MAX_PENDING_LINES: usize = 100;
+
+enum Stage {
+    /// Waiting for `HELO`, `EHLO`, or `LHLO` with LMTP
+    Greeting,
+    Ready,
+    /// After `MAIL FROM`, collecting recipients
+    Envelope,
+    /// Reading the message, after `DATA`
+    Data,
+}
+
+/// The one reply to addresses which are not drop-boxes, whatever was wrong,
+/// so clients can't tell tokens or collections which exist from others
+const NO_SUCH_DROP_BOX: &str = "550 5.1.1 No such drop-box";
+
+/// A drop-box address whose token was accepted
+struct Recipient {
+    address: String,
+    collection: SelectChoiceId,
+    user_id: UserId,
+}
+
+/// One client's conversation. Lines arriving while a recipient is checked or
+/// a message filed are handled once that is done, so clients may pipeline.
+pub struct MailSession {
+    peer: String,
+    protocol: MailProtocol,
+    hostname: String,
+    ingest: Ingest,
+    writer: FramedWrite<WriteHalf<TcpStream>, LineCodec>,
+    stage: Stage,
+    sender: String,
+    recipients: Vec<Recipient>,
+    message: Vec<u8>,
+    too_large: bool,
+    busy: bool,
+    pending: VecDeque<Vec<u8>>,
+    /// The client is gone, stop once what it sent is handled
+    disconnected: bool,
+    /// The last reply is sent, nothing more is read or replied
+    closing: bool,
+    last_seen: Instant,
+}
+
+impl MailSession {
+    pub fn start(
+        stream: TcpStream,
+        peer: String,
+        protocol: MailProtocol,
+        hostname: String,
+        ingest: Ingest,
+    ) -> Addr<MailSession> {
+        MailSession::create(move |ctx| {
+            let (reader, writer) = stream.split();
+            ctx.add_stream(FramedRead::new(reader, LineCodec));
+            MailSession {
+                peer,
+                protocol,
+                hostname,
+                ingest,
+                writer: FramedWrite::new(writer, LineCodec, ctx),
+                stage: Stage::Greeting,
+                sender: String::new(),
+                recipients: Vec::new(),
+                message: Vec::new(),
+                too_large: false,
+                busy: false,
+                pending: VecDeque::new(),
+                disconnected: false,
+                closing: false,
+                last_seen: Instant::now(),
+            }
+        })
+    }
+
+    fn reply<S: Into<String>>(&mut self, reply: S) {
+        if !self.closing {
+            self.writer.write(reply.into());
+        }
+    }
+
+    /// Reply, and close the connection once the reply is sent
+    fn close(&mut self, reply: &str) {
+        self.reply(reply);
+        self.closing = true;
+        self.pending.clear();
+        self.writer.close();
+    }
+
+    fn reset(&mut self) {
+        self.sender.clear();
+        self.recipients.clear();
+        self.message.clear();
+        self.too_large = false;
+        if let Stage::Envelope = self.stage {
+            self.stage = Stage::Ready;
+        }
+    }
+
+    /// Handle the lines which arrived while busy, until busy again
+    fn resume(&mut self, ctx: &mut Context<Self>) {
+        while !self.busy {
+            match self.pending.pop_front() {
+                Some(line) => self.handle_line(line, ctx),
+                None => break,
+            }
+        }
+        if self.disconnected && !self.busy {
+            ctx.stop();
+        }
+    }
+
+    fn handle_line(&mut self, line: Vec<u8>, ctx: &mut Context<Self>) {
+        if let Stage::Data = self.stage {
+            return self.read_data(line, ctx);
+        }
+        let line = String::from_utf8_lossy(&line).into_owned();
+        let mut parts = line.trim().splitn(2, ' ');
+        let verb = parts.next().unwrap_or("").to_uppercase();
+        let args = parts.next().unwrap_or("").trim();
+
+        match (verb.as_str(), self.protocol) {
+            ("HELO", MailProtocol::Smtp) => {
+                self.reset();
+                self.stage = Stage::Ready;
+                let reply = format!("250 {}", self.hostname);
+                self.reply(reply);
+            }
+            ("EHLO", MailProtocol::Smtp) | ("LHLO", MailProtocol::Lmtp) => {
+                self.reset();
+                self.stage = Stage::Ready;
+                let reply = format!("250-{}", self.hostname);
+                self.reply(reply);
+                self.reply(format!("250-SIZE {}", MAX_MESSAGE_BYTES));
+                self.reply("250-8BITMIME");
+                self.reply("250-PIPELINING");
+                self.reply("250 ENHANCEDSTATUSCODES");
+            }
+            ("HELO", _) | ("EHLO", _) | ("LHLO", _) => {
+                let reply = format!("500 5.5.1 This is an {} server", self.protocol);
+                self.reply(reply);
+            }
+            ("MAIL", _) => self.mail_from(args),
+            ("RCPT", _) => self.rcpt_to(args, ctx),
+            ("DATA", _) => match self.stage {
+                Stage::Envelope if !self.recipients.is_empty() => {
+                    self.stage = Stage::Data;
+                    self.reply("354 End data with <CR><LF>.<CR><LF>");
+                }
+                Stage::Envelope => self.reply("554 5.5.1 No valid recipients"),
+                _ => self.reply("503 5.5.1 MAIL FROM first"),
+            },
+            ("RSET", _) => {
+                self.reset();
+                self.reply("250 2.0.0 OK");
+            }
+            ("NOOP", _) => self.reply("250 2.0.0 OK"),
+            ("VRFY", _) => self.reply("252 2.1.5 Send some mail and see"),
+            ("QUIT", _) => self.close("221 2.0.0 Bye"),
+            _ => self.reply("502 5.5.2 Command not implemented"),
+        }
+    }
+
+    fn mail_from(&mut self, args: &str) {
+        match self.stage {
+            Stage::Greeting => return self.reply("503 5.5.1 Say hello first"),
+            Stage::Envelope => return self.reply("503 5.5.1 Sender already given"),
+            _ => {}
+        }
+        let (sender, params) = match path_argument(args, "FROM:") {
+            Some(path) => path,
+            None => return self.reply("501 5.5.4 Syntax: MAIL FROM:<address>"),
+        };
+        let size = params
+            .split_whitespace()
+            .filter_map(|param| {
+                let mut pair = param.splitn(2, '=');
+                match pair.next() {
+                    Some(key) if key.eq_ignore_ascii_case("SIZE") => pair.next(),
+                    _ => None,
+                }
+            })
+            .next()
+            .and_then(|size| size.parse::<usize>().ok());
+        if size.map_or(false, |size| size > MAX_MESSAGE_BYTES) {
+            return self.reply("552 5.3.4 Message too big");
+        }
+        self.sender = sender;
+        self.stage = Stage::Envelope;
+        self.reply("250 2.1.0 OK");
+    }
+
+    /// Accept a recipient once its token is found to be a drop-box token of
+    /// its collection
+    fn rcpt_to(&mut self, args: &str, ctx: &mut Context<Self>) {
+        match self.stage {
+            Stage::Envelope => {}
+            _ => return self.reply("503 5.5.1 MAIL FROM first"),
+        }
+        let address = match path_argument(args, "TO:") {
+            Some((address, _)) => address,
+            None => return self.reply("501 5.5.4 Syntax: RCPT TO:<address>"),
+        };
+        if self.recipients.len() >= MAX_RECIPIENTS {
+            return self.reply("452 4.5.3 Too many recipients");
+        }
+        let (collection, secret) = match parse_recipient(&address) {
+            Some(recipient) => recipient,
+            None => return self.reply(NO_SUCH_DROP_BOX),
+        };
+
+        self.busy = true;
+        let check = self
+            .ingest
+            .db
+            .send(AuthenticateDropBoxToken {
+                secret,
+                collection_id: collection.clone(),
+            })
+            .from_err::<Error>()
+            .and_then(|res| res)
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Some(user_id)) => {
+                        act.recipients.push(Recipient {
+                            address,
+                            collection,
+                            user_id,
+                        });
+                        act.reply("250 2.1.5 OK");
+                    }
+                    Ok(None) => act.reply(NO_SUCH_DROP_BOX),
+                    Err(e) => {
+                        error!("MailSession recipient check error: {:?}", e);
+                        act.reply("451 4.3.0 Try again later");
+                    }
+                }
+                act.busy = false;
+                act.resume(ctx);
+                actix::fut::ok(())
+            });
+        ctx.spawn(check);
+    }
+
+    fn read_data(&mut self, line: Vec<u8>, ctx: &mut Context<Self>) {
+        if line == b"." {
+            return self.deliver(ctx);
+        }
+        if self.too_large {
+            return;
+        }
+        // a leading dot is doubled by the client
+        let line = if line.starts_with(b".") {
+            &line[1..]
+        } else {
+            &line[..]
+        };
+        if self.message.len() + line.len() + 2 > MAX_MESSAGE_BYTES {
+            self.too_large = true;
+            self.message = Vec::new();
+            return;
+        }
+        self.message.extend_from_slice(line);
+        self.message.extend_from_slice(b"\r\n");
+    }
+
+    /// File the message into the collection of each recipient, once for
+    /// recipients of the same collection and user, then reply once with SMTP,
+    /// or for each recipient with LMTP
+    fn deliver(&mut self, ctx: &mut Context<Self>) {
+        let sender = mem::take(&mut self.sender);
+        let recipients = mem::take(&mut self.recipients);
+        let message = mem::take(&mut self.message);
+        self.stage = Stage::Ready;
+        let replies = match self.protocol {
+            MailProtocol::Smtp => 1,
+            MailProtocol::Lmtp => recipients.len(),
+        };
+        if self.too_large {
+            self.too_large = false;
+            for _ in 0..replies {
+                self.reply("552 5.3.4 Message too big");
+            }
+            return;
+        }
+
+        let filename = email_filename(&message, "message");
+        let request_id: String = thread_rng().sample_iter(&Alphanumeric).take(16).collect();
+        let mut targets: Vec<(SelectChoiceId, UserId)> = Vec::new();
+        for recipient in &recipients {
+            let target = (recipient.collection.clone(), recipient.user_id.clone());
+            if !targets.contains(&target) {
+                targets.push(target);
+            }
+        }
+        let ingest = self.ingest.clone();
+        let deliveries: Vec<_> = targets
+            .iter()
+            .map(|(collection, user_id)| {
+                ingest
+                    .create_objects(
+                        Upload {
+                            filename: filename.clone(),
+                            content: message.clone(),
+                            modified: None,
+                        },
+                        user_id.clone(),
+                        Some(collection.clone()),
+                        Some(format!("mail-{}", request_id)),
+                    )
+                    .then(future::ok::<_, Error>)
+            })
+            .collect();
+
+        self.busy = true;
+        let filing = future::join_all(deliveries)
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                let outcomes = res.unwrap_or_default();
+                for ((collection, _), outcome) in targets.iter().zip(&outcomes) {
+                    match outcome {
+                        Ok(object_ids) => info!(
+                            "Filed mail from <{}> into collection {} as {:?}",
+                            sender, collection, object_ids
+                        ),
+                        Err(e) => error!("MailSession filing error: {:?}", e),
+                    }
+                }
+                let filed = |recipient: &Recipient| {
+                    let target = (recipient.collection.clone(), recipient.user_id.clone());
+                    targets
+                        .iter()
+                        .position(|found| *found == target)
+                        .map_or(false, |index| outcomes[index].is_ok())
+                };
+                if act.protocol == MailProtocol::Lmtp {
+                    for recipient in &recipients {
+                        let reply = if filed(recipient) {
+                            format!("250 2.0.0 <{}> filed", recipient.address)
+                        } else {
+                            format!(
+                                "451 4.3.0 <{}> not filed, try again later",
+                                recipient.address
+                            )
+                        };
+                        act.reply(reply);
+                    }
+                } else if recipients.iter().all(filed) {
+                    act.reply("250 2.0.0 Filed");
+                } else {
+                    act.reply("451 4.3.0 Not filed, try again later");
+                }
+                act.busy = false;
+                act.resume(ctx);
+                actix::fut::ok(())
+            });
+        ctx.spawn(filing);
+    }
+}
+
+/// The address and the parameters after it of e.g. `FROM:<a@b.c> SIZE=100`
+fn path_argument(args: &str, keyword: &str) -> Option<(String, String)> {
+    match args.get(..keyword.len()) {
+        Some(start) if start.eq_ignore_ascii_case(keyword) => {}
+        _ => return None,
+    }
+    let rest = args[keyword.len()..].trim_start();
+    let (address, params) = if rest.starts_with('<') {
+        let end = rest.find('>')?;
+        (&rest[1..end], &rest[end + 1..])
+    } else {
+        let end = rest.find(' ').unwrap_or_else(|| rest.len());
+        (&rest[..end], &rest[end..])
+    };
+    Some((address.trim().to_string(), params.trim().to_string()))
+}
+
+impl Actor for MailSession {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let greeting = format!(
+            "220 {} Dewey {} drop-box ready",
+            self.hostname, self.protocol
+        );
+        self.reply(greeting);
+        ctx.run_interval(Duration::from_secs(30), |act, _ctx| {
+            if !act.busy && act.last_seen.elapsed() > Duration::from_secs(IDLE_TIMEOUT_SECONDS) {
+                info!("Closing idle mail connection from {}", act.peer);
+                act.close("421 4.4.2 Idle for too long");
+            }
+        });
+    }
+}
+
+impl StreamHandler<Vec<u8>, io::Error> for MailSession {
+    fn handle(&mut self, line: Vec<u8>, ctx: &mut Self::Context) {
+        self.last_seen = Instant::now();
+        if self.closing {
+            return;
+        }
+        if self.busy {
+            if self.pending.len() >= MAX_PENDING_LINES {
+                warn!("Closing mail connection from {}, which sent too much at once", self.peer);
+                return self.close("421 4.7.0 Too many commands at once");
+            }
+            self.pending.push_back(line);
+        } else {
+            self.handle_line(line, ctx);
+        }
+    }
+
+    fn error(&mut self, e: io::Error, _: &mut Self::Context) -> Running {
+        warn!("Mail connection from {} failed: {}", self.peer, e);
+        Running::Stop
+    }
+
+    fn finished(&mut self, ctx: &mut Self::Context) {
+        self.disconnected = true;
+        if !self.busy {
+            ctx.stop();
+        }
+    }
+}
+
+impl WriteHandler<io::Error> for MailSession {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(address: &str, params: &str) -> Option<(String, String)> {
+        Some((address.to_string(), params.to_string()))
+    }
+
+    #[test]
+    fn path_arguments() {
+        assert_eq!(path_argument("FROM:<a@b.c>", "FROM:"), parsed("a@b.c", ""));
+        assert_eq!(
+            path_argument("from: <a@b.c> SIZE=100 BODY=8BITMIME", "FROM:"),
+            parsed("a@b.c", "SIZE=100 BODY=8BITMIME")
+        );
+        assert_eq!(path_argument("FROM:<>", "FROM:"), parsed("", ""));
+        assert_eq!(
+            path_argument("TO:a@b.c SIZE=1", "TO:"),
+            parsed("a@b.c", "SIZE=1")
+        );
+        assert_eq!(path_argument("TO:<a@b.c", "TO:"), None);
+        assert_eq!(path_argument("FROM:<a@b.c>", "TO:"), None);
+        assert_eq!(path_argument("TO", "TO:"), None);
+    }
+}
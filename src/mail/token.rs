@@ -0,0 +1,44 @@
+use ::chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+use crate::property::SelectChoiceId;
+use crate::user::UserId;
+
+/// Prefix of every secret, telling drop-box tokens from API tokens
+const SECRET_PREFIX: &str = "dbx_";
+const SECRET_LENGTH: usize = 40;
+
+/// Represents a DropBoxTokenId
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(DieselNewType)]
+pub struct DropBoxTokenId(i64);
+
+use std::fmt;
+
+impl fmt::Display for DropBoxTokenId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// The token of a drop-box address, without its secret
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct DropBoxToken {
+    pub id: DropBoxTokenId,
+    pub user_id: UserId,
+    pub collection_id: SelectChoiceId,
+    /// Name of the collection
+    pub collection: String,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A new random secret to hand to the token's owner
+pub fn new_drop_box_secret() -> String {
+    let random: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .collect();
+    format!("{}{}", SECRET_PREFIX, random)
+}
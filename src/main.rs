@@ -1,3 +1,5 @@
+#![recursion_limit = "256"] // for the large json! of the OpenAPI schemas
+
 #[allow(unused_imports)]
 #[macro_use]
 extern crate diesel;
@@ -6,65 +8,133 @@ extern crate diesel_derive_newtype;
 #[macro_use]
 extern crate diesel_derive_enum;
 #[macro_use]
+extern crate diesel_migrations;
+#[macro_use]
 extern crate serde_derive;
 #[macro_use]
+extern crate serde_json;
+#[macro_use]
 extern crate redis_async;
 extern crate actix_web;
 extern crate dotenv;
 #[macro_use]
-extern crate dotenv_codegen;
-#[macro_use]
 extern crate log;
 extern crate askama; // for the Template trait and custom derive macro
+extern crate regex;
 
+pub mod live;
+pub mod mail;
 pub mod object;
 pub mod property;
+pub mod query;
 pub mod user;
+pub mod webhook;
 mod app;
+mod cli;
+mod config;
 
 use actix::Addr;
-use actix_redis::RedisActor;
+use std::sync::Arc;
 
 mod db;
 use db::DbExecutor;
 use clap::{App, Arg, SubCommand};
 
 mod sessions;
-use sessions::flash::SessionFlash;
 use sessions::session_manager::SessionManager;
-use sessions::session_routes::{self, is_signed_in_guard, SigninState}; // enable inserting and applying flash messages to the page
 
 pub use object::store;
 use self::store::ObjectStore;
+use object::Ingest;
 
 /// State with DbExecutor address
 pub struct State {
     db: Addr<DbExecutor>,
-    mem: Addr<RedisActor>,
     sessions: Addr<SessionManager>,
     store: Addr<ObjectStore>,
+    config: Arc<config::Config>,
+}
+
+impl State {
+    /// Storing new objects and versions
+    pub fn ingest(&self) -> Ingest {
+        Ingest {
+            db: self.db.clone(),
+            store: self.store.clone(),
+        }
+    }
 }
 
 mod logging;
 
+/// The configuration, or exit describing what is wrong with `sections` of it
+fn load_config(overrides: &config::Overrides, sections: &[config::Section]) -> config::Config {
+    match config::Config::load(overrides, sections) {
+        Ok(config) => config,
+        Err(e) => {
+            eprint!("{}", e);
+            std::process::exit(2)
+        }
+    }
+}
+
 fn main() {
     let args = App::new("Dewey Collect")
-        .version(dotenv!("CARGO_PKG_VERSION"))
-        .author(dotenv!("CARGO_PKG_AUTHORS"))
+        .version(env!("CARGO_PKG_VERSION"))
+        .author(env!("CARGO_PKG_AUTHORS"))
         .about("File collection and organization")
         .arg(Arg::with_name("debug")
              .short("d")
              .help("Verbose output for troubleshooting"))
+        .arg(Arg::with_name("config")
+             .short("c")
+             .long("config")
+             .value_name("FILE")
+             .global(true)
+             .help("TOML file of settings, overridden by environment variables [default: $DEWEY_CONFIG]"))
         .subcommand(SubCommand::with_name("start")
                     .about("Starts the Dewey Collect Web Application Server")
                     .arg(Arg::with_name("port")
                          .short("p")
+                         .long("port")
                          .value_name("PORT")
-                         .help("Specify the port to start on [default: 8088]")))
+                         .help("Specify the port to start on [default: 8088]"))
+                    .arg(Arg::with_name("bind")
+                         .short("b")
+                         .long("bind")
+                         .value_name("ADDRESS")
+                         .help("Specify the address to listen on [default: 127.0.0.1]")))
+        .subcommands(cli::subcommands())
         .get_matches();
 
-    match args.subcommand_name() {
-        Some("start") => app::start(),
+    match args.subcommand() {
+        ("start", Some(start_args)) => app::start(load_config(
+            &config::Overrides {
+                config_file: start_args.value_of("config").map(String::from),
+                bind: start_args.value_of("bind").map(String::from),
+                port: start_args.value_of("port").map(String::from),
+            },
+            config::Section::ALL,
+        )),
+        ("sync", Some(sync_args)) => {
+            if let Err(e) = cli::sync(sync_args) {
+                eprintln!("{}", e);
+                std::process::exit(1)
+            }
+        }
+        (name, Some(command_args)) => {
+            let config = load_config(
+                &config::Overrides {
+                    config_file: command_args.value_of("config").map(String::from),
+                    ..Default::default()
+                },
+                cli::sections(name, command_args),
+            );
+            if let Err(e) = cli::run(config, name, command_args) {
+                eprintln!("{}", e);
+                std::process::exit(1)
+            }
+        }
         _ => {}
     }
 }
@@ -0,0 +1,346 @@
+//! Reading emails and mailboxes into their headers and attachments
+use ::chrono::{DateTime, TimeZone, Utc};
+use mailparse::{MailHeaderMap, ParsedMail};
+use regex::Regex;
+
+use super::Upload;
+
+/// Longest filename given to a message of a mailbox, in characters
+const MAX_FILENAME_LEN: usize = 100;
+/// A message id in its angle brackets, capturing the id
+const MESSAGE_ID_PATTERN: &str = r"<([^<>\s]+)>";
+
+/// The headers of an email kept as its values
+#[derive(Debug, Clone, Default)]
+pub struct EmailHeaders {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub cc: Option<String>,
+    pub subject: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    /// Without its angle brackets
+    pub message_id: Option<String>,
+    /// The message this one replies to, from `In-Reply-To` or else the last of `References`
+    pub in_reply_to: Option<String>,
+}
+
+/// An email's headers and the files attached to it
+pub struct Email {
+    pub headers: EmailHeaders,
+    pub attachments: Vec<Upload>,
+}
+
+/// Whether content named `filename` is a single email or a mailbox of them
+pub fn is_email(filename: &str) -> bool {
+    super::extension_of(filename) == ".eml"
+}
+
+pub fn is_mailbox(filename: &str) -> bool {
+    super::extension_of(filename) == ".mbox"
+}
+
+pub fn parse_email(content: &[u8]) -> Result<Email, String> {
+    let mail = mailparse::parse_mail(content).map_err(|e| format!("not an email: {}", e))?;
+    let header = |name: &str| {
+        mail.headers
+            .get_first_value(name)
+            .ok()
+            .and_then(|value| value)
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    let bracketed = Regex::new(MESSAGE_ID_PATTERN).expect("valid message id pattern");
+    let ids = |header: String| message_ids(&header, &bracketed);
+    let headers = EmailHeaders {
+        from: header("From"),
+        to: header("To"),
+        cc: header("Cc"),
+        subject: header("Subject"),
+        date: header("Date")
+            .and_then(|date| mailparse::dateparse(&date).ok())
+            .and_then(|seconds| Utc.timestamp_opt(seconds, 0).single()),
+        message_id: header("Message-ID").and_then(|header| ids(header).into_iter().next()),
+        in_reply_to: header("In-Reply-To")
+            .and_then(|header| ids(header).into_iter().next())
+            .or_else(|| header("References").and_then(|header| ids(header).pop())),
+    };
+
+    let mut attachments = Vec::new();
+    collect_attachments(&mail, &mut attachments)?;
+    for attachment in &mut attachments {
+        attachment.modified = headers.date;
+    }
+    Ok(Email {
+        headers,
+        attachments,
+    })
+}
+
+/// The ids in a header like `<a@example.com> <b@example.com>`, captured by
+/// `bracketed`, or the whole header when it has no brackets
+fn message_ids(header: &str, bracketed: &Regex) -> Vec<String> {
+    let ids: Vec<String> = bracketed
+        .captures_iter(header)
+        .map(|id| id[1].to_string())
+        .collect();
+    if ids.is_empty() {
+        header
+            .split_whitespace()
+            .next()
+            .map(String::from)
+            .into_iter()
+            .collect()
+    } else {
+        ids
+    }
+}
+
+fn collect_attachments(part: &ParsedMail, attachments: &mut Vec<Upload>) -> Result<(), String> {
+    if !part.subparts.is_empty() {
+        for subpart in &part.subparts {
+            collect_attachments(subpart, attachments)?;
+        }
+        return Ok(());
+    }
+    if let Some(filename) = attachment_name(part) {
+        attachments.push(Upload {
+            filename,
+            content: part
+                .get_body_raw()
+                .map_err(|e| format!("reading attachment: {}", e))?,
+            modified: None,
+        });
+    }
+    Ok(())
+}
+
+/// The filename of a part sent as an attachment, `None` for the parts of
+/// the message's body
+pub fn attachment_name(part: &ParsedMail) -> Option<String> {
+    let disposition = part
+        .headers
+        .get_first_value("Content-Disposition")
+        .ok()
+        .and_then(|value| value)
+        .unwrap_or_default();
+    let filename = header_param(&disposition, "filename")
+        .or_else(|| part.ctype.params.get("name").cloned())
+        .filter(|name| !name.trim().is_empty());
+
+    let attached = disposition.trim().to_lowercase().starts_with("attachment");
+    // files without a disposition, like scanned documents from copiers
+    let named_file =
+        disposition.is_empty() && filename.is_some() && !part.ctype.mimetype.starts_with("text/");
+    if !attached && !named_file {
+        return None;
+    }
+    Some(filename.unwrap_or_else(|| {
+        let extension = match part.ctype.mimetype.as_str() {
+            "application/pdf" => ".pdf",
+            "message/rfc822" => ".eml",
+            "text/plain" => ".txt",
+            "text/html" => ".html",
+            _ => "",
+        };
+        format!("attachment{}", extension)
+    }))
+}
+
+/// The value of `name` in a header like `attachment; filename="a b.pdf"`
+fn header_param(header: &str, name: &str) -> Option<String> {
+    header.split(';').skip(1).find_map(|param| {
+        let mut pair = param.splitn(2, '=');
+        let key = pair.next()?.trim();
+        let value = pair.next()?.trim().trim_matches('"');
+        if key.eq_ignore_ascii_case(name) {
+            Some(value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// A filename for a message from its subject
+pub fn message_filename(headers: &EmailHeaders, fallback: &str) -> String {
+    let subject: String = headers
+        .subject
+        .as_ref()
+        .map(|subject| {
+            subject
+                .chars()
+                .filter(|c| !c.is_control() && !"/\\:*?\"<>|".contains(*c))
+                .take(MAX_FILENAME_LEN)
+                .collect()
+        })
+        .unwrap_or_default();
+    match subject.trim() {
+        "" => format!("{}.eml", fallback),
+        subject => format!("{}.eml", subject),
+    }
+}
+
+/// A filename for an email from its subject, `<fallback>.eml` without one
+pub fn email_filename(content: &[u8], fallback: &str) -> String {
+    match parse_email(content) {
+        Ok(email) => message_filename(&email.headers, fallback),
+        Err(_) => format!("{}.eml", fallback),
+    }
+}
+
+/// The messages of an mbox, each starting at a `From ` line at the start of
+/// the mailbox or after a blank line, with `>From ` quoting undone
+pub fn split_mailbox(content: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut message: Option<Vec<u8>> = None;
+    let mut after_blank = true;
+    let mut start = 0;
+    while start < content.len() {
+        let end = content[start..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(content.len(), |newline| start + newline + 1);
+        let line = &content[start..end];
+        start = end;
+
+        if after_blank && line.starts_with(b"From ") {
+            messages.extend(message.take());
+            message = Some(Vec::new());
+            after_blank = false;
+            continue;
+        }
+        after_blank = line == b"\n" || line == b"\r\n";
+        if let Some(ref mut message) = message {
+            let quoted_from = line
+                .iter()
+                .skip_while(|&&byte| byte == b'>')
+                .take(5)
+                .eq(b"From ".iter());
+            if line.starts_with(b">") && quoted_from {
+                message.extend_from_slice(&line[1..]);
+            } else {
+                message.extend_from_slice(line);
+            }
+        }
+    }
+    messages.extend(message);
+    messages.retain(|message| message.iter().any(|byte| !byte.is_ascii_whitespace()));
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bracketed() -> Regex {
+        Regex::new(MESSAGE_ID_PATTERN).unwrap()
+    }
+
+    #[test]
+    fn ids_of_headers() {
+        assert_eq!(
+            message_ids("<a@example.com>\r\n <b@example.com>", &bracketed()),
+            vec!["a@example.com", "b@example.com"]
+        );
+        assert_eq!(
+            message_ids("a@example.com (the first)", &bracketed()),
+            vec!["a@example.com"]
+        );
+        assert!(message_ids("  ", &bracketed()).is_empty());
+    }
+
+    #[test]
+    fn header_params() {
+        let header = "attachment; size=12;  FILENAME=\"a b.pdf\"";
+        assert_eq!(
+            header_param(header, "filename"),
+            Some("a b.pdf".to_string())
+        );
+        assert_eq!(header_param(header, "size"), Some("12".to_string()));
+        assert_eq!(header_param(header, "name"), None);
+        assert_eq!(header_param("filename=a.pdf", "filename"), None);
+    }
+
+    #[test]
+    fn replies() {
+        let email = parse_email(
+            b"Message-ID: <c@example.com>\r\n\
+              In-Reply-To: <b@example.com>\r\n\
+              References: <a@example.com> <b@example.com>\r\n\r\nHi",
+        )
+        .unwrap();
+        assert_eq!(email.headers.message_id, Some("c@example.com".to_string()));
+        assert_eq!(email.headers.in_reply_to, Some("b@example.com".to_string()));
+
+        let email = parse_email(
+            b"Message-ID: <c@example.com>\r\n\
+              References: <a@example.com>\r\n <b@example.com>\r\n\r\nHi",
+        )
+        .unwrap();
+        assert_eq!(email.headers.in_reply_to, Some("b@example.com".to_string()));
+    }
+
+    fn attachments(mail: &[u8]) -> Vec<Option<String>> {
+        let mail = mailparse::parse_mail(mail).unwrap();
+        mail.subparts.iter().map(attachment_name).collect()
+    }
+
+    #[test]
+    fn attachment_names() {
+        let names = attachments(
+            b"Content-Type: multipart/mixed; boundary=b\r\n\r\n\
+              --b\r\nContent-Type: text/plain; name=body.txt\r\n\r\nthe body\r\n\
+              --b\r\nContent-Type: application/pdf; name=\"scan 1.pdf\"\r\n\r\n%PDF\r\n\
+              --b\r\nContent-Type: application/pdf\r\n\
+              Content-Disposition: attachment; filename=\"report.pdf\"\r\n\r\n%PDF\r\n\
+              --b\r\nContent-Type: application/pdf\r\n\
+              Content-Disposition: attachment\r\n\r\n%PDF\r\n\
+              --b\r\nContent-Type: image/png\r\n\
+              Content-Disposition: inline\r\n\r\nPNG\r\n\
+              --b--\r\n",
+        );
+        assert_eq!(
+            names,
+            vec![
+                None,
+                Some("scan 1.pdf".to_string()),
+                Some("report.pdf".to_string()),
+                Some("attachment.pdf".to_string()),
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn mailbox_messages() {
+        let mailbox = b"From a@example.com Mon Mar  4 10:00:00 2019\n\
+                        Subject: one\n\
+                        \n\
+                        >From the start\n\
+                        >>From twice\n\
+                        >Fromage\n\
+                        From the middle of a paragraph\n\
+                        \n\
+                        From b@example.com Mon Mar  4 11:00:00 2019\n\
+                        Subject: two\n\
+                        \n\
+                        Bye\n";
+        let messages = split_mailbox(mailbox);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            String::from_utf8_lossy(&messages[0]),
+            "Subject: one\n\nFrom the start\n>From twice\n>Fromage\n\
+             From the middle of a paragraph\n\n"
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&messages[1]),
+            "Subject: two\n\nBye\n"
+        );
+    }
+
+    #[test]
+    fn empty_mailbox() {
+        assert!(split_mailbox(b"").is_empty());
+        assert!(split_mailbox(b"From a@example.com\n\n").is_empty());
+    }
+}
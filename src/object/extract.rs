@@ -0,0 +1,278 @@
+//! Pulling searchable text out of stored content
+use mailparse::MailHeaderMap;
+use regex::Regex;
+use std::io::{Cursor, Read};
+use std::panic;
+
+use super::email::attachment_name;
+use super::extension_of;
+
+/// Longest text kept for a single object, in bytes
+const MAX_TEXT_LEN: usize = 1_000_000;
+
+/// extraction_status enum
+#[derive(Debug, Clone, Copy, PartialEq, DbEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtractionStatus {
+    Pending,
+    Done,
+    Failed,
+    /// Content of a type text cannot be extracted from
+    Unsupported,
+}
+
+impl ExtractionStatus {
+    /// Name of the status as it appears in the database and JSON
+    pub fn name(self) -> &'static str {
+        match self {
+            ExtractionStatus::Pending => "pending",
+            ExtractionStatus::Done => "done",
+            ExtractionStatus::Failed => "failed",
+            ExtractionStatus::Unsupported => "unsupported",
+        }
+    }
+}
+
+/// The outcome of extracting text from content
+#[derive(Debug, Clone, PartialEq)]
+pub enum Extraction {
+    /// The text, and the number of pages of paged content such as pdfs
+    Text(String, Option<i32>),
+    Unsupported,
+    Failed(String),
+}
+
+/// Extract the text of content by the type its filename's extension suggests
+pub fn extract_text(filename: &str, content: &[u8]) -> Extraction {
+    let mut pages = None;
+    let text = match extension_of(filename).as_str() {
+        ".txt" | ".md" | ".csv" | ".json" | ".log" => {
+            Ok(String::from_utf8_lossy(content).into_owned())
+        }
+        ".html" | ".htm" => Ok(html_text(&String::from_utf8_lossy(content))),
+        ".eml" => email_text(content),
+        ".docx" => docx_text(content),
+        ".pdf" => pdf_text(content).map(|(text, count)| {
+            pages = Some(count);
+            text
+        }),
+        _ => return Extraction::Unsupported,
+    };
+    match text {
+        Ok(text) => Extraction::Text(truncate(collapse_whitespace(&text)), pages),
+        Err(message) => Extraction::Failed(message),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    pattern(r"[ \t\r\f\v]+")
+        .replace_all(text, " ")
+        .split('\n')
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn truncate(mut text: String) -> String {
+    if text.len() > MAX_TEXT_LEN {
+        let mut end = MAX_TEXT_LEN;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        text.truncate(end);
+    }
+    text
+}
+
+fn pattern(source: &str) -> Regex {
+    Regex::new(source).expect("valid extraction pattern")
+}
+
+/// Text of markup, with block level elements on their own lines
+fn markup_text(markup: &str, line_breaks: &Regex) -> String {
+    let broken = line_breaks.replace_all(markup, "\n");
+    let stripped = pattern(r"(?s)<[^>]*>").replace_all(&broken, "");
+    decode_entities(&stripped)
+}
+
+fn html_text(html: &str) -> String {
+    let without_code = pattern(r"(?is)<(script|style|head)\b.*?</(script|style|head)>")
+        .replace_all(html, "");
+    markup_text(
+        &without_code,
+        &pattern(r"(?i)<(br|/p|/div|/li|/tr|/h[1-6])\b[^>]*>"),
+    )
+}
+
+fn decode_entities(text: &str) -> String {
+    pattern(r"&(#x[0-9a-fA-F]+|#[0-9]+|[a-z]+);")
+        .replace_all(text, |caps: &regex::Captures| {
+            let entity = &caps[1];
+            let decoded = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ if entity.starts_with("#x") => u32::from_str_radix(&entity[2..], 16)
+                    .ok()
+                    .and_then(std::char::from_u32),
+                _ if entity.starts_with('#') => {
+                    entity[1..].parse().ok().and_then(std::char::from_u32)
+                }
+                _ => None,
+            };
+            decoded.map_or_else(|| caps[0].to_string(), |c| c.to_string())
+        })
+        .into_owned()
+}
+
+/// The paragraphs of a Word document's main body
+fn docx_text(content: &[u8]) -> Result<String, String> {
+    let mut archive =
+        zip::ZipArchive::new(Cursor::new(content)).map_err(|e| format!("not a docx: {}", e))?;
+    let mut document = String::new();
+    archive
+        .by_name("word/document.xml")
+        .map_err(|e| format!("not a docx: {}", e))?
+        .read_to_string(&mut document)
+        .map_err(|e| format!("reading docx: {}", e))?;
+    Ok(markup_text(&document, &pattern(r"</w:p>|<w:br/>|<w:tab/>")))
+}
+
+/// The text of a pdf, and its number of pages.
+/// pdf_extract panics on some malformed documents, which would otherwise stop
+/// the extractor along with every extraction after it
+fn pdf_text(content: &[u8]) -> Result<(String, i32), String> {
+    panic::catch_unwind(|| {
+        let document =
+            lopdf::Document::load_from(content).map_err(|e| format!("not a pdf: {:?}", e))?;
+        let mut text = String::new();
+        pdf_extract::output_doc(&document, &mut pdf_extract::PlainTextOutput::new(&mut text));
+        Ok((text, document.get_pages().len() as i32))
+    })
+    .unwrap_or_else(|panic| {
+        let message = panic
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        Err(format!("malformed pdf: {}", message))
+    })
+}
+
+/// The headers people read and every text part of an email's body
+pub fn email_text(content: &[u8]) -> Result<String, String> {
+    let mail = mailparse::parse_mail(content).map_err(|e| format!("not an email: {}", e))?;
+    let mut text = String::new();
+    for header in &["Subject", "From", "To", "Cc", "Date"] {
+        if let Ok(Some(value)) = mail.headers.get_first_value(header) {
+            text.push_str(&format!("{}: {}\n", header, value));
+        }
+    }
+    push_mail_parts(&mail, &mut text)?;
+    Ok(text)
+}
+
+fn push_mail_parts(part: &mailparse::ParsedMail, text: &mut String) -> Result<(), String> {
+    if !part.subparts.is_empty() {
+        // alternatives repeat the same text, so only the plainest is kept
+        let parts: Vec<_> = if part.ctype.mimetype == "multipart/alternative" {
+            part.subparts.iter().take(1).collect()
+        } else {
+            part.subparts.iter().collect()
+        };
+        for subpart in parts {
+            push_mail_parts(subpart, text)?;
+        }
+        return Ok(());
+    }
+    // attachments are objects of their own, with text of their own
+    if attachment_name(part).is_some() {
+        return Ok(());
+    }
+    let body = || part.get_body().map_err(|e| format!("reading email: {}", e));
+    match part.ctype.mimetype.as_str() {
+        "text/plain" => text.push_str(&body()?),
+        "text/html" => text.push_str(&html_text(&body()?)),
+        _ => return Ok(()),
+    }
+    text.push('\n');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A one page pdf showing "Hello", with the page dictionary given
+    fn pdf(page: &str) -> Vec<u8> {
+        let contents = "BT /F1 12 Tf 72 712 Td (Hello) Tj ET";
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            page.to_string(),
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                contents.len(),
+                contents
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+        let mut pdf = "%PDF-1.4\n".to_string();
+        let mut offsets = Vec::new();
+        for (n, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", n + 1, object));
+        }
+        let xref = pdf.len();
+        pdf.push_str(&format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        ));
+        for offset in offsets {
+            pdf.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        pdf.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        ));
+        pdf.into_bytes()
+    }
+
+    #[test]
+    fn pdf_text() {
+        let content = pdf("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 612 792] \
+                           /Contents 4 0 R /Resources << /Font << /F1 5 0 R >> >> >>");
+        assert_eq!(
+            extract_text("hello.pdf", &content),
+            Extraction::Text("Hello".to_string(), Some(1))
+        );
+    }
+
+    #[test]
+    fn malformed_pdf_fails() {
+        // pdf_extract panics on a page without a MediaBox
+        let content = pdf("<< /Type /Page /Parent 2 0 R /Contents 4 0 R >>");
+        match extract_text("broken.pdf", &content) {
+            Extraction::Failed(message) => assert!(message.starts_with("malformed pdf")),
+            other => panic!("expected a failure, not {:?}", other),
+        }
+        // and extraction goes on after it
+        assert_eq!(
+            extract_text("notes.txt", b"still  working"),
+            Extraction::Text("still working".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn not_a_pdf() {
+        match extract_text("fake.pdf", b"just text") {
+            Extraction::Failed(message) => assert!(message.starts_with("not a pdf")),
+            other => panic!("expected a failure, not {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use ::actix::prelude::*;
+use actix_web::Error;
+use futures::{stream, Future, Stream};
+use std::time::Duration;
+
+use super::extract::{extract_text, Extraction};
+use super::store::{GetBlob, ObjectStore};
+use crate::db::{DbExecutor, ListPendingExtractions, SaveExtraction};
+
+/// How often the queue of pending extractions is checked
+const POLL_EVERY_SECONDS: u64 = 30;
+/// Extractions taken from the queue at a time
+const BATCH_SIZE: i64 = 10;
+
+/// Extracts the text of new object versions in the background so search can
+/// find them by their contents. Extraction blocks, so the actor should be
+/// started on an arbiter of its own.
+pub struct TextExtractor {
+    pub pg: Addr<DbExecutor>,
+    pub store: Addr<ObjectStore>,
+    busy: bool,
+}
+
+impl TextExtractor {
+    pub fn new(pg: Addr<DbExecutor>, store: Addr<ObjectStore>) -> Self {
+        TextExtractor {
+            pg,
+            store,
+            busy: false,
+        }
+    }
+
+    fn extract_pending(&mut self, ctx: &mut Context<Self>) {
+        if self.busy {
+            return;
+        }
+        self.busy = true;
+
+        let pg = self.pg.clone();
+        let store = self.store.clone();
+        let work = self
+            .pg
+            .send(ListPendingExtractions { limit: BATCH_SIZE })
+            .from_err::<Error>()
+            .and_then(|res| res)
+            .and_then(move |pending| {
+                stream::iter_ok(pending).for_each(move |job| {
+                    let pg = pg.clone();
+                    store
+                        .send(GetBlob {
+                            hash: job.hash.clone(),
+                        })
+                        .from_err::<Error>()
+                        .and_then(|res| res)
+                        .then(move |content| {
+                            let extraction = match content {
+                                Ok(content) => extract_text(&job.filename, &content),
+                                Err(e) => Extraction::Failed(format!("reading content: {}", e)),
+                            };
+                            pg.send(SaveExtraction {
+                                object_id: job.object_id,
+                                version: job.version,
+                                extraction,
+                            })
+                            .from_err()
+                            .and_then(|res| res)
+                        })
+                })
+            })
+            .into_actor(self)
+            .then(|res, act, _ctx| {
+                if let Err(e) = res {
+                    error!("TextExtractor error: {:?}", e);
+                }
+                act.busy = false;
+                actix::fut::ok(())
+            });
+        ctx.spawn(work);
+    }
+}
+
+impl Actor for TextExtractor {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.extract_pending(ctx);
+        ctx.run_interval(Duration::from_secs(POLL_EVERY_SECONDS), |act, ctx| {
+            act.extract_pending(ctx)
+        });
+    }
+}
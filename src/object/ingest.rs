@@ -0,0 +1,201 @@
+use ::actix::prelude::*;
+use ::chrono::Utc;
+use actix_web::Error;
+use futures::future::{self, Either};
+use futures::{stream, Future, Stream};
+use std::path::Path;
+
+use super::email::{self, email_filename, is_email, is_mailbox, parse_email};
+use super::store::{ObjectStore, PutBlob};
+use super::{ObjectId, Upload};
+use crate::db::{AddObjectVersion, CreateObject, DbExecutor, FileEmail, VersionContent};
+use crate::property::SelectChoiceId;
+use crate::user::UserId;
+
+/// Stores uploaded content and records it as objects or their new versions
+#[derive(Clone)]
+pub struct Ingest {
+    pub db: Addr<DbExecutor>,
+    pub store: Addr<ObjectStore>,
+}
+
+impl Ingest {
+    /// Put the content in the store, returning what the object version records
+    fn store(&self, upload: Upload) -> impl Future<Item = VersionContent, Error = Error> {
+        let content = VersionContent {
+            extension: upload.extension(),
+            hash: upload.hash(),
+            size: upload.size(),
+            filename: upload.filename,
+        };
+        self.store
+            .send(PutBlob {
+                hash: content.hash.clone(),
+                content: upload.content,
+            })
+            .from_err()
+            .and_then(|res| res)
+            .map(move |_| content)
+    }
+
+    /// Create a new object, optionally in a collection
+    pub fn create_object(
+        &self,
+        upload: Upload,
+        created_by: UserId,
+        collection: Option<SelectChoiceId>,
+        request_id: Option<String>,
+    ) -> impl Future<Item = ObjectId, Error = Error> {
+        let db = self.db.clone();
+        let modified = upload.modified.unwrap_or_else(Utc::now);
+        self.store(upload).and_then(move |content| {
+            db.send(CreateObject {
+                object_id: ObjectId::generate(),
+                content,
+                modified,
+                created_by,
+                collection,
+                request_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+        })
+    }
+
+    /// Create the objects of an upload, optionally in a collection: an email
+    /// and one of each of its attachments, each message of a mailbox, or else
+    /// a single object. Responds with their ids, each email before its
+    /// attachments.
+    pub fn create_objects(
+        &self,
+        upload: Upload,
+        created_by: UserId,
+        collection: Option<SelectChoiceId>,
+        request_id: Option<String>,
+    ) -> impl Future<Item = Vec<ObjectId>, Error = Error> {
+        if is_email(&upload.filename) {
+            return Either::A(Either::A(
+                self.create_email(upload, created_by, collection, request_id),
+            ));
+        }
+        let messages = if is_mailbox(&upload.filename) {
+            email::split_mailbox(&upload.content)
+        } else {
+            Vec::new()
+        };
+        if messages.is_empty() {
+            return Either::B(
+                self.create_object(upload, created_by, collection, request_id)
+                    .map(|object_id| vec![object_id]),
+            );
+        }
+
+        // one at a time, so replies find the messages before them
+        let ingest = self.clone();
+        let mailbox = Path::new(&upload.filename).file_stem().map_or_else(
+            || "message".to_string(),
+            |stem| stem.to_string_lossy().into_owned(),
+        );
+        Either::A(Either::B(
+            stream::iter_ok(messages.into_iter().enumerate())
+                .and_then(move |(index, content)| {
+                    let filename = email_filename(&content, &format!("{} {}", mailbox, index + 1));
+                    ingest.create_email(
+                        Upload {
+                            filename,
+                            content,
+                            modified: upload.modified,
+                        },
+                        created_by.clone(),
+                        collection.clone(),
+                        request_id.clone(),
+                    )
+                })
+                .concat2(),
+        ))
+    }
+
+    /// Create an object of an email with its headers as values, and an object
+    /// of each of its attachments related to it. Content which can't be read
+    /// as an email is stored as it is.
+    fn create_email(
+        &self,
+        mut upload: Upload,
+        created_by: UserId,
+        collection: Option<SelectChoiceId>,
+        request_id: Option<String>,
+    ) -> impl Future<Item = Vec<ObjectId>, Error = Error> {
+        let email = match parse_email(&upload.content) {
+            Ok(email) => email,
+            Err(e) => {
+                warn!("Storing {} as it is, {}", upload.filename, e);
+                return Either::A(
+                    self.create_object(upload, created_by, collection, request_id)
+                        .map(|object_id| vec![object_id]),
+                );
+            }
+        };
+        let (headers, attachments) = (email.headers, email.attachments);
+        upload.modified = headers.date.or(upload.modified);
+
+        let (ingest, db) = (self.clone(), self.db.clone());
+        Either::B(
+            self.create_object(
+                upload,
+                created_by.clone(),
+                collection.clone(),
+                request_id.clone(),
+            )
+            .and_then(move |message_id| {
+                let attachments: Vec<_> = attachments
+                    .into_iter()
+                    .map(|attachment| {
+                        ingest.create_object(
+                            attachment,
+                            created_by.clone(),
+                            collection.clone(),
+                            request_id.clone(),
+                        )
+                    })
+                    .collect();
+                future::join_all(attachments).and_then(move |attachments| {
+                    db.send(FileEmail {
+                        object_id: message_id.clone(),
+                        headers,
+                        attachments: attachments.clone(),
+                        user_id: created_by,
+                        request_id,
+                    })
+                    .from_err()
+                    .and_then(|res| res)
+                    .map(move |_| {
+                        let mut object_ids = vec![message_id];
+                        object_ids.extend(attachments);
+                        object_ids
+                    })
+                })
+            }),
+        )
+    }
+
+    /// Add a new version to an existing object, returning the version number
+    pub fn add_version(
+        &self,
+        object_id: ObjectId,
+        upload: Upload,
+        created_by: UserId,
+        request_id: Option<String>,
+    ) -> impl Future<Item = i32, Error = Error> {
+        let db = self.db.clone();
+        self.store(upload).and_then(move |content| {
+            db.send(AddObjectVersion {
+                object_id,
+                content,
+                created_by,
+                request_id,
+            })
+            .from_err()
+            .and_then(|res| res)
+        })
+    }
+}
@@ -1,6 +1,24 @@
 pub mod store;
 pub use store::ObjectStore;
 
+mod ingest;
+pub use ingest::Ingest;
+
+mod email;
+pub use email::{email_filename, EmailHeaders};
+
+pub mod extract;
+pub use extract::{Extraction, ExtractionStatus, ExtractionStatusMapping};
+
+mod extractor;
+pub use extractor::TextExtractor;
+
+mod upload;
+pub use upload::{extension_of, Upload};
+
+mod object_version;
+pub use object_version::ObjectVersion;
+
 mod object_id;
 pub use object_id::ObjectId;
 
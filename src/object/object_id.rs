@@ -1,5 +1,5 @@
 /// Represents a ObjectId
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[derive(DieselNewType)]
 pub struct ObjectId(String);
 
@@ -11,6 +11,22 @@ impl fmt::Display for ObjectId {
     }
 }
 
+impl From<String> for ObjectId {
+    fn from(id: String) -> Self {
+        ObjectId(id)
+    }
+}
+
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+
+impl ObjectId {
+    /// A new random id for an object
+    pub fn generate() -> Self {
+        ObjectId(thread_rng().sample_iter(&Alphanumeric).take(12).collect())
+    }
+}
+
 use crate::db::{db_error, Fetch};
 use super::ObjectRow;
 use actix_web::Result;
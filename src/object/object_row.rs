@@ -9,6 +9,8 @@ pub struct ObjectRow {
     pub created_by: UserId,
     pub created_at: DateTime<Utc>,
     pub extension: String,
+    /// `None` for objects without any content
+    pub current_version: Option<i32>,
 }
 
 impl Object for ObjectRow {
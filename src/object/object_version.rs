@@ -0,0 +1,17 @@
+use ::chrono::{DateTime, Utc};
+
+use super::ObjectId;
+use crate::user::UserId;
+
+/// One of the successive contents of an object
+#[derive(Debug, Clone, Queryable, Serialize)]
+pub struct ObjectVersion {
+    pub object_id: ObjectId,
+    pub version: i32,
+    /// SHA-256 of the content, and its key in the `ObjectStore`
+    pub hash: String,
+    pub size: i64,
+    pub filename: String,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
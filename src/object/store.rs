@@ -1,13 +1,24 @@
 use ::actix::prelude::*;
+use ::chrono::{DateTime, Utc};
+use actix_web::{error, Error};
+use futures::future::{self, Future, Loop};
 use futures::stream::Stream;
 use rusoto_core::request::{HttpClient, TlsError};
 use rusoto_core::{self, Region};
 use rusoto_credential::StaticProvider;
-use rusoto_s3::{self, S3Client};
+use rusoto_s3::{
+    self, DeleteObjectRequest, GetObjectRequest, HeadBucketRequest, ListObjectsV2Request,
+    PutObjectRequest, S3Client, S3,
+};
+use std::sync::Arc;
+
+/// Bucket holding object contents, keyed by their hash
+const OBJECTS_BUCKET: &str = "objects";
 
 /// This is object store actor
 pub struct ObjectStore {
-    s3: S3Client,
+    /// Shared with the futures listing blobs a page at a time
+    s3: Arc<S3Client>,
 }
 
 impl Actor for ObjectStore {
@@ -20,11 +31,190 @@ impl ObjectStore {
         secret_key: &str,
     ) -> Result<ObjectStore, TlsError> {
         Ok(ObjectStore {
-            s3: S3Client::new_with(
+            s3: Arc::new(S3Client::new_with(
                 HttpClient::new()?,
                 StaticProvider::new_minimal(access_key.to_string(), secret_key.to_string()),
                 Region::UsEast1,
-            ),
+            )),
         })
     }
 }
+
+/// Store content under its hash
+pub struct PutBlob {
+    pub hash: String,
+    pub content: Vec<u8>,
+}
+
+impl Message for PutBlob {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<PutBlob> for ObjectStore {
+    type Result = ResponseFuture<(), Error>;
+
+    fn handle(&mut self, msg: PutBlob, _: &mut Self::Context) -> Self::Result {
+        let size = msg.content.len() as i64;
+        Box::new(
+            self.s3
+                .put_object(PutObjectRequest {
+                    bucket: OBJECTS_BUCKET.to_string(),
+                    key: msg.hash,
+                    content_length: Some(size),
+                    body: Some(msg.content.into()),
+                    ..Default::default()
+                })
+                .map(|_| ())
+                .map_err(|e| {
+                    error!("ObjectStore put_object error: {:?}", e);
+                    error::ErrorInternalServerError("Error storing object content")
+                }),
+        )
+    }
+}
+
+/// Read content by its hash
+pub struct GetBlob {
+    pub hash: String,
+}
+
+impl Message for GetBlob {
+    type Result = Result<Vec<u8>, Error>;
+}
+
+impl Handler<GetBlob> for ObjectStore {
+    type Result = ResponseFuture<Vec<u8>, Error>;
+
+    fn handle(&mut self, msg: GetBlob, _: &mut Self::Context) -> Self::Result {
+        Box::new(
+            self.s3
+                .get_object(GetObjectRequest {
+                    bucket: OBJECTS_BUCKET.to_string(),
+                    key: msg.hash,
+                    ..Default::default()
+                })
+                .map_err(|e| {
+                    error!("ObjectStore get_object error: {:?}", e);
+                    error::ErrorNotFound("Object content not found")
+                })
+                .and_then(|output| {
+                    output
+                        .body
+                        .ok_or_else(|| error::ErrorNotFound("Object content is empty"))
+                })
+                .and_then(|body| body.concat2().map_err(error::ErrorInternalServerError)),
+        )
+    }
+}
+
+/// A blob in the store
+#[derive(Debug, Clone)]
+pub struct StoredBlob {
+    pub hash: String,
+    pub size: i64,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Every blob in the store, read a page at a time
+pub struct ListBlobs;
+
+impl Message for ListBlobs {
+    type Result = Result<Vec<StoredBlob>, Error>;
+}
+
+impl Handler<ListBlobs> for ObjectStore {
+    type Result = ResponseFuture<Vec<StoredBlob>, Error>;
+
+    fn handle(&mut self, _: ListBlobs, _: &mut Self::Context) -> Self::Result {
+        let s3 = self.s3.clone();
+        Box::new(future::loop_fn(
+            (Vec::new(), None),
+            move |(mut blobs, continuation_token): (Vec<StoredBlob>, Option<String>)| {
+                s3.list_objects_v2(ListObjectsV2Request {
+                    bucket: OBJECTS_BUCKET.to_string(),
+                    continuation_token,
+                    ..Default::default()
+                })
+                .map_err(|e| {
+                    error!("ObjectStore list_objects_v2 error: {:?}", e);
+                    error::ErrorInternalServerError("Error listing stored objects")
+                })
+                .map(move |output| {
+                    blobs.extend(output.contents.unwrap_or_default().into_iter().filter_map(
+                        |object| {
+                            Some(StoredBlob {
+                                hash: object.key?,
+                                size: object.size.unwrap_or(0),
+                                last_modified: object
+                                    .last_modified
+                                    .and_then(|at| DateTime::parse_from_rfc3339(&at).ok())
+                                    .map(|at| at.with_timezone(&Utc)),
+                            })
+                        },
+                    ));
+                    match output.next_continuation_token {
+                        Some(token) if output.is_truncated == Some(true) => {
+                            Loop::Continue((blobs, Some(token)))
+                        }
+                        _ => Loop::Break(blobs),
+                    }
+                })
+            },
+        ))
+    }
+}
+
+/// Remove content by its hash
+pub struct DeleteBlob {
+    pub hash: String,
+}
+
+impl Message for DeleteBlob {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<DeleteBlob> for ObjectStore {
+    type Result = ResponseFuture<(), Error>;
+
+    fn handle(&mut self, msg: DeleteBlob, _: &mut Self::Context) -> Self::Result {
+        Box::new(
+            self.s3
+                .delete_object(DeleteObjectRequest {
+                    bucket: OBJECTS_BUCKET.to_string(),
+                    key: msg.hash,
+                    ..Default::default()
+                })
+                .map(|_| ())
+                .map_err(|e| {
+                    error!("ObjectStore delete_object error: {:?}", e);
+                    error::ErrorInternalServerError("Error deleting object content")
+                }),
+        )
+    }
+}
+
+/// Check the bucket exists and the credentials may use it
+pub struct CheckBucket;
+
+impl Message for CheckBucket {
+    type Result = Result<(), Error>;
+}
+
+impl Handler<CheckBucket> for ObjectStore {
+    type Result = ResponseFuture<(), Error>;
+
+    fn handle(&mut self, _: CheckBucket, _: &mut Self::Context) -> Self::Result {
+        Box::new(
+            self.s3
+                .head_bucket(HeadBucketRequest {
+                    bucket: OBJECTS_BUCKET.to_string(),
+                })
+                .map_err(|e| {
+                    error::ErrorInternalServerError(format!(
+                        "the {} bucket can't be reached: {}",
+                        OBJECTS_BUCKET, e
+                    ))
+                }),
+        )
+    }
+}
@@ -0,0 +1,35 @@
+use ::chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
+use std::path::Path;
+
+/// e.g. ".pdf", or "" if the filename has none
+pub fn extension_of(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .and_then(OsStr::to_str)
+        .map_or("".to_string(), |ext| format!(".{}", ext.to_lowercase()))
+}
+
+/// The content and name of a file received for storage
+pub struct Upload {
+    pub filename: String,
+    pub content: Vec<u8>,
+    /// When the file was last modified, if the source knows
+    pub modified: Option<DateTime<Utc>>,
+}
+
+impl Upload {
+    /// Hex encoded SHA-256 of the content
+    pub fn hash(&self) -> String {
+        format!("{:x}", Sha256::digest(&self.content))
+    }
+
+    pub fn extension(&self) -> String {
+        extension_of(&self.filename)
+    }
+
+    pub fn size(&self) -> i64 {
+        self.content.len() as i64
+    }
+}
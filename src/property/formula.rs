@@ -0,0 +1,426 @@
+//! Formulas for computed properties
+//!
+//! A small arithmetic grammar over the numeric value of other properties:
+//!
+//! ```text
+//! expr   := term (('+' | '-') term)*
+//! term   := factor (('*' | '/') factor)*
+//! factor := '-' factor | number | '{' property_id '}' | call | '(' expr ')'
+//! call   := ident '(' (expr (',' expr)*)? ')'
+//! ```
+//!
+//! `{3}` refers to property 3 of the same object. Text values are read as
+//! numbers, timestamptz values as seconds since the unix epoch.
+//! Functions: `days_since(t)`, `abs(x)`, `round(x)`, `min(a, b, ..)`, `max(a, b, ..)`,
+//! `size()`, the size in bytes of the object's current version, and `pages()`,
+//! its number of pages once text has been extracted from it (pdfs only).
+use std::collections::HashMap;
+use std::fmt;
+
+use super::PropertyId;
+
+const SECONDS_PER_DAY: f64 = 86_400.0;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Property(i64),
+    Negate(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(Function, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Function {
+    DaysSince,
+    Abs,
+    Round,
+    Min,
+    Max,
+    Size,
+    Pages,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "days_since" => Some(Function::DaysSince),
+            "abs" => Some(Function::Abs),
+            "round" => Some(Function::Round),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            "size" => Some(Function::Size),
+            "pages" => Some(Function::Pages),
+            _ => None,
+        }
+    }
+
+    /// (minimum, maximum) number of arguments
+    fn arity(self) -> (usize, usize) {
+        match self {
+            Function::DaysSince | Function::Abs | Function::Round => (1, 1),
+            Function::Min | Function::Max => (1, usize::max_value()),
+            Function::Size | Function::Pages => (0, 0),
+        }
+    }
+}
+
+/// Parse failure with the character offset it occurred at
+#[derive(Debug, Clone, PartialEq)]
+pub struct FormulaError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FormulaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position)
+    }
+}
+
+/// What a formula is evaluated against
+pub struct Inputs {
+    /// The numeric value of each property the object has a value for
+    pub values: HashMap<PropertyId, f64>,
+    /// Size in bytes of the object's current version
+    pub size: Option<f64>,
+    /// Pages of the object's current version, where it has them
+    pub pages: Option<f64>,
+    /// Unix seconds
+    pub now: f64,
+}
+
+/// A parsed formula, ready to be evaluated against an object's inputs
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    expr: Expr,
+}
+
+impl Formula {
+    pub fn parse(source: &str) -> Result<Formula, FormulaError> {
+        let mut parser = Parser {
+            chars: source.chars().collect(),
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(Formula { expr })
+    }
+
+    /// Properties this formula reads, used to load its inputs
+    pub fn inputs(&self) -> Vec<PropertyId> {
+        fn collect(expr: &Expr, out: &mut Vec<PropertyId>) {
+            match expr {
+                Expr::Number(_) => {}
+                Expr::Property(id) => {
+                    let id = PropertyId::from(*id);
+                    if !out.contains(&id) {
+                        out.push(id);
+                    }
+                }
+                Expr::Negate(inner) => collect(inner, out),
+                Expr::Binary(_, lhs, rhs) => {
+                    collect(lhs, out);
+                    collect(rhs, out);
+                }
+                Expr::Call(_, args) => args.iter().for_each(|arg| collect(arg, out)),
+            }
+        }
+        let mut out = Vec::new();
+        collect(&self.expr, &mut out);
+        out
+    }
+
+    /// Returns `None` when an input is missing or the result is not a finite
+    /// number (e.g. division by zero).
+    pub fn evaluate(&self, inputs: &Inputs) -> Option<f64> {
+        eval(&self.expr, inputs).filter(|v| v.is_finite())
+    }
+}
+
+fn eval(expr: &Expr, inputs: &Inputs) -> Option<f64> {
+    match expr {
+        Expr::Number(n) => Some(*n),
+        Expr::Property(id) => inputs.values.get(&PropertyId::from(*id)).cloned(),
+        Expr::Negate(inner) => eval(inner, inputs).map(|v| -v),
+        Expr::Binary(op, lhs, rhs) => {
+            let lhs = eval(lhs, inputs)?;
+            let rhs = eval(rhs, inputs)?;
+            match op {
+                BinaryOp::Add => Some(lhs + rhs),
+                BinaryOp::Subtract => Some(lhs - rhs),
+                BinaryOp::Multiply => Some(lhs * rhs),
+                BinaryOp::Divide if rhs == 0.0 => None,
+                BinaryOp::Divide => Some(lhs / rhs),
+            }
+        }
+        Expr::Call(function, args) => {
+            let args = args
+                .iter()
+                .map(|arg| eval(arg, inputs))
+                .collect::<Option<Vec<f64>>>()?;
+            match function {
+                Function::DaysSince => Some((inputs.now - args[0]) / SECONDS_PER_DAY),
+                Function::Abs => Some(args[0].abs()),
+                Function::Round => Some(args[0].round()),
+                Function::Min => args.into_iter().fold(None, |acc, v| {
+                    Some(acc.map_or(v, |a: f64| a.min(v)))
+                }),
+                Function::Max => args.into_iter().fold(None, |acc, v| {
+                    Some(acc.map_or(v, |a: f64| a.max(v)))
+                }),
+                Function::Size => inputs.size,
+                Function::Pages => inputs.pages,
+            }
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn error<T: Into<String>>(&self, message: T) -> FormulaError {
+        FormulaError {
+            position: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), FormulaError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("expected '{}' but found '{}'", expected, c))),
+            None => Err(self.error(format!("expected '{}' but reached the end", expected))),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, FormulaError> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = match self.peek() {
+                Some('+') => BinaryOp::Add,
+                Some('-') => BinaryOp::Subtract,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn term(&mut self) -> Result<Expr, FormulaError> {
+        let mut lhs = self.factor()?;
+        loop {
+            let op = match self.peek() {
+                Some('*') => BinaryOp::Multiply,
+                Some('/') => BinaryOp::Divide,
+                _ => return Ok(lhs),
+            };
+            self.pos += 1;
+            let rhs = self.factor()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn factor(&mut self) -> Result<Expr, FormulaError> {
+        match self.peek() {
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Negate(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let inner = self.expr()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            Some('{') => {
+                self.pos += 1;
+                let start = self.pos;
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                let id = digits.parse::<i64>().map_err(|_| FormulaError {
+                    position: start,
+                    message: "expected a property id inside of '{}'".to_string(),
+                })?;
+                self.expect('}')?;
+                Ok(Expr::Property(id))
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                let digits = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                digits.parse::<f64>().map(Expr::Number).map_err(|_| FormulaError {
+                    position: start,
+                    message: format!("invalid number '{}'", digits),
+                })
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let function = Function::from_name(&name).ok_or_else(|| FormulaError {
+                    position: start,
+                    message: format!("unknown function '{}'", name),
+                })?;
+                self.expect('(')?;
+                let mut args = Vec::new();
+                if self.peek() != Some(')') {
+                    args.push(self.expr()?);
+                    while self.peek() == Some(',') {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                }
+                self.expect(')')?;
+                let (min, max) = function.arity();
+                if args.len() < min || args.len() > max {
+                    return Err(FormulaError {
+                        position: start,
+                        message: format!("wrong number of arguments to '{}'", name),
+                    });
+                }
+                Ok(Expr::Call(function, args))
+            }
+            Some(c) => Err(self.error(format!("unexpected '{}'", c))),
+            None => Err(self.error("unexpected end of formula")),
+        }
+    }
+
+    fn take_while<F: Fn(char) -> bool>(&mut self, predicate: F) -> String {
+        let start = self.pos;
+        while self.pos < self.chars.len() && predicate(self.chars[self.pos]) {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs(values: &[(i64, f64)]) -> Inputs {
+        Inputs {
+            values: values
+                .iter()
+                .map(|&(id, value)| (PropertyId::from(id), value))
+                .collect(),
+            size: Some(2048.0),
+            pages: Some(12.0),
+            now: 10.0 * SECONDS_PER_DAY,
+        }
+    }
+
+    fn evaluate(source: &str, values: &[(i64, f64)]) -> Option<f64> {
+        Formula::parse(source).unwrap().evaluate(&inputs(values))
+    }
+
+    fn parse_error(source: &str) -> FormulaError {
+        Formula::parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(evaluate("1 + 2 * 3", &[]), Some(7.0));
+        assert_eq!(evaluate("(1 + 2) * 3", &[]), Some(9.0));
+        assert_eq!(evaluate("8 - 4 - 2", &[]), Some(2.0));
+        assert_eq!(evaluate("8 / 4 / 2", &[]), Some(1.0));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(evaluate("-2 * 3", &[]), Some(-6.0));
+        assert_eq!(evaluate("--2", &[]), Some(2.0));
+        assert_eq!(evaluate("4 - -2", &[]), Some(6.0));
+    }
+
+    #[test]
+    fn property_references() {
+        let formula = Formula::parse("{3} * 2 + {5} - {3}").unwrap();
+        assert_eq!(
+            formula.inputs(),
+            vec![PropertyId::from(3), PropertyId::from(5)]
+        );
+        assert_eq!(formula.evaluate(&inputs(&[(3, 4.0), (5, 1.0)])), Some(5.0));
+        // a missing input leaves the value unset
+        assert_eq!(formula.evaluate(&inputs(&[(3, 4.0)])), None);
+    }
+
+    #[test]
+    fn functions() {
+        let day = SECONDS_PER_DAY;
+        assert_eq!(evaluate("days_since({3})", &[(3, 7.0 * day)]), Some(3.0));
+        assert_eq!(evaluate("round(2.5)", &[]), Some(3.0));
+        assert_eq!(
+            evaluate("round(days_since({3}))", &[(3, 9.75 * day)]),
+            Some(0.0)
+        );
+        assert_eq!(evaluate("abs(-1.5)", &[]), Some(1.5));
+        assert_eq!(evaluate("min(3, 1, 2)", &[]), Some(1.0));
+        assert_eq!(evaluate("max(3, {1}, 2)", &[(1, 5.0)]), Some(5.0));
+        assert_eq!(evaluate("size() / 1024", &[]), Some(2.0));
+        assert_eq!(evaluate("size() / pages()", &[]), Some(2048.0 / 12.0));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(evaluate("1 / 0", &[]), None);
+        assert_eq!(evaluate("{1} / ({2} - {2})", &[(1, 1.0), (2, 3.0)]), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        let error = parse_error("1 +");
+        assert_eq!(error.message, "unexpected end of formula");
+        assert_eq!(error.position, 3);
+
+        let error = parse_error("(1 + 2");
+        assert_eq!(error.message, "expected ')' but reached the end");
+
+        let error = parse_error("{x}");
+        assert_eq!(error.message, "expected a property id inside of '{}'");
+        assert_eq!(error.position, 1);
+
+        let error = parse_error("2 * median(1)");
+        assert_eq!(error.message, "unknown function 'median'");
+        assert_eq!(error.position, 4);
+
+        let error = parse_error("round(1, 2)");
+        assert_eq!(error.message, "wrong number of arguments to 'round'");
+
+        let error = parse_error("1 2");
+        assert_eq!(error.message, "unexpected trailing input");
+        assert_eq!(error.position, 2);
+
+        assert_eq!(
+            parse_error("1..2").to_string(),
+            "invalid number '1..2' (at character 0)"
+        );
+    }
+}
@@ -7,6 +7,24 @@ pub use select_choice::SelectChoiceId;
 mod property_id;
 pub use property_id::PropertyId;
 
+mod property_row;
+pub use property_row::PropertyRow;
+
+mod property_value;
+pub use property_value::PropertyValue;
+
+mod property_schema;
+pub use property_schema::{PropertySchema, PropertySchemaField, PropertySchemaId};
+
+mod validation;
+pub use validation::{FieldError, ValidationRules};
+
+pub mod formula;
+pub use formula::Formula;
+
+mod refresher;
+pub use refresher::ComputedRefresher;
+
 use crate::user::UserId;
 
 pub trait Property {
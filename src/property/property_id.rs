@@ -1,6 +1,6 @@
 /// Represents a PropertyId
 #[derive(DieselNewType)]
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct PropertyId(i64);
 
 use std::fmt;
@@ -10,3 +10,46 @@ impl fmt::Display for PropertyId {
         write!(f, "{}", self.0)
     }
 }
+
+/// Properties created by the initial migrations and assigned by the system
+impl PropertyId {
+    pub const FILENAME: PropertyId = PropertyId(1);
+    pub const HASH: PropertyId = PropertyId(2);
+    pub const LAST_MODIFIED: PropertyId = PropertyId(3);
+    pub const TAGS: PropertyId = PropertyId(10);
+    pub const COLLECTION: PropertyId = PropertyId(20);
+    pub const FROM: PropertyId = PropertyId(50);
+    pub const TO: PropertyId = PropertyId(51);
+    pub const CC: PropertyId = PropertyId(52);
+    pub const SUBJECT: PropertyId = PropertyId(53);
+    pub const DATE: PropertyId = PropertyId(54);
+    pub const MESSAGE_ID: PropertyId = PropertyId(55);
+    pub const IN_REPLY_TO: PropertyId = PropertyId(56);
+    /// Relates an email to its attachments
+    pub const ATTACHMENTS: PropertyId = PropertyId(57);
+    /// Relates a reply to the email it replies to
+    pub const REPLIES_TO: PropertyId = PropertyId(58);
+}
+
+impl From<i64> for PropertyId {
+    fn from(id: i64) -> Self {
+        PropertyId(id)
+    }
+}
+
+use crate::db::{db_error, Fetch};
+use super::PropertyRow;
+use actix_web::Result;
+use diesel::prelude::*;
+use diesel::PgConnection;
+
+impl Fetch<PropertyRow> for PropertyId {
+    fn fetch(&self, conn: &PgConnection) -> Result<PropertyRow> {
+        use crate::db::schema::properties::dsl::*;
+
+        properties
+            .filter(id.eq(&self.0))
+            .get_result::<PropertyRow>(conn)
+            .map_err(|e| db_error("db select get property by id error", e))
+    }
+}
@@ -0,0 +1,32 @@
+use ::chrono::{DateTime, Utc};
+
+use super::{Property, PropertyId, PropertyType};
+use crate::user::UserId;
+
+#[derive(Debug, Queryable)]
+pub struct PropertyRow {
+    pub id: PropertyId,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub ord: f32,
+    pub display: String,
+    pub property_type: PropertyType,
+    pub formula: Option<String>,
+    /// How relation properties read from the target's side
+    pub inverse_display: Option<String>,
+}
+
+impl Property for PropertyRow {
+    fn id(&self) -> PropertyId {
+        self.id.clone()
+    }
+    fn display(&self) -> &str {
+        &self.display
+    }
+    fn kind(&self) -> &PropertyType {
+        &self.property_type
+    }
+    fn created_by(&self) -> &UserId {
+        &self.created_by
+    }
+}
@@ -0,0 +1,33 @@
+use super::{PropertyId, PropertyValue};
+
+/// Represents a PropertySchemaId
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(DieselNewType)]
+pub struct PropertySchemaId(i64);
+
+use std::fmt;
+
+impl fmt::Display for PropertySchemaId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// An ordered set of properties which objects in a collection are given
+#[derive(Debug, Clone, Serialize)]
+pub struct PropertySchema {
+    pub id: PropertySchemaId,
+    pub display: String,
+    pub fields: Vec<PropertySchemaField>,
+}
+
+/// A property of a schema, with the value new objects start with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PropertySchemaField {
+    pub property_id: PropertyId,
+    pub ord: f32,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub default_value: Option<PropertyValue>,
+}
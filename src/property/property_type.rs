@@ -2,10 +2,26 @@
 
 // define your enum
 /// property_type enum
-#[derive(Debug, DbEnum)]
+#[derive(Debug, Clone, PartialEq, DbEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum PropertyType {
     Timestamptz,  // All variants must be fieldless
     Text,
     Relation,
     Choice,
+    /// Derived from other properties by the row's `formula`
+    Computed,
+}
+
+impl PropertyType {
+    /// Name of the type as it appears in the database and JSON
+    pub fn name(&self) -> &'static str {
+        match self {
+            PropertyType::Timestamptz => "timestamptz",
+            PropertyType::Text => "text",
+            PropertyType::Relation => "relation",
+            PropertyType::Choice => "choice",
+            PropertyType::Computed => "computed",
+        }
+    }
 }
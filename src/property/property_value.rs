@@ -0,0 +1,27 @@
+use ::chrono::{DateTime, Utc};
+
+use super::{PropertyType, SelectChoiceId};
+use crate::object::ObjectId;
+
+/// A value to assign to an object's property, tagged by the property's type
+/// e.g. `{"text": "Adidas proposal v3.pdf"}` or `{"choice": [92001]}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PropertyValue {
+    Timestamptz(Option<DateTime<Utc>>),
+    Text(String),
+    Relation(Vec<ObjectId>),
+    Choice(Vec<SelectChoiceId>),
+}
+
+impl PropertyValue {
+    /// The type of property this value may be assigned to
+    pub fn kind(&self) -> PropertyType {
+        match self {
+            PropertyValue::Timestamptz(_) => PropertyType::Timestamptz,
+            PropertyValue::Text(_) => PropertyType::Text,
+            PropertyValue::Relation(_) => PropertyType::Relation,
+            PropertyValue::Choice(_) => PropertyType::Choice,
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use ::actix::prelude::*;
+use actix_web::Error;
+use futures::Future;
+use std::time::Duration;
+
+use crate::db::{ComputedPending, DbExecutor, RecomputeBatch};
+use crate::object::ObjectId;
+
+/// How often computed values that depend on the current time are refreshed
+const REFRESH_EVERY_MINUTES: u64 = 60;
+/// How often objects missing a computed value, e.g. of a new property, are looked for
+const POLL_EVERY_SECONDS: u64 = 10;
+
+/// Recomputes every computed value in batches, periodically since formulas
+/// such as `days_since({3})` change without any of their inputs changing,
+/// and soon after a computed property is created.
+pub struct ComputedRefresher {
+    pub pg: Addr<DbExecutor>,
+    busy: bool,
+}
+
+impl ComputedRefresher {
+    pub fn new(pg: Addr<DbExecutor>) -> Self {
+        ComputedRefresher { pg, busy: false }
+    }
+
+    fn refresh(&mut self, ctx: &mut Context<Self>) {
+        if self.busy {
+            return;
+        }
+        self.busy = true;
+        self.recompute_after(None, ctx);
+    }
+
+    fn refresh_pending(&mut self, ctx: &mut Context<Self>) {
+        if self.busy {
+            return;
+        }
+
+        let check = self
+            .pg
+            .send(ComputedPending)
+            .from_err::<Error>()
+            .and_then(|res| res)
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(true) => act.refresh(ctx),
+                    Ok(false) => {}
+                    Err(e) => error!("ComputedRefresher error: {:?}", e),
+                }
+                actix::fut::ok(())
+            });
+        ctx.spawn(check);
+    }
+
+    /// Recomputes one batch, then spawns the next until every object is done
+    fn recompute_after(&mut self, after: Option<ObjectId>, ctx: &mut Context<Self>) {
+        let batch = self
+            .pg
+            .send(RecomputeBatch { after })
+            .from_err::<Error>()
+            .and_then(|res| res)
+            .into_actor(self)
+            .then(|res, act, ctx| {
+                match res {
+                    Ok(Some(last)) => act.recompute_after(Some(last), ctx),
+                    Ok(None) => act.busy = false,
+                    Err(e) => {
+                        error!("ComputedRefresher error: {:?}", e);
+                        act.busy = false;
+                    }
+                }
+                actix::fut::ok(())
+            });
+        ctx.spawn(batch);
+    }
+}
+
+impl Actor for ComputedRefresher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(
+            Duration::from_secs(REFRESH_EVERY_MINUTES * 60),
+            |act, ctx| act.refresh(ctx),
+        );
+        ctx.run_interval(Duration::from_secs(POLL_EVERY_SECONDS), |act, ctx| {
+            act.refresh_pending(ctx)
+        });
+    }
+}
@@ -1,5 +1,5 @@
 /// Represents a SelectChoiceId
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[derive(DieselNewType)]
 pub struct SelectChoiceId(i64);
 
@@ -10,3 +10,9 @@ impl fmt::Display for SelectChoiceId {
         write!(f, "{}", self.0)
     }
 }
+
+impl From<i64> for SelectChoiceId {
+    fn from(id: i64) -> Self {
+        SelectChoiceId(id)
+    }
+}
@@ -0,0 +1,306 @@
+use regex::Regex;
+
+use super::{PropertyId, PropertyValue, SelectChoiceId};
+
+/// Rules a property's values must satisfy before they are written
+#[derive(Debug, Clone, Queryable, Serialize, Deserialize)]
+pub struct ValidationRules {
+    #[serde(default)]
+    pub required: bool,
+    pub pattern: Option<String>,
+    pub min_length: Option<i32>,
+    pub max_length: Option<i32>,
+    pub min_number: Option<f64>,
+    pub max_number: Option<f64>,
+    #[serde(default = "default_multiple")]
+    pub multiple: bool,
+    pub target_extensions: Option<Vec<String>>,
+}
+
+fn default_multiple() -> bool {
+    true
+}
+
+impl Default for ValidationRules {
+    fn default() -> Self {
+        ValidationRules {
+            required: false,
+            pattern: None,
+            min_length: None,
+            max_length: None,
+            min_number: None,
+            max_number: None,
+            multiple: true,
+            target_extensions: None,
+        }
+    }
+}
+
+/// A rule violated by the value given for a property
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub property_id: PropertyId,
+    pub message: String,
+}
+
+impl ValidationRules {
+    /// Errors if the rules themselves are unusable, such as an invalid pattern
+    pub fn check_rules(&self) -> Result<(), String> {
+        if let Some(ref pattern) = self.pattern {
+            Regex::new(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// The rules with their pattern compiled, to check values against
+    pub fn compile(self) -> RuleSet {
+        let pattern = self
+            .pattern
+            .as_ref()
+            .and_then(|pattern| match Regex::new(pattern) {
+                Ok(re) => Some(re),
+                Err(e) => {
+                    warn!("Ignoring invalid validation pattern {:?}: {}", pattern, e);
+                    None
+                }
+            });
+        RuleSet {
+            rules: self,
+            pattern,
+        }
+    }
+}
+
+/// `ValidationRules` ready to check any number of values
+pub struct RuleSet {
+    rules: ValidationRules,
+    pattern: Option<Regex>,
+}
+
+impl RuleSet {
+    /// Every rule `value` breaks, described for the person editing it.
+    /// `target_extensions` are the extensions of a relation value's targets,
+    /// `known_choices` those of a choice value's ids that are options of the property.
+    pub fn check(
+        &self,
+        value: &PropertyValue,
+        target_extensions: &[String],
+        known_choices: &[SelectChoiceId],
+    ) -> Vec<String> {
+        let rules = &self.rules;
+        let mut errors = Vec::new();
+        match value {
+            PropertyValue::Text(text) => {
+                if rules.required && text.trim().is_empty() {
+                    errors.push("This field is required".to_string());
+                    return errors;
+                }
+                let length = text.chars().count() as i32;
+                if let Some(min) = rules.min_length {
+                    if length < min {
+                        errors.push(format!("Must be at least {} characters", min));
+                    }
+                }
+                if let Some(max) = rules.max_length {
+                    if length > max {
+                        errors.push(format!("Must be at most {} characters", max));
+                    }
+                }
+                if let Some(ref re) = self.pattern {
+                    if !re.is_match(text) {
+                        errors.push(format!("Must match the pattern {}", re.as_str()))
+                    }
+                }
+                if rules.min_number.is_some() || rules.max_number.is_some() {
+                    match text.trim().parse::<f64>() {
+                        Err(_) => errors.push("Must be a number".to_string()),
+                        Ok(number) => {
+                            if let Some(min) = rules.min_number {
+                                if number < min {
+                                    errors.push(format!("Must be at least {}", min));
+                                }
+                            }
+                            if let Some(max) = rules.max_number {
+                                if number > max {
+                                    errors.push(format!("Must be at most {}", max));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            PropertyValue::Timestamptz(timestamp) => {
+                if rules.required && timestamp.is_none() {
+                    errors.push("This field is required".to_string());
+                }
+            }
+            PropertyValue::Choice(choices) => {
+                if rules.required && choices.is_empty() {
+                    errors.push("Choose at least one option".to_string());
+                }
+                if !rules.multiple && choices.len() > 1 {
+                    errors.push("Choose only one option".to_string());
+                }
+                if choices.iter().any(|choice| !known_choices.contains(choice)) {
+                    errors.push("Choose among the property's own options".to_string());
+                }
+            }
+            PropertyValue::Relation(targets) => {
+                if rules.required && targets.is_empty() {
+                    errors.push("Relate at least one document".to_string());
+                }
+                if !rules.multiple && targets.len() > 1 {
+                    errors.push("Relate only one document".to_string());
+                }
+                if let Some(ref allowed) = rules.target_extensions {
+                    for extension in target_extensions {
+                        if !allowed.iter().any(|a| a.eq_ignore_ascii_case(extension)) {
+                            errors.push(format!(
+                                "Related documents must be one of {}",
+                                allowed.join(", ")
+                            ));
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::ObjectId;
+
+    fn check(rules: ValidationRules, value: &PropertyValue) -> Vec<String> {
+        rules
+            .compile()
+            .check(value, &[], &[SelectChoiceId::from(1)])
+    }
+
+    fn text(value: &str) -> PropertyValue {
+        PropertyValue::Text(value.to_string())
+    }
+
+    #[test]
+    fn permissive_by_default() {
+        let rules = ValidationRules::default;
+        assert!(check(rules(), &text("")).is_empty());
+        assert!(check(rules(), &PropertyValue::Timestamptz(None)).is_empty());
+        assert!(check(rules(), &PropertyValue::Choice(vec![])).is_empty());
+    }
+
+    #[test]
+    fn required() {
+        let rules = || ValidationRules {
+            required: true,
+            min_length: Some(3),
+            ..ValidationRules::default()
+        };
+        assert_eq!(check(rules(), &text("  ")), vec!["This field is required"]);
+        assert_eq!(
+            check(rules(), &PropertyValue::Timestamptz(None)),
+            vec!["This field is required"]
+        );
+        assert_eq!(
+            check(rules(), &PropertyValue::Relation(vec![])),
+            vec!["Relate at least one document"]
+        );
+    }
+
+    #[test]
+    fn lengths_and_numbers() {
+        let rules = || ValidationRules {
+            min_length: Some(2),
+            max_length: Some(4),
+            min_number: Some(10.0),
+            max_number: Some(99.5),
+            ..ValidationRules::default()
+        };
+        assert!(check(rules(), &text("42")).is_empty());
+        assert_eq!(
+            check(rules(), &text("5")),
+            vec!["Must be at least 2 characters", "Must be at least 10"]
+        );
+        assert_eq!(
+            check(rules(), &text("100.5")),
+            vec!["Must be at most 4 characters", "Must be at most 99.5"]
+        );
+        assert_eq!(check(rules(), &text("abc")), vec!["Must be a number"]);
+    }
+
+    #[test]
+    fn pattern_compiled_once() {
+        let rules = ValidationRules {
+            pattern: Some(r"^\d{4}-\d{2}$".to_string()),
+            ..ValidationRules::default()
+        }
+        .compile();
+        assert!(rules.check(&text("2019-03"), &[], &[]).is_empty());
+        assert_eq!(
+            rules.check(&text("March"), &[], &[]),
+            vec![r"Must match the pattern ^\d{4}-\d{2}$"]
+        );
+    }
+
+    #[test]
+    fn invalid_pattern() {
+        let rules = ValidationRules {
+            pattern: Some("(".to_string()),
+            ..ValidationRules::default()
+        };
+        assert!(rules
+            .check_rules()
+            .unwrap_err()
+            .starts_with("Invalid pattern"));
+        // rules saved before patterns were checked are ignored rather than failing every value
+        assert!(check(rules, &text("anything")).is_empty());
+    }
+
+    #[test]
+    fn choices() {
+        let single = || ValidationRules {
+            multiple: false,
+            ..ValidationRules::default()
+        };
+        let one = || PropertyValue::Choice(vec![SelectChoiceId::from(1)]);
+        assert!(check(single(), &one()).is_empty());
+        assert_eq!(
+            check(
+                single(),
+                &PropertyValue::Choice(vec![SelectChoiceId::from(1), SelectChoiceId::from(1)])
+            ),
+            vec!["Choose only one option"]
+        );
+        assert_eq!(
+            check(
+                ValidationRules::default(),
+                &PropertyValue::Choice(vec![SelectChoiceId::from(1), SelectChoiceId::from(2)])
+            ),
+            vec!["Choose among the property's own options"]
+        );
+    }
+
+    #[test]
+    fn relation_targets() {
+        let rules = ValidationRules {
+            multiple: false,
+            target_extensions: Some(vec![".pdf".to_string()]),
+            ..ValidationRules::default()
+        }
+        .compile();
+        let targets = PropertyValue::Relation(vec![
+            ObjectId::from("a".to_string()),
+            ObjectId::from("b".to_string()),
+        ]);
+        assert_eq!(
+            rules.check(&targets, &[".PDF".to_string(), ".docx".to_string()], &[]),
+            vec![
+                "Relate only one document",
+                "Related documents must be one of .pdf"
+            ]
+        );
+    }
+}
@@ -0,0 +1,45 @@
+/// A parsed object query, e.g. `tags:Proposal company:"HBO" modified>2018-01-01 ext:pdf`.
+/// An object matches when it matches every term.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Query {
+    pub terms: Vec<Term>,
+}
+
+/// One whitespace separated part of a query
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    /// Preceded by `-`, matching objects which do not match the rest of the term
+    pub negated: bool,
+    /// The field before the operator, or `None` for a bare word matched against the filename
+    pub field: Option<String>,
+    pub comparison: Comparison,
+    pub value: String,
+    /// Character offset of the term in the query, for error messages
+    pub position: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Comparison {
+    /// `:` contains for text, equals for everything else
+    Matches,
+    /// `>`
+    Greater,
+    /// `>=`
+    GreaterOrEqual,
+    /// `<`
+    Less,
+    /// `<=`
+    LessOrEqual,
+}
+
+impl Comparison {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Matches => ":",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+        }
+    }
+}
@@ -0,0 +1,396 @@
+//! Compiling a `Query` into a diesel query over `objects`
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use diesel::dsl::not;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+
+use super::ast::{Comparison, Query, Term};
+use super::parser::QueryError;
+use crate::db::schema::{
+    choice_values, computed_values, objects, relation_values, text_values, timestamptz_values,
+};
+use crate::object::ObjectId;
+use crate::property::{Property, PropertyId, PropertyRow, PropertyType, SelectChoiceId};
+
+pub type ObjectsQuery<'a> = objects::BoxedQuery<'a, Pg>;
+
+/// The properties and choices query fields and values are resolved against
+pub struct Catalog {
+    pub properties: Vec<PropertyRow>,
+    /// (id, property id, display) of every choice
+    pub choices: Vec<(SelectChoiceId, PropertyId, String)>,
+}
+
+/// Lowercase letters and digits only, so `company` matches "🏰 Company"
+pub fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// What a query field refers to
+pub enum Field<'c> {
+    Id,
+    Extension,
+    Created,
+    /// Objects related to or from an object, by any relation property
+    Related,
+    Property(&'c PropertyRow),
+}
+
+impl Catalog {
+    fn property(&self, id: &PropertyId) -> Option<&PropertyRow> {
+        self.properties.iter().find(|p| &p.id == id)
+    }
+
+    /// Resolve a field name, or `None` for bare words which search filenames
+    pub fn field(&self, name: Option<&str>, position: usize) -> Result<Field, QueryError> {
+        let name = match name {
+            None => return self.filename_field(position),
+            Some(name) => normalize(name),
+        };
+        match name.as_str() {
+            "id" => return Ok(Field::Id),
+            "ext" | "extension" => return Ok(Field::Extension),
+            "created" => return Ok(Field::Created),
+            "related" => return Ok(Field::Related),
+            "name" => return self.filename_field(position),
+            "tag" => return self.field(Some("tags"), position),
+            "modified" => {
+                if let Some(property) = self.property(&PropertyId::LAST_MODIFIED) {
+                    return Ok(Field::Property(property));
+                }
+            }
+            _ => {}
+        }
+        self.properties
+            .iter()
+            .find(|p| normalize(p.display()) == name)
+            .map(Field::Property)
+            .ok_or_else(|| QueryError::new(position, format!("unknown field '{}'", name)))
+    }
+
+    fn filename_field(&self, position: usize) -> Result<Field, QueryError> {
+        self.property(&PropertyId::FILENAME)
+            .map(Field::Property)
+            .ok_or_else(|| QueryError::new(position, "the Filename property is missing"))
+    }
+
+    fn choices_named(&self, property: &PropertyRow, name: &str) -> Vec<SelectChoiceId> {
+        let name = normalize(name);
+        self.choices
+            .iter()
+            .filter(|(_, pid, display)| pid == &property.id && normalize(display) == name)
+            .map(|(id, _, _)| id.clone())
+            .collect()
+    }
+}
+
+/// A timestamp of a query, a whole day or an instant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Moment {
+    /// `2018-01-01`, from midnight UTC
+    Date(DateTime<Utc>),
+    /// An RFC 3339 timestamp
+    Instant(DateTime<Utc>),
+}
+
+/// `2018-01-01` (midnight UTC) or an RFC 3339 timestamp
+pub fn parse_timestamp(value: &str, position: usize) -> Result<Moment, QueryError> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Moment::Instant(timestamp.with_timezone(&Utc)));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|date| Moment::Date(Utc.from_utc_date(&date).and_hms(0, 0, 0)))
+        .map_err(|_| {
+            QueryError::new(
+                position,
+                format!("expected a date like 2018-01-01 but found '{}'", value),
+            )
+        })
+}
+
+fn parse_number(value: &str, position: usize) -> Result<f64, QueryError> {
+    value.parse::<f64>().map_err(|_| {
+        QueryError::new(position, format!("expected a number but found '{}'", value))
+    })
+}
+
+/// `%value%` for ILIKE, with the value's wildcards escaped
+fn contains_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Filter `$query` to objects whose id is (or with `$negated`, is not) in `$ids`
+macro_rules! filter_ids {
+    ($query:expr, $negated:expr, $ids:expr) => {
+        if $negated {
+            $query.filter(objects::id.ne_all($ids))
+        } else {
+            $query.filter(objects::id.eq_any($ids))
+        }
+    };
+}
+
+/// Apply `$comparison` between `$column` and `$value`
+macro_rules! compare {
+    ($query:expr, $comparison:expr, $column:expr, $value:expr) => {
+        match $comparison {
+            Comparison::Matches => $query.filter($column.eq($value)),
+            Comparison::Greater => $query.filter($column.gt($value)),
+            Comparison::GreaterOrEqual => $query.filter($column.ge($value)),
+            Comparison::Less => $query.filter($column.lt($value)),
+            Comparison::LessOrEqual => $query.filter($column.le($value)),
+        }
+    };
+}
+
+/// Objects matching every term of the query
+pub fn compile<'a>(catalog: &Catalog, query: &Query) -> Result<ObjectsQuery<'a>, QueryError> {
+    let mut compiled = objects::table.into_boxed();
+    for term in &query.terms {
+        compiled = compile_term(catalog, term, compiled)?;
+    }
+    Ok(compiled)
+}
+
+fn compile_term<'a>(
+    catalog: &Catalog,
+    term: &Term,
+    query: ObjectsQuery<'a>,
+) -> Result<ObjectsQuery<'a>, QueryError> {
+    let position = term.position;
+    let value = term.value.clone();
+
+    Ok(match catalog.field(term.field.as_ref().map(String::as_str), position)? {
+        Field::Id => {
+            let id = ObjectId::from(value);
+            match (term.comparison, term.negated) {
+                (Comparison::Matches, false) => query.filter(objects::id.eq(id)),
+                (Comparison::Matches, true) => query.filter(objects::id.ne(id)),
+                _ => return Err(only_matches(term)),
+            }
+        }
+        Field::Extension => {
+            let extension = format!(".{}", value.trim_start_matches('.').to_lowercase());
+            match (term.comparison, term.negated) {
+                (Comparison::Matches, false) => query.filter(objects::extension.eq(extension)),
+                (Comparison::Matches, true) => query.filter(objects::extension.ne(extension)),
+                _ => return Err(only_matches(term)),
+            }
+        }
+        Field::Created => {
+            let moment = parse_timestamp(&value, position)?;
+            let (start, end) = timestamptz_range(term.comparison, moment);
+            let in_range = objects::created_at
+                .ge(start)
+                .and(objects::created_at.lt(end));
+            if term.negated {
+                query.filter(not(in_range))
+            } else {
+                query.filter(in_range)
+            }
+        }
+        Field::Related => {
+            if term.comparison != Comparison::Matches {
+                return Err(only_matches(term));
+            }
+            let target = ObjectId::from(value);
+            let relating = relation_values::table
+                .filter(relation_values::target_id.eq(target.clone()))
+                .select(relation_values::object_id);
+            let related = relation_values::table
+                .filter(relation_values::object_id.eq(target))
+                .select(relation_values::target_id);
+            if term.negated {
+                query.filter(objects::id.ne_all(relating).and(objects::id.ne_all(related)))
+            } else {
+                query.filter(objects::id.eq_any(relating).or(objects::id.eq_any(related)))
+            }
+        }
+        Field::Property(property) => {
+            let pid = property.id();
+            match property.kind() {
+                PropertyType::Text => {
+                    let base = text_values::table
+                        .select(text_values::object_id)
+                        .filter(text_values::property_id.eq(pid))
+                        .into_boxed();
+                    let matching = match term.comparison {
+                        Comparison::Matches => {
+                            base.filter(text_values::value.ilike(contains_pattern(&value)))
+                        }
+                        _ => compare!(base, term.comparison, text_values::value, value),
+                    };
+                    filter_ids!(query, term.negated, matching)
+                }
+                PropertyType::Timestamptz => {
+                    let moment = parse_timestamp(&value, position)?;
+                    let (start, end) = timestamptz_range(term.comparison, moment);
+                    let matching = timestamptz_values::table
+                        .filter(timestamptz_values::property_id.eq(pid))
+                        .filter(timestamptz_values::value.ge(start))
+                        .filter(timestamptz_values::value.lt(end))
+                        .select(timestamptz_values::object_id);
+                    filter_ids!(query, term.negated, matching)
+                }
+                PropertyType::Computed => {
+                    let number = parse_number(&value, position)?;
+                    let base = computed_values::table
+                        .select(computed_values::object_id)
+                        .filter(computed_values::property_id.eq(pid))
+                        .into_boxed();
+                    let matching = compare!(base, term.comparison, computed_values::value, number);
+                    filter_ids!(query, term.negated, matching)
+                }
+                PropertyType::Choice => {
+                    if term.comparison != Comparison::Matches {
+                        return Err(only_matches(term));
+                    }
+                    let choices = catalog.choices_named(property, &value);
+                    if choices.is_empty() {
+                        return Err(QueryError::new(
+                            position,
+                            format!("{} has no choice named '{}'", property.display(), value),
+                        ));
+                    }
+                    let matching = choice_values::table
+                        .filter(choice_values::property_id.eq(pid))
+                        .filter(choice_values::value_id.eq_any(choices))
+                        .select(choice_values::object_id);
+                    filter_ids!(query, term.negated, matching)
+                }
+                PropertyType::Relation => {
+                    if term.comparison != Comparison::Matches {
+                        return Err(only_matches(term));
+                    }
+                    let matching = relation_values::table
+                        .filter(relation_values::property_id.eq(pid))
+                        .filter(relation_values::target_id.eq(ObjectId::from(value)))
+                        .select(relation_values::object_id);
+                    filter_ids!(query, term.negated, matching)
+                }
+            }
+        }
+    })
+}
+
+/// `[start, end)` of the timestamps a comparison against `moment` accepts.
+/// A date is the whole day, so `:` matches any time of it and `>` only the
+/// days after it, where an instant is only equal to itself.
+fn timestamptz_range(comparison: Comparison, moment: Moment) -> (DateTime<Utc>, DateTime<Utc>) {
+    use chrono::Duration;
+    let min = Utc.ymd(1, 1, 1).and_hms(0, 0, 0);
+    let max = Utc.ymd(9999, 12, 31).and_hms(0, 0, 0);
+    // the timestamps of the moment are [start, next)
+    let (start, next) = match moment {
+        Moment::Date(midnight) => (midnight, midnight + Duration::days(1)),
+        // Postgres keeps microseconds
+        Moment::Instant(instant) => (instant, instant + Duration::microseconds(1)),
+    };
+    match comparison {
+        Comparison::Matches => (start, next),
+        Comparison::Greater => (next, max),
+        Comparison::GreaterOrEqual => (start, max),
+        Comparison::Less => (min, start),
+        Comparison::LessOrEqual => (min, next),
+    }
+}
+
+fn only_matches(term: &Term) -> QueryError {
+    QueryError::new(
+        term.position,
+        format!(
+            "'{}' can only be used with ':' not '{}'",
+            term.field.as_ref().map_or("", String::as_str),
+            term.comparison.symbol()
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::parse;
+    use diesel::debug_query;
+
+    fn date(day: u32) -> Moment {
+        Moment::Date(Utc.ymd(2019, 3, day).and_hms(0, 0, 0))
+    }
+
+    fn at(day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.ymd(2019, 3, day).and_hms(hour, 0, 0)
+    }
+
+    #[test]
+    fn dates_and_instants() {
+        assert_eq!(parse_timestamp("2019-03-01", 0).unwrap(), date(1));
+        assert_eq!(
+            parse_timestamp("2019-03-01T12:00:00+02:00", 0).unwrap(),
+            Moment::Instant(at(1, 10))
+        );
+        assert_eq!(
+            parse_timestamp("March", 7).unwrap_err(),
+            QueryError::new(7, "expected a date like 2018-01-01 but found 'March'")
+        );
+    }
+
+    #[test]
+    fn date_ranges() {
+        let range = |comparison| timestamptz_range(comparison, date(1));
+        let (min, max) = (
+            Utc.ymd(1, 1, 1).and_hms(0, 0, 0),
+            Utc.ymd(9999, 12, 31).and_hms(0, 0, 0),
+        );
+        // the whole day
+        assert_eq!(range(Comparison::Matches), (at(1, 0), at(2, 0)));
+        // from the next midnight
+        assert_eq!(range(Comparison::Greater), (at(2, 0), max));
+        assert_eq!(range(Comparison::GreaterOrEqual), (at(1, 0), max));
+        assert_eq!(range(Comparison::Less), (min, at(1, 0)));
+        // until the next midnight
+        assert_eq!(range(Comparison::LessOrEqual), (min, at(2, 0)));
+    }
+
+    #[test]
+    fn instant_ranges() {
+        let noon = at(1, 12);
+        let after = noon + chrono::Duration::microseconds(1);
+        let range = |comparison| timestamptz_range(comparison, Moment::Instant(noon));
+        let (min, max) = (
+            Utc.ymd(1, 1, 1).and_hms(0, 0, 0),
+            Utc.ymd(9999, 12, 31).and_hms(0, 0, 0),
+        );
+        // only the instant itself
+        assert_eq!(range(Comparison::Matches), (noon, after));
+        assert_eq!(range(Comparison::Greater), (after, max));
+        assert_eq!(range(Comparison::GreaterOrEqual), (noon, max));
+        assert_eq!(range(Comparison::Less), (min, noon));
+        assert_eq!(range(Comparison::LessOrEqual), (min, after));
+    }
+
+    #[test]
+    fn compiles_created() {
+        let catalog = Catalog {
+            properties: Vec::new(),
+            choices: Vec::new(),
+        };
+        let sql = |source: &str| {
+            let compiled = compile(&catalog, &parse(source).unwrap()).unwrap();
+            debug_query::<Pg, _>(&compiled).to_string()
+        };
+        let created = sql("created>2019-03-01");
+        assert!(created.contains("\"objects\".\"created_at\" >= $1"));
+        assert!(created.contains("\"objects\".\"created_at\" < $2"));
+        assert!(created.contains(&format!("{:?}", at(2, 0))));
+
+        let created = sql("-created:2019-03-01T12:00:00Z");
+        assert!(created.contains("NOT"));
+        assert!(created.contains(&format!("{:?}", at(1, 12))));
+    }
+}
@@ -0,0 +1,165 @@
+use chrono::{DateTime, Utc};
+
+use super::parser::QueryError;
+use super::Sort;
+use crate::object::ObjectId;
+
+/// Where a page of query results ended: the sort key and id of its last object.
+/// Handed to clients as an opaque string to continue from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Cursor {
+    /// The sort the cursor is only valid for, as `Sort` displays it
+    #[serde(rename = "s")]
+    pub sort: String,
+    #[serde(rename = "k")]
+    pub key: CursorKey,
+    #[serde(rename = "i")]
+    pub id: ObjectId,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CursorKey {
+    /// The object had no value to sort by
+    Null,
+    Text(String),
+    Timestamp(DateTime<Utc>),
+    Number(f64),
+}
+
+/// Types of values objects can be sorted by
+pub trait CursorValue: Sized {
+    /// `None` if the key is of another type, `Some(None)` if it is null
+    fn from_key(key: &CursorKey) -> Option<Option<Self>>;
+    fn into_key(value: Option<Self>) -> CursorKey;
+}
+
+macro_rules! cursor_value {
+    ($type:ty, $variant:ident) => {
+        impl CursorValue for $type {
+            fn from_key(key: &CursorKey) -> Option<Option<Self>> {
+                match key {
+                    CursorKey::Null => Some(None),
+                    CursorKey::$variant(value) => Some(Some(value.clone())),
+                    _ => None,
+                }
+            }
+
+            fn into_key(value: Option<Self>) -> CursorKey {
+                value.map_or(CursorKey::Null, CursorKey::$variant)
+            }
+        }
+    };
+}
+
+cursor_value!(String, Text);
+cursor_value!(DateTime<Utc>, Timestamp);
+cursor_value!(f64, Number);
+
+impl Cursor {
+    pub fn new<T: CursorValue>(sort: &Sort, key: Option<T>, id: ObjectId) -> Self {
+        Cursor {
+            sort: sort.to_string(),
+            key: T::into_key(key),
+            id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).expect("cursors serialize");
+        base64::encode_config(&json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(encoded: &str) -> Result<Cursor, QueryError> {
+        base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| QueryError::new(0, "invalid cursor"))
+    }
+
+    /// The sort key, if the cursor is for `sort` and its key is a `T`
+    pub fn key_for<T: CursorValue>(&self, sort: &Sort) -> Result<Option<T>, QueryError> {
+        if self.sort != sort.to_string() {
+            return Err(QueryError::new(
+                0,
+                format!("the cursor is for sorting by '{}' not '{}'", self.sort, sort),
+            ));
+        }
+        T::from_key(&self.key).ok_or_else(|| QueryError::new(0, "invalid cursor"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn id() -> ObjectId {
+        ObjectId::from("abc123".to_string())
+    }
+
+    #[test]
+    fn round_trip() {
+        let sort = Sort::parse("-created");
+        let created = Utc.ymd(2019, 3, 1).and_hms(12, 30, 0);
+        let cursor = Cursor::new(&sort, Some(created), id());
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(decoded, cursor);
+        assert_eq!(
+            decoded.key_for::<DateTime<Utc>>(&sort).unwrap(),
+            Some(created)
+        );
+
+        let sort = Sort::parse("company");
+        let cursor = Cursor::new(&sort, Some("Acme".to_string()), id());
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(
+            decoded.key_for::<String>(&sort).unwrap(),
+            Some("Acme".to_string())
+        );
+
+        let cursor = Cursor::new::<f64>(&Sort::parse("size"), None, id());
+        let decoded = Cursor::decode(&cursor.encode()).unwrap();
+        assert_eq!(decoded.key, CursorKey::Null);
+        assert_eq!(decoded.key_for::<f64>(&Sort::parse("size")).unwrap(), None);
+    }
+
+    #[test]
+    fn encoding_is_url_safe() {
+        let cursor = Cursor::new(&Sort::parse("title"), Some("??>>~~".repeat(8)), id());
+        let encoded = cursor.encode();
+        assert!(encoded
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    }
+
+    #[test]
+    fn malformed() {
+        for encoded in &["", "not a cursor!", "e30", "eyJzIjoidGl0bGUifQ"] {
+            let error = Cursor::decode(encoded).unwrap_err();
+            assert_eq!(error.message, "invalid cursor");
+        }
+    }
+
+    #[test]
+    fn tampered() {
+        let sort = Sort::parse("size");
+        let mut encoded = Cursor::new(&sort, Some(42.0), id()).encode();
+        encoded.insert(3, '*');
+        assert!(Cursor::decode(&encoded).is_err());
+
+        let encoded = Cursor::new(&sort, Some(42.0), id()).encode();
+        assert!(Cursor::decode(&encoded[..encoded.len() - 4]).is_err());
+    }
+
+    #[test]
+    fn wrong_sort() {
+        let cursor = Cursor::new(&Sort::parse("size"), Some(42.0), id());
+        let error = cursor.key_for::<f64>(&Sort::parse("-size")).unwrap_err();
+        assert_eq!(
+            error.message,
+            "the cursor is for sorting by 'size' not '-size'"
+        );
+        assert!(cursor.key_for::<String>(&Sort::parse("size")).is_err());
+    }
+}
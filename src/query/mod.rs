@@ -0,0 +1,60 @@
+//! The query language used to find objects by their properties
+mod ast;
+pub use ast::{Comparison, Query, Term};
+
+mod parser;
+pub use parser::{parse, QueryError};
+
+mod compile;
+pub use compile::{compile, normalize, Catalog, Field, ObjectsQuery};
+
+mod cursor;
+pub use cursor::{Cursor, CursorKey, CursorValue};
+
+mod saved_view;
+pub use saved_view::{SavedView, SavedViewId, ViewLayout, ViewLayoutMapping};
+
+/// Ordering of query results, e.g. `created`, `-modified` or `company`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sort {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl Sort {
+    /// A field name, descending when prefixed with `-`
+    pub fn parse(source: &str) -> Self {
+        let source = source.trim();
+        if source.starts_with('-') {
+            Sort {
+                field: source[1..].to_string(),
+                descending: true,
+            }
+        } else {
+            Sort {
+                field: source.to_string(),
+                descending: false,
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Sort {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.descending {
+            write!(f, "-{}", self.field)
+        } else {
+            write!(f, "{}", self.field)
+        }
+    }
+}
+
+impl Default for Sort {
+    /// Newest first
+    fn default() -> Self {
+        Sort {
+            field: "created".to_string(),
+            descending: true,
+        }
+    }
+}
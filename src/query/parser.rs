@@ -0,0 +1,352 @@
+use std::fmt;
+
+use super::ast::{Comparison, Query, Term};
+
+/// Syntax error with the character offset it occurred at
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueryError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    pub fn new<T: Into<String>>(position: usize, message: T) -> Self {
+        QueryError {
+            position,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (at character {})", self.message, self.position)
+    }
+}
+
+/// Parse a query such as `tags:Proposal company:"HBO" modified>2018-01-01 -ext:key`
+///
+/// ```text
+/// query      := term*
+/// term       := '-'? (field comparison)? value
+/// field      := [A-Za-z0-9_]+
+/// comparison := ':' | '>' | '>=' | '<' | '<='
+/// value      := '"' ([^"\\] | '\\' any)* '"' | [^\s"]+
+/// ```
+pub fn parse(source: &str) -> Result<Query, QueryError> {
+    let mut parser = Parser {
+        chars: source.chars().collect(),
+        pos: 0,
+    };
+    let mut terms = Vec::new();
+    loop {
+        parser.skip_whitespace();
+        if parser.pos >= parser.chars.len() {
+            return Ok(Query { terms });
+        }
+        terms.push(parser.term()?);
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+fn is_field_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Parser {
+    fn skip_whitespace(&mut self) {
+        while self.pos < self.chars.len() && self.chars[self.pos].is_whitespace() {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn term(&mut self) -> Result<Term, QueryError> {
+        let position = self.pos;
+        let negated = self.peek() == Some('-');
+        if negated {
+            self.pos += 1;
+        }
+
+        // A field is only a field if an operator follows it
+        let field_start = self.pos;
+        while self.peek().map_or(false, is_field_char) {
+            self.pos += 1;
+        }
+        let field: String = self.chars[field_start..self.pos].iter().collect();
+        let comparison = if field.is_empty() { None } else { self.comparison() };
+        let field = match comparison {
+            Some(_) => Some(field),
+            None => {
+                self.pos = field_start;
+                None
+            }
+        };
+
+        let value = self.value()?;
+        if value.is_empty() {
+            return Err(QueryError::new(
+                self.pos,
+                match field {
+                    Some(ref field) => format!("expected a value after '{}'", field),
+                    None => "expected a value".to_string(),
+                },
+            ));
+        }
+        Ok(Term {
+            negated,
+            field,
+            comparison: comparison.unwrap_or(Comparison::Matches),
+            value,
+            position,
+        })
+    }
+
+    fn comparison(&mut self) -> Option<Comparison> {
+        let comparison = match (self.peek(), self.chars.get(self.pos + 1).cloned()) {
+            (Some(':'), _) => (Comparison::Matches, 1),
+            (Some('>'), Some('=')) => (Comparison::GreaterOrEqual, 2),
+            (Some('>'), _) => (Comparison::Greater, 1),
+            (Some('<'), Some('=')) => (Comparison::LessOrEqual, 2),
+            (Some('<'), _) => (Comparison::Less, 1),
+            _ => return None,
+        };
+        self.pos += comparison.1;
+        Some(comparison.0)
+    }
+
+    fn value(&mut self) -> Result<String, QueryError> {
+        if self.peek() != Some('"') {
+            let start = self.pos;
+            while self.peek().map_or(false, |c| !c.is_whitespace() && c != '"') {
+                self.pos += 1;
+            }
+            if self.peek() == Some('"') {
+                return Err(QueryError::new(
+                    self.pos,
+                    "unexpected '\"' inside of a value, quote the whole value instead",
+                ));
+            }
+            return Ok(self.chars[start..self.pos].iter().collect());
+        }
+
+        let opening = self.pos;
+        self.pos += 1;
+        let mut value = String::new();
+        loop {
+            match self.peek() {
+                None => return Err(QueryError::new(opening, "unclosed '\"'")),
+                Some('"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some('\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(escaped) => value.push(escaped),
+                        None => return Err(QueryError::new(opening, "unclosed '\"'")),
+                    }
+                    self.pos += 1;
+                }
+                Some(c) => {
+                    value.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        if self.peek().map_or(false, |c| !c.is_whitespace()) {
+            return Err(QueryError::new(self.pos, "expected a space after '\"'"));
+        }
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(
+        negated: bool,
+        field: Option<&str>,
+        comparison: Comparison,
+        value: &str,
+        position: usize,
+    ) -> Term {
+        Term {
+            negated,
+            field: field.map(String::from),
+            comparison,
+            value: value.to_string(),
+            position,
+        }
+    }
+
+    fn error(source: &str) -> QueryError {
+        parse(source).unwrap_err()
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(parse("").unwrap(), Query::default());
+        assert_eq!(parse("   ").unwrap(), Query::default());
+    }
+
+    #[test]
+    fn bare_words() {
+        assert_eq!(
+            parse("budget  2019").unwrap().terms,
+            vec![
+                term(false, None, Comparison::Matches, "budget", 0),
+                term(false, None, Comparison::Matches, "2019", 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(
+            parse("tags:Proposal ext:pdf url:a:b").unwrap().terms,
+            vec![
+                term(false, Some("tags"), Comparison::Matches, "Proposal", 0),
+                term(false, Some("ext"), Comparison::Matches, "pdf", 14),
+                term(false, Some("url"), Comparison::Matches, "a:b", 22),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert_eq!(
+            parse(r#"company:"HBO \"Max\" \\ Go" "two words""#)
+                .unwrap()
+                .terms,
+            vec![
+                term(
+                    false,
+                    Some("company"),
+                    Comparison::Matches,
+                    r#"HBO "Max" \ Go"#,
+                    0
+                ),
+                term(false, None, Comparison::Matches, "two words", 28),
+            ]
+        );
+    }
+
+    #[test]
+    fn comparisons() {
+        let comparisons: Vec<(Option<String>, Comparison, String)> =
+            parse("modified>2018-01-01 size>=10 pages<3 n<=4")
+                .unwrap()
+                .terms
+                .into_iter()
+                .map(|term| (term.field, term.comparison, term.value))
+                .collect();
+        assert_eq!(
+            comparisons,
+            vec![
+                (
+                    Some("modified".to_string()),
+                    Comparison::Greater,
+                    "2018-01-01".to_string()
+                ),
+                (
+                    Some("size".to_string()),
+                    Comparison::GreaterOrEqual,
+                    "10".to_string()
+                ),
+                (Some("pages".to_string()), Comparison::Less, "3".to_string()),
+                (
+                    Some("n".to_string()),
+                    Comparison::LessOrEqual,
+                    "4".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn instants() {
+        for (source, comparison) in &[
+            ("created:2019-03-01T12:00:00+01:00", Comparison::Matches),
+            ("created>2019-03-01T12:00:00+01:00", Comparison::Greater),
+            (
+                "created>=2019-03-01T12:00:00+01:00",
+                Comparison::GreaterOrEqual,
+            ),
+            ("created<2019-03-01T12:00:00+01:00", Comparison::Less),
+            (
+                "created<=2019-03-01T12:00:00+01:00",
+                Comparison::LessOrEqual,
+            ),
+        ] {
+            assert_eq!(
+                parse(source).unwrap().terms,
+                vec![term(
+                    false,
+                    Some("created"),
+                    *comparison,
+                    "2019-03-01T12:00:00+01:00",
+                    0
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(
+            parse(r#"-ext:key -draft -"old copy""#).unwrap().terms,
+            vec![
+                term(true, Some("ext"), Comparison::Matches, "key", 0),
+                term(true, None, Comparison::Matches, "draft", 9),
+                term(true, None, Comparison::Matches, "old copy", 16),
+            ]
+        );
+        // a dash inside a value is part of it
+        assert_eq!(
+            parse("created:2019-01-01").unwrap().terms[0].value,
+            "2019-01-01"
+        );
+    }
+
+    #[test]
+    fn unclosed_quote() {
+        assert_eq!(
+            error(r#"tags:a name:"open"#),
+            QueryError::new(12, "unclosed '\"'")
+        );
+        assert_eq!(error(r#""ends in \"#), QueryError::new(0, "unclosed '\"'"));
+    }
+
+    #[test]
+    fn stray_quote() {
+        assert_eq!(
+            error(r#"ab"c""#),
+            QueryError::new(
+                2,
+                "unexpected '\"' inside of a value, quote the whole value instead"
+            )
+        );
+        assert_eq!(
+            error(r#"name:"a"b"#),
+            QueryError::new(8, "expected a space after '\"'")
+        );
+    }
+
+    #[test]
+    fn missing_value() {
+        assert_eq!(
+            error("ext:pdf tags:"),
+            QueryError::new(13, "expected a value after 'tags'")
+        );
+        assert_eq!(error("a -"), QueryError::new(3, "expected a value"));
+    }
+}
@@ -0,0 +1,51 @@
+use ::chrono::{DateTime, Utc};
+
+use crate::property::{PropertyId, SelectChoiceId};
+use crate::user::UserId;
+
+/// Represents a SavedViewId
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(DieselNewType)]
+pub struct SavedViewId(i64);
+
+use std::fmt;
+
+impl fmt::Display for SavedViewId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// view_layout enum
+#[derive(Debug, Clone, Copy, PartialEq, DbEnum, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ViewLayout {
+    #[default]
+    Table,
+    Grid,
+}
+
+/// A named query along with the columns, sort and grouping its results are shown with
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct SavedView {
+    pub id: SavedViewId,
+    pub display: String,
+    /// In the query language, e.g. `tags:Proposal -signed:yes created>=2019-01-01`
+    pub query: String,
+    pub columns: Vec<PropertyId>,
+    /// As parsed by `Sort::parse`
+    pub sort: String,
+    pub group_by: Option<PropertyId>,
+    pub layout: ViewLayout,
+    /// The collection the view is shared through, or `None` for personal views
+    pub collection_id: Option<SelectChoiceId>,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SavedView {
+    /// Whether `user` may see the view
+    pub fn visible_to(&self, user: &UserId) -> bool {
+        self.collection_id.is_some() || &self.created_by == user
+    }
+}
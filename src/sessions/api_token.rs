@@ -0,0 +1,74 @@
+use ::chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+use crate::user::UserId;
+
+/// Prefix of every secret, so leaked tokens are easy to recognize
+const SECRET_PREFIX: &str = "dwy_";
+const SECRET_LENGTH: usize = 40;
+
+/// Represents an ApiTokenId
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(DieselNewType)]
+pub struct ApiTokenId(i64);
+
+use std::fmt;
+
+impl fmt::Display for ApiTokenId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// token_scope enum
+#[derive(Debug, Clone, Copy, PartialEq, DbEnum, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenScope {
+    /// Only requests which change nothing, e.g. `GET`
+    #[default]
+    Read,
+    Write,
+}
+
+impl TokenScope {
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenScope::Read => "read",
+            TokenScope::Write => "write",
+        }
+    }
+}
+
+/// A personal API token, without its secret
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct ApiToken {
+    pub id: ApiTokenId,
+    pub user_id: UserId,
+    pub display: String,
+    pub scope: TokenScope,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.map_or(false, |expires| expires <= Utc::now())
+    }
+}
+
+/// A new random secret to hand to the token's owner
+pub fn new_secret() -> String {
+    let random: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .collect();
+    format!("{}{}", SECRET_PREFIX, random)
+}
+
+/// How a secret is stored, so a leaked database does not leak the tokens
+pub fn hash_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
@@ -6,6 +6,9 @@ mod rand_util;
 mod user_session;
 pub use user_session::UserSession;
 
+mod api_token;
+pub use api_token::{hash_secret, new_secret, ApiToken, ApiTokenId, TokenScope, TokenScopeMapping};
+
 pub mod flash;
 
 pub mod session_manager;
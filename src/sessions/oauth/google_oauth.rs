@@ -6,6 +6,8 @@ use futures::Future;
 
 use chrono::{DateTime, Utc, Duration};
 
+use crate::config::Config;
+
 #[derive(Clone, Debug)]
 pub struct GoogleAccessToken {
     pub access_token: String,
@@ -34,13 +36,13 @@ pub enum ExchangeResult {
     },
 }
 
-pub fn exchange_code_for_token(code: &str) -> FutureResponse<ExchangeResult> {
+pub fn exchange_code_for_token(config: &Config, code: &str) -> FutureResponse<ExchangeResult> {
     info!("exchange_code_for_token");
     // Construct a request against http://localhost:8020/token, the access token endpoint
-    let redirect_uri = format!("{}/login/google/callback", dotenv!("ROOT_HOST"));
+    let redirect_uri = config.google_redirect_uri();
 
-    let client_id = dotenv!("GOOGLE_OAUTH_CLIENT_ID");
-    let client_secret = dotenv!("GOOGLE_OAUTH_CLIENT_SECRET");
+    let client_id = config.google_oauth_client_id.as_str();
+    let client_secret = config.google_oauth_client_secret.as_str();
     let google_token_endpoint = "https://www.googleapis.com/oauth2/v4/token";
 
     // https://developers.google.com/identity/protocols/OAuth2WebServer#offline
@@ -119,9 +121,12 @@ struct GoogleTokenRefresh {
     pub token_type: String,   // "Bearer"
 }
 
-pub fn refresh_google_token(refresh_token: &str) -> FutureResponse<GoogleAccessToken> {
-    let client_id = dotenv!("GOOGLE_OAUTH_CLIENT_ID");
-    let client_secret = dotenv!("GOOGLE_OAUTH_CLIENT_SECRET");
+pub fn refresh_google_token(
+    config: &Config,
+    refresh_token: &str,
+) -> FutureResponse<GoogleAccessToken> {
+    let client_id = config.google_oauth_client_id.as_str();
+    let client_secret = config.google_oauth_client_secret.as_str();
     let google_token_endpoint = "https://www.googleapis.com/oauth2/v4/token";
     // let google_token_endpoint = "http://httpbin.org/post";
 
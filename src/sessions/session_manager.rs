@@ -53,8 +53,14 @@ impl Message for CreateSession {
     type Result = Result<CreateSessionResult>;
 }
 
+/// Redis key of the version a user's sessions must have, deleting it signs
+/// the user out everywhere
+pub fn session_key(user_id: &UserId) -> String {
+    format!("ut#{}", user_id)
+}
+
 fn get_auth_key_and_value(user_id: &UserId, version: i32) -> (String, String) {
-    (format!("ut#{}", user_id), format!("v#{}", version))
+    (session_key(user_id), format!("v#{}", version))
 }
 
 fn send_error<T: Debug + Display>(e: T) -> Error {
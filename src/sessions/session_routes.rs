@@ -16,7 +16,9 @@ use actix_web::{error, Error, Result};
 
 use super::flash::SessionFlash;
 use super::session_manager::{self, SessionManager};
-use super::UserSession;
+use super::{TokenScope, UserSession};
+use crate::config::Config;
+use crate::db::{AuthenticateApiToken, TokenAuthentication};
 
 // Main application state
 use super::super::State;
@@ -38,9 +40,56 @@ pub enum SigninState {
     NotSignedIn,
 }
 
+/// The secret of an `Authorization: Bearer <secret>` header
+pub fn bearer_token(req: &HttpRequest<State>) -> Option<String> {
+    req.headers()
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| {
+            let mut parts = value.splitn(2, ' ');
+            match (parts.next(), parts.next()) {
+                (Some(scheme), Some(secret)) if scheme.eq_ignore_ascii_case("bearer") => {
+                    Some(secret.trim().to_string())
+                }
+                _ => None,
+            }
+        })
+}
+
+/// Signs in with an API token, which is rejected outright rather than
+/// treated as signed out when invalid, or read only for a change
+fn token_signin(
+    req: &HttpRequest<State>,
+    secret: String,
+) -> impl Future<Item = SigninState, Error = Error> {
+    let changes = !req.method().is_safe();
+    req.state()
+        .db
+        .send(AuthenticateApiToken { secret })
+        .from_err()
+        .and_then(|res| res)
+        .and_then(move |authentication| match authentication {
+            TokenAuthentication::Valid(_, TokenScope::Read) if changes => Err(
+                error::ErrorForbidden("The API token is read only"),
+            ),
+            TokenAuthentication::Valid(session, _) => Ok(SigninState::Valid(session)),
+            TokenAuthentication::Invalid => Err(error::ErrorUnauthorized(
+                "The API token is invalid, revoked or expired",
+            )),
+        })
+}
+
+/// Signs in with the request's API token, or else its session cookie
 pub fn is_signed_in_guard(
     req: &HttpRequest<State>,
 ) -> impl Future<Item = SigninState, Error = Error> {
+    match bearer_token(req) {
+        Some(secret) => Either::A(token_signin(req, secret)),
+        None => Either::B(cookie_signin(req)),
+    }
+}
+
+fn cookie_signin(req: &HttpRequest<State>) -> impl Future<Item = SigninState, Error = Error> {
     let req_session = req.session();
     let session_mgr: Addr<SessionManager> = req.state().sessions.clone();
 
@@ -73,6 +122,16 @@ pub fn is_signed_in_guard(
         })
 }
 
+/// Resolves to the signed in user's session, or errors with forbidden
+pub fn require_session(
+    req: &HttpRequest<State>,
+) -> impl Future<Item = UserSession, Error = Error> {
+    is_signed_in_guard(req).and_then(|signin_state| match signin_state {
+        SigninState::Valid(session) => Ok(session),
+        _ => Err(error::ErrorForbidden("Must sign in")),
+    })
+}
+
 fn login(req: &HttpRequest<State>) -> Box<Future<Item = HttpResponse, Error = Error>> {
     let req_session = req.session();
 
@@ -97,8 +156,8 @@ fn login(req: &HttpRequest<State>) -> Box<Future<Item = HttpResponse, Error = Er
     )
 }
 
-fn login_google(_req: &HttpRequest<State>) -> Result<HttpResponse> {
-    let redirect_uri = format!("{}/login/google/callback", dotenv!("ROOT_HOST"));
+fn login_google(req: &HttpRequest<State>) -> Result<HttpResponse> {
+    let config = &req.state().config;
 
     // TODO: Think about using state to transfer login across browsers like events-nyc does
     // This essentially requires us to set the state directly into Redis, and may require us
@@ -109,7 +168,7 @@ fn login_google(_req: &HttpRequest<State>) -> Result<HttpResponse> {
     Ok(HttpResponse::Found()
         .header(
             "location",
-            get_redirect_url(&redirect_uri, None, Some("reaktor.fi")),
+            get_redirect_url(config, None, Some("reaktor.fi")),
         )
         .finish())
 }
@@ -143,7 +202,7 @@ fn login_google_callback(
         &conn_info
     );
     Box::new(
-        google_oauth::exchange_code_for_token(&code).and_then(move |result| {
+        google_oauth::exchange_code_for_token(&request.state().config, &code).and_then(move |result| {
             info!("login_google_callback exchange_code_for_token result");
             use google_oauth::ExchangeResult::*;
             let create_session = match result {
@@ -214,8 +273,9 @@ fn login_google_callback(
     )
 }
 
-pub fn get_redirect_url(redirect_uri: &str, state: Option<&str>, domain: Option<&str>) -> String {
-    let gapi_client_id = dotenv!("GOOGLE_OAUTH_CLIENT_ID");
+pub fn get_redirect_url(config: &Config, state: Option<&str>, domain: Option<&str>) -> String {
+    let gapi_client_id = &config.google_oauth_client_id;
+    let redirect_uri = config.google_redirect_uri();
 
     let oauth_endpoint = "https://accounts.google.com/o/oauth2/v2/auth";
     // let calendar_scope = "https://www.googleapis.com/auth/calendar";
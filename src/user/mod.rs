@@ -10,11 +10,26 @@ pub use person_user::PersonUser;
 mod user;
 pub use user::User;
 
+mod plugin;
+pub use plugin::Plugin;
+
 /// user_kind enum
 /// This maps directly to a PostgreSQL enum type
-#[derive(Debug, Clone, DbEnum)]
+#[derive(Debug, Clone, PartialEq, DbEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum UserKind {
     Person,
     Reserved,
     Plugin,
 }
+
+impl UserKind {
+    /// Name of the kind as it appears in the database and JSON
+    pub fn name(&self) -> &'static str {
+        match self {
+            UserKind::Person => "person",
+            UserKind::Reserved => "reserved",
+            UserKind::Plugin => "plugin",
+        }
+    }
+}
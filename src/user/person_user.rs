@@ -14,34 +14,43 @@ pub struct PersonUser {
     pub full_name: String,
     #[serde(rename = "pu")]
     pub photo_url: Option<String>,
+    /// Plugins sign in with tokens as well, and have no email
+    #[serde(rename = "k", default = "person_kind")]
+    pub kind: UserKind,
+}
+
+fn person_kind() -> UserKind {
+    UserKind::Person
 }
 
 impl PersonUser {
-    /// Try from will fail if the user does not have a public email
+    /// Try from will fail if the user is a person without a public email
     pub fn try_from<U: User>(user: &U) -> Result<Self> {
         Ok(PersonUser {
             user_id: user.id(),
             display_name: user.display_name().to_string(),
             full_name: user.full_name().to_string(),
-            public_email: user
-                .public_email()
-                .ok_or_else(|| {
-                    error::ErrorExpectationFailed("db person should have public_email error")
-                })?
-                .to_owned(),
+            public_email: match (user.kind(), user.public_email()) {
+                (_, Some(email)) => email.to_owned(),
+                (UserKind::Plugin, None) => String::new(),
+                _ => {
+                    return Err(error::ErrorExpectationFailed(
+                        "db person should have public_email error",
+                    ))
+                }
+            },
             photo_url: user.photo_url().cloned(),
+            kind: user.kind().clone(),
         })
     }
 }
 
-const PERSON_KIND: &UserKind = &UserKind::Person;
-
 impl User for PersonUser {
     fn id(&self) -> UserId {
         self.user_id.clone()
     }
     fn kind(&self) -> &UserKind {
-        &PERSON_KIND
+        &self.kind
     }
     fn display_name(&self) -> &str {
         &self.display_name
@@ -53,6 +62,6 @@ impl User for PersonUser {
         self.photo_url.as_ref()
     }
     fn public_email(&self) -> Option<&String> {
-        Some(&self.public_email)
+        Some(&self.public_email).filter(|email| !email.is_empty())
     }
 }
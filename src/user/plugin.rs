@@ -0,0 +1,17 @@
+use ::chrono::{DateTime, Utc};
+
+use super::UserId;
+
+/// An integration registered as a user of its own
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Plugin {
+    pub user_id: UserId,
+    /// Unique, e.g. `ocr`
+    pub name: String,
+    pub display_name: String,
+    pub description: String,
+    pub homepage: Option<String>,
+    pub registered_by: UserId,
+    pub registered_at: DateTime<Utc>,
+    pub disabled_at: Option<DateTime<Utc>>,
+}
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, Serialize, Deserialize, DieselNewType)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, DieselNewType)]
 pub struct UserId(i64);
 
 use std::fmt;
@@ -9,6 +9,18 @@ impl fmt::Display for UserId {
     }
 }
 
+/// Reserved users created by the initial migrations
+impl UserId {
+    /// Acts for operators, e.g. on the command line
+    pub const ADMINISTRATOR: UserId = UserId(1);
+}
+
+impl From<i64> for UserId {
+    fn from(id: i64) -> Self {
+        UserId(id)
+    }
+}
+
 use crate::db::{db_error, Fetch};
 use crate::user::UserRow;
 use actix_web::Result;
@@ -1,3 +1,5 @@
+use chrono::{DateTime, Utc};
+
 use super::{User, UserId, UserKind};
 
 #[derive(Debug, Queryable)]
@@ -9,6 +11,24 @@ pub struct UserRow {
     public_email: Option<String>,
     kind: UserKind,
     photo_url: Option<String>,
+    admin: bool,
+    disabled_at: Option<DateTime<Utc>>,
+}
+
+impl UserRow {
+    /// Administrators may manage plugins
+    pub fn is_admin(&self) -> bool {
+        self.admin
+    }
+
+    /// Disabled users may not sign in or use their tokens
+    pub fn disabled_at(&self) -> Option<&DateTime<Utc>> {
+        self.disabled_at.as_ref()
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
 }
 
 impl User for UserRow {
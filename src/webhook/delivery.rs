@@ -0,0 +1,218 @@
+use ::chrono::{DateTime, Utc};
+use actix_web::http::Uri;
+use hmac::{Hmac, Mac};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sha2::Sha256;
+use std::net::{IpAddr, ToSocketAddrs};
+
+use super::{DeliveryStatus, WebhookEvent};
+use crate::user::UserId;
+
+/// Header carrying `sha256=` and the hex HMAC-SHA256 of the request body
+pub const SIGNATURE_HEADER: &str = "X-Dewey-Signature";
+const SECRET_LENGTH: usize = 32;
+
+/// Represents a WebhookId
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(DieselNewType)]
+pub struct WebhookId(i64);
+
+/// Represents a DeliveryId
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(DieselNewType)]
+pub struct DeliveryId(i64);
+
+use std::fmt;
+
+impl fmt::Display for WebhookId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for DeliveryId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A subscription to events. The secret is kept as is, since deliveries are
+/// signed with it, and is only shown when the subscription is created.
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Webhook {
+    pub id: WebhookId,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Every event when empty
+    pub events: Vec<String>,
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An event sent, or to be sent, to a subscription, as listed in its log
+#[derive(Debug, Clone, Serialize, Queryable)]
+pub struct Delivery {
+    pub id: DeliveryId,
+    pub webhook_id: WebhookId,
+    pub event: WebhookEvent,
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl Delivery {
+    /// The request body, whose exact bytes are signed
+    pub fn body(&self) -> String {
+        json!({
+            "id": self.id,
+            "event": self.event,
+            "created_at": self.created_at,
+            "data": self.payload,
+        })
+        .to_string()
+    }
+}
+
+/// The outcome of one attempt at a delivery
+#[derive(Debug, Clone, PartialEq)]
+pub enum DeliveryOutcome {
+    /// The receiver responded with a 2xx status
+    Delivered { status: u16, body: String },
+    /// No response, or one with another status
+    Failed {
+        status: Option<u16>,
+        body: Option<String>,
+        error: String,
+    },
+}
+
+/// A new random secret for a subscription which was not given one
+pub fn new_webhook_secret() -> String {
+    thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .collect()
+}
+
+/// Value of `X-Dewey-Signature` for `body`, which receivers compute from the
+/// raw body and their copy of the secret to check a delivery came from us
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.input(body);
+    format!("sha256={:x}", mac.result().code())
+}
+
+/// Whether `ip` is in the server's own network: loopback, link-local,
+/// private or unspecified
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_private()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_internal(IpAddr::V4(mapped)),
+            None => {
+                let first = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    // fe80::/10
+                    || first & 0xffc0 == 0xfe80
+                    // unique local fc00::/7
+                    || first & 0xfe00 == 0xfc00
+            }
+        },
+    }
+}
+
+/// Refuse a receiver whose host resolves to an internal address, so that
+/// subscriptions cannot make the server send requests into its own network
+pub fn check_receiver(url: &str) -> Result<(), String> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|e| format!("Invalid webhook URL: {}", e))?;
+    let host = uri
+        .host()
+        .ok_or_else(|| "A webhook's URL names a host".to_string())?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let default_port = if uri.scheme_str() == Some("https") {
+        443
+    } else {
+        80
+    };
+    let port = uri.port_u16().unwrap_or(default_port);
+    let addrs = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Can't resolve {}: {}", host, e))?;
+    for addr in addrs {
+        if is_internal(addr.ip()) {
+            return Err(format!(
+                "{} resolves to {}, which webhooks may not be sent to",
+                host,
+                addr.ip()
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_of_known_vector() {
+        // RFC 4231, test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn internal_receivers() {
+        for url in &[
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://10.1.2.3/hook",
+            "https://192.168.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://0.0.0.0/hook",
+            "http://[::1]:8080/hook",
+            "http://[fe80::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:172.16.0.1]/hook",
+        ] {
+            assert!(check_receiver(url).is_err(), "{} was accepted", url);
+        }
+    }
+
+    #[test]
+    fn public_receivers() {
+        assert_eq!(check_receiver("https://93.184.216.34/hook"), Ok(()));
+        assert_eq!(
+            check_receiver("http://[2606:2800:220:1::1]:8080/hook"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn new_secrets_differ() {
+        let secret = new_webhook_secret();
+        assert_eq!(secret.len(), SECRET_LENGTH);
+        assert_ne!(secret, new_webhook_secret());
+    }
+}
@@ -0,0 +1,246 @@
+use ::actix::prelude::*;
+use actix_web::client::{self, ClientResponse};
+use actix_web::{Error, HttpMessage};
+use futures::future::{self, Either};
+use futures::{Future, Stream};
+use std::time::Duration;
+
+use super::{signature, DeliveryOutcome, SIGNATURE_HEADER};
+use crate::db::{DbExecutor, DueDelivery, ListDueDeliveries, RecordDeliveryAttempt};
+
+/// How often the queue of pending deliveries is checked
+const POLL_EVERY_SECONDS: u64 = 5;
+/// Deliveries attempted at a time, concurrently
+const BATCH_SIZE: i64 = 20;
+/// How long a receiver has to respond
+const TIMEOUT_SECONDS: u64 = 10;
+/// Largest response body read from a receiver, in bytes
+const MAX_RESPONSE_LEN: usize = 64 * 1024;
+
+/// Sends queued webhook deliveries as signed JSON `POST`s and records each
+/// attempt, so failed deliveries are retried later. Deliveries are claimed
+/// before they are sent, so each server process sends its own.
+pub struct WebhookDispatcher {
+    pub pg: Addr<DbExecutor>,
+    busy: bool,
+}
+
+impl WebhookDispatcher {
+    pub fn new(pg: Addr<DbExecutor>) -> Self {
+        WebhookDispatcher { pg, busy: false }
+    }
+
+    fn dispatch_due(&mut self, ctx: &mut Context<Self>) {
+        if self.busy {
+            return;
+        }
+        self.busy = true;
+
+        let pg = self.pg.clone();
+        let work = self
+            .pg
+            .send(ListDueDeliveries { limit: BATCH_SIZE })
+            .from_err::<Error>()
+            .and_then(|res| res)
+            .and_then(move |due| {
+                future::join_all(due.into_iter().map(move |due| {
+                    let pg = pg.clone();
+                    let delivery_id = due.delivery.id.clone();
+                    let attempts = due.delivery.attempts;
+                    deliver(due).and_then(move |outcome| {
+                        pg.send(RecordDeliveryAttempt {
+                            delivery_id,
+                            attempts,
+                            outcome,
+                        })
+                        .from_err()
+                        .and_then(|res| res)
+                    })
+                }))
+            })
+            .into_actor(self)
+            .then(|res, act, _ctx| {
+                if let Err(e) = res {
+                    error!("WebhookDispatcher error: {:?}", e);
+                }
+                act.busy = false;
+                actix::fut::ok(())
+            });
+        ctx.spawn(work);
+    }
+}
+
+/// Attempt a delivery once, resolving to its outcome whether or not it worked
+fn deliver(due: DueDelivery) -> impl Future<Item = DeliveryOutcome, Error = Error> {
+    let body = due.delivery.body();
+    let request = client::post(&due.url)
+        .header("User-Agent", "Dewey-Webhooks")
+        .header("Content-Type", "application/json")
+        .header("X-Dewey-Event", due.delivery.event.name())
+        .header("X-Dewey-Delivery", due.delivery.id.to_string())
+        .header(SIGNATURE_HEADER, signature(&due.secret, body.as_bytes()))
+        .body(body);
+    let request = match request {
+        Ok(request) => request,
+        Err(e) => {
+            return Either::A(future::ok(DeliveryOutcome::Failed {
+                status: None,
+                body: None,
+                error: format!("building request: {}", e),
+            }))
+        }
+    };
+
+    Either::B(
+        request
+            .send()
+            .timeout(Duration::from_secs(TIMEOUT_SECONDS))
+            .then(|sent| match sent {
+                Ok(resp) => Either::A(outcome_of(resp)),
+                Err(e) => Either::B(future::ok(DeliveryOutcome::Failed {
+                    status: None,
+                    body: None,
+                    error: format!("sending request: {}", e),
+                })),
+            }),
+    )
+}
+
+fn outcome_of(resp: ClientResponse) -> impl Future<Item = DeliveryOutcome, Error = Error> {
+    let status = resp.status();
+    resp.payload()
+        .fold(Vec::new(), |mut read, chunk| {
+            if read.len() < MAX_RESPONSE_LEN {
+                read.extend_from_slice(&chunk);
+            }
+            Ok::<_, actix_web::error::PayloadError>(read)
+        })
+        .then(move |read| {
+            let body = read
+                .map(|read| String::from_utf8_lossy(&read).into_owned())
+                .unwrap_or_default();
+            Ok::<_, Error>(if status.is_success() {
+                DeliveryOutcome::Delivered {
+                    status: status.as_u16(),
+                    body,
+                }
+            } else {
+                DeliveryOutcome::Failed {
+                    status: Some(status.as_u16()),
+                    body: Some(body),
+                    error: format!("receiver responded {}", status),
+                }
+            })
+        })
+}
+
+impl Actor for WebhookDispatcher {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.dispatch_due(ctx);
+        ctx.run_interval(Duration::from_secs(POLL_EVERY_SECONDS), |act, ctx| {
+            act.dispatch_due(ctx)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{http, test, HttpRequest, HttpResponse};
+    use chrono::Utc;
+    use std::sync::mpsc;
+
+    use crate::webhook::{Delivery, DeliveryStatus, WebhookEvent};
+
+    fn due(url: String) -> DueDelivery {
+        DueDelivery {
+            delivery: Delivery {
+                id: serde_json::from_value(json!(7)).unwrap(),
+                webhook_id: serde_json::from_value(json!(3)).unwrap(),
+                event: WebhookEvent::Ping,
+                payload: json!({ "hello": "there" }),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                next_attempt_at: Utc::now(),
+                response_status: None,
+                response_body: None,
+                error: None,
+                created_at: Utc::now(),
+                delivered_at: None,
+            },
+            url,
+            secret: "shh".to_string(),
+        }
+    }
+
+    /// A receiver responding with `status`, which passes on the headers of
+    /// each request it gets
+    fn receiver(status: http::StatusCode) -> (test::TestServer, mpsc::Receiver<http::HeaderMap>) {
+        let (sender, received) = mpsc::channel();
+        let server = test::TestServer::new(move |app| {
+            let sender = sender.clone();
+            app.resource("/hook", move |r| {
+                let sender = sender.clone();
+                r.f(move |req: &HttpRequest| {
+                    sender.send(req.headers().clone()).unwrap();
+                    HttpResponse::build(status).body("thanks")
+                })
+            });
+        });
+        (server, received)
+    }
+
+    #[test]
+    fn delivered() {
+        let (mut server, received) = receiver(http::StatusCode::OK);
+        let due = due(server.url("/hook"));
+        let body = due.delivery.body();
+
+        let outcome = server.execute(deliver(due)).unwrap();
+        assert_eq!(
+            outcome,
+            DeliveryOutcome::Delivered {
+                status: 200,
+                body: "thanks".to_string(),
+            }
+        );
+
+        let headers = received.recv().unwrap();
+        assert_eq!(headers["X-Dewey-Event"], "ping");
+        assert_eq!(headers["X-Dewey-Delivery"], "7");
+        assert_eq!(
+            headers[SIGNATURE_HEADER].to_str().unwrap(),
+            signature("shh", body.as_bytes())
+        );
+    }
+
+    #[test]
+    fn failed() {
+        let (mut server, _received) = receiver(http::StatusCode::INTERNAL_SERVER_ERROR);
+        let outcome = server.execute(deliver(due(server.url("/hook")))).unwrap();
+        assert_eq!(
+            outcome,
+            DeliveryOutcome::Failed {
+                status: Some(500),
+                body: Some("thanks".to_string()),
+                error: "receiver responded 500 Internal Server Error".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn unreachable() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap()
+            .port();
+        let url = format!("http://127.0.0.1:{}/hook", port);
+        let outcome = System::new("test").block_on(deliver(due(url))).unwrap();
+        match outcome {
+            DeliveryOutcome::Failed { status: None, .. } => {}
+            outcome => panic!("unexpected outcome {:?}", outcome),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+mod delivery;
+pub use delivery::{
+    check_receiver, new_webhook_secret, signature, Delivery, DeliveryId, DeliveryOutcome,
+    Webhook, WebhookId, SIGNATURE_HEADER,
+};
+
+mod dispatcher;
+pub use dispatcher::WebhookDispatcher;
+
+/// webhook_event enum
+#[derive(Debug, Clone, Copy, PartialEq, DbEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// Sent once the object's first version and values are stored
+    ObjectCreated,
+    /// Any property value but the Collection changed
+    ValueChanged,
+    ObjectDeleted,
+    /// The object was added to or removed from a collection
+    CollectionChanged,
+    /// Sent on request, to check a subscription's URL and secret
+    Ping,
+}
+
+impl WebhookEvent {
+    /// Name of the event as it appears in the database, JSON and `X-Dewey-Event`
+    pub fn name(self) -> &'static str {
+        match self {
+            WebhookEvent::ObjectCreated => "object_created",
+            WebhookEvent::ValueChanged => "value_changed",
+            WebhookEvent::ObjectDeleted => "object_deleted",
+            WebhookEvent::CollectionChanged => "collection_changed",
+            WebhookEvent::Ping => "ping",
+        }
+    }
+}
+
+/// delivery_status enum
+#[derive(Debug, Clone, Copy, PartialEq, DbEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for its first or next attempt
+    Pending,
+    Delivered,
+    /// Given up on after too many attempts
+    Failed,
+}